@@ -167,3 +167,267 @@ Feature: Build statement
     And the exit status should not be 0
     Then I run `turtle`
     And the exit status should not be 0
+
+  @turtle
+  Scenario: Rebuild an output on update of a dependency in a depfile
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = echo hello && cp $in $out && echo "$out: $in bar.h" > $out.d
+      depfile = $out.d
+
+    build foo: cc bar
+
+    """
+    And a file named "bar" with ""
+    And a file named "bar.h" with ""
+    When I successfully run `turtle`
+    And a file named "bar.h" with "bar"
+    And I successfully run `turtle`
+    Then the stdout should contain exactly:
+    """
+    hello
+    hello
+    """
+
+  @turtle
+  Scenario: Rebuild an output on update of a dependency in a command output
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = echo hello && echo "Note: including file: bar.h" && cp $in $out
+      deps = msvc
+
+    build foo: cc bar
+
+    """
+    And a file named "bar" with ""
+    And a file named "bar.h" with ""
+    When I successfully run `turtle`
+    And a file named "bar.h" with "bar"
+    And I successfully run `turtle`
+    Then the stdout should contain exactly:
+    """
+    hello
+    hello
+    """
+
+  @turtle
+  Scenario: Run multiple commands in a rule
+    Given a file named "build.ninja" with:
+    """
+    rule two
+      command[0] = echo foo > $out
+      command[1] = echo bar >> $out
+
+    build foo: two
+
+    """
+    When I successfully run `turtle`
+    Then the file "foo" should contain exactly:
+    """
+    foo
+    bar
+    """
+
+  @turtle
+  Scenario: Stop multiple commands in a rule on failure
+    Given a file named "build.ninja" with:
+    """
+    rule fail
+      command[0] = false
+      command[1] = touch $out
+
+    build foo: fail
+
+    """
+    When I run `turtle`
+    Then the exit status should not be 0
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Run an always rule on every invocation
+    Given a file named "build.ninja" with:
+    """
+    rule format
+      command = echo formatted && touch $out
+      always = 1
+
+    build format: format
+
+    """
+    And I successfully run `turtle format`
+    When I successfully run `turtle format`
+    Then the stdout should contain "formatted"
+
+  @turtle
+  Scenario: Create output directories
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo/bar: touch
+
+    """
+    When I successfully run `turtle`
+    Then the file "foo/bar" should exist
+
+  @turtle
+  Scenario: Disable creation of output directories
+    Given a file named "build.ninja" with:
+    """
+    mkdir = 0
+
+    rule touch
+      command = touch $out
+
+    rule touch_mkdir
+      command = touch $out
+      mkdir = 1
+
+    build foo/bar: touch
+    build baz/qux: touch_mkdir
+
+    """
+    When I run `turtle -k 0`
+    Then the exit status should not be 0
+    And the file "baz/qux" should exist
+    And a directory named "foo" should not exist
+
+  Scenario: Run builds in a pool
+    Given a file named "build.ninja" with:
+    """
+    pool link
+      depth = 1
+
+    rule touch
+      command = touch $out
+      pool = link
+
+    build foo: touch
+    build bar: touch
+
+    """
+    When I successfully run `turtle`
+    Then the file "foo" should exist
+    And the file "bar" should exist
+
+  @turtle
+  Scenario: Override a pool depth on command line
+    Given a file named "build.ninja" with:
+    """
+    link_jobs = 1
+
+    pool link
+      depth = $link_jobs
+
+    rule touch
+      command = touch $out
+      pool = link
+
+    build foo: touch
+
+    """
+    When I run `turtle link_jobs=0`
+    Then the exit status should be 2
+    And the stderr should contain "invalid depth \"0\" of pool \"link\""
+
+  @turtle
+  Scenario: Limit jobs of a rule on command line
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+
+    """
+    When I successfully run `turtle --jobs-for-rule touch=1`
+    Then the file "foo" should exist
+    And the file "bar" should exist
+
+  Scenario: Build validations
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar |@ baz
+    build baz: cp foo
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle foo`
+    Then the file "foo" should contain "bar"
+    And the file "baz" should contain "bar"
+
+  Scenario: Fail with validations
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    rule fail
+      command = false
+
+    build foo: cp bar |@ baz
+    build baz: fail foo
+
+    """
+    And a file named "bar" with "bar"
+    When I run `turtle foo`
+    Then the exit status should not be 0
+    And the file "foo" should contain "bar"
+
+  Scenario: Use a response file
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $out.rsp $out
+      rspfile = $out.rsp
+      rspfile_content = $in_newline
+
+    build foo: cp bar baz
+
+    """
+    And a file named "bar" with "bar"
+    And a file named "baz" with "baz"
+    When I successfully run `turtle`
+    Then the file "foo" should contain "bar"
+    And the file "foo" should contain "baz"
+    And the file "foo.rsp" should not exist
+
+  @turtle
+  Scenario: Keep a response file
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $out.rsp $out
+      rspfile = $out.rsp
+      rspfile_content = $in
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --keep-rsp`
+    Then the stderr should contain "response file kept: foo.rsp"
+    And the file "foo.rsp" should contain "bar"
+
+  Scenario: Expand variables in paths
+    Given a file named "build.ninja" with:
+    """
+    builddir = out
+
+    rule touch
+      command = touch $out
+
+    build $builddir/foo: touch
+    build $builddir/bar: touch out/foo
+
+    """
+    When I successfully run `turtle out/bar`
+    Then the file "out/foo" should exist
+    And the file "out/bar" should exist
@@ -3,7 +3,7 @@ Feature: Default statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     build foo: hello
     build bar: hello
@@ -18,7 +18,7 @@ Feature: Default statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     subninja foo.ninja
 
@@ -23,3 +23,148 @@ Feature: Error
     When I run `turtle`
     Then the exit status should not be 0
     And the stderr should contain "foo.ninja"
+
+  @turtle
+  Scenario: Fail to resolve an included build file in a loop of symbolic links
+    Given a file named "build.ninja" with:
+    """
+    include foo.ninja
+
+    """
+    And I successfully run `ln -s bar.ninja foo.ninja`
+    And I successfully run `ln -s foo.ninja bar.ninja`
+    When I run `turtle`
+    Then the exit status should be 2
+    And the stderr should contain "failed to resolve \"foo.ninja\" included by \"build.ninja\""
+
+  @turtle
+  Scenario: Fail with a missing input before running builds
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo
+
+    """
+    When I run `turtle baz`
+    Then the exit status should not be 0
+    And the stderr should contain "input \"bar\" needed by \"foo\" not found"
+
+  @turtle
+  Scenario: Fail with conflicting deps options
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = cp $in $out
+      depfile = $out.d
+      deps = msvc
+
+    build foo: cc bar
+
+    """
+    When I run `turtle`
+    Then the exit status should not be 0
+    And the stderr should contain "depfile cannot be used with deps = msvc"
+
+  @turtle
+  Scenario: Fail on too deep inclusion of build files
+    Given a file named "build.ninja" with:
+    """
+    include foo.ninja
+
+    """
+    And a file named "foo.ninja" with:
+    """
+    include bar.ninja
+
+    """
+    And a file named "bar.ninja" with:
+    """
+
+    """
+    When I run `turtle --max-parse-depth 1`
+    Then the exit status should not be 0
+    And the stderr should contain "build files nested deeper than 1: build.ninja -> foo.ninja -> bar.ninja"
+
+  @turtle
+  Scenario: Report parse errors of all build files
+    Given a file named "build.ninja" with:
+    """
+    subninja foo.ninja
+    subninja bar.ninja
+
+    """
+    And a file named "foo.ninja" with:
+    """
+    build
+
+    """
+    And a file named "bar.ninja" with:
+    """
+    rule
+
+    """
+    When I run `turtle`
+    Then the exit status should be 2
+    And the stderr should contain "bar.ninja: "
+    And the stderr should contain "foo.ninja: "
+
+  @turtle
+  Scenario: Report only the first parse error of build files
+    Given a file named "build.ninja" with:
+    """
+    subninja foo.ninja
+    subninja bar.ninja
+
+    """
+    And a file named "foo.ninja" with:
+    """
+    build
+
+    """
+    And a file named "bar.ninja" with:
+    """
+    rule
+
+    """
+    When I run `turtle --fail-fast-parse`
+    Then the exit status should be 2
+    And the stderr should contain "Parsing Error"
+    And the stderr should not contain "bar.ninja: "
+    And the stderr should not contain "foo.ninja: "
+
+  @turtle
+  Scenario: Fail on an unsatisfied required version
+    Given a file named "build.ninja" with:
+    """
+    turtle_required_version = 999.0.0
+
+    """
+    When I run `turtle`
+    Then the exit status should not be 0
+    And the stderr should contain "turtle 999.0.0 or later required by build files"
+
+  @turtle
+  Scenario: Exit with a status of build failure
+    Given a file named "build.ninja" with:
+    """
+    rule fail
+      command = exit 1
+
+    build foo: fail
+
+    """
+    When I run `turtle`
+    Then the exit status should be 1
+
+  @turtle
+  Scenario: Exit with a status of an invalid build file
+    Given a file named "build.ninja" with:
+    """
+    build foo: bar
+
+    """
+    When I run `turtle`
+    Then the exit status should be 2
@@ -5,7 +5,7 @@ Feature: Include statement
     include foo.ninja
 
     rule echo
-      command = echo $x
+      command = touch $out && echo $x
 
     build foo: echo
 
@@ -29,7 +29,7 @@ Feature: Include statement
     And a file named "foo.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     """
     When I successfully run `turtle`
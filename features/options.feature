@@ -21,3 +21,740 @@ Feature: Command line options
     When I run `turtle --log-prefix tomato`
     Then the exit status should not be 0
     And the stderr should contain "tomato"
+
+  @turtle
+  Scenario: Read default arguments from a rc file
+    Given a file named "build.ninja" with:
+    """
+    rule
+    """
+    And a file named ".turtlerc" with:
+    """
+    --log-prefix tomato
+    """
+    When I run `turtle`
+    Then the exit status should not be 0
+    And the stderr should contain "tomato"
+
+  @turtle
+  Scenario: Do not read a rc file
+    Given a file named "build.ninja" with:
+    """
+    rule
+    """
+    And a file named ".turtlerc" with:
+    """
+    --log-prefix tomato
+    """
+    When I run `turtle --no-rc`
+    Then the exit status should not be 0
+    And the stderr should not contain "tomato"
+
+  @turtle
+  Scenario: Override arguments in a rc file
+    Given a file named "build.ninja" with:
+    """
+    rule
+    """
+    And a file named ".turtlerc" with:
+    """
+    --log-prefix tomato
+    """
+    When I run `turtle --log-prefix potato`
+    Then the exit status should not be 0
+    And the stderr should contain "potato"
+    And the stderr should not contain "tomato"
+
+  @turtle
+  Scenario: Read arguments from a response file
+    Given a file named "build.ninja" with:
+    """
+    rule
+    """
+    And a file named "arguments" with:
+    """
+    --log-prefix tomato
+    """
+    When I run `turtle @arguments`
+    Then the exit status should not be 0
+    And the stderr should contain "tomato"
+
+  @turtle
+  Scenario: Report stalled builds
+    Given a file named "build.ninja" with:
+    """
+    rule slow
+      command = sleep 2 && touch $out
+
+    build foo: slow
+
+    """
+    When I successfully run `turtle --stall-timeout 1`
+    Then the stderr should contain "running foo"
+
+  @turtle
+  Scenario: Abort stalled builds
+    Given a file named "build.ninja" with:
+    """
+    rule slow
+      command = sleep 2 && touch $out
+
+    build foo: slow
+
+    """
+    When I run `turtle --stall-timeout 1 --abort-on-stall`
+    Then the exit status should not be 0
+    And the stderr should contain "build stalled"
+
+  @turtle
+  Scenario: Keep going on failures
+    Given a file named "build.ninja" with:
+    """
+    rule fail
+      command = exit 1
+
+    rule slow
+      command = sleep 1 && touch $out
+
+    build foo: fail
+    build bar: slow
+
+    """
+    When I run `turtle --no-stop`
+    Then the exit status should not be 0
+    And a file named "bar" should exist
+
+  @turtle
+  Scenario: Build outputs specified last time again
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+
+    """
+    And I successfully run `turtle foo`
+    And I remove the file "foo"
+    When I successfully run `turtle --again`
+    Then a file named "foo" should exist
+    And a file named "bar" should not exist
+
+  @turtle
+  Scenario: Ignore dependencies in depfiles
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = echo hello && cp $in $out && echo "$out: $in bar.h" > $out.d
+      depfile = $out.d
+
+    build foo: cc bar
+
+    """
+    And a file named "bar" with ""
+    And a file named "bar.h" with ""
+    When I successfully run `turtle`
+    And a file named "bar.h" with "bar"
+    And I successfully run `turtle --no-deps`
+    Then the stdout should contain exactly:
+    """
+    hello
+    """
+
+  @turtle
+  Scenario: Build shards of outputs
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+    build baz: touch
+
+    """
+    When I successfully run `turtle --shard 1/2`
+    And I successfully run `turtle --shard 2/2`
+    Then a file named "foo" should exist
+    And a file named "bar" should exist
+    And a file named "baz" should exist
+
+  @turtle
+  Scenario: Trace parsing build files
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    When I successfully run `turtle --trace-parse`
+    Then the stderr should contain "parse: "
+    And the stderr should contain "(1 files)"
+    And the stderr should contain "validate: "
+    And the stderr should contain "(1 builds)"
+
+  @turtle
+  Scenario: Run commands with explicit environment variables
+    Given I set the environment variable "TURTLE_FOO" to "foo"
+    And a file named "build.ninja" with:
+    """
+    rule env
+      command = printenv TURTLE_BAR TURTLE_BAZ > $out
+      env = TURTLE_BAZ=baz
+
+    rule inherit
+      command = printenv TURTLE_FOO
+
+    build foo: env
+    build bar: inherit
+
+    """
+    And a file named "env" with:
+    """
+    PATH=/usr/bin:/bin
+    TURTLE_BAR=bar
+    """
+    When I successfully run `turtle --clear-env --env-file env foo`
+    And I run `turtle --clear-env --env-file env bar`
+    Then the exit status should not be 0
+    And the file "foo" should contain exactly:
+    """
+    bar
+    baz
+    """
+
+  @turtle
+  Scenario: Shuffle builds with a seed
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+
+    """
+    When I successfully run `turtle --shuffle=42`
+    Then the stderr should contain "shuffle seed: 42"
+    And the file "foo" should exist
+    And the file "bar" should exist
+
+  @turtle
+  Scenario: Write console outputs to a log file
+    Given a file named "build.ninja" with:
+    """
+    rule fail
+      command = echo foo && false
+
+    build bar: fail
+
+    """
+    When I run `turtle --log-file log`
+    Then the exit status should not be 0
+    And the file "log" should contain "foo"
+    And the file "log" should contain "build failed"
+
+  @turtle
+  Scenario: Show why an output is dirty
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --why baz`
+    Then the stdout should contain "baz: dirty (input \"foo\" dirty)"
+    And the stdout should contain "  foo: dirty (output missing)"
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Print source inputs of an output
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo | qux
+
+    """
+    When I successfully run `turtle --print-inputs baz`
+    Then the stdout should contain exactly:
+    """
+    bar
+    qux
+    """
+    And the file "baz" should not exist
+
+  @turtle
+  Scenario: Report builds depending on a file
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo
+    build qux: cp bar
+
+    """
+    When I successfully run `turtle --dependency-report bar`
+    Then the stdout should contain exactly:
+    """
+    3 builds depend on bar
+      baz
+      foo
+      qux
+    """
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Fail on outputs not created by commands
+    Given a file named "build.ninja" with:
+    """
+    rule echo
+      command = echo $out
+
+    build foo: echo
+
+    """
+    When I run `turtle`
+    Then the exit status should not be 0
+    And the stderr should contain "output \"foo\" not created by its command"
+    When I successfully run `turtle --allow-missing-outputs`
+    Then the file "foo" should not exist
+
+  @turtle
+  Scenario: Compile builds lazily
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    rule fail
+      command = exit 1
+
+    build foo: cp bar
+    build baz: fail
+    build qux: missing
+
+    """
+    And a file named "bar" with "bar"
+    When I run `turtle --lazy foo`
+    Then the exit status should be 0
+    And the file "foo" should contain "bar"
+
+  @turtle
+  Scenario: Keep records of builds not compiled lazily
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp bar
+    build qux: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle`
+    And I successfully run `turtle --lazy --compaction-threshold 1 foo`
+    Then the stderr should not contain "compacted"
+    When I successfully run `turtle --check-up-to-date`
+    Then the stdout should not contain "dirty"
+
+  @turtle
+  Scenario: Print default targets
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+    build baz: touch
+    default foo baz
+
+    """
+    When I successfully run `turtle --print-default-targets`
+    Then the stdout should contain exactly:
+    """
+    baz
+    foo
+    """
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Detect undeclared outputs
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out extra
+
+    build foo: touch
+
+    """
+    When I successfully run `turtle --detect-undeclared-outputs`
+    Then the stderr should contain "undeclared outputs written by \"foo\": extra"
+    And the file "foo" should exist
+
+  @turtle
+  Scenario: Build outputs in a compilation database
+    Given a file named "compile_commands.json" with:
+    """
+    [{ "directory": ".", "command": "cp foo.c foo.o", "file": "foo.c", "output": "foo.o" }]
+    """
+    And a file named "foo.c" with "foo"
+    When I successfully run `turtle --from-compdb compile_commands.json`
+    Then the file "foo.o" should contain "foo"
+
+  @turtle
+  Scenario: Keep records of builds in build files on builds from a compilation database
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    And a file named "compile_commands.json" with:
+    """
+    [{ "directory": ".", "command": "cp bar qux", "file": "bar", "output": "qux" }]
+    """
+    When I successfully run `turtle`
+    And I successfully run `turtle --from-compdb compile_commands.json --compaction-threshold 1`
+    Then the stderr should not contain "compacted"
+    When I successfully run `turtle --check-up-to-date`
+    Then the stdout should not contain "dirty"
+
+  @turtle
+  Scenario: Fail a check of stale outputs
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I run `turtle --check-up-to-date`
+    Then the exit status should be 3
+    And the stdout should contain "foo: dirty (output missing)"
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Pass a check of up-to-date outputs
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle`
+    And I run `turtle --check-up-to-date`
+    Then the exit status should be 0
+    And the stdout should not contain "dirty"
+
+  @turtle
+  Scenario: Place outputs under an output root
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --output-root out`
+    Then the file "out/baz" should contain "bar"
+    And the file "foo" should not exist
+    And the file "baz" should not exist
+    And a directory named ".turtle" should not exist
+
+  @turtle
+  Scenario: Place a build database in a database directory
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --db-dir db`
+    Then a directory named "db" should exist
+    And a directory named ".turtle" should not exist
+    When I successfully run `turtle --db-dir db --check-up-to-date`
+    Then the stdout should not contain "dirty"
+
+  @turtle
+  Scenario: Explain dirty outputs in JSON
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --explain-json`
+    Then the stdout should contain exactly:
+    """
+    [{"output":"foo","reason":{"type":"output_missing"}}]
+    """
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Dump a build database
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    And I successfully run `turtle`
+    When I successfully run `turtle --dump-database`
+    Then the stdout should contain "build foo"
+    And the stdout should contain "timestamp hash: "
+    And the stdout should contain "output foo"
+
+  @turtle
+  Scenario: Explain expansion of a command
+    Given a file named "build.ninja" with:
+    """
+    flags = -a
+    rule cp
+      command = cp $flags $in $out
+
+    build foo: cp bar
+      flags = -p
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --explain-command foo`
+    Then the stdout should contain exactly:
+    """
+    rule: cp $flags $in $out
+    global variables: cp -a $in $out
+    rule variables: cp -a $in $out
+    build variables: cp -p $in $out
+    $in and $out: cp -p bar foo
+    """
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Explain expansion of a command with a temporary directory
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $tmpdir/$out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --explain-command foo`
+    Then the stdout should contain exactly:
+    """
+    rule: cp $in $tmpdir/$out
+    global variables: cp $in $tmpdir/$out
+    rule variables: cp $in $tmpdir/$out
+    build variables: cp $in $tmpdir/$out
+    $in and $out: cp bar $tmpdir/foo
+    """
+
+  @turtle
+  Scenario: Set a job limit as a percentage of CPUs
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    When I successfully run `turtle -j 50%`
+    Then the file "foo" should exist
+
+  @turtle
+  Scenario: Fail with a zero percentage of CPUs
+    Given a file named "build.ninja" with:
+    """
+    """
+    When I run `turtle -j 0%`
+    Then the exit status should not be 0
+    And the stderr should contain "invalid percentage of CPUs"
+
+  @turtle
+  Scenario: Omit terminal titles for non-terminal outputs
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    When I successfully run `turtle --terminal-title`
+    Then the file "foo" should exist
+    And the stderr should not contain "]0;"
+
+  @turtle
+  Scenario: Build through a daemon
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = echo foo && touch $out
+
+    build foo: touch
+
+    """
+    When I run `turtle --daemon` in background
+    And I successfully run `sh -c 'until [ -S .turtle/daemon.sock ]; do sleep 0.1; done'`
+    And I successfully run `turtle`
+    Then the stdout should contain "foo"
+    And the file "foo" should exist
+    Given a file named "build.ninja" with:
+    """
+    rule fail
+      command = echo bar && exit 1
+
+    build foo: fail
+
+    """
+    When I run `turtle`
+    Then the exit status should be 1
+    And the stdout should contain "bar"
+    And the stderr should contain "build failed"
+
+  @turtle
+  Scenario: Reject options different from ones of a daemon
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    When I run `turtle --daemon` in background
+    And I successfully run `sh -c 'until [ -S .turtle/daemon.sock ]; do sleep 0.1; done'`
+    And I run `turtle --clear-env`
+    Then the exit status should be 1
+    And the stderr should contain "options --clear-env differ from ones of a daemon"
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Build outputs in a subdirectory
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = mkdir -p src/net src/ui && touch $out
+
+    build src/net/foo: touch
+    build src/ui/bar: touch
+
+    """
+    When I successfully run `turtle --subdir src/net`
+    Then the file "src/net/foo" should exist
+    And the file "src/ui/bar" should not exist
+
+  Scenario: Run builds dry
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+      description = cp $out
+
+    build foo: cp bar
+    build baz: cp foo
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle -n`
+    Then the stderr should contain "cp foo"
+    And the stderr should contain "cp baz"
+    And the file "foo" should not exist
+    When I successfully run `turtle`
+    Then the stderr should contain "cp baz"
+
+  @turtle
+  Scenario: Collapse repeated error outputs
+    Given a file named "build.ninja" with:
+    """
+    rule fail
+      command = echo error >&2 && false
+
+    build foo: fail
+    build bar: fail
+    build baz: fail
+
+    """
+    When I run `turtle -k 0 --collapse-errors`
+    Then the exit status should not be 0
+    And the stderr should contain "turtle: error (repeated 3 times)"
+
+  @turtle
+  Scenario: Record provenance of outputs
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle --provenance`
+    Then the file ".turtle/provenance.jsonl" should contain "cp bar foo"
+
+  @turtle
+  Scenario: Build without an unusable database directory
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    And an empty file named ".turtle"
+    When I successfully run `turtle`
+    Then the stderr should contain "warning: cannot open a build database"
+    And a file named "foo" should exist
+
+  @turtle
+  Scenario: Require a database
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    And an empty file named ".turtle"
+    When I run `turtle --require-database`
+    Then the exit status should not be 0
+    And a file named "foo" should not exist
@@ -10,7 +10,7 @@ Feature: Others
     Given a file named "foo.ninja" with:
     """
     rule echo
-      command = echo hello
+      command = touch $out && echo hello
 
     build foo: echo
 
@@ -34,6 +34,54 @@ Feature: Others
     When I successfully run `turtle -C foo`
     Then a file named "foo/foo" should exist
 
+  Scenario: Change a directory first with a custom build file location
+    Given a directory named "foo"
+    And I cd to "foo"
+    And a file named "bar.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build baz/foo: cp bar
+
+    """
+    And a file named "bar" with ""
+    And I cd to ".."
+    When I successfully run `turtle -C foo -f bar.ninja baz/foo`
+    Then a file named "foo/baz/foo" should exist
+
+  @turtle
+  Scenario: Put a database in a changed directory
+    Given a directory named "foo"
+    And I cd to "foo"
+    And a file named "bar.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with ""
+    And I cd to ".."
+    When I successfully run `turtle -C foo -f bar.ninja`
+    Then a directory named "foo/.turtle" should exist
+    And a directory named ".turtle" should not exist
+
+  @turtle
+  Scenario: Report a missing build file relative to a changed directory
+    Given a directory named "foo"
+    And I cd to "foo"
+    And a file named "bar.ninja" with:
+    """
+    include baz.ninja
+
+    """
+    And I cd to ".."
+    When I run `turtle -C foo -f bar.ninja`
+    Then the exit status should not be 0
+    And the stderr should contain ": baz.ninja"
+
   Scenario: Prepare an output directory
     Given a file named "build.ninja" with:
     """
@@ -59,7 +107,7 @@ Feature: Others
     Given a file named "build.ninja" with:
     """
     rule echo
-      command = echo hello
+      command = touch $out && echo hello
 
     build foo: echo
     build bar: echo
@@ -69,3 +117,19 @@ Feature: Others
     """
     When I successfully run `turtle foo`
     Then the stdout should contain exactly "hello"
+
+  @turtle
+  Scenario: Read a build file from stdin
+    Given a file named "foo.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: phony foo
+
+    """
+    When I run `turtle -f - bar` interactively
+    And I pipe in the file "foo.ninja"
+    Then the exit status should be 0
+    And a file named "foo" should exist
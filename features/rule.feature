@@ -3,7 +3,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     build foo: hello
 
@@ -15,7 +15,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     build foo: hello bar
 
@@ -28,7 +28,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     build foo: hello bar
 
@@ -40,7 +40,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule echo
-      command = echo $in
+      command = touch $out && echo $in
 
     build foo: echo bar
 
@@ -53,7 +53,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule echo
-      command = echo $out
+      command = touch $out && echo $out
 
     build foo: echo bar
 
@@ -66,7 +66,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule echo
-      command = echo $x
+      command = touch $out && echo $x
 
     build foo: echo
       x = hello
@@ -80,7 +80,7 @@ Feature: Rule statement
     Given a file named "build.ninja" with:
     """
     rule echo
-      command = echo $x $y
+      command = touch $out && echo $x $y
 
     build foo: echo
       x = hello
@@ -141,3 +141,182 @@ Feature: Rule statement
     hello
     hello
     """
+
+  @turtle
+  Scenario: Show a status category
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = touch $out
+      description = CC $out
+      status = compile
+
+    build foo: cc
+
+    """
+    When I successfully run `turtle --color always`
+    Then the stderr should contain "compile"
+    And the stderr should contain "CC foo"
+
+  @turtle
+  Scenario: Hide a status category without color
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = touch $out
+      description = CC $out
+      status = compile
+
+    build foo: cc
+
+    """
+    When I successfully run `turtle --color never`
+    Then the stderr should contain exactly "CC foo"
+
+  Scenario: Override a description of a rule
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = touch $out
+      description = CC $out
+
+    build foo: cc
+    build bar: cc
+      description = GEN $out
+
+    """
+    When I successfully run `turtle`
+    Then the stderr should contain "CC foo"
+    And the stderr should contain "GEN bar"
+
+  @turtle
+  Scenario: Report rebuilt members of nested phony groups
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build t1: touch
+    build t2: touch
+    build d1: touch
+    build tests: phony t1 t2
+    build all: phony tests d1
+
+    """
+    When I successfully run `turtle all tests`
+    Then the stderr should contain "all: 2 of 2 rebuilt"
+    And the stderr should contain "tests: 2 of 2 rebuilt"
+    And the file "t1" should exist
+    And the file "t2" should exist
+    And the file "d1" should exist
+
+  @turtle
+  Scenario: Report no rebuilt members of phony groups
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build t1: touch
+    build tests: phony t1
+
+    """
+    When I successfully run `turtle tests`
+    And I successfully run `turtle tests`
+    Then the stderr should contain "tests: 0 of 1 rebuilt"
+
+  @turtle
+  Scenario: Use default values of variables
+    Given a file named "build.ninja" with:
+    """
+    rule echo
+      command = echo ${message|default} > $out
+
+    build foo: echo
+    build bar: echo
+      message = bar
+
+    """
+    When I successfully run `turtle`
+    Then the file "foo" should contain "default"
+    And the file "bar" should contain "bar"
+
+  @turtle
+  Scenario: Use immediate variable definitions
+    Given a file named "build.ninja" with:
+    """
+    flags = -O2
+    flags := $flags -g
+    lazy = $name
+    name = foo
+
+    rule echo
+      command = echo $flags $lazy > $out
+
+    build foo: echo
+
+    """
+    When I successfully run `turtle`
+    Then the file "foo" should contain "-O2 -g foo"
+
+  @turtle
+  Scenario: Warn about a rule not referencing its outputs
+    Given a file named "build.ninja" with:
+    """
+    rule echo
+      command = echo hello
+
+    build foo: echo
+
+    """
+    When I successfully run `turtle --allow-missing-outputs`
+    Then the stderr should contain "warning: command of rule \"echo\" does not reference $out"
+    When I run `turtle -w outref=err`
+    Then the exit status should not be 0
+
+  @turtle
+  Scenario: Use a temporary directory
+    Given a file named "build.ninja" with:
+    """
+    rule tmp
+      command = echo foo > $tmpdir/foo && cp $tmpdir/foo $out
+
+    build bar: tmp
+
+    """
+    When I successfully run `turtle`
+    Then the file "bar" should contain "foo"
+
+  @turtle
+  Scenario: Disable built-in rules
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build all: phony foo
+
+    """
+    When I run `turtle --no-builtin-rules all`
+    Then the exit status should not be 0
+    And the stderr should contain "phony"
+    When I successfully run `turtle all`
+    Then a file named "foo" should exist
+
+  @turtle
+  Scenario: Extend a rule
+    Given a file named "build.ninja" with:
+    """
+    rule hello
+      command = touch $out && echo $message
+      message = hello
+
+    rule world : hello
+      message = world
+
+    build foo: world
+
+    """
+    When I successfully run `turtle`
+    Then the stdout should contain exactly "world"
@@ -3,7 +3,7 @@ Feature: Subninja statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     subninja foo.ninja
 
@@ -20,7 +20,7 @@ Feature: Subninja statement
     Given a file named "build.ninja" with:
     """
     rule hello
-      command = echo hello
+      command = touch $out && echo hello
 
     subninja foo.ninja
 
@@ -37,3 +37,23 @@ Feature: Subninja statement
     """
     When I successfully run `turtle`
     Then the stdout should contain exactly "hello"
+
+  Scenario: Isolate variables in a child build file
+    Given a file named "build.ninja" with:
+    """
+    x = foo
+    rule echo
+      command = touch $out && echo $x
+
+    subninja foo.ninja
+    build bar: echo
+
+    """
+    And a file named "foo.ninja" with:
+    """
+    x = bar
+    build foo: echo
+
+    """
+    When I successfully run `turtle bar`
+    Then the stdout should contain exactly "foo"
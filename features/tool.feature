@@ -28,3 +28,377 @@ Feature: Tool
     And I successfully run `turtle`
     When I successfully run `turtle -t cleandead`
     Then the file "foo" should exist
+
+  @turtle
+  Scenario: Touch outputs
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = echo hello && cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    And a file named "foo" with "bar"
+    When I successfully run `turtle -t touch`
+    And I successfully run `turtle`
+    Then the stdout should not contain "hello"
+
+  @turtle
+  Scenario: Touch outputs without running commands
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = echo hello && cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle -t touch`
+    Then the stdout should not contain "hello"
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: List dead outputs
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+
+    """
+    And I successfully run `turtle`
+    When a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build bar: touch
+
+    """
+    And I successfully run `turtle -t dead`
+    Then the stdout should contain exactly "foo"
+    And the file "foo" should exist
+
+  @turtle
+  Scenario: Clean outputs matching a pattern
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo.o: touch
+    build bar.o: touch
+    build foo.a: touch
+
+    """
+    And I successfully run `turtle`
+    When I successfully run `turtle -t clean *.o`
+    Then the file "foo.o" should not exist
+    And the file "bar.o" should not exist
+    And the file "foo.a" should exist
+
+  @turtle
+  Scenario: List outputs matching a pattern
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo.o: touch
+    build foo.a: touch
+
+    """
+    When I successfully run `turtle -t targets *.o`
+    Then the stdout should contain exactly "foo.o"
+
+  @turtle
+  Scenario: Expand build files
+    Given a file named "build.ninja" with:
+    """
+    x = foo
+    rule echo
+      command = echo $x $out
+
+    build bar: echo
+
+    """
+    When I successfully run `turtle -t expand`
+    Then the stdout should contain "command = echo foo bar"
+
+  @turtle
+  Scenario: List inputs of outputs
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo
+
+    """
+    When I successfully run `turtle -t inputs --dependency-order baz`
+    Then the stdout should contain exactly:
+    """
+    bar
+    foo
+    """
+
+  @turtle
+  Scenario: Query transitive inputs of outputs
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: cp foo foo
+
+    """
+    When I successfully run `turtle -t query --transitive baz`
+    Then the stdout should contain exactly:
+    """
+    baz
+      foo
+        bar
+      foo ...
+    """
+
+  @turtle
+  Scenario: Print a graph in Mermaid
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp src/bar
+
+    """
+    When I successfully run `turtle -t graph --mermaid`
+    Then the stdout should contain exactly:
+    """
+    graph LR
+      n0["foo"]
+      n1["bar"]
+      n1 -->|cp| n0
+    """
+
+  @turtle
+  Scenario: Show builds producing and consuming a file
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build bar: cp foo
+    build baz: cp bar
+    build qux: cp bar
+
+    """
+    When I successfully run `turtle -t edges bar`
+    Then the stdout should contain exactly:
+    """
+    bar:
+      produced by:
+        cp: bar
+      consumed by:
+        cp: baz
+        cp: qux
+    """
+
+  @turtle
+  Scenario: List unused rules and pools
+    Given a file named "build.ninja" with:
+    """
+    pool link_pool
+      depth = 1
+
+    rule cp
+      command = cp $in $out
+
+    rule link
+      command = link $in -o $out
+      pool = link_pool
+
+    build bar: cp foo
+
+    """
+    When I successfully run `turtle -t unused`
+    Then the stdout should contain exactly:
+    """
+    rule link
+    pool link_pool
+    """
+
+  @turtle
+  Scenario: Export builds as a Makefile
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build all: phony foo
+
+    """
+    When I successfully run `turtle -t makefile`
+    Then the stdout should contain ".PHONY: turtle-default all"
+    And the stdout should contain "foo: bar"
+    And the stdout should contain "cp bar foo"
+
+  @turtle
+  Scenario: Check an environment
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    When I successfully run `turtle --doctor`
+    Then the stdout should contain "pass: shell: runs commands"
+    And the stdout should contain "pass: build file: parsed"
+    And the file "foo" should not exist
+
+  @turtle
+  Scenario: Check an environment with broken build files
+    Given a file named "build.ninja" with:
+    """
+    build foo: cp bar
+
+    """
+    When I run `turtle --doctor`
+    Then the exit status should not be 0
+    And the stdout should contain "fail: build file: rule \"cp\" not found"
+
+  @turtle
+  Scenario: Check valid build files
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    And a file named "bar" with "bar"
+    When I successfully run `turtle -t check`
+    Then the file "foo" should not exist
+
+  @turtle
+  Scenario: Check an undefined rule
+    Given a file named "build.ninja" with:
+    """
+    build foo: cp bar
+
+    """
+    When I run `turtle -t check`
+    Then the exit status should not be 0
+    And the stderr should contain "rule \"cp\" not found"
+
+  @turtle
+  Scenario: Check a missing input
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+
+    """
+    When I run `turtle -t check`
+    Then the exit status should not be 0
+    And the stderr should contain "input \"bar\" needed by \"foo\" not found"
+
+  @turtle
+  Scenario: Check duplicate outputs
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build foo: touch
+
+    """
+    When I run `turtle -t check`
+    Then the exit status should not be 0
+    And the stderr should contain "multiple builds generate output \"foo\""
+
+  @turtle
+  Scenario: Check a circular dependency
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build bar: cp foo
+
+    """
+    When I run `turtle -t check`
+    Then the exit status should not be 0
+    And the stderr should contain "dependency cycle detected"
+
+  @turtle
+  Scenario: Check multiple problems at once
+    Given a file named "build.ninja" with:
+    """
+    rule cp
+      command = cp $in $out
+
+    build foo: cp bar
+    build baz: touch
+
+    """
+    When I run `turtle -t check`
+    Then the exit status should not be 0
+    And the stderr should contain "rule \"touch\" not found"
+    And the stderr should contain "input \"bar\" needed by \"foo\" not found"
+    And the stderr should contain "2 problem(s) found"
+
+  @turtle
+  Scenario: Export dependencies in Ninja's format
+    Given a file named "build.ninja" with:
+    """
+    rule cc
+      command = cp $in $out && echo $out: $in foo.h > $out.d
+      depfile = $out.d
+      deps = gcc
+
+    build foo.o: cc foo.c
+
+    """
+    And a file named "foo.c" with "foo"
+    And a file named "foo.h" with "foo"
+    And I successfully run `turtle`
+    When I successfully run `turtle -t ninjadeps`
+    Then the file ".ninja_deps" should exist
+
+  @turtle
+  Scenario: Compact a build database automatically
+    Given a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+    build bar: touch
+
+    """
+    And I successfully run `turtle`
+    When a file named "build.ninja" with:
+    """
+    rule touch
+      command = touch $out
+
+    build foo: touch
+
+    """
+    And I successfully run `turtle --compaction-threshold 1`
+    Then the stderr should contain "compacted a build database by removing 1 stale builds"
@@ -1,30 +1,1400 @@
-use clap::{Parser, ValueEnum};
+use crate::{
+    compile::{self, RuleRedefinition, Shell},
+    fnv,
+    infrastructure::DEFAULT_CAPTURE_LIMIT,
+    run::{ErrorFormat, OutputMode, ProfileFormat, Schedule, Shard},
+};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, Parser, ValueEnum};
+use glob::Pattern;
+use serde::Deserialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const RESPONSE_FILE_PREFIX: char = '@';
+const NO_RC_FLAG: &str = "--no-rc";
+const DEFAULT_PAGER: &str = "less";
 
 #[derive(Parser)]
-#[clap(about = "The Ninja build system clone written in Rust", version)]
+#[clap(
+    about = "The Ninja build system clone written in Rust",
+    bin_name = "turtle",
+    version,
+    args_override_self = true
+)]
 pub struct Arguments {
-    #[clap(help = "Specify outputs")]
+    #[clap(help = "Specify outputs or variables of NAME=VALUE overriding ones in build files")]
     pub outputs: Vec<String>,
     #[clap(short, help = "Set a root build file")]
     pub file: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with = "file",
+        help = "Build outputs of entries in a compilation database (e.g. compile_commands.json) instead of build files"
+    )]
+    pub from_compdb: Option<String>,
+    #[clap(
+        long,
+        conflicts_with = "outputs",
+        help = "Build outputs specified in the last invocation again"
+    )]
+    pub again: bool,
     #[clap(short = 'C', help = "Set a working directory")]
     pub directory: Option<String>,
-    #[clap(short, help = "Set a job limit")]
-    pub job_limit: Option<usize>,
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Place outputs of builds and a build database under a directory instead of a source tree"
+    )]
+    pub output_root: Option<String>,
+    #[clap(
+        long,
+        value_name = "DIR",
+        env = "TURTLE_DB_DIR",
+        help = "Place a build database under a directory keyed by a root build file instead of a build directory"
+    )]
+    pub db_dir: Option<PathBuf>,
+    #[clap(
+        short,
+        value_name = "N|N%|auto",
+        help = "Set a job limit as a number, a percentage of CPUs, or auto (the number of CPUs by default)"
+    )]
+    pub job_limit: Option<JobLimit>,
+    #[clap(
+        long,
+        value_name = "COMMAND",
+        env = "TURTLE_SHELL",
+        help = "Run commands of rules without shell variables in a shell given as words followed by the commands (Nushell by default)"
+    )]
+    pub shell: Option<String>,
+    #[clap(
+        long,
+        value_name = "RULE=N",
+        help = "Limit jobs of a rule running concurrently in addition to its pool"
+    )]
+    pub jobs_for_rule: Vec<RuleJobLimit>,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Spawn at most a number of commands per second regardless of a job limit"
+    )]
+    pub spawn_rate: Option<u64>,
+    #[clap(
+        long,
+        value_name = "fifo|fair|critical",
+        default_value = "fifo",
+        help = "Choose jobs to run next in order of readiness, round-robin among rules, or lengths of chains of builds depending on them"
+    )]
+    pub schedule: Schedule,
+    #[clap(
+        long,
+        value_name = "plain|tree|grouped",
+        default_value = "plain",
+        help = "Show descriptions of builds one per line, outputs of builds in trees of directories, or outputs grouped by rules"
+    )]
+    pub output_mode: OutputMode,
+    #[clap(
+        long,
+        value_name = "full|short",
+        default_value = "full",
+        help = "Show descriptions of builds with outputs of commands or only outputs of commands and headers of failed builds for quickfix lists of editors"
+    )]
+    pub error_format: ErrorFormat,
+    #[clap(
+        long,
+        value_name = "MB",
+        help = "Defer starting jobs while available memory of a system is below megabytes"
+    )]
+    pub max_memory: Option<u64>,
+    #[clap(
+        long,
+        value_name = "BYTES",
+        help = "Stop builds when a total size of outputs built exceeds bytes"
+    )]
+    pub max_output_bytes: Option<u64>,
+    #[clap(
+        long,
+        value_name = "BYTES",
+        default_value_t = DEFAULT_CAPTURE_LIMIT,
+        help = "Truncate each of stdout and stderr captured from a command beyond bytes"
+    )]
+    pub max_capture: usize,
+    #[clap(
+        long,
+        value_name = "COMMAND",
+        help = "Run a command after builds finish with TURTLE_BUILD_RESULT, TURTLE_BUILD_COUNT, and TURTLE_FAILURE_COUNT environment variables"
+    )]
+    pub post_build: Option<String>,
+    #[clap(short = 'w', value_name = "FLAG", help = "Adjust warnings")]
+    pub warnings: Vec<WarningFlag>,
+    #[clap(
+        long,
+        help = "Disable built-in rules (i.e. phony) so that every rule must be defined in build files"
+    )]
+    pub no_builtin_rules: bool,
     #[clap(long, help = "Set a log prefix")]
     pub log_prefix: Option<String>,
+    #[clap(
+        short = 'k',
+        value_name = "N",
+        default_value = "1",
+        overrides_with_all = ["stop_on_first_error", "no_stop"],
+        help = "Keep going until N jobs fail (0 means infinity)"
+    )]
+    pub keep_going: usize,
+    #[clap(
+        short = 'n',
+        help = "Show builds without running commands or updating build records (dry run)"
+    )]
+    pub dry_run: bool,
+    #[clap(
+        long,
+        overrides_with_all = ["keep_going", "no_stop"],
+        help = "Stop on the first failure of build jobs (default; same as -k 1)"
+    )]
+    pub stop_on_first_error: bool,
+    #[clap(
+        long,
+        overrides_with_all = ["keep_going", "stop_on_first_error"],
+        help = "Keep going regardless of failures of build jobs (same as -k 0)"
+    )]
+    pub no_stop: bool,
+    #[clap(
+        long,
+        value_name = "OUTPUT",
+        conflicts_with_all = ["keep_going", "stop_on_first_error", "no_stop"],
+        help = "Keep going regardless of failures of other build jobs until an output is built or fails"
+    )]
+    pub keep_going_until: Option<String>,
+    #[clap(
+        long,
+        help = "Ignore dependencies recorded from depfiles on rebuild checks (builds can be stale if declared inputs are incomplete)"
+    )]
+    pub no_deps: bool,
+    #[clap(long, help = "Do not record dependencies from depfiles")]
+    pub no_deps_record: bool,
+    #[clap(
+        long,
+        help = "Do not check if inputs not built by any builds exist before running builds"
+    )]
+    pub missing_ok: bool,
+    #[clap(
+        long,
+        help = "Do not fail builds whose commands succeed without creating their outputs"
+    )]
+    pub allow_missing_outputs: bool,
+    #[clap(
+        long,
+        value_name = "i/n",
+        help = "Build only the i-th of n deterministic subsets of outputs to split builds across machines"
+    )]
+    pub shard: Option<Shard>,
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        help = "Skip outputs matching a glob pattern among default or specified ones unless others depend on them"
+    )]
+    pub exclude: Vec<Pattern>,
+    #[clap(
+        long,
+        value_name = "TAG",
+        help = "Build only builds with a tag among default or specified ones and their dependencies"
+    )]
+    pub tag: Vec<String>,
+    #[clap(
+        long,
+        value_name = "OUTPUT",
+        help = "Rebuild an output even if it is up to date"
+    )]
+    pub rebuild: Vec<String>,
+    #[clap(
+        long,
+        requires = "rebuild",
+        help = "Rebuild transitive dependencies of outputs given by --rebuild as well"
+    )]
+    pub rebuild_deps: bool,
+    #[clap(
+        long,
+        value_name = "DIRECTORY",
+        help = "Build only outputs under a directory among default or specified ones and their dependencies"
+    )]
+    pub subdir: Option<String>,
+    #[clap(
+        long,
+        value_name = "SEED",
+        num_args = 0..=1,
+        require_equals = true,
+        help = "Run builds one by one in an order shuffled with a seed (random by default) to find undeclared dependencies"
+    )]
+    pub shuffle: Option<Option<u64>>,
+    #[clap(
+        long,
+        help = "Warn about files written by commands in directories of their outputs but not declared as outputs (slow)"
+    )]
+    pub detect_undeclared_outputs: bool,
+    #[clap(
+        long,
+        help = "Warn about inputs modified while builds run after their commands started"
+    )]
+    pub detect_modified_inputs: bool,
+    #[clap(
+        long,
+        help = "Fail builds if inputs are modified while they run after their commands started"
+    )]
+    pub strict_inputs: bool,
+    #[clap(
+        long,
+        help = "Keep response files of rules after commands succeed and show their paths for debugging"
+    )]
+    pub keep_rsp: bool,
+    #[clap(
+        long,
+        help = "Show identical error outputs of commands only once with counts of their repetitions"
+    )]
+    pub collapse_errors: bool,
+    #[clap(
+        long,
+        help = "Append commands, input hashes, and timestamps of built outputs to .turtle/provenance.jsonl"
+    )]
+    pub provenance: bool,
+    #[clap(
+        long,
+        help = "Replace outputs identical to outputs of other builds with hard links to save space"
+    )]
+    pub dedup_outputs: bool,
+    #[clap(
+        long,
+        help = "Show progress in a terminal title and notify build completion with OSC escape sequences"
+    )]
+    pub terminal_title: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "256",
+        help = "Set a maximum depth of build files included or nested by subninja statements"
+    )]
+    pub max_parse_depth: usize,
+    #[clap(
+        long,
+        help = "Fetch build files included from URLs again instead of using their caches"
+    )]
+    pub refresh: bool,
+    #[clap(
+        long,
+        help = "Stop at the first parse error of build files instead of reporting errors of all of them"
+    )]
+    pub fail_fast_parse: bool,
+    #[clap(long, help = "Run commands without environment variables inherited")]
+    pub clear_env: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Set environment variables of commands from KEY=VALUE lines in a file"
+    )]
+    pub env_file: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write all console outputs to a file too with timestamps"
+    )]
+    pub log_file: Option<String>,
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "1000",
+        help = "Remove records of builds not defined in build files anymore from a build database at startup when their number reaches N (0 disables it)"
+    )]
+    pub compaction_threshold: usize,
+    #[clap(
+        long,
+        help = "Fail if a build database cannot be opened instead of building everything without it"
+    )]
+    pub require_database: bool,
     #[clap(long, help = "Show no message on failure of build jobs")]
     pub quiet: bool,
     #[clap(long, help = "Show debug logs", env = "TURTLE_DEBUG")]
     pub debug: bool,
     #[clap(long, help = "Show profile timings", env = "TURTLE_PROFILE")]
     pub profile: bool,
+    #[clap(
+        long,
+        value_name = "text|json|chrome",
+        help = "Show profile timings as text, or write them to .turtle/profile.json as records of builds or to .turtle/trace.json in the Chrome trace event format"
+    )]
+    pub profile_format: Option<ProfileFormat>,
+    #[clap(
+        long,
+        help = "Show a critical path of builds which limited wall-clock time after builds"
+    )]
+    pub time_report: bool,
+    #[clap(
+        long,
+        help = "Show timings of parsing, validating, and compiling build files"
+    )]
+    pub trace_parse: bool,
+    #[clap(
+        long,
+        requires = "outputs",
+        conflicts_with_all = ["output_root", "tool", "daemon"],
+        help = "Compile only builds which given outputs depend on"
+    )]
+    pub lazy: bool,
+    #[clap(long, default_value = "auto", help = "Set when to color outputs")]
+    pub color: Color,
+    #[clap(
+        long,
+        overrides_with = "no_pager",
+        help = "Pipe outputs of listing tools on terminals to a pager of a PAGER environment variable or less"
+    )]
+    pub pager: bool,
+    #[clap(
+        long,
+        overrides_with = "pager",
+        help = "Do not pipe outputs to a pager (default)"
+    )]
+    pub no_pager: bool,
+    #[clap(
+        long,
+        default_value = "300",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Set seconds without any build started or finished before showing running builds"
+    )]
+    pub stall_timeout: u64,
+    #[clap(long, help = "Abort builds when they stall")]
+    pub abort_on_stall: bool,
+    #[clap(
+        long,
+        value_name = "OUTPUT",
+        conflicts_with_all = ["outputs", "tool"],
+        help = "Show why an output is dirty as a tree of builds without building it"
+    )]
+    pub why: Option<String>,
+    #[clap(
+        long,
+        value_name = "OUTPUT",
+        conflicts_with_all = ["outputs", "tool", "why"],
+        help = "Print source files which an output depends on transitively without building it"
+    )]
+    pub print_inputs: Option<String>,
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["outputs", "tool", "why", "print_inputs"],
+        help = "Print a number and a sample of builds which rebuild transitively when a file changes without building them"
+    )]
+    pub dependency_report: Option<String>,
+    #[clap(
+        long,
+        conflicts_with_all = ["tool", "why", "print_inputs"],
+        help = "Fail listing dirty outputs without building them if any outputs are not up to date"
+    )]
+    pub check_up_to_date: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["tool", "why", "print_inputs", "check_up_to_date"],
+        help = "Print why builds would run as JSON without building them"
+    )]
+    pub explain_json: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["tool", "why", "print_inputs", "check_up_to_date", "explain_json", "dry_run"],
+        help = "Build outputs twice from scratch and fail listing ones whose contents differ between the builds"
+    )]
+    pub verify_deterministic: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["outputs", "tool", "why", "print_inputs", "check_up_to_date", "explain_json"],
+        help = "Print records of builds, outputs, and targets in a build database without building them"
+    )]
+    pub dump_database: bool,
+    #[clap(
+        long,
+        value_name = "OUTPUT",
+        conflicts_with_all = ["tool", "why", "print_inputs", "check_up_to_date", "explain_json", "dump_database"],
+        help = "Print a command of an output expanded step by step from a rule through variables of scopes"
+    )]
+    pub explain_command: Option<String>,
+    #[clap(
+        long,
+        conflicts_with_all = ["outputs", "tool", "why", "print_inputs", "check_up_to_date", "explain_json", "dump_database", "explain_command"],
+        help = "Check if a shell, a database directory, an open file limit, and build files are ready for builds"
+    )]
+    pub doctor: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["outputs", "tool", "why", "print_inputs", "check_up_to_date", "explain_json", "dump_database", "explain_command", "doctor"],
+        help = "Keep build files loaded and run builds requested by other invocations in a working directory"
+    )]
+    pub daemon: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["tool", "why", "print_inputs", "check_up_to_date", "explain_json", "dump_database", "explain_command", "doctor", "daemon"],
+        help = "Import builds recorded in a Ninja log as up to date before building"
+    )]
+    pub import_ninja_log: Option<PathBuf>,
+    #[clap(
+        long,
+        conflicts_with_all = ["outputs", "tool", "why", "print_inputs", "check_up_to_date", "explain_json", "dump_database", "explain_command", "doctor", "daemon", "import_ninja_log"],
+        help = "Print outputs built by default without building them"
+    )]
+    pub print_default_targets: bool,
+    #[clap(
+        long,
+        help = "Print options in effect with their sources (cli, file, env, toml, or default) and exit"
+    )]
+    pub print_config: bool,
     #[clap(short, help = "Use a complementary tool")]
     pub tool: Option<Tool>,
+    #[clap(
+        long,
+        requires = "tool",
+        help = "List inputs in an order where inputs come before outputs depending on them with -t inputs"
+    )]
+    pub dependency_order: bool,
+    #[clap(
+        long,
+        requires = "tool",
+        help = "Show trees of transitive inputs of outputs with -t query"
+    )]
+    pub transitive: bool,
+    #[clap(
+        long,
+        requires = "tool",
+        help = "Print a graph in Mermaid instead of DOT with -t graph"
+    )]
+    pub mermaid: bool,
+    #[clap(
+        long,
+        help = "Do not read default arguments from a .turtlerc file in a working directory"
+    )]
+    pub no_rc: bool,
+}
+
+impl Arguments {
+    // Returns outputs and variables separately.
+    pub fn split_outputs(&self) -> (Vec<String>, Vec<(String, String)>) {
+        let mut outputs = vec![];
+        let mut variables = vec![];
+
+        for output in &self.outputs {
+            if let Some((name, value)) = output.split_once('=') {
+                variables.push((name.into(), value.into()));
+            } else {
+                outputs.push(output.clone());
+            }
+        }
+
+        (outputs, variables)
+    }
+
+    // The last flags for warnings take effect.
+    pub fn is_warning_error(&self) -> bool {
+        self.warnings.iter().rev().find(|flag| {
+            matches!(
+                flag,
+                WarningFlag::OutputReferenceWarn | WarningFlag::OutputReferenceError
+            )
+        }) == Some(&WarningFlag::OutputReferenceError)
+    }
+
+    // A profile format option implies profiling.
+    pub fn profile_format(&self) -> Option<ProfileFormat> {
+        self.profile_format
+            .or(self.profile.then_some(ProfileFormat::Text))
+    }
+
+    pub fn compile_options(&self) -> compile::Options {
+        compile::Options {
+            rule_redefinition: self.rule_redefinition(),
+            no_builtin_rules: self.no_builtin_rules,
+            shell: self
+                .shell
+                .as_deref()
+                .map(|shell| Shell::from_command(&shell.split_whitespace().collect::<Vec<_>>()))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn rule_redefinition(&self) -> RuleRedefinition {
+        self.warnings
+            .iter()
+            .rev()
+            .find_map(|flag| match flag {
+                WarningFlag::DuplicateRuleError => Some(RuleRedefinition::Error),
+                WarningFlag::DuplicateRuleWarn => Some(RuleRedefinition::Last),
+                WarningFlag::DuplicateRuleFirst => Some(RuleRedefinition::First),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    // Tools only listing things on stdout
+    pub fn is_listing_tool(&self) -> bool {
+        matches!(
+            self.tool,
+            Some(
+                Tool::Dead
+                    | Tool::Edges
+                    | Tool::Expand
+                    | Tool::Graph
+                    | Tool::Inputs
+                    | Tool::Makefile
+                    | Tool::Query
+                    | Tool::Targets
+                    | Tool::Unused
+            )
+        )
+    }
+
+    // Databases of different root build files do not collide in a database
+    // directory. Their keys are stable across Turtle builds so that records
+    // survive upgrades.
+    pub fn database_directory(&self, build_file: &Path) -> Option<PathBuf> {
+        self.db_dir.as_ref().map(|directory| {
+            directory.join(format!(
+                "{:016x}",
+                fnv::hash(build_file.as_os_str().as_encoded_bytes())
+            ))
+        })
+    }
+
+    // Returns a pager command if outputs are paged on a terminal.
+    pub fn pager(&self, terminal: bool, variable: Option<&str>) -> Option<String> {
+        (self.pager && self.is_listing_tool() && terminal).then(|| {
+            variable
+                .filter(|command| !command.trim().is_empty())
+                .unwrap_or(DEFAULT_PAGER)
+                .into()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum JobLimit {
+    Auto,
+    Count(usize),
+    Percentage(usize),
+}
+
+impl JobLimit {
+    // Percentages are rounded down but at least one job is run.
+    pub fn resolve(self, cpu_count: usize) -> usize {
+        match self {
+            Self::Auto => cpu_count,
+            Self::Count(count) => count,
+            Self::Percentage(percentage) => (cpu_count * percentage / 100).max(1),
+        }
+    }
+}
+
+impl FromStr for JobLimit {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        if string == "auto" {
+            Ok(Self::Auto)
+        } else if let Some(percentage) = string.strip_suffix('%') {
+            match percentage.parse() {
+                Ok(0) | Err(_) => Err(format!("invalid percentage of CPUs: {string}")),
+                Ok(percentage) => Ok(Self::Percentage(percentage)),
+            }
+        } else {
+            string
+                .parse()
+                .map(Self::Count)
+                .map_err(|_| format!("invalid job limit: {string}"))
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuleJobLimit {
+    pub rule: String,
+    pub count: usize,
+}
+
+impl FromStr for RuleJobLimit {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let Some((rule, count)) = string.split_once('=') else {
+            return Err(format!("invalid job limit of rule: {string}"));
+        };
+
+        match count.parse() {
+            Ok(count) if !rule.is_empty() && count > 0 => Ok(Self {
+                rule: rule.into(),
+                count,
+            }),
+            _ => Err(format!("invalid job limit of rule: {string}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum WarningFlag {
+    /// Warn about rules whose commands do not reference $out (default)
+    #[value(name = "outref=warn")]
+    OutputReferenceWarn,
+    /// Fail on rules whose commands do not reference $out
+    #[value(name = "outref=err")]
+    OutputReferenceError,
+    /// Fail on rules defined twice in the same scope (default)
+    #[value(name = "duprule=err")]
+    DuplicateRuleError,
+    /// Warn about rules defined twice in the same scope and use the last ones
+    #[value(name = "duprule=warn")]
+    DuplicateRuleWarn,
+    /// Warn about rules defined twice in the same scope and use the first ones
+    #[value(name = "duprule=first")]
+    DuplicateRuleFirst,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Clone, ValueEnum)]
 #[clap(rename_all = "lower")]
 pub enum Tool {
+    /// Check build files for problems without running builds
+    Check,
+    /// Remove outputs of builds optionally filtered by glob patterns given as outputs
+    Clean,
+    /// Remove outputs not defined in build files anymore
     CleanDead,
+    /// List outputs not defined in build files anymore without removing them
+    Dead,
+    /// Show builds producing and consuming files given as outputs
+    Edges,
+    /// Print build files with all of them inlined and variables resolved into concrete commands
+    Expand,
+    /// Write a build graph into a SQLite database at a path given as an output
+    #[value(name = "export-sqlite")]
+    ExportSqlite,
+    /// Print builds which outputs depend on as a graph in DOT (or Mermaid with --mermaid)
+    Graph,
+    /// List transitive inputs of outputs including generated ones (sorted or in dependency order with --dependency-order)
+    Inputs,
+    /// Print builds as a GNU Makefile to run them without Turtle
+    Makefile,
+    /// Export dependencies recorded from depfiles to a .ninja_deps file in Ninja's format
+    NinjaDeps,
+    /// Show inputs and dependents of outputs (or trees of their transitive inputs with --transitive)
+    Query,
+    /// Remove records of builds not defined in build files anymore from a build database
+    Recompact,
+    /// List outputs of builds optionally filtered by glob patterns given as outputs
+    Targets,
+    /// Mark outputs up to date without running commands (dangerous: only build records are updated)
+    Touch,
+    /// List rules and pools defined but not used by any builds
+    Unused,
+}
+
+// Arguments in a rc file come first and the ones in response files are
+// expanded in place. So explicit command line arguments take precedence. A rc
+// file is read from a working directory set by `-C` unless `--no-rc` is given
+// on command line or in response files.
+pub fn expand_arguments(
+    arguments: impl IntoIterator<Item = String>,
+    rc_file: &Path,
+) -> Result<Vec<String>, io::Error> {
+    let mut arguments = arguments.into_iter();
+    let command = arguments.next();
+    let mut expanded_arguments = vec![];
+
+    for argument in arguments {
+        if let Some(path) = argument.strip_prefix(RESPONSE_FILE_PREFIX) {
+            expanded_arguments.extend(read_argument_file(path.as_ref())?);
+        } else {
+            expanded_arguments.push(argument);
+        }
+    }
+
+    let rc_file = find_directory(command.iter().chain(&expanded_arguments)).join(rc_file);
+    let rc_arguments = if !expanded_arguments
+        .iter()
+        .any(|argument| argument == NO_RC_FLAG)
+        && rc_file.exists()
+    {
+        read_argument_file(&rc_file)?
+    } else {
+        vec![]
+    };
+
+    Ok(command
+        .into_iter()
+        .chain(rc_arguments)
+        .chain(expanded_arguments)
+        .collect())
+}
+
+// Working directories are relative to current ones. Invalid arguments are left
+// for a parser to report.
+fn find_directory<'a>(arguments: impl IntoIterator<Item = &'a String>) -> PathBuf {
+    Arguments::command()
+        .try_get_matches_from(arguments)
+        .ok()
+        .and_then(|matches| matches.get_one::<String>("directory").cloned())
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+// Defaults in a project configuration file come below command line arguments
+// and environment variables.
+#[derive(Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct ProjectConfiguration {
+    shell: Option<String>,
+    jobs: Option<ProjectJobLimit>,
+    color: Option<String>,
+    output_mode: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+enum ProjectJobLimit {
+    Number(usize),
+    String(String),
+}
+
+// Options not given on command line or by environment variables are added
+// from a project configuration file in a working directory set by `-C` in
+// front of arguments. Invalid arguments are left for a parser to report.
+pub fn apply_project_configuration(
+    arguments: Vec<String>,
+    path: &Path,
+) -> Result<Vec<String>, io::Error> {
+    let path = find_directory(&arguments).join(path);
+
+    if !path.exists() {
+        return Ok(arguments);
+    }
+
+    let configuration =
+        toml::from_str::<ProjectConfiguration>(&fs::read_to_string(&path).map_err(|error| {
+            io::Error::new(error.kind(), format!("{}: {}", error, path.display()))
+        })?)
+        .map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), error.message()),
+            )
+        })?;
+    let Ok(matches) = Arguments::command().try_get_matches_from(&arguments) else {
+        return Ok(arguments);
+    };
+    let mut defaults = vec![];
+
+    for (id, flag, value) in [
+        ("shell", "--shell", configuration.shell),
+        (
+            "job_limit",
+            "-j",
+            configuration.jobs.map(|jobs| match jobs {
+                ProjectJobLimit::Number(count) => count.to_string(),
+                ProjectJobLimit::String(string) => string,
+            }),
+        ),
+        ("color", "--color", configuration.color),
+        ("output_mode", "--output-mode", configuration.output_mode),
+    ] {
+        if let Some(value) = value.filter(|_| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        }) {
+            defaults.extend([flag.into(), value]);
+        }
+    }
+
+    let mut arguments = arguments.into_iter();
+
+    Ok(arguments
+        .next()
+        .into_iter()
+        .chain(defaults)
+        .chain(arguments)
+        .collect())
+}
+
+// Sources of options are distinguished by parsing arguments at each stage of
+// their expansion. `file` denotes `.turtlerc` and response files.
+pub fn format_configuration(
+    command_line_arguments: &[String],
+    expanded_arguments: &[String],
+    arguments: &[String],
+) -> String {
+    let parse = |arguments: &[String]| Arguments::command().try_get_matches_from(arguments).ok();
+    let command_line_matches = parse(command_line_arguments);
+    let expanded_matches = parse(expanded_arguments);
+    let Some(matches) = parse(arguments) else {
+        return String::new();
+    };
+    let is_from_command_line = |matches: &Option<ArgMatches>, id| {
+        matches
+            .as_ref()
+            .is_some_and(|matches| matches.value_source(id) == Some(ValueSource::CommandLine))
+    };
+    let mut string = String::new();
+
+    for argument in Arguments::command().get_arguments() {
+        let id = argument.get_id().as_str();
+
+        if matches!(id, "help" | "version") {
+            continue;
+        }
+
+        let name = argument.get_long().map(String::from).unwrap_or_else(|| {
+            argument
+                .get_short()
+                .map(|short| short.to_string())
+                .unwrap_or_else(|| id.into())
+        });
+        let source = match matches.value_source(id) {
+            Some(ValueSource::CommandLine) if is_from_command_line(&command_line_matches, id) => {
+                "cli"
+            }
+            Some(ValueSource::CommandLine) if is_from_command_line(&expanded_matches, id) => "file",
+            Some(ValueSource::CommandLine) => "toml",
+            Some(ValueSource::EnvVariable) => "env",
+            _ => "default",
+        };
+
+        match matches.get_raw(id) {
+            Some(values) => {
+                string.push_str(&format!(
+                    "{name} = {} ({source})\n",
+                    values
+                        .map(|value| value.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ));
+            }
+            None => string.push_str(&format!("{name} = (unset)\n")),
+        }
+    }
+
+    string
+}
+
+fn read_argument_file(path: &Path) -> Result<Vec<String>, io::Error> {
+    Ok(fs::read_to_string(path)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", error, path.display())))?
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(From::from)
+        .collect())
+}
+
+pub fn read_environment_file(path: &Path) -> Result<Vec<(String, String)>, io::Error> {
+    fs::read_to_string(path)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", error, path.display())))?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.split_once('=')
+                .map(|(name, value)| (name.trim().into(), value.into()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "invalid environment variable \"{}\": {}",
+                            line,
+                            path.display()
+                        ),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use tempfile::tempdir;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments.iter().map(|&argument| argument.into()).collect()
+    }
+
+    #[test]
+    fn apply_project_configuration_under_command_line() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("turtle.toml");
+
+        fs::write(
+            &path,
+            "shell = \"bash -c\"\njobs = 4\ncolor = \"never\"\noutput-mode = \"tree\"\n",
+        )
+        .unwrap();
+
+        let parse = |raw_arguments: &[&str]| {
+            Arguments::parse_from(
+                apply_project_configuration(arguments(raw_arguments), &path).unwrap(),
+            )
+        };
+
+        let arguments = parse(&["turtle", "foo"]);
+
+        assert_eq!(arguments.shell.as_deref(), Some("bash -c"));
+        assert_eq!(arguments.job_limit, Some(JobLimit::Count(4)));
+        assert_eq!(arguments.color, Color::Never);
+        assert_eq!(arguments.output_mode, OutputMode::Tree);
+        assert_eq!(arguments.outputs, vec!["foo"]);
+
+        let arguments = parse(&["turtle", "--shell", "sh -c", "-j", "2", "--color", "always"]);
+
+        assert_eq!(arguments.shell.as_deref(), Some("sh -c"));
+        assert_eq!(arguments.job_limit, Some(JobLimit::Count(2)));
+        assert_eq!(arguments.color, Color::Always);
+    }
+
+    #[test]
+    fn key_database_directories_by_build_files() {
+        let arguments = Arguments::parse_from(["turtle", "--db-dir", "/tmp/db"]);
+        let directory = arguments
+            .database_directory(Path::new("/foo/build.ninja"))
+            .unwrap();
+
+        assert!(directory.starts_with("/tmp/db"));
+        assert_ne!(directory, Path::new("/tmp/db"));
+        assert_eq!(
+            arguments.database_directory(Path::new("/foo/build.ninja")),
+            Some(directory.clone())
+        );
+        assert_ne!(
+            arguments.database_directory(Path::new("/bar/build.ninja")),
+            Some(directory)
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle"]).database_directory(Path::new("/foo/build.ninja")),
+            None
+        );
+    }
+
+    #[test]
+    fn key_database_directories_stably() {
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--db-dir", "/tmp/db"])
+                .database_directory(Path::new("foobar")),
+            Some(PathBuf::from("/tmp/db/85944171f73967e8"))
+        );
+    }
+
+    #[test]
+    fn apply_project_configuration_in_working_directory() {
+        let directory = tempdir().unwrap();
+        fs::write(directory.path().join("turtle.toml"), "jobs = 4\n").unwrap();
+        let path = directory.path().display().to_string();
+
+        assert_eq!(
+            apply_project_configuration(
+                arguments(&["turtle", "-C", &path]),
+                "turtle.toml".as_ref()
+            )
+            .unwrap(),
+            arguments(&["turtle", "-j", "4", "-C", &path])
+        );
+    }
+
+    #[test]
+    fn apply_no_project_configuration() {
+        let directory = tempdir().unwrap();
+
+        assert_eq!(
+            apply_project_configuration(
+                arguments(&["turtle"]),
+                &directory.path().join("turtle.toml")
+            )
+            .unwrap(),
+            arguments(&["turtle"])
+        );
+    }
+
+    #[test]
+    fn fail_to_apply_unknown_project_configuration() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("turtle.toml");
+
+        fs::write(&path, "foo = 42\n").unwrap();
+
+        assert!(apply_project_configuration(arguments(&["turtle"]), &path).is_err());
+    }
+
+    #[test]
+    fn format_configuration_with_sources() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("turtle.toml");
+
+        fs::write(&path, "jobs = 3\n").unwrap();
+        // No other test refers to this variable.
+        env::set_var("TURTLE_DEBUG", "true");
+
+        let command_line_arguments = arguments(&["turtle", "--color", "never", "foo"]);
+        let expanded_arguments = [
+            arguments(&["turtle", "--output-mode", "tree"]),
+            command_line_arguments[1..].to_vec(),
+        ]
+        .concat();
+        let string = format_configuration(
+            &command_line_arguments,
+            &expanded_arguments,
+            &apply_project_configuration(expanded_arguments.clone(), &path).unwrap(),
+        );
+        let lines = string.lines().collect::<Vec<_>>();
+
+        for line in [
+            "outputs = foo (cli)",
+            "color = never (cli)",
+            "output-mode = tree (file)",
+            "j = 3 (toml)",
+            "debug = true (env)",
+            "schedule = fifo (default)",
+            "shell = (unset)",
+        ] {
+            assert!(lines.contains(&line), "{line}");
+        }
+    }
+
+    #[test]
+    fn expand_nothing() {
+        let directory = tempdir().unwrap();
+
+        assert_eq!(
+            expand_arguments(
+                arguments(&["turtle", "-j", "1"]),
+                &directory.path().join(".turtlerc")
+            )
+            .unwrap(),
+            arguments(&["turtle", "-j", "1"])
+        );
+    }
+
+    #[test]
+    fn expand_rc_file() {
+        let directory = tempdir().unwrap();
+        let rc_file = directory.path().join(".turtlerc");
+        fs::write(&rc_file, "# comment\n-j 2\n--log-prefix foo\n").unwrap();
+
+        assert_eq!(
+            expand_arguments(arguments(&["turtle", "bar"]), &rc_file).unwrap(),
+            arguments(&["turtle", "-j", "2", "--log-prefix", "foo", "bar"])
+        );
+    }
+
+    #[test]
+    fn skip_rc_file() {
+        let directory = tempdir().unwrap();
+        let rc_file = directory.path().join(".turtlerc");
+        fs::write(&rc_file, "-j 2\n").unwrap();
+
+        assert_eq!(
+            expand_arguments(arguments(&["turtle", "--no-rc"]), &rc_file).unwrap(),
+            arguments(&["turtle", "--no-rc"])
+        );
+    }
+
+    #[test]
+    fn expand_rc_file_in_working_directory() {
+        let directory = tempdir().unwrap();
+        fs::write(directory.path().join(".turtlerc"), "-j 2\n").unwrap();
+        let path = directory.path().display().to_string();
+
+        assert_eq!(
+            expand_arguments(arguments(&["turtle", "-C", &path]), ".turtlerc".as_ref()).unwrap(),
+            arguments(&["turtle", "-j", "2", "-C", &path])
+        );
+    }
+
+    #[test]
+    fn skip_rc_file_by_response_file() {
+        let directory = tempdir().unwrap();
+        let rc_file = directory.path().join(".turtlerc");
+        let response_file = directory.path().join("arguments");
+        fs::write(&rc_file, "-j 2\n").unwrap();
+        fs::write(&response_file, "--no-rc\n").unwrap();
+
+        assert_eq!(
+            expand_arguments(
+                arguments(&["turtle", &format!("@{}", response_file.display())]),
+                &rc_file
+            )
+            .unwrap(),
+            arguments(&["turtle", "--no-rc"])
+        );
+    }
+
+    #[test]
+    fn expand_response_file() {
+        let directory = tempdir().unwrap();
+        let response_file = directory.path().join("arguments");
+        fs::write(&response_file, "-j 2\n").unwrap();
+
+        assert_eq!(
+            expand_arguments(
+                arguments(&["turtle", &format!("@{}", response_file.display()), "foo"]),
+                &directory.path().join(".turtlerc")
+            )
+            .unwrap(),
+            arguments(&["turtle", "-j", "2", "foo"])
+        );
+    }
+
+    #[test]
+    fn fail_to_expand_missing_response_file() {
+        let directory = tempdir().unwrap();
+
+        assert!(expand_arguments(
+            arguments(&["turtle", "@missing"]),
+            &directory.path().join(".turtlerc")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn override_rc_file_arguments() {
+        let directory = tempdir().unwrap();
+        let rc_file = directory.path().join(".turtlerc");
+        fs::write(&rc_file, "-j 2\n--quiet\n").unwrap();
+
+        let arguments = Arguments::parse_from(
+            expand_arguments(arguments(&["turtle", "-j", "4", "--quiet"]), &rc_file).unwrap(),
+        );
+
+        assert_eq!(arguments.job_limit, Some(JobLimit::Count(4)));
+        assert!(arguments.quiet);
+    }
+
+    #[test]
+    fn find_pager() {
+        let arguments = Arguments::parse_from(["turtle", "--pager", "-t", "targets"]);
+
+        assert_eq!(arguments.pager(true, None), Some("less".into()));
+        assert_eq!(
+            arguments.pager(true, Some("more -s")),
+            Some("more -s".into())
+        );
+        assert_eq!(arguments.pager(true, Some("")), Some("less".into()));
+    }
+
+    #[test]
+    fn find_no_pager_without_terminal() {
+        let arguments = Arguments::parse_from(["turtle", "--pager", "-t", "targets"]);
+
+        assert_eq!(arguments.pager(false, Some("less")), None);
+    }
+
+    #[test]
+    fn find_no_pager_for_builds() {
+        for arguments in [
+            vec!["turtle", "--pager"],
+            vec!["turtle", "--pager", "-t", "clean"],
+            vec!["turtle", "-t", "targets"],
+            vec!["turtle", "--pager", "--no-pager", "-t", "targets"],
+        ] {
+            assert_eq!(Arguments::parse_from(arguments).pager(true, None), None);
+        }
+    }
+
+    #[test]
+    fn override_keep_going_with_no_stop() {
+        let arguments = Arguments::parse_from(["turtle", "-k", "3", "--no-stop"]);
+
+        assert!(arguments.no_stop);
+        assert_eq!(arguments.keep_going, 1);
+    }
+
+    #[test]
+    fn override_no_stop_with_keep_going() {
+        let arguments = Arguments::parse_from(["turtle", "--no-stop", "-k", "3"]);
+
+        assert!(!arguments.no_stop);
+        assert_eq!(arguments.keep_going, 3);
+    }
+
+    #[test]
+    fn promote_warnings_to_errors() {
+        assert!(!Arguments::parse_from(["turtle"]).is_warning_error());
+        assert!(Arguments::parse_from(["turtle", "-w", "outref=err"]).is_warning_error());
+        assert!(
+            !Arguments::parse_from(["turtle", "-w", "outref=err", "-w", "outref=warn"])
+                .is_warning_error()
+        );
+        assert!(
+            Arguments::parse_from(["turtle", "-w", "outref=err", "-w", "duprule=warn"])
+                .is_warning_error()
+        );
+    }
+
+    #[test]
+    fn parse_profile_format() {
+        assert_eq!(Arguments::parse_from(["turtle"]).profile_format(), None);
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--profile"]).profile_format(),
+            Some(ProfileFormat::Text)
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--profile", "--profile-format", "chrome"])
+                .profile_format(),
+            Some(ProfileFormat::Chrome)
+        );
+    }
+
+    #[test]
+    fn parse_rule_redefinition() {
+        assert_eq!(
+            Arguments::parse_from(["turtle"]).rule_redefinition(),
+            RuleRedefinition::Error
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "-w", "duprule=warn"]).rule_redefinition(),
+            RuleRedefinition::Last
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "-w", "duprule=first", "-w", "outref=err"])
+                .rule_redefinition(),
+            RuleRedefinition::First
+        );
+    }
+
+    #[test]
+    fn split_outputs() {
+        assert_eq!(
+            Arguments::parse_from(["turtle", "foo", "jobs=2", "bar", "x="]).split_outputs(),
+            (
+                vec!["foo".into(), "bar".into()],
+                vec![("jobs".into(), "2".into()), ("x".into(), "".into())]
+            )
+        );
+    }
+
+    #[test]
+    fn parse_job_limit() {
+        assert_eq!(
+            Arguments::parse_from(["turtle", "-j", "4"]).job_limit,
+            Some(JobLimit::Count(4))
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "-j", "50%"]).job_limit,
+            Some(JobLimit::Percentage(50))
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "-j", "auto"]).job_limit,
+            Some(JobLimit::Auto)
+        );
+        assert!(Arguments::try_parse_from(["turtle", "-j", "0%"]).is_err());
+        assert!(Arguments::try_parse_from(["turtle", "-j", "foo%"]).is_err());
+        assert!(Arguments::try_parse_from(["turtle", "-j", "foo"]).is_err());
+    }
+
+    #[test]
+    fn parse_rule_job_limits() {
+        assert_eq!(
+            Arguments::parse_from([
+                "turtle",
+                "--jobs-for-rule",
+                "link=2",
+                "--jobs-for-rule",
+                "cc=8"
+            ])
+            .jobs_for_rule,
+            vec![
+                RuleJobLimit {
+                    rule: "link".into(),
+                    count: 2
+                },
+                RuleJobLimit {
+                    rule: "cc".into(),
+                    count: 8
+                }
+            ]
+        );
+        assert!(Arguments::try_parse_from(["turtle", "--jobs-for-rule", "link"]).is_err());
+        assert!(Arguments::try_parse_from(["turtle", "--jobs-for-rule", "link=0"]).is_err());
+        assert!(Arguments::try_parse_from(["turtle", "--jobs-for-rule", "=2"]).is_err());
+    }
+
+    #[test]
+    fn parse_output_mode() {
+        assert_eq!(
+            Arguments::parse_from(["turtle"]).output_mode,
+            OutputMode::Plain
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--output-mode", "tree"]).output_mode,
+            OutputMode::Tree
+        );
+        assert!(Arguments::try_parse_from(["turtle", "--output-mode", "foo"]).is_err());
+    }
+
+    #[test]
+    fn parse_schedule() {
+        assert_eq!(Arguments::parse_from(["turtle"]).schedule, Schedule::Fifo);
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--schedule", "fair"]).schedule,
+            Schedule::Fair
+        );
+        assert!(Arguments::try_parse_from(["turtle", "--schedule", "foo"]).is_err());
+    }
+
+    #[test]
+    fn resolve_job_limit() {
+        assert_eq!(JobLimit::Auto.resolve(8), 8);
+        assert_eq!(JobLimit::Count(3).resolve(8), 3);
+        assert_eq!(JobLimit::Percentage(50).resolve(8), 4);
+        assert_eq!(JobLimit::Percentage(50).resolve(3), 1);
+        assert_eq!(JobLimit::Percentage(10).resolve(4), 1);
+        assert_eq!(JobLimit::Percentage(200).resolve(4), 8);
+    }
+
+    #[test]
+    fn parse_shuffle() {
+        assert_eq!(Arguments::parse_from(["turtle"]).shuffle, None);
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--shuffle"]).shuffle,
+            Some(None)
+        );
+        assert_eq!(
+            Arguments::parse_from(["turtle", "--shuffle=42", "foo"]).shuffle,
+            Some(Some(42))
+        );
+    }
+
+    #[test]
+    fn read_environment_variables() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("env");
+        fs::write(&path, "# comment\nFOO=foo\n\nBAR=bar=baz\n").unwrap();
+
+        assert_eq!(
+            read_environment_file(&path).unwrap(),
+            vec![
+                ("FOO".into(), "foo".into()),
+                ("BAR".into(), "bar=baz".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_to_read_invalid_environment_variable() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("env");
+        fs::write(&path, "FOO\n").unwrap();
+
+        assert!(read_environment_file(&path).is_err());
+    }
 }
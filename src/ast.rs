@@ -4,6 +4,7 @@ mod dynamic_build;
 mod dynamic_module;
 mod include;
 mod module;
+mod pool;
 mod rule;
 mod statement;
 mod submodule;
@@ -15,6 +16,7 @@ pub use dynamic_build::*;
 pub use dynamic_module::*;
 pub use include::*;
 pub use module::*;
+pub use pool::*;
 pub use rule::*;
 pub use statement::*;
 pub use submodule::*;
@@ -8,10 +8,12 @@ pub struct Build {
     inputs: Vec<String>,
     implicit_inputs: Vec<String>,
     order_only_inputs: Vec<String>,
+    validations: Vec<String>,
     variable_definitions: Vec<VariableDefinition>,
 }
 
 impl Build {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         outputs: Vec<String>,
         implicit_outputs: Vec<String>,
@@ -19,6 +21,7 @@ impl Build {
         inputs: Vec<String>,
         implicit_inputs: Vec<String>,
         order_only_inputs: Vec<String>,
+        validations: Vec<String>,
         variable_definitions: Vec<VariableDefinition>,
     ) -> Self {
         Self {
@@ -28,6 +31,7 @@ impl Build {
             inputs,
             implicit_inputs,
             order_only_inputs,
+            validations,
             variable_definitions,
         }
     }
@@ -56,6 +60,10 @@ impl Build {
         &self.order_only_inputs
     }
 
+    pub fn validations(&self) -> &[String] {
+        &self.validations
+    }
+
     pub fn variable_definitions(&self) -> &[VariableDefinition] {
         &self.variable_definitions
     }
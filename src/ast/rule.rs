@@ -1,20 +1,42 @@
+use super::VariableDefinition;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Rule {
     name: String,
-    command: String,
+    // A rule whose bindings are inherited
+    base: Option<String>,
+    commands: Vec<String>,
     description: Option<String>,
+    variable_definitions: Vec<VariableDefinition>,
 }
 
 impl Rule {
     pub fn new(
         name: impl Into<String>,
-        command: impl Into<String>,
+        commands: Vec<String>,
         description: Option<String>,
+        variable_definitions: Vec<VariableDefinition>,
     ) -> Self {
         Self {
             name: name.into(),
-            command: command.into(),
+            base: None,
+            commands,
             description,
+            variable_definitions,
+        }
+    }
+
+    // Commands and descriptions are inherited if they are empty or `None`.
+    pub fn new_extension(
+        name: impl Into<String>,
+        base: impl Into<String>,
+        commands: Vec<String>,
+        description: Option<String>,
+        variable_definitions: Vec<VariableDefinition>,
+    ) -> Self {
+        Self {
+            base: Some(base.into()),
+            ..Self::new(name, commands, description, variable_definitions)
         }
     }
 
@@ -22,11 +44,19 @@ impl Rule {
         &self.name
     }
 
-    pub fn command(&self) -> &str {
-        &self.command
+    pub fn base(&self) -> Option<&str> {
+        self.base.as_deref()
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
     }
 
     pub fn description(&self) -> Option<&str> {
         self.description.as_deref()
     }
+
+    pub fn variable_definitions(&self) -> &[VariableDefinition] {
+        &self.variable_definitions
+    }
 }
@@ -2,6 +2,8 @@
 pub struct VariableDefinition {
     name: String,
     value: String,
+    // Immediate definitions of `:=` are evaluated where they are defined.
+    immediate: bool,
 }
 
 impl VariableDefinition {
@@ -9,6 +11,14 @@ impl VariableDefinition {
         Self {
             name: name.into(),
             value: value.into(),
+            immediate: false,
+        }
+    }
+
+    pub fn new_immediate(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            immediate: true,
+            ..Self::new(name, value)
         }
     }
 
@@ -19,4 +29,8 @@ impl VariableDefinition {
     pub fn value(&self) -> &str {
         &self.value
     }
+
+    pub fn is_immediate(&self) -> bool {
+        self.immediate
+    }
 }
@@ -27,7 +27,11 @@ impl BuildGraph {
             primary_outputs: HashMap::new(),
         };
 
-        for (output, build) in outputs {
+        // Outputs are sorted so that the same cycles are reported every time.
+        for (output, build) in outputs
+            .iter()
+            .sorted_by(|(one, _), (other, _)| one.cmp(other))
+        {
             for input in build.inputs().iter().chain(build.order_only_inputs()) {
                 this.add_edge(output.clone(), input.clone());
             }
@@ -139,9 +143,10 @@ mod tests {
         Build::new(
             outputs,
             vec![],
-            Rule::new("", None).into(),
+            Rule::new("foo".into(), vec!["".into()], Default::default()).into(),
             inputs,
             vec![],
+            vec![],
             None,
         )
     }
@@ -190,9 +195,10 @@ mod tests {
                     Build::new(
                         vec!["foo".into()],
                         vec![],
-                        Rule::new("", None).into(),
+                        Rule::new("foo".into(), vec!["".into()], Default::default()).into(),
                         vec![],
                         vec!["bar".into()],
+                        vec![],
                         None
                     )
                     .into()
@@ -228,9 +234,10 @@ mod tests {
                     Build::new(
                         vec!["foo".into()],
                         vec![],
-                        Rule::new("", None).into(),
+                        Rule::new("foo".into(), vec!["".into()], Default::default()).into(),
                         vec![],
                         vec!["foo".into()],
+                        vec![],
                         None
                     )
                     .into()
@@ -291,6 +298,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn report_same_cycle_repeatedly() {
+        let validate = || {
+            validate_builds(
+                &[
+                    (
+                        "foo".into(),
+                        explicit_build(vec!["foo".into()], vec!["bar".into()]).into(),
+                    ),
+                    (
+                        "bar".into(),
+                        explicit_build(vec!["bar".into()], vec!["baz".into()]).into(),
+                    ),
+                    (
+                        "baz".into(),
+                        explicit_build(vec!["baz".into()], vec!["foo".into()]).into(),
+                    ),
+                    (
+                        "qux".into(),
+                        explicit_build(vec!["qux".into()], vec!["quux".into()]).into(),
+                    ),
+                    (
+                        "quux".into(),
+                        explicit_build(vec!["quux".into()], vec!["corge".into()]).into(),
+                    ),
+                    (
+                        "corge".into(),
+                        explicit_build(vec!["corge".into()], vec!["qux".into()]).into(),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            )
+        };
+        let error = validate();
+
+        assert!(error.is_err());
+
+        for _ in 0..100 {
+            assert_eq!(validate(), error);
+        }
+    }
+
     #[test]
     fn validate_with_dynamic_configuration() {
         let mut graph = BuildGraph::new(
@@ -0,0 +1,178 @@
+use crate::ir::{Build, Configuration, Rule};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const COMPILATION_DATABASE_RULE: &str = "compdb";
+
+#[derive(Deserialize)]
+struct Entry {
+    directory: String,
+    file: String,
+    command: Option<String>,
+    arguments: Option<Vec<String>>,
+    output: Option<String>,
+}
+
+// This builds a configuration from a compilation database (e.g.
+// `compile_commands.json`) where each entry is a build of its output from its
+// file. Paths are made relative to a working directory if possible.
+pub fn compile(source: &str, working_directory: &Path) -> Result<Configuration, Box<dyn Error>> {
+    let entries = serde_json::from_str::<Vec<Entry>>(source)
+        .map_err(|error| format!("invalid compilation database: {error}"))?;
+    let mut outputs = HashMap::<Arc<str>, Arc<Build>>::new();
+
+    for entry in entries {
+        let Some(output) = &entry.output else {
+            return Err(format!(
+                "output missing for file \"{}\" in compilation database",
+                entry.file
+            )
+            .into());
+        };
+        let output = resolve_path(&entry.directory, output, working_directory);
+        let command = if let Some(command) = &entry.command {
+            command.clone()
+        } else if let Some(arguments) = &entry.arguments {
+            arguments
+                .iter()
+                .map(|argument| quote(argument))
+                .collect::<Vec<_>>()
+                .join(" ")
+        } else {
+            return Err(format!(
+                "command missing for file \"{}\" in compilation database",
+                entry.file
+            )
+            .into());
+        };
+
+        let build = Build::new(
+            vec![output.clone()],
+            vec![],
+            Some(Rule::new(
+                COMPILATION_DATABASE_RULE.into(),
+                vec![format!("cd {} && {command}", quote(&entry.directory))],
+                Default::default(),
+            )),
+            vec![resolve_path(
+                &entry.directory,
+                &entry.file,
+                working_directory,
+            )],
+            vec![],
+            vec![],
+            None,
+        );
+
+        if outputs.insert(output.clone(), build.into()).is_some() {
+            return Err(format!("output \"{output}\" duplicated in compilation database").into());
+        }
+    }
+
+    let default_outputs = outputs.keys().cloned().collect();
+
+    Ok(Configuration::new(
+        outputs,
+        default_outputs,
+        Default::default(),
+        Default::default(),
+        None,
+    ))
+}
+
+fn resolve_path(directory: &str, path: &str, working_directory: &Path) -> Arc<str> {
+    let path = Path::new(directory).join(path);
+
+    path.strip_prefix(working_directory)
+        .map(PathBuf::from)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+        .into()
+}
+
+fn quote(argument: &str) -> String {
+    if !argument.is_empty()
+        && argument
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "-_./=+,:@%".contains(character))
+    {
+        argument.into()
+    } else {
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_entry_with_command() {
+        let configuration = compile(
+            r#"[{
+                "directory": "/project/build",
+                "command": "cc -c -o foo.o ../foo.c",
+                "file": "../foo.c",
+                "output": "foo.o"
+            }]"#,
+            Path::new("/project/build"),
+        )
+        .unwrap();
+        let build = &configuration.outputs()["foo.o"];
+
+        assert_eq!(build.inputs(), &["../foo.c".into()]);
+        assert_eq!(
+            build.rule().unwrap().commands(),
+            &["cd /project/build && cc -c -o foo.o ../foo.c".to_string()]
+        );
+        assert!(configuration.default_outputs().contains("foo.o"));
+    }
+
+    #[test]
+    fn compile_entry_with_arguments() {
+        let configuration = compile(
+            r#"[{
+                "directory": "/project",
+                "arguments": ["cc", "-DFOO=\"foo bar\"", "-c", "foo.c"],
+                "file": "/project/foo.c",
+                "output": "out/foo.o"
+            }]"#,
+            Path::new("/other"),
+        )
+        .unwrap();
+        let build = &configuration.outputs()["/project/out/foo.o"];
+
+        assert_eq!(build.inputs(), &["/project/foo.c".into()]);
+        assert_eq!(
+            build.rule().unwrap().commands(),
+            &["cd /project && cc '-DFOO=\"foo bar\"' -c foo.c".to_string()]
+        );
+    }
+
+    #[test]
+    fn fail_to_compile_entry_without_output() {
+        assert!(compile(
+            r#"[{ "directory": "/", "command": "cc foo.c", "file": "foo.c" }]"#,
+            Path::new("/"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn fail_to_compile_duplicate_outputs() {
+        assert!(compile(
+            r#"[
+                { "directory": "/", "command": "cc foo.c", "file": "foo.c", "output": "foo.o" },
+                { "directory": "/", "command": "cc bar.c", "file": "bar.c", "output": "foo.o" }
+            ]"#,
+            Path::new("/"),
+        )
+        .is_err());
+    }
+}
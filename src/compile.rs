@@ -1,21 +1,39 @@
 mod context;
 mod error;
+mod expansion_layer;
 mod global_state;
 mod module_state;
+mod options;
+mod quote;
+mod reference;
 
-pub use self::error::CompileError;
-use self::{context::Context, global_state::GlobalState, module_state::ModuleState};
+use self::{
+    context::{Context, Selection},
+    global_state::GlobalState,
+    module_state::ModuleState,
+    reference::{find_references, Reference},
+};
+pub use self::{
+    error::CompileError,
+    expansion_layer::ExpansionLayer,
+    options::{Options, RuleRedefinition},
+    quote::Shell,
+};
 use crate::{
     ast,
-    ir::{Build, Configuration, DynamicBuild, DynamicConfiguration, Rule},
+    ir::{
+        Build, Configuration, DynamicBuild, DynamicConfiguration, Pool, ResponseFile, Rule,
+        RuleOptions, TEMPORARY_DIRECTORY_PLACEHOLDER,
+    },
     module_dependency::ModuleDependencyMap,
 };
-use once_cell::sync::Lazy;
-use regex::{Captures, Regex};
+use encoding_rs::Encoding;
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     sync::Arc,
+    thread,
 };
 use train_map::TrainMap;
 
@@ -23,9 +41,36 @@ const PHONY_RULE: &str = "phony";
 const BUILD_DIRECTORY_VARIABLE: &str = "builddir";
 const DYNAMIC_MODULE_VARIABLE: &str = "dyndep";
 const SOURCE_VARIABLE_NAME: &str = "srcdep";
+const TAGS_VARIABLE: &str = "tags";
+const RAW_VARIABLE: &str = "raw";
+const ALWAYS_VARIABLE: &str = "always";
+const MKDIR_VARIABLE: &str = "mkdir";
+const NO_OUTPUT_VARIABLE: &str = "no_output";
+const SHELL_VARIABLE: &str = "shell";
+const PINNED_MODIFIED_TIME_VARIABLE: &str = "pin_mtime";
+const STATUS_VARIABLE: &str = "status";
+const DESCRIPTION_VARIABLE: &str = "description";
+const DEPFILE_VARIABLE: &str = "depfile";
+const DEPS_VARIABLE: &str = "deps";
+const MSVC_DEPS_PREFIX_VARIABLE: &str = "msvc_deps_prefix";
+const ENVIRONMENT_VARIABLE: &str = "env";
+const ENVIRONMENT_DEPENDENCIES_VARIABLE: &str = "env_deps";
+const OUTPUT_ENCODING_VARIABLE: &str = "output_encoding";
+const REQUIRED_VERSION_VARIABLE: &str = "turtle_required_version";
+const POOL_VARIABLE: &str = "pool";
+const RESPONSE_FILE_VARIABLE: &str = "rspfile";
+const RESPONSE_FILE_CONTENT_VARIABLE: &str = "rspfile_content";
+const GENERATOR_VARIABLE: &str = "generator";
+const TEMPORARY_DIRECTORY_VARIABLE: &str = "tmpdir";
+const CONSOLE_POOL: &str = "console";
+const DEFAULT_MSVC_DEPS_PREFIX: &str = "Note: including file: ";
 
-static VARIABLE_PATTERN: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"\$([[:alpha:]_][[:alnum:]_]*)").unwrap());
+// Configurations, non-fatal errors, and traced expansion of a command
+type CompiledModules = (
+    Configuration,
+    Vec<CompileError>,
+    Option<Vec<ExpansionLayer>>,
+);
 
 // TODO Use a string pool for paths.
 pub fn compile(
@@ -33,20 +78,217 @@ pub fn compile(
     dependencies: &ModuleDependencyMap,
     root_module_path: &Path,
 ) -> Result<Configuration, CompileError> {
-    let context = Context::new(modules, dependencies);
+    let (configuration, errors) = compile_with_errors(
+        modules,
+        dependencies,
+        root_module_path,
+        &[],
+        None,
+        Default::default(),
+    )?;
+
+    if let Some(error) = errors.into_iter().find(|error| !error.is_warning()) {
+        return Err(error);
+    }
+
+    Ok(configuration)
+}
+
+// Compile modules skipping invalid builds and collecting their errors. Given
+// variables override the ones defined in the modules. Outputs of non-phony
+// builds are relocated under an output root if it is given.
+pub fn compile_with_errors(
+    modules: &HashMap<PathBuf, ast::Module>,
+    dependencies: &ModuleDependencyMap,
+    root_module_path: &Path,
+    variables: &[(String, String)],
+    output_root: Option<&Path>,
+    options: Options,
+) -> Result<(Configuration, Vec<CompileError>), CompileError> {
+    let (configuration, errors, _) = compile_configuration(
+        &create_context(
+            modules,
+            dependencies,
+            root_module_path,
+            variables,
+            output_root,
+            None,
+            options,
+        )?,
+        root_module_path,
+    )?;
+
+    Ok((configuration, errors))
+}
+
+// Compile modules tracing expansion of commands of a build for an output layer
+// by layer from raw commands of its rule to final ones. It returns `None` if
+// the output is not found or phony.
+pub fn trace_command(
+    modules: &HashMap<PathBuf, ast::Module>,
+    dependencies: &ModuleDependencyMap,
+    root_module_path: &Path,
+    variables: &[(String, String)],
+    output_root: Option<&Path>,
+    output: &str,
+    options: Options,
+) -> Result<Option<Vec<ExpansionLayer>>, CompileError> {
+    Ok(compile_configuration(
+        &create_context(
+            modules,
+            dependencies,
+            root_module_path,
+            variables,
+            output_root,
+            Some(output),
+            options,
+        )?,
+        root_module_path,
+    )?
+    .2)
+}
+
+// Compile only builds which given outputs depend on transitively. Outputs and
+// inputs of all builds are indexed first without compiling their rules which
+// is most of compilation.
+pub fn compile_lazily(
+    modules: &HashMap<PathBuf, ast::Module>,
+    dependencies: &ModuleDependencyMap,
+    root_module_path: &Path,
+    variables: &[(String, String)],
+    outputs: &[String],
+    options: Options,
+) -> Result<(Configuration, Vec<CompileError>), CompileError> {
+    let compile = |selection| {
+        compile_configuration(
+            &Context::new(
+                modules,
+                dependencies,
+                variables,
+                None,
+                Default::default(),
+                None,
+                selection,
+                options,
+                num_cpus::get(),
+            ),
+            root_module_path,
+        )
+    };
+    let (index, _, _) = compile(Selection::Index)?;
+    let (configuration, errors, _) =
+        compile(Selection::Outputs(find_reachable_outputs(&index, outputs)))?;
 
+    Ok((configuration, errors))
+}
+
+// Validations and dynamic dependency files are reachable as well as inputs.
+fn find_reachable_outputs(configuration: &Configuration, outputs: &[String]) -> HashSet<String> {
+    let mut pending = outputs.to_vec();
+    let mut reachable = HashSet::new();
+
+    while let Some(output) = pending.pop() {
+        if let Some(build) = configuration.outputs().get(output.as_str()) {
+            if reachable.insert(output) {
+                pending.extend(
+                    build
+                        .inputs()
+                        .iter()
+                        .chain(build.order_only_inputs())
+                        .chain(build.validations())
+                        .chain(build.dynamic_module())
+                        .map(|input| input.to_string()),
+                );
+            }
+        }
+    }
+
+    reachable
+}
+
+fn create_context<'a>(
+    modules: &'a HashMap<PathBuf, ast::Module>,
+    dependencies: &'a ModuleDependencyMap,
+    root_module_path: &Path,
+    variables: &'a [(String, String)],
+    output_root: Option<&'a Path>,
+    traced_output: Option<&'a str>,
+    options: Options,
+) -> Result<Context<'a>, CompileError> {
+    // Outputs are collected in advance as builds can refer to ones defined
+    // after them.
+    let relocated_outputs = if output_root.is_some() {
+        compile_configuration(
+            &Context::new(
+                modules,
+                dependencies,
+                variables,
+                None,
+                Default::default(),
+                None,
+                Default::default(),
+                options,
+                num_cpus::get(),
+            ),
+            root_module_path,
+        )?
+        .0
+        .outputs()
+        .iter()
+        .filter(|(_, build)| build.rule().is_some())
+        .map(|(output, _)| output.to_string())
+        .collect()
+    } else {
+        Default::default()
+    };
+
+    Ok(Context::new(
+        modules,
+        dependencies,
+        variables,
+        output_root,
+        relocated_outputs,
+        traced_output,
+        Default::default(),
+        options,
+        num_cpus::get(),
+    ))
+}
+
+fn compile_configuration(
+    context: &Context,
+    root_module_path: &Path,
+) -> Result<CompiledModules, CompileError> {
     let mut global_state = GlobalState {
         outputs: Default::default(),
         default_outputs: Default::default(),
         source_map: Default::default(),
+        tags: Default::default(),
+        pools: [(CONSOLE_POOL.into(), Pool::new(CONSOLE_POOL, 1))]
+            .into_iter()
+            .collect(),
+        errors: vec![],
+        expansion_layers: None,
     };
     let mut module_state = ModuleState {
         rules: TrainMap::new(),
+        rule_paths: TrainMap::new(),
         variables: TrainMap::new(),
     };
 
+    module_state.variables.insert(
+        TEMPORARY_DIRECTORY_VARIABLE,
+        TEMPORARY_DIRECTORY_PLACEHOLDER.into(),
+    );
+    module_state.variables.extend(
+        context
+            .variables()
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str().into())),
+    );
+
     compile_module(
-        &context,
+        context,
         &mut global_state,
         &mut module_state,
         root_module_path,
@@ -58,14 +300,34 @@ pub fn compile(
         global_state.default_outputs
     };
 
-    Ok(Configuration::new(
-        global_state.outputs,
-        default_outputs,
-        global_state.source_map,
-        module_state
-            .variables
-            .get(BUILD_DIRECTORY_VARIABLE)
-            .cloned(),
+    let build_directory = module_state
+        .variables
+        .get(BUILD_DIRECTORY_VARIABLE)
+        .cloned();
+    // Build databases are placed in output roots not to write source trees.
+    let build_directory = if let Some(root) = context.output_root() {
+        Some(
+            normalize_path(&format!(
+                "{}/{}",
+                root.display(),
+                build_directory.as_deref().unwrap_or_default()
+            ))
+            .into(),
+        )
+    } else {
+        build_directory
+    };
+
+    Ok((
+        Configuration::new(
+            global_state.outputs,
+            default_outputs,
+            global_state.source_map,
+            global_state.tags,
+            build_directory,
+        ),
+        global_state.errors,
+        global_state.expansion_layers,
     ))
 }
 
@@ -80,80 +342,387 @@ fn compile_module<'a>(
         .get(path)
         .ok_or_else(|| CompileError::ModuleNotFound(path.into()))?;
 
+    let mut submodules = vec![];
+
     for statement in module.statements() {
+        if let ast::Statement::Submodule(submodule) = statement {
+            submodules.push(submodule.path());
+            continue;
+        }
+
+        compile_submodules(context, global_state, module_state, path, &submodules)?;
+        submodules.clear();
+
         match statement {
             ast::Statement::Build(build) => {
+                // Paths are expanded with variables of modules and builds but not rules.
+                let mut path_variables = module_state.variables.fork();
+
+                path_variables.extend(build.variable_definitions().iter().map(|definition| {
+                    (
+                        definition.name(),
+                        evaluate_definition(definition, &module_state.variables),
+                    )
+                }));
+
+                let expand_paths = |paths: &[String]| {
+                    paths
+                        .iter()
+                        .map(|path| {
+                            relocate_path(
+                                context,
+                                normalize_path(&interpolate_variables(path, &path_variables)),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let outputs = expand_paths(build.outputs());
+                let implicit_outputs = expand_paths(build.implicit_outputs());
+
+                if let Selection::Outputs(selected_outputs) = context.selection() {
+                    if !outputs
+                        .iter()
+                        .chain(&implicit_outputs)
+                        .any(|output| selected_outputs.contains(output))
+                    {
+                        continue;
+                    }
+                }
+
+                let rule = if build.rule() == PHONY_RULE && !context.options().no_builtin_rules
+                    || matches!(context.selection(), Selection::Index)
+                {
+                    None
+                } else {
+                    match resolve_rule(&module_state.rules, build.rule()) {
+                        Ok(rule) => Some(rule),
+                        Err(error) => {
+                            global_state.errors.push(error);
+                            continue;
+                        }
+                    }
+                };
+                let rule = rule.as_deref();
+
+                let inputs = expand_paths(build.inputs());
+                let implicit_inputs = expand_paths(build.implicit_inputs());
+                let order_only_inputs = expand_paths(build.order_only_inputs());
+                let validations = expand_paths(build.validations());
+
                 let mut variables = module_state.variables.fork();
 
                 variables.extend(
-                    build
-                        .variable_definitions()
-                        .iter()
-                        .map(|definition| (definition.name(), definition.value().into()))
-                        .chain([
-                            ("in", build.inputs().join(" ").into()),
-                            ("out", build.outputs().join(" ").into()),
-                        ]),
+                    rule.iter()
+                        .flat_map(|rule| rule.variable_definitions())
+                        .chain(build.variable_definitions())
+                        .map(|definition| {
+                            (
+                                definition.name(),
+                                evaluate_definition(definition, &module_state.variables),
+                            )
+                        }),
                 );
 
+                // Shells are defined by `shell.<name>` variables as words of
+                // commands followed by commands of rules.
+                let shell = match rule
+                    .and_then(|_| variables.get(SHELL_VARIABLE))
+                    .map(|name| interpolate_variables(name, &variables))
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        variables
+                            .get(format!("{SHELL_VARIABLE}.{name}").as_str())
+                            .map(|shell| {
+                                interpolate_variables(shell, &variables)
+                                    .split_whitespace()
+                                    .map(From::from)
+                                    .collect::<Vec<String>>()
+                            })
+                            .filter(|words| !words.is_empty())
+                            .ok_or_else(|| CompileError::ShellNotFound(build.rule().into(), name))
+                    })
+                    .transpose()
+                {
+                    Ok(shell) => shell,
+                    Err(error) => {
+                        global_state.errors.push(error);
+                        continue;
+                    }
+                };
+                let quoting_shell = shell
+                    .as_deref()
+                    .map(Shell::from_command)
+                    .unwrap_or(context.options().shell);
+                let raw = variables
+                    .get(RAW_VARIABLE)
+                    .map(|value| value.as_ref() == "1")
+                    .unwrap_or_default();
+                // Paths are escaped as values of variables are interpolated.
+                let quote = |paths: &[String], separator| {
+                    paths
+                        .iter()
+                        .map(|path| {
+                            if raw {
+                                path.clone()
+                            } else {
+                                quoting_shell.quote(path)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(separator)
+                        .replace('$', "$$")
+                };
+
+                let traced = context.traced_output().is_some_and(|traced| {
+                    outputs
+                        .iter()
+                        .chain(&implicit_outputs)
+                        .any(|output| output == traced)
+                });
+                let mut expansion_layers = vec![];
+
+                if let Some(rule) = rule.filter(|_| traced) {
+                    let mut rule_variables = module_state.variables.fork();
+
+                    rule_variables.extend(rule.variable_definitions().iter().map(|definition| {
+                        (
+                            definition.name(),
+                            evaluate_definition(definition, &module_state.variables),
+                        )
+                    }));
+
+                    let expand = |variables: &TrainMap<&str, Arc<str>>| {
+                        rule.commands()
+                            .iter()
+                            .map(|command| interpolate_defined_variables(command, variables))
+                            .collect()
+                    };
+
+                    expansion_layers.extend([
+                        ExpansionLayer::new("rule", rule.commands().to_vec()),
+                        ExpansionLayer::new("global variables", expand(&module_state.variables)),
+                        ExpansionLayer::new("rule variables", expand(&rule_variables)),
+                        ExpansionLayer::new("build variables", expand(&variables)),
+                    ]);
+                }
+
+                // Indexes do not need commands.
+                if !matches!(context.selection(), Selection::Index) {
+                    variables.extend([
+                        ("in", quote(&inputs, " ").into()),
+                        ("in_newline", quote(&inputs, "\n").into()),
+                        ("out", quote(&outputs, " ").into()),
+                    ]);
+                }
+
+                if let Some(rule) = rule.filter(|_| traced) {
+                    expansion_layers.push(ExpansionLayer::new(
+                        "$in and $out",
+                        rule.commands()
+                            .iter()
+                            .map(|command| interpolate_variables(command, &variables))
+                            .collect(),
+                    ));
+                    global_state.expansion_layers = Some(expansion_layers);
+                }
+
+                let rule = match rule
+                    .map(|rule| {
+                        let depfile = variables
+                            .get(DEPFILE_VARIABLE)
+                            .map(|depfile| interpolate_variables(depfile, &variables));
+                        let msvc_deps_prefix =
+                            compile_deps(build.rule(), depfile.as_deref(), &variables)?;
+                        let pool = variables
+                            .get(POOL_VARIABLE)
+                            .map(|name| interpolate_variables(name, &variables))
+                            .filter(|name| !name.is_empty())
+                            .map(|name| {
+                                global_state
+                                    .pools
+                                    .get(name.as_str())
+                                    .cloned()
+                                    .ok_or(CompileError::PoolNotFound(name))
+                            })
+                            .transpose()?;
+                        let response_file = variables
+                            .get(RESPONSE_FILE_VARIABLE)
+                            .map(|path| interpolate_variables(path, &variables))
+                            .filter(|path| !path.is_empty())
+                            .map(|path| {
+                                ResponseFile::new(
+                                    path,
+                                    variables
+                                        .get(RESPONSE_FILE_CONTENT_VARIABLE)
+                                        .map(|content| interpolate_variables(content, &variables))
+                                        .unwrap_or_default(),
+                                )
+                            });
+                        let output_encoding = variables
+                            .get(OUTPUT_ENCODING_VARIABLE)
+                            .map(|label| {
+                                let label = interpolate_variables(label, &variables);
+
+                                Encoding::for_label(label.as_bytes()).ok_or_else(|| {
+                                    CompileError::UnknownEncoding(build.rule().into(), label)
+                                })
+                            })
+                            .transpose()?;
+                        let pinned_modified_time = variables
+                            .get(PINNED_MODIFIED_TIME_VARIABLE)
+                            .map(|time| interpolate_variables(time, &variables))
+                            .filter(|time| !time.is_empty())
+                            .map(|time| {
+                                time.parse().map_err(|_| {
+                                    CompileError::InvalidModifiedTime(build.rule().into(), time)
+                                })
+                            })
+                            .transpose()?;
+
+                        let environment = variables
+                            .get(ENVIRONMENT_VARIABLE)
+                            .map(|environment| {
+                                parse_environment(&interpolate_variables(environment, &variables))
+                                    .map_err(|variable| {
+                                        CompileError::InvalidEnvironment(
+                                            build.rule().into(),
+                                            variable,
+                                        )
+                                    })
+                            })
+                            .transpose()?
+                            .unwrap_or_default();
+
+                        Ok(Rule::new(
+                            build.rule().into(),
+                            rule.commands()
+                                .iter()
+                                .map(|command| interpolate_variables(command, &variables))
+                                .collect(),
+                            RuleOptions {
+                                // Descriptions of builds override the ones of rules.
+                                description: if build
+                                    .variable_definitions()
+                                    .iter()
+                                    .any(|definition| definition.name() == DESCRIPTION_VARIABLE)
+                                {
+                                    variables
+                                        .get(DESCRIPTION_VARIABLE)
+                                        .map(|value| value.as_ref())
+                                } else {
+                                    rule.description()
+                                }
+                                .map(|description| interpolate_variables(description, &variables)),
+                                status: variables
+                                    .get(STATUS_VARIABLE)
+                                    .map(|status| interpolate_variables(status, &variables)),
+                                depfile,
+                                msvc_deps_prefix,
+                                environment,
+                                output_encoding,
+                                always: variables
+                                    .get(ALWAYS_VARIABLE)
+                                    .map(|value| value.as_ref() == "1")
+                                    .unwrap_or_default(),
+                                mkdir: variables
+                                    .get(MKDIR_VARIABLE)
+                                    .map(|value| value.as_ref() != "0")
+                                    .unwrap_or(true),
+                                pool,
+                                response_file,
+                                pinned_modified_time,
+                                no_output: variables
+                                    .get(NO_OUTPUT_VARIABLE)
+                                    .map(|value| value.as_ref() == "1")
+                                    .unwrap_or_default(),
+                                shell: shell.clone(),
+                                environment_dependencies: variables
+                                    .get(ENVIRONMENT_DEPENDENCIES_VARIABLE)
+                                    .map(|names| {
+                                        interpolate_variables(names, &variables)
+                                            .split_whitespace()
+                                            .map(From::from)
+                                            .collect()
+                                    })
+                                    .unwrap_or_default(),
+                            },
+                        ))
+                    })
+                    .transpose()
+                {
+                    Ok(rule) => rule,
+                    Err(error) => {
+                        global_state.errors.push(error);
+                        continue;
+                    }
+                };
+
                 let ir = Arc::new(Build::new(
-                    build
-                        .outputs()
+                    outputs.iter().map(|path| path.as_str().into()).collect(),
+                    implicit_outputs
                         .iter()
-                        .map(|string| string.as_str().into())
+                        .map(|path| path.as_str().into())
                         .collect(),
-                    build
-                        .implicit_outputs()
+                    rule,
+                    inputs
                         .iter()
-                        .map(|string| string.as_str().into())
+                        .chain(&implicit_inputs)
+                        .map(|path| path.as_str().into())
                         .collect(),
-                    if build.rule() == PHONY_RULE {
-                        None
-                    } else {
-                        let rule = &module_state
-                            .rules
-                            .get(build.rule())
-                            .ok_or_else(|| CompileError::RuleNotFound(build.rule().into()))?;
-
-                        Some(Rule::new(
-                            interpolate_variables(rule.command(), &variables),
-                            rule.description()
-                                .map(|description| interpolate_variables(description, &variables)),
-                        ))
-                    },
-                    build
-                        .inputs()
+                    order_only_inputs
                         .iter()
-                        .chain(build.implicit_inputs())
-                        .map(|string| string.as_str().into())
+                        .map(|path| path.as_str().into())
                         .collect(),
-                    build
-                        .order_only_inputs()
+                    validations
                         .iter()
-                        .map(|string| string.as_str().into())
+                        .map(|path| path.as_str().into())
                         .collect(),
-                    variables.get(DYNAMIC_MODULE_VARIABLE).cloned(),
+                    variables
+                        .get(DYNAMIC_MODULE_VARIABLE)
+                        .map(|path| relocate_path(context, normalize_path(path)).into()),
                 ));
 
-                let outputs = || build.outputs().iter().chain(build.implicit_outputs());
+                for output in outputs.iter().chain(&implicit_outputs) {
+                    if global_state.outputs.contains_key(output.as_str()) {
+                        global_state
+                            .errors
+                            .push(CompileError::DuplicateOutput(output.clone()));
+                    }
 
-                global_state
-                    .outputs
-                    .extend(outputs().map(|output| (output.as_str().into(), ir.clone())));
+                    global_state
+                        .outputs
+                        .insert(output.as_str().into(), ir.clone());
+                }
+
+                if let Some(tags) = variables.get(TAGS_VARIABLE) {
+                    global_state.tags.insert(
+                        ir.id(),
+                        interpolate_variables(tags, &variables)
+                            .split_whitespace()
+                            .map(From::from)
+                            .collect(),
+                    );
+                }
 
                 if let Some(source) = variables.get(SOURCE_VARIABLE_NAME) {
-                    global_state
-                        .source_map
-                        .extend(outputs().map(|output| (output.as_str().into(), source.clone())));
+                    global_state.source_map.extend(
+                        outputs
+                            .iter()
+                            .chain(&implicit_outputs)
+                            .map(|output| (output.as_str().into(), source.clone())),
+                    );
                 }
             }
             ast::Statement::Default(default) => {
-                global_state.default_outputs.extend(
-                    default
-                        .outputs()
-                        .iter()
-                        .map(|string| string.as_str().into()),
-                );
+                global_state
+                    .default_outputs
+                    .extend(default.outputs().iter().map(|string| {
+                        relocate_path(
+                            context,
+                            normalize_path(&interpolate_variables(string, &module_state.variables)),
+                        )
+                        .into()
+                    }));
             }
             ast::Statement::Include(include) => {
                 compile_module(
@@ -163,28 +732,348 @@ fn compile_module<'a>(
                     resolve_dependency(context, path, include.path())?,
                 )?;
             }
-            ast::Statement::Rule(rule) => {
-                module_state.rules.insert(rule.name(), rule.clone());
+            ast::Statement::Pool(pool) => {
+                let depth = interpolate_variables(pool.depth(), &module_state.variables);
+
+                match depth.parse().ok().filter(|&depth| depth > 0) {
+                    Some(depth) => {
+                        global_state
+                            .pools
+                            .insert(pool.name().into(), Pool::new(pool.name(), depth));
+                    }
+                    None => global_state
+                        .errors
+                        .push(CompileError::InvalidPoolDepth(pool.name().into(), depth)),
+                }
             }
-            ast::Statement::Submodule(submodule) => {
-                compile_module(
-                    context,
-                    global_state,
-                    &mut module_state.fork(),
-                    resolve_dependency(context, path, submodule.path())?,
-                )?;
+            ast::Statement::Rule(rule) => {
+                // Commands of bases are checked on their definitions.
+                if !(rule.base().is_some() && rule.commands().is_empty() || references_output(rule))
+                {
+                    global_state
+                        .errors
+                        .push(CompileError::OutputNotReferenced(rule.name().into()));
+                }
+
+                // Submodules can shadow rules of their parents.
+                if let Some(previous_path) =
+                    module_state.rule_paths.insert(rule.name(), path.into())
+                {
+                    match context.options().rule_redefinition {
+                        RuleRedefinition::Error => {
+                            global_state.errors.push(CompileError::DuplicateRule(
+                                rule.name().into(),
+                                previous_path,
+                                path.into(),
+                            ))
+                        }
+                        RuleRedefinition::First => {
+                            global_state.errors.push(CompileError::RuleRedefined(
+                                rule.name().into(),
+                                path.into(),
+                                previous_path.clone(),
+                            ));
+                            module_state.rule_paths.insert(rule.name(), previous_path);
+                            continue;
+                        }
+                        RuleRedefinition::Last => {
+                            global_state.errors.push(CompileError::RuleRedefined(
+                                rule.name().into(),
+                                previous_path,
+                                path.into(),
+                            ))
+                        }
+                    }
+                }
+
+                let definitions = rule
+                    .variable_definitions()
+                    .iter()
+                    .map(|definition| {
+                        ast::VariableDefinition::new(
+                            definition.name(),
+                            evaluate_definition(definition, &module_state.variables).as_ref(),
+                        )
+                    })
+                    .collect();
+
+                let commands = rule.commands().to_vec();
+                let description = rule.description().map(From::from);
+
+                module_state.rules.insert(
+                    rule.name(),
+                    if let Some(base) = rule.base() {
+                        ast::Rule::new_extension(
+                            rule.name(),
+                            base,
+                            commands,
+                            description,
+                            definitions,
+                        )
+                    } else {
+                        ast::Rule::new(rule.name(), commands, description, definitions)
+                    },
+                );
             }
+            ast::Statement::Submodule(_) => unreachable!(),
             ast::Statement::VariableDefinition(definition) => {
-                module_state
-                    .variables
-                    .insert(definition.name(), definition.value().into());
+                if definition.name() == REQUIRED_VERSION_VARIABLE {
+                    check_version(definition.value(), env!("CARGO_PKG_VERSION"))?;
+                }
+
+                if !context
+                    .variables()
+                    .iter()
+                    .any(|(name, _)| name == definition.name())
+                {
+                    let value = evaluate_definition(definition, &module_state.variables);
+
+                    module_state.variables.insert(definition.name(), value);
+                }
             }
         }
     }
 
+    compile_submodules(context, global_state, module_state, path, &submodules)
+}
+
+// Submodules copy scopes of their parents while included modules share them.
+// So consecutive submodules are compiled in parallel as they only read the
+// same scopes. Submodules defining pools are compiled sequentially as later
+// modules can refer to the pools.
+fn compile_submodules<'a>(
+    context: &'a Context,
+    global_state: &mut GlobalState,
+    module_state: &ModuleState<'a, '_>,
+    path: &Path,
+    submodules: &[&str],
+) -> Result<(), CompileError> {
+    let paths = submodules
+        .iter()
+        .map(|submodule| resolve_dependency(context, path, submodule))
+        .collect::<Result<Vec<_>, _>>();
+    let thread_count = match &paths {
+        Ok(paths) if paths.len() > 1 => context.acquire_threads(paths.len()),
+        _ => 0,
+    };
+
+    if thread_count < 2
+        || paths.as_ref().map_or(true, |paths| {
+            paths.iter().any(|path| defines_pool(context, path))
+        })
+    {
+        context.release_threads(thread_count);
+
+        for submodule in submodules {
+            compile_module(
+                context,
+                global_state,
+                &mut module_state.fork(),
+                resolve_dependency(context, path, submodule)?,
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    let paths = paths?;
+    let states = thread::scope(|scope| {
+        paths
+            .chunks(paths.len().div_ceil(thread_count))
+            .map(|paths| {
+                let mut state = global_state.fork();
+
+                scope.spawn(move || {
+                    for path in paths {
+                        compile_module(context, &mut state, &mut module_state.fork(), path)?;
+                    }
+
+                    Ok(state)
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    context.release_threads(thread_count);
+
+    for state in states {
+        global_state.merge(state?);
+    }
+
     Ok(())
 }
 
+// Pools defined transitively in a module are detected. Unresolved modules are
+// considered to define them so that they are compiled sequentially.
+fn defines_pool(context: &Context, path: &Path) -> bool {
+    let Some(module) = context.modules().get(path) else {
+        return true;
+    };
+
+    module.statements().iter().any(|statement| {
+        let submodule_path = match statement {
+            ast::Statement::Pool(_) => return true,
+            ast::Statement::Include(include) => include.path(),
+            ast::Statement::Submodule(submodule) => submodule.path(),
+            _ => return false,
+        };
+
+        resolve_dependency(context, path, submodule_path)
+            .map_or(true, |path| defines_pool(context, path))
+    })
+}
+
+// Only direct references in commands are detected. Generator rules are
+// exempted as they often write outputs at paths fixed in their commands.
+// Bases of rules are looked up when builds use the rules so that they can be
+// defined after their extensions. Bindings of extensions override the ones of
+// their bases.
+fn resolve_rule<'a>(
+    rules: &'a TrainMap<&str, ast::Rule>,
+    name: &str,
+) -> Result<Cow<'a, ast::Rule>, CompileError> {
+    let mut names = vec![];
+    let mut chain = vec![];
+    let mut name = name;
+
+    loop {
+        if names.contains(&name) {
+            names.push(name);
+
+            return Err(CompileError::CircularRuleExtension(
+                names.into_iter().map(From::from).collect(),
+            ));
+        }
+
+        let rule = rules
+            .get(name)
+            .ok_or_else(|| CompileError::RuleNotFound(name.into()))?;
+
+        names.push(name);
+        chain.push(rule);
+
+        if let Some(base) = rule.base() {
+            name = base;
+        } else {
+            break;
+        }
+    }
+
+    let Some((base, extensions)) = chain.split_last() else {
+        unreachable!()
+    };
+
+    if extensions.is_empty() {
+        return Ok(Cow::Borrowed(base));
+    }
+
+    let mut commands = base.commands();
+    let mut description = base.description();
+    let mut definitions = base.variable_definitions().to_vec();
+
+    for rule in extensions.iter().rev() {
+        if !rule.commands().is_empty() {
+            commands = rule.commands();
+        }
+
+        description = rule.description().or(description);
+        definitions.retain(|definition| {
+            !rule
+                .variable_definitions()
+                .iter()
+                .any(|other| other.name() == definition.name())
+        });
+        definitions.extend(rule.variable_definitions().iter().cloned());
+    }
+
+    Ok(Cow::Owned(ast::Rule::new(
+        chain[0].name(),
+        commands.to_vec(),
+        description.map(From::from),
+        definitions,
+    )))
+}
+
+fn references_output(rule: &ast::Rule) -> bool {
+    rule.variable_definitions()
+        .iter()
+        .any(|definition| definition.name() == GENERATOR_VARIABLE && definition.value() == "1")
+        || rule.commands().iter().any(|command| {
+            find_references(command)
+                .any(|(_, reference)| matches!(reference, Reference::Variable { name: "out", .. }))
+        })
+}
+
+// Immediate definitions are evaluated at definition and escaped not to be
+// evaluated again on use.
+fn evaluate_definition(
+    definition: &ast::VariableDefinition,
+    variables: &TrainMap<&str, Arc<str>>,
+) -> Arc<str> {
+    if definition.is_immediate() {
+        interpolate_variables(definition.value(), variables)
+            .replace('$', "$$")
+            .into()
+    } else {
+        definition.value().into()
+    }
+}
+
+fn check_version(required: &str, current: &str) -> Result<(), CompileError> {
+    let parse = |version: &str| {
+        let mut components = version
+            .trim()
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<Vec<u64>, _>>()
+            .ok()
+            .filter(|components| components.len() <= 3)?;
+
+        components.resize(3, 0);
+
+        Some(components)
+    };
+    let error = || CompileError::InvalidVersion(required.into());
+
+    if parse(current).ok_or_else(error)? < parse(required).ok_or_else(error)? {
+        Err(CompileError::IncompatibleVersion(
+            required.into(),
+            current.into(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// Returns a prefix of dependency lines in command outputs for `deps = msvc`.
+fn compile_deps(
+    rule: &str,
+    depfile: Option<&str>,
+    variables: &TrainMap<&str, Arc<str>>,
+) -> Result<Option<String>, CompileError> {
+    let deps = variables
+        .get(DEPS_VARIABLE)
+        .map(|deps| interpolate_variables(deps, variables));
+    let error = |message: &str| CompileError::InvalidDeps(rule.into(), message.into());
+
+    match (deps.as_deref(), depfile) {
+        (None, _) | (Some("gcc"), Some(_)) | (Some("none"), None) => Ok(None),
+        (Some("gcc"), None) => Err(error("deps = gcc requires depfile")),
+        (Some("msvc"), None) => Ok(Some(
+            variables
+                .get(MSVC_DEPS_PREFIX_VARIABLE)
+                .map(|prefix| interpolate_variables(prefix, variables))
+                .unwrap_or_else(|| DEFAULT_MSVC_DEPS_PREFIX.into()),
+        )),
+        (Some("msvc"), Some(_)) => Err(error("depfile cannot be used with deps = msvc")),
+        (Some("none"), Some(_)) => Err(error("depfile cannot be used with deps = none")),
+        (Some(deps), _) => Err(error(&format!("unknown deps \"{deps}\""))),
+    }
+}
+
 pub fn compile_dynamic(module: &ast::DynamicModule) -> Result<DynamicConfiguration, CompileError> {
     Ok(DynamicConfiguration::new(
         module
@@ -192,12 +1081,12 @@ pub fn compile_dynamic(module: &ast::DynamicModule) -> Result<DynamicConfigurati
             .iter()
             .map(|build| {
                 (
-                    build.output().into(),
+                    normalize_path(build.output()).into(),
                     DynamicBuild::new(
                         build
                             .implicit_inputs()
                             .iter()
-                            .map(|string| string.as_str().into())
+                            .map(|string| normalize_path(string).into())
                             .collect(),
                     ),
                 )
@@ -219,35 +1108,165 @@ fn resolve_dependency<'a>(
         .ok_or_else(|| CompileError::ModuleNotFound(submodule_path.into()))?)
 }
 
-fn interpolate_variables(template: &str, variables: &TrainMap<&str, Arc<str>>) -> String {
-    VARIABLE_PATTERN
-        .replace_all(template, |captures: &Captures| {
-            variables
-                .get(&captures[1])
-                .map(|string| string.as_ref())
-                .unwrap_or_default()
+// Environment variables are separated by spaces like `FOO=foo BAR=bar`. Values
+// containing spaces are quoted by single or double quotes like `FOO="foo bar"`.
+// Malformed variables are returned as errors.
+fn parse_environment(string: &str) -> Result<Vec<(String, String)>, String> {
+    // Raw and unquoted strings of variables
+    let mut variables = vec![];
+    let mut variable = None::<(String, String)>;
+    let mut quote = None;
+
+    for character in string.chars() {
+        if quote.is_none() && character.is_whitespace() {
+            variables.extend(variable.take());
+            continue;
+        }
+
+        let (raw, unquoted) = variable.get_or_insert_with(Default::default);
+
+        raw.push(character);
+
+        match quote {
+            None if matches!(character, '\'' | '"') => quote = Some(character),
+            Some(quote_character) if character == quote_character => quote = None,
+            _ => unquoted.push(character),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(variable.map(|(raw, _)| raw).unwrap_or_default());
+    }
+
+    variables.extend(variable);
+
+    variables
+        .into_iter()
+        .map(|(raw, unquoted)| match unquoted.split_once('=') {
+            Some((name, value)) if !name.is_empty() => Ok((name.into(), value.into())),
+            _ => Err(raw),
         })
-        .replace("$$", "$")
+        .collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::ast;
-    use once_cell::sync::Lazy;
-    use pretty_assertions::assert_eq;
-    use std::collections::HashSet;
+// Normalize paths lexically without resolving symbolic links.
+fn normalize_path(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut components = vec![];
 
-    static ROOT_MODULE_PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("build.ninja"));
-    static DEFAULT_DEPENDENCIES: Lazy<ModuleDependencyMap> = Lazy::new(|| {
-        [(ROOT_MODULE_PATH.clone(), Default::default())]
-            .into_iter()
-            .collect()
-    });
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components
+                    .last()
+                    .map(|&component| component != "..")
+                    .unwrap_or_default()
+                {
+                    components.pop();
+                } else if !absolute {
+                    components.push(component);
+                }
+            }
+            component => components.push(component),
+        }
+    }
 
-    fn ast_explicit_build(
-        outputs: Vec<String>,
-        rule: impl Into<String>,
+    let path = components.join("/");
+
+    if absolute {
+        format!("/{path}")
+    } else if path.is_empty() {
+        ".".into()
+    } else {
+        path
+    }
+}
+
+fn relocate_path(context: &Context, path: String) -> String {
+    match context.output_root() {
+        Some(root) if context.relocated_outputs().contains(&path) => {
+            normalize_path(&format!("{}/{}", root.display(), path))
+        }
+        _ => path,
+    }
+}
+
+fn interpolate_variables(template: &str, variables: &TrainMap<&str, Arc<str>>) -> String {
+    interpolate_variables_recursively(template, variables, false, &mut vec![])
+}
+
+// Undefined variables and escaped dollar signs are left as they are so that
+// they can be interpolated in narrower scopes later.
+fn interpolate_defined_variables(template: &str, variables: &TrainMap<&str, Arc<str>>) -> String {
+    interpolate_variables_recursively(template, variables, true, &mut vec![])
+}
+
+// Values of variables are interpolated lazily on their uses. Variables
+// referencing themselves directly or indirectly are interpolated into empty
+// strings.
+fn interpolate_variables_recursively(
+    template: &str,
+    variables: &TrainMap<&str, Arc<str>>,
+    partial: bool,
+    names: &mut Vec<String>,
+) -> String {
+    let mut interpolate_variable =
+        |name: &str, default: Option<&str>| match (variables.get(name), default) {
+            (Some(_), _) if names.iter().any(|other| other == name) => Some(String::new()),
+            (Some(value), _) => {
+                names.push(name.into());
+                let value = interpolate_variables_recursively(value, variables, partial, names);
+                names.pop();
+                Some(value)
+            }
+            (None, _) if partial => None,
+            (None, Some(default)) => Some(interpolate_variables_recursively(
+                default, variables, partial, names,
+            )),
+            (None, None) => Some(String::new()),
+        };
+
+    let mut string = String::with_capacity(template.len());
+    let mut index = 0;
+
+    for (range, reference) in find_references(template) {
+        string.push_str(&template[index..range.start]);
+
+        match reference {
+            Reference::Dollar => string.push_str(if partial { "$$" } else { "$" }),
+            Reference::Variable { name, default } => string.push_str(
+                &interpolate_variable(name, default)
+                    .unwrap_or_else(|| template[range.clone()].into()),
+            ),
+        }
+
+        index = range.end;
+    }
+
+    string.push_str(&template[index..]);
+
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast;
+    use once_cell::sync::Lazy;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashSet;
+
+    static ROOT_MODULE_PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("build.ninja"));
+    static DEFAULT_DEPENDENCIES: Lazy<ModuleDependencyMap> = Lazy::new(|| {
+        [(ROOT_MODULE_PATH.clone(), Default::default())]
+            .into_iter()
+            .collect()
+    });
+
+    fn ast_explicit_build(
+        outputs: Vec<String>,
+        rule: impl Into<String>,
         inputs: Vec<String>,
         variable_definitions: Vec<ast::VariableDefinition>,
     ) -> ast::Build {
@@ -258,19 +1277,26 @@ mod tests {
             inputs,
             vec![],
             vec![],
+            vec![],
             variable_definitions,
         )
     }
 
     fn ir_explicit_build(outputs: Vec<Arc<str>>, rule: Rule, inputs: Vec<Arc<str>>) -> Build {
-        Build::new(outputs, vec![], rule.into(), inputs, vec![], None)
+        Build::new(outputs, vec![], rule.into(), inputs, vec![], vec![], None)
     }
 
     fn create_simple_configuration(
         outputs: HashMap<Arc<str>, Arc<Build>>,
         default_outputs: HashSet<Arc<str>>,
     ) -> Configuration {
-        Configuration::new(outputs, default_outputs, Default::default(), None)
+        Configuration::new(
+            outputs,
+            default_outputs,
+            Default::default(),
+            Default::default(),
+            None,
+        )
     }
 
     #[test]
@@ -296,7 +1322,7 @@ mod tests {
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
                         ast::VariableDefinition::new("x", "42").into(),
-                        ast::Rule::new("foo", "$x", None).into(),
+                        ast::Rule::new("foo", vec!["$x".into()], None, vec![]).into(),
                         ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
                     ])
                 )]
@@ -309,7 +1335,12 @@ mod tests {
             create_simple_configuration(
                 [(
                     "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("42", None), vec![]).into()
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
                 )]
                 .into_iter()
                 .collect(),
@@ -327,7 +1358,7 @@ mod tests {
                     ast::Module::new(vec![
                         ast::VariableDefinition::new("x", "1").into(),
                         ast::VariableDefinition::new("y", "2").into(),
-                        ast::Rule::new("foo", "$x $y", None).into(),
+                        ast::Rule::new("foo", vec!["$x $y".into()], None, vec![]).into(),
                         ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
                     ])
                 )]
@@ -340,7 +1371,12 @@ mod tests {
             create_simple_configuration(
                 [(
                     "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("1 2", None), vec![]).into()
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["1 2".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
                 )]
                 .into_iter()
                 .collect(),
@@ -357,7 +1393,7 @@ mod tests {
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
                         ast::VariableDefinition::new("x_y", "42").into(),
-                        ast::Rule::new("foo", "$x_y", None).into(),
+                        ast::Rule::new("foo", vec!["$x_y".into()], None, vec![]).into(),
                         ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
                     ])
                 )]
@@ -370,7 +1406,12 @@ mod tests {
             create_simple_configuration(
                 [(
                     "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("42", None), vec![]).into()
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
                 )]
                 .into_iter()
                 .collect(),
@@ -379,6 +1420,78 @@ mod tests {
         );
     }
 
+    mod variable_default {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn variables() -> TrainMap<'static, &'static str, Arc<str>> {
+            let mut variables = TrainMap::new();
+            variables.insert("foo", "foo".into());
+            variables.insert("empty", "".into());
+            variables
+        }
+
+        #[test]
+        fn interpolate_braced_variable() {
+            assert_eq!(interpolate_variables("${foo}bar", &variables()), "foobar");
+            assert_eq!(interpolate_variables("${missing}bar", &variables()), "bar");
+        }
+
+        #[test]
+        fn interpolate_defined_variable_with_default() {
+            assert_eq!(interpolate_variables("${foo|bar}", &variables()), "foo");
+            assert_eq!(interpolate_variables("${empty|bar}", &variables()), "");
+        }
+
+        #[test]
+        fn interpolate_undefined_variable_with_default() {
+            assert_eq!(interpolate_variables("${missing|bar}", &variables()), "bar");
+            assert_eq!(
+                interpolate_variables("${missing|-O2 $foo}", &variables()),
+                "-O2 foo"
+            );
+            assert_eq!(interpolate_variables("${missing|}", &variables()), "");
+        }
+
+        #[test]
+        fn interpolate_undefined_variable_with_braced_default() {
+            assert_eq!(
+                interpolate_variables("${missing|${foo}}", &variables()),
+                "foo"
+            );
+            assert_eq!(
+                interpolate_variables("${missing|${foo}}bar", &variables()),
+                "foobar"
+            );
+        }
+
+        #[test]
+        fn interpolate_undefined_variable_with_undefined_braced_default() {
+            assert_eq!(
+                interpolate_variables("${missing|${other}}bar", &variables()),
+                "bar"
+            );
+            assert_eq!(
+                interpolate_variables("${missing|${other|${foo}}}", &variables()),
+                "foo"
+            );
+        }
+
+        #[test]
+        fn interpolate_undefined_variable_without_default() {
+            assert_eq!(interpolate_variables("a${missing}b", &variables()), "ab");
+            assert_eq!(interpolate_variables("a$missing b", &variables()), "a b");
+        }
+
+        #[test]
+        fn interpolate_escaped_braces() {
+            assert_eq!(
+                interpolate_variables("$${foo|bar}", &variables()),
+                "${foo|bar}"
+            );
+        }
+    }
+
     #[test]
     fn interpolate_dollar_sign_in_command() {
         assert_eq!(
@@ -386,7 +1499,7 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "$$", None).into(),
+                        ast::Rule::new("foo", vec!["$$".into()], None, vec![]).into(),
                         ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into()
                     ])
                 )]
@@ -399,7 +1512,12 @@ mod tests {
             create_simple_configuration(
                 [(
                     "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("$", None), vec![]).into()
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["$".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
                 )]
                 .into_iter()
                 .collect(),
@@ -415,104 +1533,1479 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "$in", None).into(),
-                        ast_explicit_build(vec!["bar".into()], "foo", vec!["baz".into()], vec![])
-                            .into(),
-                    ])
+                        ast::Rule::new("foo", vec!["$in".into()], None, vec![]).into(),
+                        ast_explicit_build(vec!["bar".into()], "foo", vec!["baz".into()], vec![])
+                            .into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["baz".into()], Default::default()),
+                        vec!["baz".into()]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn interpolate_in_variable_with_implicit_input() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new("foo", vec!["$in".into()], None, vec![]).into(),
+                        ast::Build::new(
+                            vec!["bar".into()],
+                            vec![],
+                            "foo",
+                            vec!["baz".into()],
+                            vec!["blah".into()],
+                            vec![],
+                            vec![],
+                            vec![]
+                        )
+                        .into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["baz".into()], Default::default()),
+                        vec!["baz".into(), "blah".into()]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn interpolate_out_variable_in_command() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new("foo", vec!["$out".into()], None, vec![]).into(),
+                        ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["bar".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn quote_in_variable_in_command() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new("foo", vec!["$in".into()], None, vec![]).into(),
+                        ast_explicit_build(
+                            vec!["bar".into()],
+                            "foo",
+                            vec!["baz blah".into()],
+                            vec![]
+                        )
+                        .into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["'baz blah'".into()], Default::default()),
+                        vec!["baz blah".into()]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn do_not_quote_in_variable_in_raw_rule() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new(
+                            "foo",
+                            vec!["$in".into()],
+                            None,
+                            vec![ast::VariableDefinition::new("raw", "1")]
+                        )
+                        .into(),
+                        ast_explicit_build(
+                            vec!["bar".into()],
+                            "foo",
+                            vec!["baz blah".into()],
+                            vec![]
+                        )
+                        .into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["baz blah".into()], Default::default()),
+                        vec!["baz blah".into()]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn do_not_quote_out_variable_in_raw_build() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new("foo", vec!["$out".into()], None, vec![]).into(),
+                        ast_explicit_build(
+                            vec!["bar baz".into()],
+                            "foo",
+                            vec![],
+                            vec![ast::VariableDefinition::new("raw", "1")]
+                        )
+                        .into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar baz".into(),
+                    ir_explicit_build(
+                        vec!["bar baz".into()],
+                        Rule::new("foo".into(), vec!["bar baz".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar baz".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn compile_status_variable() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new(
+                            "foo",
+                            vec!["".into()],
+                            Some("$out".into()),
+                            vec![ast::VariableDefinition::new("status", "compile")]
+                        )
+                        .into(),
+                        ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new(
+                            "foo".into(),
+                            vec!["".into()],
+                            RuleOptions {
+                                description: Some("bar".into()),
+                                status: Some("compile".into()),
+                                ..Default::default()
+                            }
+                        ),
+                        vec![]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    #[test]
+    fn compile_depfile_variable() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new(
+                            "foo",
+                            vec!["".into()],
+                            None,
+                            vec![ast::VariableDefinition::new("depfile", "$out.d")]
+                        )
+                        .into(),
+                        ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
+                    ])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "bar".into(),
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new(
+                            "foo".into(),
+                            vec!["".into()],
+                            RuleOptions {
+                                depfile: Some("bar.d".into()),
+                                ..Default::default()
+                            }
+                        ),
+                        vec![]
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["bar".into()].into_iter().collect()
+            )
+        );
+    }
+
+    fn compile_rule(
+        variable_definitions: Vec<ast::VariableDefinition>,
+    ) -> Result<Configuration, CompileError> {
+        compile(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::Rule::new("foo", vec!["".into()], None, variable_definitions).into(),
+                    ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+        )
+    }
+
+    #[test]
+    fn compile_deps() {
+        for definitions in [
+            vec![ast::VariableDefinition::new("deps", "none")],
+            vec![
+                ast::VariableDefinition::new("deps", "gcc"),
+                ast::VariableDefinition::new("depfile", "$out.d"),
+            ],
+        ] {
+            assert_eq!(
+                compile_rule(definitions)
+                    .unwrap()
+                    .outputs()
+                    .get("bar")
+                    .unwrap()
+                    .rule()
+                    .unwrap()
+                    .msvc_deps_prefix(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn compile_msvc_deps() {
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("deps", "msvc")])
+                .unwrap()
+                .outputs()
+                .get("bar")
+                .unwrap()
+                .rule()
+                .unwrap()
+                .msvc_deps_prefix(),
+            Some(DEFAULT_MSVC_DEPS_PREFIX)
+        );
+        assert_eq!(
+            compile_rule(vec![
+                ast::VariableDefinition::new("deps", "msvc"),
+                ast::VariableDefinition::new("msvc_deps_prefix", "Hinweis: "),
+            ])
+            .unwrap()
+            .outputs()
+            .get("bar")
+            .unwrap()
+            .rule()
+            .unwrap()
+            .msvc_deps_prefix(),
+            Some("Hinweis: ")
+        );
+    }
+
+    #[test]
+    fn fail_to_compile_invalid_deps() {
+        for (definitions, message) in [
+            (
+                vec![ast::VariableDefinition::new("deps", "gcc")],
+                "deps = gcc requires depfile",
+            ),
+            (
+                vec![
+                    ast::VariableDefinition::new("deps", "msvc"),
+                    ast::VariableDefinition::new("depfile", "$out.d"),
+                ],
+                "depfile cannot be used with deps = msvc",
+            ),
+            (
+                vec![
+                    ast::VariableDefinition::new("deps", "none"),
+                    ast::VariableDefinition::new("depfile", "$out.d"),
+                ],
+                "depfile cannot be used with deps = none",
+            ),
+            (
+                vec![ast::VariableDefinition::new("deps", "clang")],
+                "unknown deps \"clang\"",
+            ),
+        ] {
+            assert_eq!(
+                compile_rule(definitions),
+                Err(CompileError::InvalidDeps("foo".into(), message.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn compile_environment_variable() {
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("env", "FOO=$out BAR=")])
+                .unwrap()
+                .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .environment(),
+            &[("FOO".into(), "bar".into()), ("BAR".into(), "".into())]
+        );
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("env", "FOO")]),
+            Err(CompileError::InvalidEnvironment("foo".into(), "FOO".into()))
+        );
+    }
+
+    #[test]
+    fn parse_environment_variables() {
+        assert_eq!(
+            parse_environment(" FOO=foo  BAR=bar=baz "),
+            Ok(vec![
+                ("FOO".into(), "foo".into()),
+                ("BAR".into(), "bar=baz".into())
+            ])
+        );
+        assert_eq!(
+            parse_environment("FOO=\"foo bar\" BAR='\"baz\"' BAZ=a\"b c\"d"),
+            Ok(vec![
+                ("FOO".into(), "foo bar".into()),
+                ("BAR".into(), "\"baz\"".into()),
+                ("BAZ".into(), "ab cd".into())
+            ])
+        );
+        assert_eq!(parse_environment(""), Ok(vec![]));
+        assert_eq!(parse_environment("FOO=foo BAR"), Err("BAR".into()));
+        assert_eq!(parse_environment("=foo"), Err("=foo".into()));
+        assert_eq!(parse_environment("FOO=\"foo"), Err("FOO=\"foo".into()));
+    }
+
+    #[test]
+    fn compile_output_encoding_variable() {
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new(
+                "output_encoding",
+                "shift_jis"
+            )])
+            .unwrap()
+            .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .output_encoding(),
+            Some(encoding_rs::SHIFT_JIS)
+        );
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("output_encoding", "foo")]),
+            Err(CompileError::UnknownEncoding("foo".into(), "foo".into()))
+        );
+    }
+
+    #[test]
+    fn compile_pin_mtime_variable() {
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("pin_mtime", "42")])
+                .unwrap()
+                .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .pinned_modified_time(),
+            Some(42)
+        );
+        assert_eq!(
+            compile_rule(vec![]).unwrap().outputs()["bar"]
+                .rule()
+                .unwrap()
+                .pinned_modified_time(),
+            None
+        );
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("pin_mtime", "foo")]),
+            Err(CompileError::InvalidModifiedTime(
+                "foo".into(),
+                "foo".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn compile_no_output_variable() {
+        assert!(
+            compile_rule(vec![ast::VariableDefinition::new("no_output", "1")])
+                .unwrap()
+                .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .no_output()
+        );
+        assert!(!compile_rule(vec![]).unwrap().outputs()["bar"]
+            .rule()
+            .unwrap()
+            .no_output());
+    }
+
+    #[test]
+    fn compile_env_deps_variable() {
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("env_deps", "CC  CFLAGS")])
+                .unwrap()
+                .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .environment_dependencies(),
+            ["CC", "CFLAGS"]
+        );
+        assert!(compile_rule(vec![]).unwrap().outputs()["bar"]
+            .rule()
+            .unwrap()
+            .environment_dependencies()
+            .is_empty());
+    }
+
+    #[test]
+    fn compile_shell_variable() {
+        assert_eq!(
+            compile_rule(vec![
+                ast::VariableDefinition::new("shell.bash", "/bin/bash  -c"),
+                ast::VariableDefinition::new("shell", "bash"),
+            ])
+            .unwrap()
+            .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .shell(),
+            Some(&["/bin/bash".into(), "-c".into()][..])
+        );
+        assert_eq!(
+            compile_rule(vec![]).unwrap().outputs()["bar"]
+                .rule()
+                .unwrap()
+                .shell(),
+            None
+        );
+        assert_eq!(
+            compile_rule(vec![ast::VariableDefinition::new("shell", "bash")]),
+            Err(CompileError::ShellNotFound("foo".into(), "bash".into()))
+        );
+    }
+
+    #[test]
+    fn compile_always_variable() {
+        assert!(
+            compile_rule(vec![ast::VariableDefinition::new("always", "1")])
+                .unwrap()
+                .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .always()
+        );
+        assert!(!compile_rule(vec![]).unwrap().outputs()["bar"]
+            .rule()
+            .unwrap()
+            .always());
+    }
+
+    #[test]
+    fn compile_mkdir_variable() {
+        let compile_mkdir = |global: Option<&str>, rule: Option<&str>| {
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(
+                        global
+                            .map(|value| ast::VariableDefinition::new("mkdir", value).into())
+                            .into_iter()
+                            .chain([
+                                ast::Rule::new(
+                                    "foo",
+                                    vec!["".into()],
+                                    None,
+                                    rule.map(|value| ast::VariableDefinition::new("mkdir", value))
+                                        .into_iter()
+                                        .collect(),
+                                )
+                                .into(),
+                                ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![])
+                                    .into(),
+                            ])
+                            .collect(),
+                    ),
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+            )
+            .unwrap()
+            .outputs()["bar"]
+                .rule()
+                .unwrap()
+                .mkdir()
+        };
+
+        assert!(compile_mkdir(None, None));
+        assert!(!compile_mkdir(Some("0"), None));
+        assert!(compile_mkdir(Some("0"), Some("1")));
+        assert!(!compile_mkdir(None, Some("0")));
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize_path("foo"), "foo");
+        assert_eq!(normalize_path("./foo"), "foo");
+        assert_eq!(normalize_path("foo//bar/"), "foo/bar");
+        assert_eq!(normalize_path("foo/./bar"), "foo/bar");
+        assert_eq!(normalize_path("foo/../bar"), "bar");
+        assert_eq!(normalize_path("../foo/../../bar"), "../../bar");
+        assert_eq!(normalize_path("/foo/../../bar"), "/bar");
+        assert_eq!(normalize_path("./"), ".");
+    }
+
+    #[test]
+    fn unify_aliased_paths() {
+        let configuration = compile(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::Rule::new("foo", vec!["$in".into()], None, vec![]).into(),
+                    ast_explicit_build(vec!["./src/foo.o".into()], "foo", vec![], vec![]).into(),
+                    ast_explicit_build(
+                        vec!["bar".into()],
+                        "foo",
+                        vec!["src//foo.o".into()],
+                        vec![],
+                    )
+                    .into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+        )
+        .unwrap();
+
+        assert_eq!(configuration.outputs().len(), 2);
+        assert_eq!(
+            configuration.outputs()["bar"].inputs(),
+            &["src/foo.o".into()]
+        );
+        assert_eq!(
+            configuration.outputs()["bar"].rule().unwrap().commands(),
+            &["src/foo.o".to_string()]
+        );
+        assert!(configuration.outputs().contains_key("src/foo.o"));
+    }
+
+    #[test]
+    fn interpolate_variables_in_paths() {
+        let configuration = compile(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::VariableDefinition::new("builddir", "out").into(),
+                    ast::Rule::new("cc", vec!["cc -o $out $in".into()], None, vec![]).into(),
+                    ast_explicit_build(
+                        vec!["$builddir/foo.o".into()],
+                        "cc",
+                        vec!["foo.c".into()],
+                        vec![],
+                    )
+                    .into(),
+                    ast_explicit_build(
+                        vec!["${builddir}/bar".into()],
+                        "cc",
+                        vec!["$builddir//foo.o".into()],
+                        vec![],
+                    )
+                    .into(),
+                    ast::DefaultOutput::new(vec!["$builddir/bar".into()]).into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+        )
+        .unwrap();
+
+        assert_eq!(configuration.outputs().len(), 2);
+        assert_eq!(
+            configuration.outputs()["out/foo.o"]
+                .rule()
+                .unwrap()
+                .commands(),
+            &["cc -o out/foo.o foo.c".to_string()]
+        );
+        assert_eq!(
+            configuration.outputs()["out/bar"].inputs(),
+            &["out/foo.o".into()]
+        );
+        assert_eq!(
+            configuration.outputs()["out/bar"]
+                .rule()
+                .unwrap()
+                .commands(),
+            &["cc -o out/bar out/foo.o".to_string()]
+        );
+        assert_eq!(
+            configuration.default_outputs(),
+            &["out/bar".into()].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn interpolate_build_local_variables_in_paths() {
+        let configuration = compile(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::Rule::new(
+                        "cc",
+                        vec!["cc $in".into()],
+                        None,
+                        vec![ast::VariableDefinition::new("arch", "x86")],
+                    )
+                    .into(),
+                    ast_explicit_build(
+                        vec!["$arch/foo.o".into()],
+                        "cc",
+                        vec!["foo.c".into()],
+                        vec![ast::VariableDefinition::new("arch", "arm")],
+                    )
+                    .into(),
+                    ast_explicit_build(vec!["bar.o".into()], "cc", vec!["$arch.c".into()], vec![])
+                        .into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+        )
+        .unwrap();
+
+        assert!(configuration.outputs().contains_key("arm/foo.o"));
+        // Variables of rules are not visible in paths.
+        assert_eq!(configuration.outputs()["bar.o"].inputs(), &[".c".into()]);
+    }
+
+    #[test]
+    fn override_rule_description() {
+        let configuration = compile(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::VariableDefinition::new("description", "global").into(),
+                    ast::Rule::new("cc", vec!["cc $in".into()], Some("cc $out".into()), vec![])
+                        .into(),
+                    ast_explicit_build(vec!["foo.o".into()], "cc", vec!["foo.c".into()], vec![])
+                        .into(),
+                    ast_explicit_build(
+                        vec!["bar.o".into()],
+                        "cc",
+                        vec!["bar.c".into()],
+                        vec![ast::VariableDefinition::new("description", "generate $out")],
+                    )
+                    .into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+        )
+        .unwrap();
+        let description = |output: &str| {
+            configuration.outputs()[output]
+                .rule()
+                .unwrap()
+                .description()
+        };
+
+        assert_eq!(description("foo.o"), Some("cc foo.o"));
+        assert_eq!(description("bar.o"), Some("generate bar.o"));
+    }
+
+    #[test]
+    fn compile_tags() {
+        let configuration = compile(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::VariableDefinition::new("suite", "ci").into(),
+                    ast::Rule::new("cc", vec!["cc $out".into()], None, vec![]).into(),
+                    ast_explicit_build(
+                        vec!["foo".into()],
+                        "cc",
+                        vec![],
+                        vec![ast::VariableDefinition::new("tags", "fast  $suite")],
+                    )
+                    .into(),
+                    ast_explicit_build(vec!["bar".into()], "cc", vec![], vec![]).into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+        )
+        .unwrap();
+
+        assert_eq!(
+            configuration.tags(),
+            &[(
+                configuration.outputs()["foo"].id(),
+                vec!["fast".into(), "ci".into()]
+            )]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn trace_command_expansion() {
+        let layers = trace_command(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::VariableDefinition::new("cflags", "-O0").into(),
+                    ast::VariableDefinition::new("opt", "-g").into(),
+                    ast::Rule::new(
+                        "cc",
+                        vec!["cc $cflags $opt $extra $$HOME $in -o $out".into()],
+                        None,
+                        vec![ast::VariableDefinition::new("cflags", "-Wall")],
+                    )
+                    .into(),
+                    ast_explicit_build(
+                        vec!["foo.o".into()],
+                        "cc",
+                        vec!["foo.c".into()],
+                        vec![ast::VariableDefinition::new("opt", "-O2")],
+                    )
+                    .into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+            &[],
+            None,
+            "foo.o",
+            Default::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            layers,
+            vec![
+                ExpansionLayer::new(
+                    "rule",
+                    vec!["cc $cflags $opt $extra $$HOME $in -o $out".into()]
+                ),
+                ExpansionLayer::new(
+                    "global variables",
+                    vec!["cc -O0 -g $extra $$HOME $in -o $out".into()]
+                ),
+                ExpansionLayer::new(
+                    "rule variables",
+                    vec!["cc -Wall -g $extra $$HOME $in -o $out".into()]
+                ),
+                ExpansionLayer::new(
+                    "build variables",
+                    vec!["cc -Wall -O2 $extra $$HOME $in -o $out".into()]
+                ),
+                ExpansionLayer::new(
+                    "$in and $out",
+                    vec!["cc -Wall -O2  $HOME foo.c -o foo.o".into()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn do_not_trace_command_of_unknown_output() {
+        assert_eq!(
+            trace_command(
+                &[(ROOT_MODULE_PATH.clone(), ast::Module::new(vec![]))]
+                    .into_iter()
+                    .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+                &[],
+                None,
+                "foo",
+                Default::default(),
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn relocate_outputs_under_output_root() {
+        let (configuration, errors) = compile_with_errors(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::Rule::new("cc", vec!["cc $in -o $out".into()], None, vec![]).into(),
+                    // An input is relocated even if its build is defined later.
+                    ast_explicit_build(vec!["foo".into()], "cc", vec!["foo.o".into()], vec![])
+                        .into(),
+                    ast_explicit_build(vec!["foo.o".into()], "cc", vec!["foo.c".into()], vec![])
+                        .into(),
+                    ast_explicit_build(vec!["all".into()], "phony", vec!["foo".into()], vec![])
+                        .into(),
+                    ast::DefaultOutput::new(vec!["all".into(), "foo".into()]).into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+            &[],
+            Some(Path::new("out")),
+            Default::default(),
+        )
+        .unwrap();
+        let build = |output: &str| configuration.outputs()[output].clone();
+
+        assert_eq!(errors, vec![]);
+        assert_eq!(
+            build("out/foo").rule().unwrap().commands(),
+            ["cc out/foo.o -o out/foo"]
+        );
+        assert_eq!(
+            build("out/foo.o").rule().unwrap().commands(),
+            ["cc foo.c -o out/foo.o"]
+        );
+        assert_eq!(build("all").inputs(), [Arc::from("out/foo")]);
+        assert!(!configuration.outputs().contains_key("foo"));
+        assert_eq!(
+            configuration.default_outputs(),
+            &["all".into(), "out/foo".into()].into_iter().collect()
+        );
+        assert_eq!(
+            configuration.build_directory().map(|path| path.as_ref()),
+            Some("out")
+        );
+    }
+
+    mod immediate_variable {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn compile_command(statements: Vec<ast::Statement>) -> String {
+            compile(
+                &[(ROOT_MODULE_PATH.clone(), ast::Module::new(statements))]
+                    .into_iter()
+                    .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+            )
+            .unwrap()
+            .outputs()["foo"]
+                .rule()
+                .unwrap()
+                .commands()[0]
+                .clone()
+        }
+
+        #[test]
+        fn evaluate_module_variables() {
+            assert_eq!(
+                compile_command(vec![
+                    ast::VariableDefinition::new("y", "foo").into(),
+                    ast::VariableDefinition::new("lazy", "$y").into(),
+                    ast::VariableDefinition::new_immediate("immediate", "$y").into(),
+                    ast::VariableDefinition::new("y", "bar").into(),
+                    ast::Rule::new("echo", vec!["echo $lazy $immediate".into()], None, vec![])
+                        .into(),
+                    ast_explicit_build(vec!["foo".into()], "echo", vec![], vec![]).into(),
+                ]),
+                "echo bar foo"
+            );
+        }
+
+        #[test]
+        fn evaluate_rule_variables() {
+            assert_eq!(
+                compile_command(vec![
+                    ast::VariableDefinition::new("y", "foo").into(),
+                    ast::Rule::new(
+                        "echo",
+                        vec!["echo $lazy $immediate".into()],
+                        None,
+                        vec![
+                            ast::VariableDefinition::new("lazy", "$y"),
+                            ast::VariableDefinition::new_immediate("immediate", "$y"),
+                        ],
+                    )
+                    .into(),
+                    ast::VariableDefinition::new("y", "bar").into(),
+                    ast_explicit_build(vec!["foo".into()], "echo", vec![], vec![]).into(),
+                ]),
+                "echo bar foo"
+            );
+        }
+
+        #[test]
+        fn evaluate_build_variables_without_rule_variables() {
+            assert_eq!(
+                compile_command(vec![
+                    ast::VariableDefinition::new("y", "foo").into(),
+                    ast::Rule::new(
+                        "echo",
+                        vec!["echo $lazy $immediate".into()],
+                        None,
+                        vec![ast::VariableDefinition::new("y", "bar")],
+                    )
+                    .into(),
+                    ast_explicit_build(
+                        vec!["foo".into()],
+                        "echo",
+                        vec![],
+                        vec![
+                            ast::VariableDefinition::new("lazy", "$y"),
+                            ast::VariableDefinition::new_immediate("immediate", "$y"),
+                        ],
+                    )
+                    .into(),
+                ]),
+                "echo bar foo"
+            );
+        }
+
+        #[test]
+        fn evaluate_self_reference() {
+            assert_eq!(
+                compile_command(vec![
+                    ast::VariableDefinition::new("flags", "-O2").into(),
+                    ast::VariableDefinition::new_immediate("flags", "$flags -g").into(),
+                    ast::Rule::new("echo", vec!["echo $flags".into()], None, vec![]).into(),
+                    ast_explicit_build(vec!["foo".into()], "echo", vec![], vec![]).into(),
+                ]),
+                "echo -O2 -g"
+            );
+        }
+
+        #[test]
+        fn keep_escaped_dollar_signs() {
+            assert_eq!(
+                compile_command(vec![
+                    ast::VariableDefinition::new_immediate("x", "$$y").into(),
+                    ast::Rule::new("echo", vec!["echo $x".into()], None, vec![]).into(),
+                    ast_explicit_build(vec!["foo".into()], "echo", vec![], vec![]).into(),
+                ]),
+                "echo $y"
+            );
+        }
+    }
+
+    #[test]
+    fn fail_to_compile_duplicate_outputs() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new("foo", vec!["".into()], None, vec![]).into(),
+                        ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
+                        ast_explicit_build(vec!["./bar".into()], "foo", vec![], vec![]).into(),
+                    ]),
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+            ),
+            Err(CompileError::DuplicateOutput("bar".into()))
+        );
+    }
+
+    mod version {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn compile_version(version: &str) -> Result<Configuration, CompileError> {
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![ast::VariableDefinition::new(
+                        "turtle_required_version",
+                        version,
+                    )
+                    .into()]),
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+            )
+        }
+
+        #[test]
+        fn compile_satisfied_version() {
+            for version in ["0", "0.1", "0.0.1", env!("CARGO_PKG_VERSION")] {
+                assert!(compile_version(version).is_ok());
+            }
+        }
+
+        #[test]
+        fn fail_to_compile_unsatisfied_version() {
+            assert_eq!(
+                compile_version("999.0.0"),
+                Err(CompileError::IncompatibleVersion(
+                    "999.0.0".into(),
+                    env!("CARGO_PKG_VERSION").into()
+                ))
+            );
+        }
+
+        #[test]
+        fn fail_to_compile_invalid_version() {
+            for version in ["", "foo", "1.2.3.4", "1.x"] {
+                assert_eq!(
+                    compile_version(version),
+                    Err(CompileError::InvalidVersion(version.into()))
+                );
+            }
+        }
+
+        #[test]
+        fn check_versions() {
+            assert!(check_version("1.2.3", "1.2.3").is_ok());
+            assert!(check_version("1.2", "1.10.0").is_ok());
+            assert!(check_version("1.10", "1.2.0").is_err());
+            assert!(check_version("2", "1.99.99").is_err());
+        }
+    }
+
+    mod pool {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn compile_pool(
+            statements: Vec<ast::Statement>,
+            variables: &[(String, String)],
+        ) -> (Option<Pool>, Vec<CompileError>) {
+            let (configuration, errors) = compile_with_errors(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(
+                        statements
+                            .into_iter()
+                            .chain([ast_explicit_build(
+                                vec!["bar".into()],
+                                "foo",
+                                vec![],
+                                vec![ast::VariableDefinition::new("pool", "link")],
+                            )
+                            .into()])
+                            .collect(),
+                    ),
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+                variables,
+                None,
+                Default::default(),
+            )
+            .unwrap();
+
+            (
+                configuration
+                    .outputs()
+                    .get("bar")
+                    .and_then(|build| build.rule())
+                    .and_then(|rule| rule.pool())
+                    .cloned(),
+                errors,
+            )
+        }
+
+        fn rule() -> ast::Statement {
+            ast::Rule::new("foo", vec!["link $out".into()], None, vec![]).into()
+        }
+
+        #[test]
+        fn compile_pool_with_variable_depth() {
+            assert_eq!(
+                compile_pool(
+                    vec![
+                        ast::VariableDefinition::new("link_jobs", "2").into(),
+                        ast::Pool::new("link", "$link_jobs").into(),
+                        rule(),
+                    ],
+                    &[]
+                ),
+                (Some(Pool::new("link", 2)), vec![])
+            );
+        }
+
+        #[test]
+        fn override_pool_depth_with_variable() {
+            assert_eq!(
+                compile_pool(
+                    vec![
+                        ast::VariableDefinition::new("link_jobs", "2").into(),
+                        ast::Pool::new("link", "$link_jobs").into(),
+                        rule(),
+                    ],
+                    &[("link_jobs".into(), "4".into())]
+                ),
+                (Some(Pool::new("link", 4)), vec![])
+            );
+        }
+
+        #[test]
+        fn fail_to_compile_invalid_pool_depth() {
+            for depth in ["0", "-1", "foo", ""] {
+                assert_eq!(
+                    compile_pool(vec![ast::Pool::new("link", depth).into(), rule()], &[]),
+                    (
+                        None,
+                        vec![
+                            CompileError::InvalidPoolDepth("link".into(), depth.into()),
+                            CompileError::PoolNotFound("link".into())
+                        ]
+                    )
+                );
+            }
+        }
+
+        #[test]
+        fn fail_to_compile_undefined_pool() {
+            assert_eq!(
+                compile_pool(vec![rule()], &[]),
+                (None, vec![CompileError::PoolNotFound("link".into())])
+            );
+        }
+    }
+
+    mod output_reference {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn compile_rule(
+            command: &str,
+            variables: Vec<ast::VariableDefinition>,
+        ) -> Vec<CompileError> {
+            compile_with_errors(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![ast::Rule::new(
+                        "cc",
+                        vec![command.into()],
+                        None,
+                        variables,
+                    )
+                    .into()]),
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+                &[],
+                None,
+                Default::default(),
+            )
+            .unwrap()
+            .1
+        }
+
+        #[test]
+        fn warn_about_command_without_output() {
+            let errors = compile_rule("cc -c $in", vec![]);
+
+            assert_eq!(errors, vec![CompileError::OutputNotReferenced("cc".into())]);
+            assert!(errors[0].is_warning());
+        }
+
+        #[test]
+        fn warn_about_command_with_escaped_output() {
+            assert_eq!(
+                compile_rule("echo $$out", vec![]),
+                vec![CompileError::OutputNotReferenced("cc".into())]
+            );
+        }
+
+        #[test]
+        fn do_not_warn_about_command_with_output() {
+            for command in ["cc -o $out $in", "cc -o ${out} $in", "cc > ${out|foo}"] {
+                assert_eq!(compile_rule(command, vec![]), vec![]);
+            }
+        }
+
+        #[test]
+        fn do_not_warn_about_generator_rule() {
+            assert_eq!(
+                compile_rule(
+                    "configure",
+                    vec![ast::VariableDefinition::new("generator", "1")]
+                ),
+                vec![]
+            );
+        }
+
+        #[test]
+        fn compile_rule_without_output_reference() {
+            assert!(compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![
+                        ast::Rule::new("cc", vec!["cc".into()], None, vec![]).into()
+                    ]),
                 )]
                 .into_iter()
                 .collect(),
                 &DEFAULT_DEPENDENCIES,
-                &ROOT_MODULE_PATH
-            )
-            .unwrap(),
-            create_simple_configuration(
-                [(
-                    "bar".into(),
-                    ir_explicit_build(
-                        vec!["bar".into()],
-                        Rule::new("baz", None),
-                        vec!["baz".into()]
-                    )
-                    .into()
-                )]
-                .into_iter()
-                .collect(),
-                ["bar".into()].into_iter().collect()
+                &ROOT_MODULE_PATH,
             )
-        );
+            .is_ok());
+        }
     }
 
     #[test]
-    fn interpolate_in_variable_with_implicit_input() {
-        assert_eq!(
-            compile(
-                &[(
-                    ROOT_MODULE_PATH.clone(),
-                    ast::Module::new(vec![
-                        ast::Rule::new("foo", "$in", None).into(),
-                        ast::Build::new(
-                            vec!["bar".into()],
-                            vec![],
-                            "foo",
-                            vec!["baz".into()],
-                            vec!["blah".into()],
-                            vec![],
-                            vec![]
-                        )
-                        .into(),
-                    ])
-                )]
-                .into_iter()
-                .collect(),
-                &DEFAULT_DEPENDENCIES,
-                &ROOT_MODULE_PATH
-            )
-            .unwrap(),
-            create_simple_configuration(
-                [(
-                    "bar".into(),
-                    ir_explicit_build(
-                        vec!["bar".into()],
-                        Rule::new("baz", None),
-                        vec!["baz".into(), "blah".into()]
+    fn collect_compile_errors() {
+        let (configuration, errors) = compile_with_errors(
+            &[(
+                ROOT_MODULE_PATH.clone(),
+                ast::Module::new(vec![
+                    ast::Rule::new(
+                        "foo",
+                        vec!["cc $out".into()],
+                        None,
+                        vec![ast::VariableDefinition::new("deps", "gcc")],
                     )
-                    .into()
-                )]
-                .into_iter()
-                .collect(),
-                ["bar".into()].into_iter().collect()
-            )
+                    .into(),
+                    ast::Rule::new("bar", vec!["cc $out".into()], None, vec![]).into(),
+                    ast_explicit_build(vec!["a".into()], "foo", vec![], vec![]).into(),
+                    ast_explicit_build(vec!["b".into()], "baz", vec![], vec![]).into(),
+                    ast_explicit_build(vec!["c".into()], "bar", vec![], vec![]).into(),
+                    ast_explicit_build(vec!["c".into()], "bar", vec![], vec![]).into(),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            &DEFAULT_DEPENDENCIES,
+            &ROOT_MODULE_PATH,
+            &[],
+            None,
+            Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            errors,
+            vec![
+                CompileError::InvalidDeps("foo".into(), "deps = gcc requires depfile".into()),
+                CompileError::RuleNotFound("baz".into()),
+                CompileError::DuplicateOutput("c".into()),
+            ]
+        );
+        assert_eq!(
+            configuration.outputs().keys().collect::<Vec<_>>(),
+            vec![&Arc::from("c")]
         );
     }
 
     #[test]
-    fn interpolate_out_variable_in_command() {
-        assert_eq!(
-            compile(
-                &[(
-                    ROOT_MODULE_PATH.clone(),
-                    ast::Module::new(vec![
-                        ast::Rule::new("foo", "$out", None).into(),
-                        ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
-                    ])
-                )]
-                .into_iter()
-                .collect(),
+    fn quote_paths() {
+        assert_eq!(Shell::Nu.quote("foo/bar.o"), "foo/bar.o");
+        assert_eq!(Shell::Nu.quote("foo bar"), "'foo bar'");
+        assert_eq!(Shell::Nu.quote("foo$bar"), "'foo$bar'");
+        assert_eq!(Shell::Nu.quote("foo$$bar"), "'foo$$bar'");
+        assert_eq!(Shell::Nu.quote("foo\"bar"), "'foo\"bar'");
+        assert_eq!(Shell::Nu.quote("foo'bar"), "\"foo'bar\"");
+        assert_eq!(Shell::Nu.quote("foo'\"bar"), "\"foo'\\\"bar\"");
+    }
+
+    mod quoting {
+        use super::*;
+        use crate::parse::parse;
+        use pretty_assertions::assert_eq;
+
+        fn compile_command(source: &str, shell: Shell) -> String {
+            let (configuration, errors) = compile_with_errors(
+                &[(ROOT_MODULE_PATH.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
                 &DEFAULT_DEPENDENCIES,
-                &ROOT_MODULE_PATH
-            )
-            .unwrap(),
-            create_simple_configuration(
-                [(
-                    "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("bar", None), vec![]).into()
-                )]
-                .into_iter()
-                .collect(),
-                ["bar".into()].into_iter().collect()
+                &ROOT_MODULE_PATH,
+                &[],
+                None,
+                Options {
+                    shell,
+                    ..Default::default()
+                },
             )
-        );
+            .unwrap();
+
+            assert_eq!(errors, vec![]);
+
+            configuration.outputs()["out"].rule().unwrap().commands()[0].clone()
+        }
+
+        #[test]
+        fn quote_paths_for_nu_by_default() {
+            assert_eq!(
+                compile_command(
+                    "rule cp\n  command = cp $in $out\nbuild out: cp it's$$HOME\n",
+                    Default::default(),
+                ),
+                "cp \"it's$HOME\" out"
+            );
+        }
+
+        #[test]
+        fn quote_paths_for_default_shell() {
+            assert_eq!(
+                compile_command(
+                    "rule cp\n  command = cp $in $out\nbuild out: cp it's$$HOME\n",
+                    Shell::Posix,
+                ),
+                "cp 'it'\\''s$HOME' out"
+            );
+        }
+
+        #[test]
+        fn quote_paths_for_rule_shell() {
+            assert_eq!(
+                compile_command(
+                    "shell.sh = /bin/sh -c\nrule cp\n  command = cp $in $out\n  shell = sh\nbuild out: cp it's$$HOME\n",
+                    Shell::Nu,
+                ),
+                "cp 'it'\\''s$HOME' out"
+            );
+        }
     }
 
     #[test]
@@ -520,7 +3013,8 @@ mod tests {
         let build = Arc::new(Build::new(
             vec!["bar".into()],
             vec!["baz".into()],
-            Rule::new("bar", None).into(),
+            Rule::new("foo".into(), vec!["bar".into()], Default::default()).into(),
+            vec![],
             vec![],
             vec![],
             None,
@@ -531,7 +3025,7 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "$out", None).into(),
+                        ast::Rule::new("foo", vec!["$out".into()], None, vec![]).into(),
                         ast::Build::new(
                             vec!["bar".into()],
                             vec!["baz".into()],
@@ -539,6 +3033,7 @@ mod tests {
                             vec![],
                             vec![],
                             vec![],
+                            vec![],
                             vec![]
                         )
                         .into(),
@@ -566,7 +3061,7 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "$in", None).into(),
+                        ast::Rule::new("foo", vec!["$in".into()], None, vec![]).into(),
                         ast::Build::new(
                             vec!["bar".into()],
                             vec![],
@@ -574,6 +3069,7 @@ mod tests {
                             vec![],
                             vec![],
                             vec!["baz".into()],
+                            vec![],
                             vec![]
                         )
                         .into(),
@@ -591,9 +3087,10 @@ mod tests {
                     Build::new(
                         vec!["bar".into()],
                         vec![],
-                        Some(Rule::new("", None)),
+                        Some(Rule::new("foo".into(), vec!["".into()], Default::default())),
                         vec![],
                         vec!["baz".into()],
+                        vec![],
                         None
                     )
                     .into()
@@ -612,7 +3109,7 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "", None).into(),
+                        ast::Rule::new("foo", vec!["".into()], None, vec![]).into(),
                         ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![]).into(),
                         ast_explicit_build(vec!["baz".into()], "foo", vec![], vec![]).into()
                     ])
@@ -627,11 +3124,21 @@ mod tests {
                 [
                     (
                         "bar".into(),
-                        ir_explicit_build(vec!["bar".into()], Rule::new("", None), vec![]).into()
+                        ir_explicit_build(
+                            vec!["bar".into()],
+                            Rule::new("foo".into(), vec!["".into()], Default::default()),
+                            vec![]
+                        )
+                        .into()
                     ),
                     (
                         "baz".into(),
-                        ir_explicit_build(vec!["baz".into()], Rule::new("", None), vec![]).into()
+                        ir_explicit_build(
+                            vec!["baz".into()],
+                            Rule::new("foo".into(), vec!["".into()], Default::default()),
+                            vec![]
+                        )
+                        .into()
                     )
                 ]
                 .into_iter()
@@ -648,7 +3155,7 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "$x", None).into(),
+                        ast::Rule::new("foo", vec!["$x".into()], None, vec![]).into(),
                         ast_explicit_build(
                             vec!["bar".into()],
                             "foo",
@@ -667,7 +3174,12 @@ mod tests {
             create_simple_configuration(
                 [(
                     "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("42", None), vec![]).into()
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
                 )]
                 .into_iter()
                 .collect(),
@@ -683,7 +3195,7 @@ mod tests {
                 &[(
                     ROOT_MODULE_PATH.clone(),
                     ast::Module::new(vec![
-                        ast::Rule::new("foo", "foo", None).into(),
+                        ast::Rule::new("foo", vec!["foo".into()], None, vec![]).into(),
                         ast_explicit_build(
                             vec!["bar".into()],
                             "foo",
@@ -705,12 +3217,18 @@ mod tests {
             Configuration::new(
                 [(
                     "bar".into(),
-                    ir_explicit_build(vec!["bar".into()], Rule::new("foo", None), vec![]).into()
+                    ir_explicit_build(
+                        vec!["bar".into()],
+                        Rule::new("foo".into(), vec!["foo".into()], Default::default()),
+                        vec![]
+                    )
+                    .into()
                 )]
                 .into_iter()
                 .collect(),
                 ["bar".into()].into_iter().collect(),
                 [("bar".into(), "oh-my-src".into())].into_iter().collect(),
+                Default::default(),
                 None,
             )
         );
@@ -745,6 +3263,7 @@ mod tests {
                         None,
                         vec!["bar".into()],
                         vec![],
+                        vec![],
                         None
                     )
                     .into()
@@ -762,61 +3281,408 @@ mod tests {
             compile(
                 &[(
                     ROOT_MODULE_PATH.clone(),
-                    ast::Module::new(vec![ast::VariableDefinition::new("builddir", "foo").into()])
+                    ast::Module::new(vec![ast::VariableDefinition::new("builddir", "foo").into()])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            Configuration::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Some("foo".into())
+            )
+        );
+    }
+
+    #[test]
+    fn compile_dynamic_module_variable() {
+        assert_eq!(
+            compile(
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    ast::Module::new(vec![ast_explicit_build(
+                        vec!["foo".into()],
+                        "phony",
+                        vec![],
+                        vec![ast::VariableDefinition::new("dyndep", "bar")]
+                    )
+                    .into()])
+                )]
+                .into_iter()
+                .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH
+            )
+            .unwrap(),
+            create_simple_configuration(
+                [(
+                    "foo".into(),
+                    Build::new(
+                        vec!["foo".into()],
+                        vec![],
+                        None,
+                        vec![],
+                        vec![],
+                        vec![],
+                        Some("bar".into())
+                    )
+                    .into()
+                )]
+                .into_iter()
+                .collect(),
+                ["foo".into()].into_iter().collect()
+            )
+        );
+    }
+
+    mod builtin_rule {
+        use super::*;
+        use crate::parse::parse;
+        use pretty_assertions::assert_eq;
+
+        fn compile_source(
+            source: &str,
+            no_builtin_rules: bool,
+        ) -> (Configuration, Vec<CompileError>) {
+            compile_with_errors(
+                &[(ROOT_MODULE_PATH.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+                &[],
+                None,
+                Options {
+                    no_builtin_rules,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn compile_phony_build() {
+            let (configuration, errors) = compile_source("build all: phony foo\n", false);
+
+            assert_eq!(errors, vec![]);
+            assert!(configuration.outputs()["all"].rule().is_none());
+        }
+
+        #[test]
+        fn fail_to_compile_phony_build_without_builtin_rules() {
+            assert_eq!(
+                compile_source("build all: phony foo\n", true).1,
+                vec![CompileError::RuleNotFound("phony".into())]
+            );
+        }
+
+        #[test]
+        fn compile_phony_rule_defined_without_builtin_rules() {
+            let (configuration, errors) = compile_source(
+                "rule phony\n  command = touch $out\nbuild all: phony foo\n",
+                true,
+            );
+
+            assert_eq!(errors, vec![]);
+            assert_eq!(
+                configuration.outputs()["all"].rule().unwrap().commands(),
+                ["touch all"]
+            );
+        }
+    }
+
+    mod duplicate_rule {
+        use super::*;
+        use crate::parse::parse;
+        use pretty_assertions::assert_eq;
+
+        const INCLUDED_MODULE_PATH: &str = "rules.ninja";
+
+        fn compile_modules(
+            root_source: &str,
+            included_source: &str,
+            rule_redefinition: RuleRedefinition,
+        ) -> (Option<Configuration>, Vec<CompileError>) {
+            let result = compile_with_errors(
+                &[
+                    (ROOT_MODULE_PATH.clone(), parse(root_source).unwrap()),
+                    (INCLUDED_MODULE_PATH.into(), parse(included_source).unwrap()),
+                ]
+                .into_iter()
+                .collect(),
+                &[(
+                    ROOT_MODULE_PATH.clone(),
+                    [(
+                        INCLUDED_MODULE_PATH.into(),
+                        PathBuf::from(INCLUDED_MODULE_PATH),
+                    )]
+                    .into_iter()
+                    .collect(),
+                )]
+                .into_iter()
+                .collect(),
+                &ROOT_MODULE_PATH,
+                &[],
+                None,
+                Options {
+                    rule_redefinition,
+                    ..Default::default()
+                },
+            );
+
+            match result {
+                Ok((configuration, errors)) => (Some(configuration), errors),
+                Err(error) => (None, vec![error]),
+            }
+        }
+
+        fn command(configuration: &Configuration) -> &str {
+            &configuration.outputs()["foo"].rule().unwrap().commands()[0]
+        }
+
+        const ROOT_SOURCE: &str =
+            "rule cc\n  command = first $out\ninclude rules.ninja\nbuild foo: cc\n";
+        const INCLUDED_SOURCE: &str = "rule cc\n  command = last $out\n";
+
+        #[test]
+        fn fail_on_rule_defined_twice() {
+            let (_, errors) =
+                compile_modules(ROOT_SOURCE, INCLUDED_SOURCE, RuleRedefinition::Error);
+
+            assert_eq!(
+                errors,
+                vec![CompileError::DuplicateRule(
+                    "cc".into(),
+                    ROOT_MODULE_PATH.clone(),
+                    INCLUDED_MODULE_PATH.into()
+                )]
+            );
+        }
+
+        #[test]
+        fn use_last_rule_defined_twice() {
+            let (configuration, errors) =
+                compile_modules(ROOT_SOURCE, INCLUDED_SOURCE, RuleRedefinition::Last);
+
+            assert_eq!(command(&configuration.unwrap()), "last foo");
+            assert_eq!(
+                errors,
+                vec![CompileError::RuleRedefined(
+                    "cc".into(),
+                    ROOT_MODULE_PATH.clone(),
+                    INCLUDED_MODULE_PATH.into()
                 )]
-                .into_iter()
-                .collect(),
-                &DEFAULT_DEPENDENCIES,
-                &ROOT_MODULE_PATH
-            )
-            .unwrap(),
-            Configuration::new(
-                Default::default(),
-                Default::default(),
-                Default::default(),
-                Some("foo".into())
-            )
-        );
+            );
+        }
+
+        #[test]
+        fn use_first_rule_defined_twice() {
+            let (configuration, errors) =
+                compile_modules(ROOT_SOURCE, INCLUDED_SOURCE, RuleRedefinition::First);
+
+            assert_eq!(command(&configuration.unwrap()), "first foo");
+            assert_eq!(
+                errors,
+                vec![CompileError::RuleRedefined(
+                    "cc".into(),
+                    INCLUDED_MODULE_PATH.into(),
+                    ROOT_MODULE_PATH.clone()
+                )]
+            );
+        }
+
+        #[test]
+        fn shadow_rule_in_submodule() {
+            let (configuration, errors) = compile_modules(
+                "rule cc\n  command = first $out\nsubninja rules.ninja\n",
+                "rule cc\n  command = last $out\nbuild foo: cc\n",
+                RuleRedefinition::Error,
+            );
+
+            assert_eq!(command(&configuration.unwrap()), "last foo");
+            assert_eq!(errors, vec![]);
+        }
     }
 
-    #[test]
-    fn compile_dynamic_module_variable() {
-        assert_eq!(
+    mod rule_extension {
+        use super::*;
+        use crate::parse::parse;
+        use pretty_assertions::assert_eq;
+
+        fn compile_source(source: &str) -> Result<Configuration, CompileError> {
             compile(
-                &[(
-                    ROOT_MODULE_PATH.clone(),
-                    ast::Module::new(vec![ast_explicit_build(
-                        vec!["foo".into()],
-                        "phony",
-                        vec![],
-                        vec![ast::VariableDefinition::new("dyndep", "bar")]
-                    )
-                    .into()])
-                )]
-                .into_iter()
-                .collect(),
+                &[(ROOT_MODULE_PATH.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
                 &DEFAULT_DEPENDENCIES,
-                &ROOT_MODULE_PATH
+                &ROOT_MODULE_PATH,
             )
-            .unwrap(),
-            create_simple_configuration(
-                [(
+        }
+
+        fn rule<'a>(configuration: &'a Configuration, output: &str) -> &'a Rule {
+            configuration.outputs()[output].rule().unwrap()
+        }
+
+        const SOURCE: &str = "
+rule cc
+  command = cc $flags $in -o $out
+  description = CC $out
+  flags = -O2
+rule cxx : cc
+  flags = -std=c++17
+rule cxx_debug : cxx
+  description = CXX $out
+";
+
+        #[test]
+        fn inherit_bindings() {
+            let configuration =
+                compile_source(&format!("{SOURCE}build foo.o: cxx foo.cpp\n")).unwrap();
+            let rule = rule(&configuration, "foo.o");
+
+            assert_eq!(rule.name(), "cxx");
+            assert_eq!(rule.commands(), ["cc -std=c++17 foo.cpp -o foo.o"]);
+            assert_eq!(rule.description(), Some("CC foo.o"));
+        }
+
+        #[test]
+        fn inherit_bindings_through_multiple_levels() {
+            let configuration =
+                compile_source(&format!("{SOURCE}build foo.o: cxx_debug foo.cpp\n")).unwrap();
+            let rule = rule(&configuration, "foo.o");
+
+            assert_eq!(rule.commands(), ["cc -std=c++17 foo.cpp -o foo.o"]);
+            assert_eq!(rule.description(), Some("CXX foo.o"));
+        }
+
+        #[test]
+        fn override_inherited_command() {
+            let configuration = compile_source(&format!(
+                "{SOURCE}rule link : cc\n  command = link $flags $in -o $out\nbuild foo: link foo.o\n"
+            ))
+            .unwrap();
+
+            assert_eq!(
+                rule(&configuration, "foo").commands(),
+                ["link -O2 foo.o -o foo"]
+            );
+        }
+
+        #[test]
+        fn keep_base_rule() {
+            let configuration =
+                compile_source(&format!("{SOURCE}build foo.o: cc foo.c\n")).unwrap();
+
+            assert_eq!(
+                rule(&configuration, "foo.o").commands(),
+                ["cc -O2 foo.c -o foo.o"]
+            );
+        }
+
+        #[test]
+        fn fail_on_circular_extension() {
+            assert_eq!(
+                compile_source(
+                    "rule foo : bar\n  x = 1\nrule bar : foo\n  x = 2\nbuild baz: foo\n"
+                ),
+                Err(CompileError::CircularRuleExtension(vec![
                     "foo".into(),
-                    Build::new(
-                        vec!["foo".into()],
-                        vec![],
-                        None,
-                        vec![],
-                        vec![],
-                        Some("bar".into())
-                    )
-                    .into()
-                )]
-                .into_iter()
-                .collect(),
-                ["foo".into()].into_iter().collect()
+                    "bar".into(),
+                    "foo".into()
+                ]))
+            );
+        }
+
+        #[test]
+        fn fail_on_unknown_base() {
+            assert_eq!(
+                compile_source("rule foo : bar\n  x = 1\nbuild baz: foo\n"),
+                Err(CompileError::RuleNotFound("bar".into()))
+            );
+        }
+    }
+
+    mod lazy {
+        use super::*;
+        use crate::parse::parse;
+        use pretty_assertions::assert_eq;
+
+        fn compile_source(source: &str, outputs: &[&str]) -> Configuration {
+            let (configuration, errors) = compile_lazily(
+                &[(ROOT_MODULE_PATH.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
+                &DEFAULT_DEPENDENCIES,
+                &ROOT_MODULE_PATH,
+                &[],
+                &outputs
+                    .iter()
+                    .map(|&output| output.into())
+                    .collect::<Vec<_>>(),
+                Default::default(),
             )
-        );
+            .unwrap();
+
+            assert_eq!(errors, vec![]);
+
+            configuration
+        }
+
+        fn sorted_outputs(configuration: &Configuration) -> Vec<&str> {
+            let mut outputs = configuration
+                .outputs()
+                .keys()
+                .map(|output| output.as_ref())
+                .collect::<Vec<_>>();
+
+            outputs.sort();
+
+            outputs
+        }
+
+        #[test]
+        fn compile_builds_reachable_from_outputs() {
+            let configuration = compile_source(
+                "rule cc\n  command = cc $out\nbuild foo.o: cc foo.c\nbuild bar.o: cc bar.c\nbuild foo: cc foo.o | bar.h || baz\nbuild bar.h: cc\nbuild baz: phony\nbuild qux: cc bar.o\n",
+                &["foo"],
+            );
+
+            assert_eq!(
+                sorted_outputs(&configuration),
+                vec!["bar.h", "baz", "foo", "foo.o"]
+            );
+            assert_eq!(
+                configuration.outputs()["foo.o"].rule().unwrap().commands(),
+                &["cc foo.o".to_string()]
+            );
+        }
+
+        #[test]
+        fn compile_validations_and_other_outputs_of_builds() {
+            assert_eq!(
+                sorted_outputs(&compile_source(
+                    "rule cc\n  command = cc $out\nbuild foo | bar: cc |@ baz\nbuild baz: cc\nbuild qux: cc\n",
+                    &["bar"],
+                )),
+                vec!["bar", "baz", "foo"]
+            );
+        }
+
+        #[test]
+        fn compile_builds_after_their_dependents() {
+            assert_eq!(
+                sorted_outputs(&compile_source(
+                    "rule cc\n  command = cc $out\nbuild foo: cc bar\nbuild bar: cc\nbuild baz: cc\n",
+                    &["foo"],
+                )),
+                vec!["bar", "foo"]
+            );
+        }
     }
 
     mod submodule {
@@ -840,7 +3706,7 @@ mod tests {
                         (
                             SUBMODULE_PATH.into(),
                             ast::Module::new(vec![
-                                ast::Rule::new("foo", "$x", None).into(),
+                                ast::Rule::new("foo", vec!["$x".into()], None, vec![]).into(),
                                 ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![])
                                     .into()
                             ])
@@ -862,7 +3728,12 @@ mod tests {
                 create_simple_configuration(
                     [(
                         "bar".into(),
-                        ir_explicit_build(vec!["bar".into()], Rule::new("42", None), vec![]).into()
+                        ir_explicit_build(
+                            vec!["bar".into()],
+                            Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                            vec![]
+                        )
+                        .into()
                     )]
                     .into_iter()
                     .collect(),
@@ -882,7 +3753,7 @@ mod tests {
                             ROOT_MODULE_PATH.clone(),
                             ast::Module::new(vec![
                                 ast::VariableDefinition::new("x", "42").into(),
-                                ast::Rule::new("foo", "$x", None).into(),
+                                ast::Rule::new("foo", vec!["$x".into()], None, vec![]).into(),
                                 ast::Submodule::new(SUBMODULE_PATH).into(),
                             ])
                         ),
@@ -913,7 +3784,12 @@ mod tests {
                 create_simple_configuration(
                     [(
                         "bar".into(),
-                        ir_explicit_build(vec!["bar".into()], Rule::new("42", None), vec![]).into()
+                        ir_explicit_build(
+                            vec!["bar".into()],
+                            Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                            vec![]
+                        )
+                        .into()
                     )]
                     .into_iter()
                     .collect(),
@@ -933,7 +3809,7 @@ mod tests {
                             ROOT_MODULE_PATH.clone(),
                             ast::Module::new(vec![
                                 ast::VariableDefinition::new("x", "42").into(),
-                                ast::Rule::new("foo", "$x", None).into(),
+                                ast::Rule::new("foo", vec!["$x".into()], None, vec![]).into(),
                                 ast::Submodule::new(SUBMODULE_PATH).into(),
                                 ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![])
                                     .into(),
@@ -960,7 +3836,108 @@ mod tests {
                 create_simple_configuration(
                     [(
                         "bar".into(),
-                        ir_explicit_build(vec!["bar".into()], Rule::new("42", None), vec![]).into()
+                        ir_explicit_build(
+                            vec!["bar".into()],
+                            Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                            vec![]
+                        )
+                        .into()
+                    )]
+                    .into_iter()
+                    .collect(),
+                    ["bar".into()].into_iter().collect()
+                )
+            );
+        }
+
+        #[test]
+        fn do_not_reference_rule_in_child_module() {
+            const SUBMODULE_PATH: &str = "foo.ninja";
+
+            assert_eq!(
+                compile(
+                    &[
+                        (
+                            ROOT_MODULE_PATH.clone(),
+                            ast::Module::new(vec![
+                                ast::Submodule::new(SUBMODULE_PATH).into(),
+                                ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![])
+                                    .into(),
+                            ])
+                        ),
+                        (
+                            SUBMODULE_PATH.into(),
+                            ast::Module::new(vec![ast::Rule::new(
+                                "foo",
+                                vec!["".into()],
+                                None,
+                                vec![]
+                            )
+                            .into()])
+                        )
+                    ]
+                    .into_iter()
+                    .collect(),
+                    &[(
+                        ROOT_MODULE_PATH.clone(),
+                        [(SUBMODULE_PATH.into(), PathBuf::from(SUBMODULE_PATH))]
+                            .into_iter()
+                            .collect()
+                    )]
+                    .into_iter()
+                    .collect(),
+                    &ROOT_MODULE_PATH
+                ),
+                Err(CompileError::RuleNotFound("foo".into()))
+            );
+        }
+
+        #[test]
+        fn do_not_reference_variable_defined_after_submodule() {
+            const SUBMODULE_PATH: &str = "foo.ninja";
+
+            assert_eq!(
+                compile(
+                    &[
+                        (
+                            ROOT_MODULE_PATH.clone(),
+                            ast::Module::new(vec![
+                                ast::VariableDefinition::new("x", "42").into(),
+                                ast::Submodule::new(SUBMODULE_PATH).into(),
+                                ast::VariableDefinition::new("x", "13").into(),
+                            ])
+                        ),
+                        (
+                            SUBMODULE_PATH.into(),
+                            ast::Module::new(vec![
+                                ast::Rule::new("foo", vec!["$x".into()], None, vec![]).into(),
+                                ast_explicit_build(vec!["bar".into()], "foo", vec![], vec![])
+                                    .into()
+                            ])
+                        )
+                    ]
+                    .into_iter()
+                    .collect(),
+                    &[(
+                        ROOT_MODULE_PATH.clone(),
+                        [(SUBMODULE_PATH.into(), PathBuf::from(SUBMODULE_PATH))]
+                            .into_iter()
+                            .collect()
+                    )]
+                    .into_iter()
+                    .collect(),
+                    &ROOT_MODULE_PATH
+                )
+                .unwrap(),
+                create_simple_configuration(
+                    [(
+                        "bar".into(),
+                        ir_explicit_build(
+                            vec!["bar".into()],
+                            Rule::new("foo".into(), vec!["42".into()], Default::default()),
+                            vec![]
+                        )
+                        .into()
                     )]
                     .into_iter()
                     .collect(),
@@ -968,5 +3945,96 @@ mod tests {
                 )
             );
         }
+
+        fn compile_submodules_with_threads(
+            sources: &[(&str, &str)],
+            thread_count: usize,
+        ) -> Result<CompiledModules, CompileError> {
+            let modules = sources
+                .iter()
+                .map(|(path, source)| (PathBuf::from(path), crate::parse::parse(source).unwrap()))
+                .collect();
+            let dependencies = [(
+                ROOT_MODULE_PATH.clone(),
+                sources[1..]
+                    .iter()
+                    .map(|(path, _)| (path.to_string(), PathBuf::from(path)))
+                    .collect(),
+            )]
+            .into_iter()
+            .chain(
+                sources[1..]
+                    .iter()
+                    .map(|(path, _)| (PathBuf::from(path), Default::default())),
+            )
+            .collect();
+
+            compile_configuration(
+                &Context::new(
+                    &modules,
+                    &dependencies,
+                    &[],
+                    None,
+                    Default::default(),
+                    None,
+                    Default::default(),
+                    Default::default(),
+                    thread_count,
+                ),
+                &ROOT_MODULE_PATH,
+            )
+        }
+
+        #[test]
+        fn compile_submodules_in_parallel() {
+            let sources = [
+                (
+                    "build.ninja",
+                    "rule cc\n  command = cc $out\nsubninja a.ninja\nsubninja b.ninja\nsubninja c.ninja\nsubninja d.ninja\n",
+                ),
+                ("a.ninja", "x = a\nbuild a: cc\n  description = $x\n"),
+                ("b.ninja", "build b: cc\nbuild x: cc\n"),
+                ("c.ninja", "build c: cc\nbuild x: cc\n"),
+                ("d.ninja", "build d: cc\n  description = $x\n"),
+            ];
+            let (configuration, errors, _) = compile_submodules_with_threads(&sources, 4).unwrap();
+
+            assert_eq!((configuration.clone(), errors.clone()), {
+                let (configuration, errors, _) =
+                    compile_submodules_with_threads(&sources, 1).unwrap();
+                (configuration, errors)
+            });
+            assert_eq!(errors, vec![CompileError::DuplicateOutput("x".into())]);
+            assert_eq!(
+                configuration.outputs()["a"].rule().unwrap().description(),
+                Some("a")
+            );
+            assert_eq!(
+                configuration.outputs()["d"].rule().unwrap().description(),
+                Some("")
+            );
+        }
+
+        #[test]
+        fn compile_submodules_referring_to_pools_of_previous_ones() {
+            let (configuration, errors, _) = compile_submodules_with_threads(
+                &[
+                    (
+                        "build.ninja",
+                        "rule cc\n  command = cc $out\nsubninja a.ninja\nsubninja b.ninja\n",
+                    ),
+                    ("a.ninja", "pool foo\n  depth = 2\n"),
+                    ("b.ninja", "build b: cc\n  pool = foo\n"),
+                ],
+                4,
+            )
+            .unwrap();
+
+            assert_eq!(errors, vec![]);
+            assert_eq!(
+                configuration.outputs()["b"].rule().unwrap().pool(),
+                Some(&Pool::new("foo", 2))
+            );
+        }
     }
 }
@@ -1,20 +1,61 @@
+use super::options::Options;
 use crate::{ast::Module, module_dependency::ModuleDependencyMap};
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Builds to compile for requested outputs
+#[derive(Debug, Default)]
+pub enum Selection {
+    #[default]
+    All,
+    // Builds are compiled as phony ones only to index their outputs and inputs.
+    Index,
+    // Only builds of the outputs are compiled.
+    Outputs(HashSet<String>),
+}
 
 #[derive(Debug)]
 pub struct Context<'a> {
     modules: &'a HashMap<PathBuf, Module>,
     dependencies: &'a ModuleDependencyMap,
+    variables: &'a [(String, String)],
+    output_root: Option<&'a Path>,
+    // Outputs of non-phony builds relocated under an output root
+    relocated_outputs: HashSet<String>,
+    // An output whose command expansion is traced
+    traced_output: Option<&'a str>,
+    selection: Selection,
+    options: Options,
+    // Threads available to compile modules in parallel
+    thread_count: AtomicUsize,
 }
 
 impl<'a> Context<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         modules: &'a HashMap<PathBuf, Module>,
         dependencies: &'a ModuleDependencyMap,
+        variables: &'a [(String, String)],
+        output_root: Option<&'a Path>,
+        relocated_outputs: HashSet<String>,
+        traced_output: Option<&'a str>,
+        selection: Selection,
+        options: Options,
+        thread_count: usize,
     ) -> Self {
         Self {
             modules,
             dependencies,
+            variables,
+            output_root,
+            relocated_outputs,
+            traced_output,
+            selection,
+            options,
+            thread_count: AtomicUsize::new(thread_count),
         }
     }
 
@@ -25,4 +66,44 @@ impl<'a> Context<'a> {
     pub fn dependencies(&self) -> &ModuleDependencyMap {
         self.dependencies
     }
+
+    pub fn variables(&self) -> &[(String, String)] {
+        self.variables
+    }
+
+    pub fn output_root(&self) -> Option<&Path> {
+        self.output_root
+    }
+
+    pub fn relocated_outputs(&self) -> &HashSet<String> {
+        &self.relocated_outputs
+    }
+
+    pub fn traced_output(&self) -> Option<&str> {
+        self.traced_output
+    }
+
+    pub fn selection(&self) -> &Selection {
+        &self.selection
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    // Threads are acquired at most up to a given count.
+    pub fn acquire_threads(&self, count: usize) -> usize {
+        let previous_count = self
+            .thread_count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |available_count| {
+                Some(available_count - available_count.min(count))
+            })
+            .unwrap_or_default();
+
+        previous_count.min(count)
+    }
+
+    pub fn release_threads(&self, count: usize) {
+        self.thread_count.fetch_add(count, Ordering::SeqCst);
+    }
 }
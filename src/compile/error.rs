@@ -6,8 +6,32 @@ use std::{
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum CompileError {
+    // Rules extending each other in order where the first one is repeated last
+    CircularRuleExtension(Vec<String>),
+    DuplicateOutput(String),
+    // A rule and paths of modules defining it
+    DuplicateRule(String, PathBuf, PathBuf),
+    IncompatibleVersion(String, String),
+    InvalidDeps(String, String),
+    InvalidEnvironment(String, String),
+    InvalidModifiedTime(String, String),
+    InvalidPoolDepth(String, String),
+    InvalidVersion(String),
     ModuleNotFound(PathBuf),
+    OutputNotReferenced(String),
+    PoolNotFound(String),
     RuleNotFound(String),
+    // A rule and paths of modules defining ignored and used definitions
+    RuleRedefined(String, PathBuf, PathBuf),
+    ShellNotFound(String, String),
+    UnknownEncoding(String, String),
+}
+
+impl CompileError {
+    // Warnings are reported but do not fail builds unless promoted to errors.
+    pub fn is_warning(&self) -> bool {
+        matches!(self, Self::OutputNotReferenced(_) | Self::RuleRedefined(..))
+    }
 }
 
 impl Error for CompileError {}
@@ -15,12 +39,79 @@ impl Error for CompileError {}
 impl Display for CompileError {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
+            Self::CircularRuleExtension(rules) => {
+                write!(formatter, "circular rule extension: {}", rules.join(" -> "))
+            }
+            Self::DuplicateOutput(output) => {
+                write!(formatter, "multiple builds generate output \"{output}\"")
+            }
+            Self::DuplicateRule(rule, first, second) => {
+                write!(
+                    formatter,
+                    "rule \"{rule}\" defined in both {} and {}",
+                    first.display(),
+                    second.display()
+                )
+            }
+            Self::IncompatibleVersion(required, current) => {
+                write!(
+                    formatter,
+                    "turtle {required} or later required by build files but {current} running"
+                )
+            }
+            Self::InvalidDeps(rule, message) => {
+                write!(formatter, "invalid deps in rule \"{rule}\": {message}")
+            }
+            Self::InvalidEnvironment(rule, variable) => {
+                write!(
+                    formatter,
+                    "invalid environment variable \"{variable}\" in rule \"{rule}\""
+                )
+            }
+            Self::InvalidModifiedTime(rule, time) => {
+                write!(
+                    formatter,
+                    "invalid pinned modification time \"{time}\" in rule \"{rule}\""
+                )
+            }
+            Self::InvalidPoolDepth(pool, depth) => {
+                write!(formatter, "invalid depth \"{depth}\" of pool \"{pool}\"")
+            }
+            Self::InvalidVersion(version) => {
+                write!(formatter, "invalid required version \"{version}\"")
+            }
             Self::ModuleNotFound(path) => {
                 write!(formatter, "module \"{}\" not found", path.display())
             }
+            Self::OutputNotReferenced(rule) => {
+                write!(
+                    formatter,
+                    "command of rule \"{rule}\" does not reference $out"
+                )
+            }
+            Self::PoolNotFound(pool) => {
+                write!(formatter, "pool \"{pool}\" not found")
+            }
             Self::RuleNotFound(rule) => {
                 write!(formatter, "rule \"{rule}\" not found")
             }
+            Self::RuleRedefined(rule, ignored, used) => {
+                write!(
+                    formatter,
+                    "rule \"{rule}\" in {} ignored in favor of one in {}",
+                    ignored.display(),
+                    used.display()
+                )
+            }
+            Self::ShellNotFound(rule, shell) => {
+                write!(formatter, "shell \"{shell}\" not found in rule \"{rule}\"")
+            }
+            Self::UnknownEncoding(rule, encoding) => {
+                write!(
+                    formatter,
+                    "unknown output encoding \"{encoding}\" in rule \"{rule}\""
+                )
+            }
         }
     }
 }
@@ -0,0 +1,20 @@
+// A snapshot of commands of a build expanded with variables up to a scope
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpansionLayer {
+    name: &'static str,
+    commands: Vec<String>,
+}
+
+impl ExpansionLayer {
+    pub fn new(name: &'static str, commands: Vec<String>) -> Self {
+        Self { name, commands }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+}
@@ -1,4 +1,5 @@
-use crate::ir::Build;
+use super::{CompileError, ExpansionLayer};
+use crate::ir::{Build, BuildId, Pool};
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
@@ -9,4 +10,50 @@ pub struct GlobalState {
     pub outputs: HashMap<Arc<str>, Arc<Build>>,
     pub default_outputs: HashSet<Arc<str>>,
     pub source_map: HashMap<Arc<str>, Arc<str>>,
+    pub tags: HashMap<BuildId, Vec<Arc<str>>>,
+    pub pools: HashMap<String, Pool>,
+    pub errors: Vec<CompileError>,
+    pub expansion_layers: Option<Vec<ExpansionLayer>>,
+}
+
+impl GlobalState {
+    // Forked states start empty except pools defined so far.
+    pub fn fork(&self) -> Self {
+        Self {
+            outputs: Default::default(),
+            default_outputs: Default::default(),
+            source_map: Default::default(),
+            tags: Default::default(),
+            pools: self.pools.clone(),
+            errors: vec![],
+            expansion_layers: None,
+        }
+    }
+
+    // Outputs defined in both states are reported in a sorted order.
+    pub fn merge(&mut self, other: Self) {
+        let mut duplicate_outputs = other
+            .outputs
+            .keys()
+            .filter(|output| self.outputs.contains_key(*output))
+            .collect::<Vec<_>>();
+
+        duplicate_outputs.sort();
+
+        self.errors.extend(other.errors);
+        self.errors.extend(
+            duplicate_outputs
+                .into_iter()
+                .map(|output| CompileError::DuplicateOutput(output.to_string())),
+        );
+        self.outputs.extend(other.outputs);
+        self.default_outputs.extend(other.default_outputs);
+        self.source_map.extend(other.source_map);
+        self.tags.extend(other.tags);
+        self.pools.extend(other.pools);
+
+        if other.expansion_layers.is_some() {
+            self.expansion_layers = other.expansion_layers;
+        }
+    }
 }
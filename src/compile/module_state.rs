@@ -1,10 +1,12 @@
 use crate::ast;
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 use train_map::TrainMap;
 
 #[derive(Clone, Debug)]
 pub struct ModuleState<'a, 'm> {
     pub rules: TrainMap<'m, &'a str, ast::Rule>,
+    // Paths of modules where rules are defined
+    pub rule_paths: TrainMap<'m, &'a str, PathBuf>,
     pub variables: TrainMap<'m, &'a str, Arc<str>>,
 }
 
@@ -12,6 +14,7 @@ impl<'a, 'm> ModuleState<'a, 'm> {
     pub fn fork(&'m self) -> Self {
         Self {
             rules: self.rules.fork(),
+            rule_paths: self.rule_paths.fork(),
             variables: self.variables.fork(),
         }
     }
@@ -0,0 +1,22 @@
+use super::Shell;
+
+// Handling of rules defined again in the same scope, e.g. by included files
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RuleRedefinition {
+    #[default]
+    Error,
+    // The first definitions are used with warnings.
+    First,
+    // The last definitions are used with warnings.
+    Last,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Options {
+    pub rule_redefinition: RuleRedefinition,
+    // Built-in rules (i.e. `phony`) are unknown unless build files define them.
+    pub no_builtin_rules: bool,
+    // Paths are quoted for the shell of command runners unless rules select
+    // their own shells.
+    pub shell: Shell,
+}
@@ -0,0 +1,169 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::path::Path;
+
+static UNQUOTED_PATH_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[[:alnum:]_./+-]+$").unwrap());
+
+// Shells decoding quoted paths in commands
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Shell {
+    #[default]
+    Nu,
+    Posix,
+    Cmd,
+}
+
+impl Shell {
+    // Shells are detected by names of their programs. Unknown ones are
+    // regarded as POSIX shells.
+    pub fn from_command(words: &[impl AsRef<str>]) -> Self {
+        let name = words
+            .first()
+            .and_then(|program| Path::new(program.as_ref()).file_stem())
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "nu" => Self::Nu,
+            "cmd" => Self::Cmd,
+            _ => Self::Posix,
+        }
+    }
+
+    // Paths are quoted so that they are passed to shells as single arguments.
+    pub fn quote(self, path: &str) -> String {
+        if UNQUOTED_PATH_PATTERN.is_match(path) {
+            return path.into();
+        }
+
+        match self {
+            // Double quotes are used only when paths contain single quotes
+            // because they have escape sequences.
+            Self::Nu => {
+                if !path.contains('\'') {
+                    format!("'{path}'")
+                } else {
+                    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+                }
+            }
+            Self::Posix => format!("'{}'", path.replace('\'', "'\\''")),
+            Self::Cmd => format!("\"{}\"", path.replace('"', "\"\"")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHS: &[&str] = &[
+        "foo/bar.o",
+        "foo bar",
+        "foo$bar",
+        "foo'bar",
+        "foo\"bar",
+        "foo'\"bar",
+        "foo\\bar",
+        "foo;bar&baz",
+        "'foo' \"bar\"",
+    ];
+
+    // Quoted strings are decoded as shells do for round trips.
+    fn unquote(shell: Shell, string: &str) -> String {
+        let mut characters = string.chars();
+        let mut string = String::new();
+
+        while let Some(character) = characters.next() {
+            match (shell, character) {
+                (Shell::Nu | Shell::Posix, '\'') => string.extend(
+                    characters
+                        .by_ref()
+                        .take_while(|&character| character != '\''),
+                ),
+                (Shell::Posix, '\\') => string.extend(characters.next()),
+                (Shell::Nu, '"') => {
+                    while let Some(character) = characters.next() {
+                        match character {
+                            '"' => break,
+                            '\\' => string.extend(characters.next()),
+                            _ => string.push(character),
+                        }
+                    }
+                }
+                (Shell::Cmd, '"') => {
+                    let mut characters = characters.by_ref().peekable();
+
+                    while let Some(character) = characters.next() {
+                        match character {
+                            '"' if characters.peek() == Some(&'"') => {
+                                characters.next();
+                                string.push('"');
+                            }
+                            '"' => break,
+                            _ => string.push(character),
+                        }
+                    }
+                }
+                _ => string.push(character),
+            }
+        }
+
+        string
+    }
+
+    #[test]
+    fn quote_paths_in_nu() {
+        assert_eq!(Shell::Nu.quote("foo/bar.o"), "foo/bar.o");
+        assert_eq!(Shell::Nu.quote("foo bar"), "'foo bar'");
+        assert_eq!(Shell::Nu.quote("foo'bar"), "\"foo'bar\"");
+    }
+
+    #[test]
+    fn quote_paths_in_posix_shell() {
+        assert_eq!(Shell::Posix.quote("foo/bar.o"), "foo/bar.o");
+        assert_eq!(Shell::Posix.quote("foo bar"), "'foo bar'");
+        assert_eq!(Shell::Posix.quote("foo'bar"), "'foo'\\''bar'");
+    }
+
+    #[test]
+    fn quote_paths_in_cmd() {
+        assert_eq!(Shell::Cmd.quote("foo/bar.o"), "foo/bar.o");
+        assert_eq!(Shell::Cmd.quote("foo bar"), "\"foo bar\"");
+        assert_eq!(Shell::Cmd.quote("foo\"bar"), "\"foo\"\"bar\"");
+    }
+
+    #[test]
+    fn detect_shells() {
+        assert_eq!(Shell::from_command(&["nu", "-c"]), Shell::Nu);
+        assert_eq!(Shell::from_command(&["/usr/bin/nu"]), Shell::Nu);
+        assert_eq!(Shell::from_command(&["sh", "-c"]), Shell::Posix);
+        assert_eq!(Shell::from_command(&["/bin/bash", "-c"]), Shell::Posix);
+        assert_eq!(Shell::from_command(&["cmd", "/c"]), Shell::Cmd);
+        assert_eq!(Shell::from_command(&["CMD.EXE", "/c"]), Shell::Cmd);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn decode_quoted_paths_in_posix_shell() {
+        for path in PATHS.iter().chain(&["it's$HOME", "foo`bar`", "foo\nbar"]) {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", Shell::Posix.quote(path)))
+                .output()
+                .unwrap();
+
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), *path);
+        }
+    }
+
+    #[test]
+    fn round_trip_quoted_paths() {
+        for shell in [Shell::Nu, Shell::Posix, Shell::Cmd] {
+            for path in PATHS {
+                assert_eq!(&unquote(shell, &shell.quote(path)), path, "{shell:?}");
+            }
+        }
+    }
+}
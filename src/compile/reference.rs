@@ -0,0 +1,178 @@
+use std::ops::Range;
+
+// References in templates. Defaults in `${name|default}` extend to matching
+// closing braces so that they can contain braced references themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reference<'a> {
+    Dollar,
+    Variable {
+        name: &'a str,
+        default: Option<&'a str>,
+    },
+}
+
+// This finds references with their byte ranges in a template. Dollar signs
+// not starting any references are left as literals.
+pub fn find_references(template: &str) -> impl Iterator<Item = (Range<usize>, Reference<'_>)> {
+    let mut index = 0;
+
+    std::iter::from_fn(move || {
+        while let Some(offset) = template[index..].find('$') {
+            let start = index + offset;
+
+            if let Some((end, reference)) = parse_reference(template, start + 1) {
+                index = end;
+                return Some((start..end, reference));
+            }
+
+            index = start + 1;
+        }
+
+        index = template.len();
+
+        None
+    })
+}
+
+fn parse_reference(template: &str, start: usize) -> Option<(usize, Reference<'_>)> {
+    let rest = &template[start..];
+
+    if rest.starts_with('$') {
+        Some((start + 1, Reference::Dollar))
+    } else if let Some(rest) = rest.strip_prefix('{') {
+        let name = parse_name(rest)?;
+        let rest = &rest[name.len()..];
+
+        if rest.starts_with('}') {
+            Some((
+                start + name.len() + 2,
+                Reference::Variable {
+                    name,
+                    default: None,
+                },
+            ))
+        } else if let Some(rest) = rest.strip_prefix('|') {
+            let default = parse_default(rest)?;
+
+            Some((
+                start + name.len() + default.len() + 3,
+                Reference::Variable {
+                    name,
+                    default: Some(default),
+                },
+            ))
+        } else {
+            None
+        }
+    } else {
+        let name = parse_name(rest)?;
+
+        Some((
+            start + name.len(),
+            Reference::Variable {
+                name,
+                default: None,
+            },
+        ))
+    }
+}
+
+fn parse_name(string: &str) -> Option<&str> {
+    let end = string
+        .find(|character: char| !(character.is_ascii_alphanumeric() || character == '_'))
+        .unwrap_or(string.len());
+    let name = &string[..end];
+
+    if name.starts_with(|character: char| character.is_ascii_alphabetic() || character == '_') {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+// Defaults end at closing braces balanced with opening ones in them.
+fn parse_default(string: &str) -> Option<&str> {
+    let mut depth = 0;
+
+    for (index, character) in string.char_indices() {
+        match character {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(&string[..index]),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn find(template: &str) -> Vec<(Range<usize>, Reference<'_>)> {
+        find_references(template).collect()
+    }
+
+    fn variable<'a>(name: &'a str, default: Option<&'a str>) -> Reference<'a> {
+        Reference::Variable { name, default }
+    }
+
+    #[test]
+    fn find_nothing() {
+        assert_eq!(find(""), vec![]);
+        assert_eq!(find("foo"), vec![]);
+        assert_eq!(find("$ $1 ${} ${foo"), vec![]);
+    }
+
+    #[test]
+    fn find_dollar_signs() {
+        assert_eq!(find("$$"), vec![(0..2, Reference::Dollar)]);
+        assert_eq!(
+            find("$$$$"),
+            vec![(0..2, Reference::Dollar), (2..4, Reference::Dollar)]
+        );
+    }
+
+    #[test]
+    fn find_variables() {
+        assert_eq!(find("$foo bar"), vec![(0..4, variable("foo", None))]);
+        assert_eq!(find("a${foo}b"), vec![(1..7, variable("foo", None))]);
+    }
+
+    #[test]
+    fn find_variables_with_defaults() {
+        assert_eq!(
+            find("${foo|bar}"),
+            vec![(0..10, variable("foo", Some("bar")))]
+        );
+        assert_eq!(find("${foo|}"), vec![(0..7, variable("foo", Some("")))]);
+    }
+
+    #[test]
+    fn find_variables_with_braced_defaults() {
+        assert_eq!(
+            find("${foo|${bar}}baz"),
+            vec![(0..13, variable("foo", Some("${bar}")))]
+        );
+        assert_eq!(
+            find("${foo|${bar|${baz}}}"),
+            vec![(0..20, variable("foo", Some("${bar|${baz}}")))]
+        );
+    }
+
+    #[test]
+    fn find_variables_with_escaped_dollar_signs_in_defaults() {
+        assert_eq!(find("${foo|$$}"), vec![(0..9, variable("foo", Some("$$")))]);
+        assert_eq!(
+            find("${foo|$${bar}}"),
+            vec![(0..14, variable("foo", Some("$${bar}")))]
+        );
+    }
+
+    #[test]
+    fn skip_unclosed_defaults() {
+        assert_eq!(find("${foo|${bar}"), vec![(6..12, variable("bar", None))]);
+    }
+}
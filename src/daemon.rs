@@ -0,0 +1,201 @@
+mod console;
+mod message;
+mod request;
+
+use self::message::{read_message, write_message, Message};
+pub use self::{console::SocketConsole, request::Request};
+use std::{error::Error, io::ErrorKind, path::Path, sync::Arc};
+use tokio::{
+    fs::remove_file,
+    io::{AsyncWrite, AsyncWriteExt},
+    net::{unix::OwnedWriteHalf, UnixListener, UnixStream},
+    sync::Mutex,
+};
+
+// A daemon keeps build files loaded and runs builds requested by clients one
+// by one streaming console outputs back to them.
+pub struct Server {
+    listener: UnixListener,
+}
+
+impl Server {
+    pub async fn bind(path: &Path) -> Result<Self, Box<dyn Error>> {
+        // A socket file is left behind when a previous daemon is killed.
+        match remove_file(path).await {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+
+        Ok(Self {
+            listener: UnixListener::bind(path)?,
+        })
+    }
+
+    pub async fn accept(&self) -> Result<(Request, Connection), Box<dyn Error>> {
+        let (stream, _) = self.listener.accept().await?;
+        let (mut reader, writer) = stream.into_split();
+
+        match read_message(&mut reader).await? {
+            Some(Message::Request(request)) => Ok((
+                request,
+                Connection {
+                    writer: Arc::new(Mutex::new(writer)),
+                },
+            )),
+            _ => Err("invalid daemon request".into()),
+        }
+    }
+}
+
+pub struct Connection {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+}
+
+impl Connection {
+    pub fn console(&self) -> SocketConsole {
+        SocketConsole::new(self.writer.clone())
+    }
+
+    pub async fn exit(self, code: i32) -> Result<(), Box<dyn Error>> {
+        let mut writer = self.writer.lock().await;
+
+        write_message(&mut *writer, &Message::Exit(code)).await?;
+        writer.shutdown().await?;
+
+        Ok(())
+    }
+}
+
+// This forwards a request to a daemon and returns an exit code of a build. It
+// returns `None` if no daemon is running.
+pub async fn forward(
+    path: &Path,
+    request: &Request,
+    stdout: &mut (impl AsyncWrite + Unpin),
+    stderr: &mut (impl AsyncWrite + Unpin),
+) -> Result<Option<i32>, Box<dyn Error>> {
+    // Unusable database directories have no daemons either.
+    let stream = match UnixStream::connect(path).await {
+        Ok(stream) => stream,
+        Err(error)
+            if matches!(
+                error.kind(),
+                ErrorKind::NotFound
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::NotADirectory
+                    | ErrorKind::PermissionDenied
+            ) =>
+        {
+            return Ok(None)
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let (mut reader, mut writer) = stream.into_split();
+
+    write_message(&mut writer, &Message::Request(request.clone())).await?;
+
+    loop {
+        match read_message(&mut reader).await? {
+            Some(Message::Stdout(buffer)) => {
+                stdout.write_all(&buffer).await?;
+                stdout.flush().await?;
+            }
+            Some(Message::Stderr(buffer)) => {
+                stderr.write_all(&buffer).await?;
+                stderr.flush().await?;
+            }
+            Some(Message::Exit(code)) => return Ok(Some(code)),
+            Some(Message::Request(_)) => return Err("invalid daemon response".into()),
+            None => return Err("daemon disconnected".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::Console;
+    use tempfile::tempdir;
+
+    fn empty_request() -> Request {
+        Request {
+            arguments: vec![],
+            color: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn forward_arguments() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("daemon.sock");
+        let server = Server::bind(&path).await.unwrap();
+
+        let handle = tokio::spawn(async move {
+            let (request, connection) = server.accept().await.unwrap();
+            let mut console = connection.console();
+
+            assert!(request.color);
+            console
+                .write_stdout(request.arguments.join(" ").as_bytes())
+                .await
+                .unwrap();
+            console.write_stderr(b"bar").await.unwrap();
+            connection.exit(42).await.unwrap();
+        });
+
+        let mut stdout = vec![];
+        let mut stderr = vec![];
+
+        assert_eq!(
+            forward(
+                &path,
+                &Request {
+                    arguments: vec!["turtle".into(), "foo".into()],
+                    color: true,
+                },
+                &mut stdout,
+                &mut stderr
+            )
+            .await
+            .unwrap(),
+            Some(42)
+        );
+        assert_eq!(stdout, b"turtle foo");
+        assert_eq!(stderr, b"bar");
+
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn forward_nothing_without_daemon() {
+        let directory = tempdir().unwrap();
+
+        assert_eq!(
+            forward(
+                &directory.path().join("daemon.sock"),
+                &empty_request(),
+                &mut vec![],
+                &mut vec![]
+            )
+            .await
+            .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn replace_stale_socket_file() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("daemon.sock");
+
+        drop(Server::bind(&path).await.unwrap());
+
+        assert_eq!(
+            forward(&path, &empty_request(), &mut vec![], &mut vec![])
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(Server::bind(&path).await.is_ok());
+    }
+}
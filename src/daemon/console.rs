@@ -0,0 +1,36 @@
+use super::message::{write_message, Message};
+use crate::infrastructure::Console;
+use async_trait::async_trait;
+use std::{error::Error, sync::Arc};
+use tokio::{net::unix::OwnedWriteHalf, sync::Mutex};
+
+// This sends console outputs of a daemon to a client.
+#[derive(Clone, Debug)]
+pub struct SocketConsole {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+}
+
+impl SocketConsole {
+    pub(super) fn new(writer: Arc<Mutex<OwnedWriteHalf>>) -> Self {
+        Self { writer }
+    }
+}
+
+#[async_trait]
+impl Console for SocketConsole {
+    async fn write_stdout(&mut self, buffer: &[u8]) -> Result<(), Box<dyn Error>> {
+        write_message(
+            &mut *self.writer.lock().await,
+            &Message::Stdout(buffer.to_vec()),
+        )
+        .await
+    }
+
+    async fn write_stderr(&mut self, buffer: &[u8]) -> Result<(), Box<dyn Error>> {
+        write_message(
+            &mut *self.writer.lock().await,
+            &Message::Stderr(buffer.to_vec()),
+        )
+        .await
+    }
+}
@@ -0,0 +1,73 @@
+use super::Request;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Message {
+    Request(Request),
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+    Exit(i32),
+}
+
+// Messages are prefixed with their lengths.
+pub async fn write_message(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &Message,
+) -> Result<(), Box<dyn Error>> {
+    let buffer = bincode::serialize(message)?;
+
+    writer.write_u32_le(buffer.len().try_into()?).await?;
+    writer.write_all(&buffer).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+// This returns `None` on the end of a stream.
+pub async fn read_message(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> Result<Option<Message>, Box<dyn Error>> {
+    let length = match reader.read_u32_le().await {
+        Ok(length) => length,
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let mut buffer = vec![0; length as usize];
+
+    reader.read_exact(&mut buffer).await?;
+
+    Ok(Some(bincode::deserialize(&buffer)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn write_and_read_messages() {
+        let messages = [
+            Message::Request(Request {
+                arguments: vec!["turtle".into(), "foo".into()],
+                color: true,
+            }),
+            Message::Stdout(b"foo".to_vec()),
+            Message::Stderr(b"bar".to_vec()),
+            Message::Exit(1),
+        ];
+        let mut buffer = vec![];
+
+        for message in &messages {
+            write_message(&mut buffer, message).await.unwrap();
+        }
+
+        let mut reader = buffer.as_slice();
+
+        for message in messages {
+            assert_eq!(read_message(&mut reader).await.unwrap(), Some(message));
+        }
+
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+// A build request of a client
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Request {
+    pub arguments: Vec<String>,
+    // Whether colors are enabled by default on a client's stderr, which a
+    // daemon cannot detect by itself
+    pub color: bool,
+}
@@ -1,5 +1,5 @@
 use crate::{
-    build_graph::BuildGraphError, compile::CompileError, ir::Build,
+    build_graph::BuildGraphError, compile::CompileError, infrastructure::CommandError, ir::Build,
     module_dependency::ModuleDependencyError, parse::ParseError,
 };
 use std::{
@@ -9,21 +9,55 @@ use std::{
 };
 use tokio::{io, task::JoinError};
 
+const BUILD_EXIT_CODE: i32 = 1;
+const CONFIGURATION_EXIT_CODE: i32 = 2;
+const OUT_OF_DATE_EXIT_CODE: i32 = 3;
+// It follows a convention of shells for SIGINT.
+const INTERRUPTION_EXIT_CODE: i32 = 130;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ApplicationError {
     Build,
     BuildGraph(BuildGraphError),
+    Command(CommandError),
     Compile(CompileError),
     DefaultOutputNotFound(Arc<str>),
     DynamicDependencyNotFound(Arc<Build>),
     FileNotFound(String),
     InputNotBuilt(String),
     InputNotFound(String),
+    Interrupted,
+    MissingInput(String, String),
     ModuleDependency(ModuleDependencyError),
+    ModuleNotResolved(String, String, String),
     Other(String),
+    OutOfDate(usize),
     OutputNotFound(String),
     Parse(ParseError),
+    // Parse errors of build files at their paths
+    Parses(Vec<(String, ParseError)>),
     Sled(sled::Error),
+    Stalled,
+}
+
+impl ApplicationError {
+    // Failures of builds and problems in build files or command line arguments
+    // are distinguished for scripts.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::BuildGraph(_)
+            | Self::Compile(_)
+            | Self::DefaultOutputNotFound(_)
+            | Self::ModuleDependency(_)
+            | Self::ModuleNotResolved(..)
+            | Self::OutputNotFound(_)
+            | Self::Parse(_)
+            | Self::Parses(_) => CONFIGURATION_EXIT_CODE,
+            Self::Interrupted => INTERRUPTION_EXIT_CODE,
+            Self::OutOfDate(_) => OUT_OF_DATE_EXIT_CODE,
+            _ => BUILD_EXIT_CODE,
+        }
+    }
 }
 
 impl Error for ApplicationError {}
@@ -32,6 +66,7 @@ impl Display for ApplicationError {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Self::Build => write!(formatter, "build failed"),
+            Self::Command(error) => write!(formatter, "{error}"),
             Self::Compile(error) => write!(formatter, "{error}"),
             Self::DefaultOutputNotFound(output) => {
                 write!(formatter, "default output \"{output}\" not found")
@@ -51,15 +86,39 @@ impl Display for ApplicationError {
             Self::InputNotFound(input) => {
                 write!(formatter, "input \"{input}\" not found")
             }
+            Self::Interrupted => write!(formatter, "interrupted"),
+            Self::MissingInput(input, output) => {
+                write!(
+                    formatter,
+                    "input \"{input}\" needed by \"{output}\" not found"
+                )
+            }
             Self::ModuleDependency(error) => {
                 write!(formatter, "{error}")
             }
+            Self::ModuleNotResolved(path, parent, message) => {
+                write!(
+                    formatter,
+                    "failed to resolve \"{path}\" included by \"{parent}\": {message}"
+                )
+            }
             Self::Other(message) => write!(formatter, "{message}"),
+            Self::OutOfDate(count) => write!(formatter, "{count} outputs not up to date"),
             Self::OutputNotFound(output) => {
                 write!(formatter, "output \"{output}\" not found")
             }
             Self::Parse(error) => write!(formatter, "{error}"),
+            Self::Parses(errors) => write!(
+                formatter,
+                "{}",
+                errors
+                    .iter()
+                    .map(|(path, error)| format!("{path}: {error}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
             Self::Sled(error) => write!(formatter, "{error}"),
+            Self::Stalled => write!(formatter, "build stalled"),
             Self::BuildGraph(error) => write!(formatter, "{error}"),
         }
     }
@@ -71,6 +130,12 @@ impl From<Box<dyn Error>> for ApplicationError {
     }
 }
 
+impl From<CommandError> for ApplicationError {
+    fn from(error: CommandError) -> Self {
+        Self::Command(error)
+    }
+}
+
 impl From<CompileError> for ApplicationError {
     fn from(error: CompileError) -> Self {
         Self::Compile(error)
@@ -112,3 +177,47 @@ impl From<BuildGraphError> for ApplicationError {
         Self::BuildGraph(error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code() {
+        for error in [
+            ApplicationError::Build,
+            ApplicationError::InputNotFound("foo".into()),
+            ApplicationError::Stalled,
+            ApplicationError::Other("foo".into()),
+        ] {
+            assert_eq!(error.exit_code(), 1);
+        }
+
+        for error in [
+            ApplicationError::Compile(CompileError::RuleNotFound("foo".into())),
+            ApplicationError::ModuleDependency(ModuleDependencyError::CircularDependency),
+            ApplicationError::ModuleNotResolved("foo".into(), "bar".into(), "baz".into()),
+            ApplicationError::OutputNotFound("foo".into()),
+            ApplicationError::Parse(ParseError::new("foo")),
+            ApplicationError::Parses(vec![("foo.ninja".into(), ParseError::new("foo"))]),
+            ApplicationError::BuildGraph(BuildGraphError::CircularDependency(vec![])),
+        ] {
+            assert_eq!(error.exit_code(), 2);
+        }
+
+        assert_eq!(ApplicationError::OutOfDate(1).exit_code(), 3);
+        assert_eq!(ApplicationError::Interrupted.exit_code(), 130);
+    }
+
+    #[test]
+    fn display_parse_errors() {
+        assert_eq!(
+            ApplicationError::Parses(vec![
+                ("bar.ninja".into(), ParseError::new("foo")),
+                ("foo.ninja".into(), ParseError::new("bar")),
+            ])
+            .to_string(),
+            "bar.ninja: foo\nfoo.ninja: bar"
+        );
+    }
+}
@@ -1,9 +1,23 @@
 mod command_runner;
 mod console;
 mod database;
+#[cfg(test)]
+mod fake_command_runner;
+#[cfg(test)]
+mod fake_file_system;
 mod file_system;
+mod read_only_database;
+#[cfg(test)]
+mod recording_console;
 
 pub use command_runner::*;
 pub use console::*;
 pub use database::*;
+#[cfg(test)]
+pub use fake_command_runner::*;
+#[cfg(test)]
+pub use fake_file_system::*;
 pub use file_system::*;
+pub use read_only_database::*;
+#[cfg(test)]
+pub use recording_console::*;
@@ -1,34 +1,724 @@
 use async_trait::async_trait;
-use std::{error::Error, process::Output};
-use tokio::{process::Command, sync::Semaphore};
+use std::{
+    env,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    future::Future,
+    path::PathBuf,
+    process::{Command, ExitStatus, Output},
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::{
+    sync::Semaphore,
+    time::{sleep, Instant},
+};
+
+const SHELL: &str = "nu";
+pub const DEFAULT_CAPTURE_LIMIT: usize = 4 * 1024 * 1024;
+const TRUNCATION_MARKER: &[u8] = b"\n(output truncated)\n";
+
+// Commands are passed to a shell as single arguments whose lengths are limited
+// by platforms.
+#[cfg(target_os = "linux")]
+const COMMAND_LENGTH_LIMIT: usize = 128 * 1024 - 1;
+#[cfg(windows)]
+const COMMAND_LENGTH_LIMIT: usize = 32 * 1024 - 1;
+#[cfg(not(any(target_os = "linux", windows)))]
+const COMMAND_LENGTH_LIMIT: usize = 256 * 1024;
 
 #[async_trait]
 pub trait CommandRunner {
-    async fn run(&self, command: &str) -> Result<Output, Box<dyn Error>>;
+    // Commands run sequentially and the first failure stops the rest. Shells
+    // are words of commands given commands as their last arguments.
+    async fn run(
+        &self,
+        commands: &[String],
+        environment: &[(String, String)],
+        shell: Option<&[String]>,
+    ) -> Result<(Output, Option<ResourceUsage>), CommandError>;
+}
+
+// Outputs of commands are truncated beyond capture limits with markers.
+pub fn is_truncated(output: &[u8]) -> bool {
+    output
+        .windows(TRUNCATION_MARKER.len())
+        .any(|window| window == TRUNCATION_MARKER)
+}
+
+// Resource usage of commands is available only on some platforms.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ResourceUsage {
+    pub cpu_time: Duration,
+    // A peak resident set size in bytes
+    pub max_rss: u64,
+}
+
+impl ResourceUsage {
+    // Commands of the same build run one after another.
+    fn merge(self, other: Self) -> Self {
+        Self {
+            cpu_time: self.cpu_time + other.cpu_time,
+            max_rss: self.max_rss.max(other.max_rss),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct OsCommandRunner {
     semaphore: Semaphore,
+    shell: PathBuf,
+    // A shell of commands whose rules do not specify any
+    default_shell: Option<Vec<String>>,
+    command_length_limit: usize,
+    spawn_limiter: Option<SpawnLimiter>,
+    // A number of bytes of each of stdout and stderr kept for each command
+    capture_limit: usize,
+    clear_environment: bool,
+    environment: Vec<(String, String)>,
 }
 
 impl OsCommandRunner {
-    pub fn new(job_limit: usize) -> Self {
+    pub fn new(
+        job_limit: usize,
+        clear_environment: bool,
+        environment: Vec<(String, String)>,
+        default_shell: Option<Vec<String>>,
+    ) -> Self {
         Self {
             semaphore: Semaphore::new(job_limit),
+            shell: find_shell(),
+            default_shell: default_shell.filter(|shell| !shell.is_empty()),
+            command_length_limit: COMMAND_LENGTH_LIMIT,
+            spawn_limiter: None,
+            capture_limit: DEFAULT_CAPTURE_LIMIT,
+            clear_environment,
+            environment,
+        }
+    }
+
+    // Commands are spawned at most a number of times per second regardless of
+    // a job limit.
+    pub fn limit_spawn_rate(self, rate: u64) -> Self {
+        Self {
+            spawn_limiter: Some(SpawnLimiter::new(rate)),
+            ..self
+        }
+    }
+
+    pub fn limit_capture(self, limit: usize) -> Self {
+        Self {
+            capture_limit: limit,
+            ..self
+        }
+    }
+
+    // A permit is kept in a guard until a job finishes so that it is released
+    // even on panics or cancellation of the job.
+    async fn with_permit<T>(&self, job: impl Future<Output = T>) -> Result<T, CommandError> {
+        let _permit = self
+            .semaphore
+            .acquire()
+            .await
+            .map_err(|_| CommandError::SemaphoreClosed)?;
+
+        Ok(job.await)
+    }
+
+    // Environment variables of rules override the ones of a runner.
+    fn command(
+        &self,
+        command: &str,
+        environment: &[(String, String)],
+        shell: Option<&[String]>,
+    ) -> Command {
+        let mut process = if let Some((program, arguments)) = shell
+            .or(self.default_shell.as_deref())
+            .and_then(|shell| shell.split_first())
+        {
+            let mut process = Command::new(program);
+            process.args(arguments);
+            process
+        } else {
+            let mut process = Command::new(&self.shell);
+            process.arg("-c");
+            process
+        };
+
+        if self.clear_environment {
+            process.env_clear();
+        }
+
+        process
+            .envs(self.environment.iter().chain(environment).cloned())
+            .arg(command);
+
+        process
+    }
+}
+
+// A child process is reaped by `wait4` to get its resource usage which the
+// standard library does not expose.
+#[cfg(unix)]
+async fn run_command(
+    command: Command,
+    capture_limit: usize,
+) -> Result<(Output, Option<ResourceUsage>), CommandError> {
+    tokio::task::spawn_blocking(move || wait_command(command, capture_limit))
+        .await
+        .map_err(|error| CommandError::Spawn(error.to_string()))?
+        .map(|(output, usage)| (output, Some(usage)))
+        .map_err(|error| CommandError::Spawn(error.to_string()))
+}
+
+#[cfg(not(unix))]
+async fn run_command(
+    mut command: Command,
+    capture_limit: usize,
+) -> Result<(Output, Option<ResourceUsage>), CommandError> {
+    tokio::task::spawn_blocking(move || {
+        let mut child = spawn_child(&mut command)?;
+        let (stdout, stderr) = read_outputs(&mut child, capture_limit)?;
+
+        Ok::<_, std::io::Error>(Output {
+            status: child.wait()?,
+            stdout,
+            stderr,
+        })
+    })
+    .await
+    .map_err(|error| CommandError::Spawn(error.to_string()))?
+    .map(|output| (output, None))
+    .map_err(|error| CommandError::Spawn(error.to_string()))
+}
+
+fn spawn_child(command: &mut Command) -> std::io::Result<std::process::Child> {
+    use std::process::Stdio;
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+}
+
+// Pipes are drained to the end so that children do not block on them.
+fn read_outputs(
+    child: &mut std::process::Child,
+    capture_limit: usize,
+) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    use std::thread;
+
+    let (Some(stdout_pipe), Some(stderr_pipe)) = (child.stdout.take(), child.stderr.take()) else {
+        unreachable!()
+    };
+    let (stdout, stderr) = thread::scope(|scope| {
+        let stdout = scope.spawn(move || read_capped(stdout_pipe, capture_limit));
+        let stderr = read_capped(stderr_pipe, capture_limit);
+
+        (stdout.join().unwrap(), stderr)
+    });
+
+    Ok((stdout?, stderr?))
+}
+
+// Bytes beyond a limit are discarded with a marker appended.
+fn read_capped(mut reader: impl std::io::Read, limit: usize) -> std::io::Result<Vec<u8>> {
+    use std::io::{self, Read};
+
+    let mut buffer = vec![];
+
+    reader
+        .by_ref()
+        .take(limit as u64)
+        .read_to_end(&mut buffer)?;
+
+    if io::copy(&mut reader, &mut io::sink())? > 0 {
+        buffer.extend(TRUNCATION_MARKER);
+    }
+
+    Ok(buffer)
+}
+
+#[cfg(unix)]
+fn wait_command(
+    mut command: Command,
+    capture_limit: usize,
+) -> std::io::Result<(Output, ResourceUsage)> {
+    use std::{io, mem::MaybeUninit, os::unix::process::ExitStatusExt};
+
+    let mut child = spawn_child(&mut command)?;
+    let (stdout, stderr) = read_outputs(&mut child, capture_limit)?;
+
+    let mut status = 0;
+    let mut usage = MaybeUninit::<libc::rusage>::zeroed();
+
+    // SAFETY: The child is not reaped elsewhere and pointers are valid.
+    while unsafe { libc::wait4(child.id() as _, &mut status, 0, usage.as_mut_ptr()) } < 0 {
+        let error = io::Error::last_os_error();
+
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
         }
     }
+
+    // SAFETY: `wait4` succeeded and initialized the usage.
+    let usage = unsafe { usage.assume_init() };
+    let time = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as _) + Duration::from_micros(time.tv_usec as _)
+    };
+
+    Ok((
+        Output {
+            status: ExitStatus::from_raw(status),
+            stdout,
+            stderr,
+        },
+        ResourceUsage {
+            cpu_time: time(usage.ru_utime) + time(usage.ru_stime),
+            // Peak resident set sizes are in kilobytes except on macOS.
+            max_rss: usage.ru_maxrss as u64 * if cfg!(target_os = "macos") { 1 } else { 1024 },
+        },
+    ))
+}
+
+// Find a shell in a path of the current process so that environment variables
+// of commands do not affect it.
+fn find_shell() -> PathBuf {
+    env::var_os("PATH")
+        .and_then(|paths| {
+            env::split_paths(&paths)
+                .map(|path| path.join(SHELL))
+                .find(|path| path.is_file())
+        })
+        .unwrap_or_else(|| SHELL.into())
 }
 
 #[async_trait]
 impl CommandRunner for OsCommandRunner {
-    async fn run(&self, command: &str) -> Result<Output, Box<dyn Error>> {
-        let permit = self.semaphore.acquire().await?;
+    async fn run(
+        &self,
+        commands: &[String],
+        environment: &[(String, String)],
+        shell: Option<&[String]>,
+    ) -> Result<(Output, Option<ResourceUsage>), CommandError> {
+        if let Some(command) = commands
+            .iter()
+            .find(|command| command.len() > self.command_length_limit)
+        {
+            return Err(CommandError::TooLong(
+                command.len(),
+                self.command_length_limit,
+            ));
+        }
+
+        let (output, usage) = self
+            .with_permit(async {
+                let mut output = Output {
+                    status: ExitStatus::default(),
+                    stdout: vec![],
+                    stderr: vec![],
+                };
+                let mut usage = None::<ResourceUsage>;
+
+                for command in commands {
+                    if let Some(limiter) = &self.spawn_limiter {
+                        sleep(limiter.reserve(Instant::now())).await;
+                    }
+
+                    let (
+                        Output {
+                            status,
+                            stdout,
+                            stderr,
+                        },
+                        command_usage,
+                    ) = run_command(
+                        self.command(command, environment, shell),
+                        self.capture_limit,
+                    )
+                    .await?;
+
+                    output.status = status;
+                    output.stdout.extend(stdout);
+                    output.stderr.extend(stderr);
+
+                    if let Some(command_usage) = command_usage {
+                        usage = Some(usage.unwrap_or_default().merge(command_usage));
+                    }
+
+                    if !status.success() {
+                        break;
+                    }
+                }
+
+                Ok::<_, CommandError>((output, usage))
+            })
+            .await??;
+
+        if output.status.success() {
+            Ok((output, usage))
+        } else if output.status.code().is_some() {
+            Err(CommandError::Exit(output))
+        } else {
+            Err(CommandError::Signal(output))
+        }
+    }
+}
+
+// A token bucket of a single token refilled at a rate
+#[derive(Debug)]
+struct SpawnLimiter {
+    interval: Duration,
+    next_time: Mutex<Option<Instant>>,
+}
+
+impl SpawnLimiter {
+    fn new(rate: u64) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / rate.clamp(1, u32::MAX.into()) as u32,
+            next_time: Mutex::new(None),
+        }
+    }
+
+    // This reserves a spawn at the earliest time available and returns a delay
+    // until it.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut next_time = self.next_time.lock().unwrap();
+        let time = next_time.map_or(now, |next_time| next_time.max(now));
+
+        *next_time = Some(time + self.interval);
+
+        time - now
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CommandError {
+    Exit(Output),
+    SemaphoreClosed,
+    Signal(Output),
+    Spawn(String),
+    Timeout(Duration),
+    // Lengths of a command and its limit in bytes
+    TooLong(usize, usize),
+}
+
+impl CommandError {
+    pub fn output(&self) -> Option<&Output> {
+        match self {
+            Self::Exit(output) | Self::Signal(output) => Some(output),
+            Self::SemaphoreClosed | Self::Spawn(_) | Self::Timeout(_) | Self::TooLong(..) => None,
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+impl Display for CommandError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Exit(output) => write!(
+                formatter,
+                "command exited with status {}",
+                output.status.code().unwrap_or_default()
+            ),
+            Self::SemaphoreClosed => write!(formatter, "job semaphore closed"),
+            Self::Signal(_) => write!(formatter, "command terminated by signal"),
+            Self::Spawn(message) => write!(formatter, "failed to spawn command: {message}"),
+            Self::Timeout(duration) => {
+                write!(
+                    formatter,
+                    "command timed out after {}ms",
+                    duration.as_millis()
+                )
+            }
+            Self::TooLong(length, limit) => write!(
+                formatter,
+                "command of {length} bytes longer than the limit of {limit} bytes (use rspfile instead)"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{future::pending, sync::Arc};
+    use tokio::{spawn, time::timeout};
+
+    #[cfg(unix)]
+    fn exit_status(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn get_output_of_exit() {
+        let output = Output {
+            status: exit_status(1),
+            stdout: b"foo".to_vec(),
+            stderr: b"bar".to_vec(),
+        };
+
+        assert_eq!(CommandError::Exit(output.clone()).output(), Some(&output));
+        assert_eq!(CommandError::Spawn("foo".into()).output(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn display_exit() {
+        assert_eq!(
+            CommandError::Exit(Output {
+                status: exit_status(42),
+                stdout: vec![],
+                stderr: vec![],
+            })
+            .to_string(),
+            "command exited with status 42"
+        );
+    }
+
+    #[test]
+    fn convert_into_boxed_error() {
+        let error: Box<dyn Error> = CommandError::Timeout(Duration::from_millis(42)).into();
+
+        assert_eq!(error.to_string(), "command timed out after 42ms");
+    }
+
+    #[tokio::test]
+    async fn release_permit_on_panic() {
+        let runner = Arc::new(OsCommandRunner::new(2, false, vec![], None));
+
+        assert!(spawn({
+            let runner = runner.clone();
+
+            async move {
+                runner
+                    .with_permit(async {
+                        panic!("foo");
+                    })
+                    .await
+            }
+        })
+        .await
+        .unwrap_err()
+        .is_panic());
+        assert_eq!(runner.semaphore.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn release_permit_on_cancellation() {
+        let runner = OsCommandRunner::new(1, false, vec![], None);
+
+        assert!(timeout(
+            Duration::from_millis(1),
+            runner.with_permit(pending::<()>())
+        )
+        .await
+        .is_err());
+        assert_eq!(runner.semaphore.available_permits(), 1);
+    }
 
-        let output = Command::new("nu").arg("-c").arg(command).output().await?;
+    #[tokio::test]
+    async fn fail_on_closed_semaphore() {
+        let runner = OsCommandRunner::new(1, false, vec![], None);
 
-        drop(permit);
+        runner.semaphore.close();
+
+        assert_eq!(
+            runner.with_permit(async {}).await,
+            Err(CommandError::SemaphoreClosed)
+        );
+    }
+
+    #[test]
+    fn pace_spawns() {
+        let limiter = SpawnLimiter::new(4);
+        let time = Instant::now();
+
+        assert_eq!(limiter.reserve(time), Duration::ZERO);
+        assert_eq!(limiter.reserve(time), Duration::from_millis(250));
+        assert_eq!(limiter.reserve(time), Duration::from_millis(500));
+        assert_eq!(
+            limiter.reserve(time + Duration::from_millis(100)),
+            Duration::from_millis(650)
+        );
+    }
+
+    #[test]
+    fn refill_spawns_after_idle_time() {
+        let limiter = SpawnLimiter::new(4);
+        let time = Instant::now();
+
+        limiter.reserve(time);
+        limiter.reserve(time);
+
+        assert_eq!(
+            limiter.reserve(time + Duration::from_secs(1)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn limit_spawns_per_second() {
+        let limiter = SpawnLimiter::new(10);
+        let time = Instant::now();
+        // Spawns requested every 10 milliseconds for a second
+        let spawn_times = (0..100)
+            .map(|index| {
+                let now = time + Duration::from_millis(10 * index);
+
+                now + limiter.reserve(now)
+            })
+            .collect::<Vec<_>>();
+
+        for window in spawn_times.windows(2) {
+            assert!(window[1] - window[0] >= Duration::from_millis(100));
+        }
+
+        assert_eq!(
+            spawn_times
+                .iter()
+                .filter(|&&spawn_time| spawn_time < time + Duration::from_secs(1))
+                .count(),
+            10
+        );
+    }
+
+    #[tokio::test]
+    async fn fail_to_run_too_long_command() {
+        let mut runner = OsCommandRunner::new(1, false, vec![], None);
+
+        runner.command_length_limit = 8;
+
+        assert_eq!(
+            runner
+                .run(&["echo foo".into(), "echo foobar".into()], &[], None)
+                .await,
+            Err(CommandError::TooLong(11, 8))
+        );
+        assert_eq!(
+            CommandError::TooLong(11, 8).to_string(),
+            "command of 11 bytes longer than the limit of 8 bytes (use rspfile instead)"
+        );
+    }
+
+    #[test]
+    fn set_environment_variables() {
+        let runner = OsCommandRunner::new(
+            1,
+            true,
+            vec![("FOO".into(), "foo".into()), ("BAR".into(), "bar".into())],
+            None,
+        );
+        let command = runner.command("", &[("BAR".into(), "baz".into())], None);
+
+        assert_eq!(
+            command
+                .get_envs()
+                .map(|(name, value)| (
+                    name.to_str().unwrap(),
+                    value.and_then(|value| value.to_str())
+                ))
+                .collect::<Vec<_>>(),
+            vec![("BAR", Some("baz")), ("FOO", Some("foo"))]
+        );
+    }
+
+    #[test]
+    fn run_commands_in_shell() {
+        let runner = OsCommandRunner::new(1, false, vec![], None);
+        let command = runner.command("echo foo", &[], Some(&["bash".into(), "-c".into()]));
+
+        assert_eq!(command.get_program(), "bash");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec!["-c", "echo foo"]
+        );
+    }
+
+    #[test]
+    fn run_commands_in_default_shell() {
+        let runner = OsCommandRunner::new(1, false, vec![], Some(vec!["sh".into(), "-c".into()]));
+
+        assert_eq!(runner.command("echo foo", &[], None).get_program(), "sh");
+        assert_eq!(
+            runner
+                .command("echo foo", &[], Some(&["bash".into(), "-c".into()]))
+                .get_program(),
+            "bash"
+        );
+    }
+
+    #[tokio::test]
+    async fn truncate_captured_outputs() {
+        let runner = OsCommandRunner::new(1, false, vec![], None).limit_capture(1000);
+
+        let Err(CommandError::Exit(output)) = runner
+            .run(
+                &["head -c 100000 /dev/zero; head -c 10 /dev/zero >&2; exit 3".into()],
+                &[],
+                Some(&["sh".into(), "-c".into()]),
+            )
+            .await
+        else {
+            panic!("command not failed")
+        };
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout.len(), 1000 + TRUNCATION_MARKER.len());
+        assert!(output.stdout.ends_with(TRUNCATION_MARKER));
+        assert!(is_truncated(&output.stdout));
+        assert_eq!(output.stderr, vec![0; 10]);
+        assert!(!is_truncated(&output.stderr));
+    }
+
+    #[test]
+    fn read_output_within_limit() {
+        assert_eq!(read_capped(&b"foo"[..], 3).unwrap(), b"foo");
+        assert_eq!(
+            read_capped(&b"foobar"[..], 3).unwrap(),
+            [&b"foo"[..], TRUNCATION_MARKER].concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn record_resource_usage() {
+        let runner = OsCommandRunner::new(1, false, vec![], None);
+        let (_, usage) = runner
+            .run(
+                &["exit 0".into(), "exit 0".into()],
+                &[],
+                Some(&["sh".into(), "-c".into()]),
+            )
+            .await
+            .unwrap();
+
+        if cfg!(unix) {
+            assert!(usage.unwrap().max_rss > 0);
+        } else {
+            assert_eq!(usage, None);
+        }
+    }
 
-        Ok(output)
+    #[test]
+    fn merge_resource_usage() {
+        assert_eq!(
+            ResourceUsage {
+                cpu_time: Duration::from_millis(1),
+                max_rss: 2,
+            }
+            .merge(ResourceUsage {
+                cpu_time: Duration::from_millis(3),
+                max_rss: 1,
+            }),
+            ResourceUsage {
+                cpu_time: Duration::from_millis(4),
+                max_rss: 2,
+            }
+        );
     }
 }
@@ -1,39 +1,272 @@
 use async_trait::async_trait;
-use std::{error::Error, fmt::Debug};
-use tokio::io::{stderr, stdout, AsyncWriteExt, Stderr, Stdout};
+use once_cell::sync::Lazy;
+use regex::bytes::Regex;
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::Debug,
+    io::{self, IsTerminal},
+    process::Stdio,
+    time::Instant,
+};
+use terminal_size::{terminal_size_of, Width};
+use tokio::{
+    fs::File,
+    io::{stderr, stdout, AsyncWriteExt, Stderr, Stdout},
+    process::{Child, Command},
+};
+
+// CSI and OSC sequences
+static ESCAPE_SEQUENCE_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)").unwrap());
 
 #[async_trait]
 pub trait Console {
     async fn write_stdout(&mut self, buffer: &[u8]) -> Result<(), Box<dyn Error>>;
     async fn write_stderr(&mut self, buffer: &[u8]) -> Result<(), Box<dyn Error>>;
+
+    // Returns a width of a terminal of stderr if any.
+    fn width(&self) -> Option<usize> {
+        None
+    }
+
+    async fn set_title(&mut self, _title: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn notify(&mut self, _message: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // Waits for a pager to exit if any.
+    async fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub struct OsConsole {
     stdout: Stdout,
     stderr: Stderr,
+    log_file: Option<File>,
+    start_time: Instant,
+    line_start: bool,
+    terminal: bool,
+    plain_stdout: bool,
+    pager: Option<Child>,
 }
 
 impl OsConsole {
-    pub fn new() -> Self {
+    pub fn new(log_file: Option<File>) -> Self {
         Self {
             stdout: stdout(),
             stderr: stderr(),
+            log_file,
+            start_time: Instant::now(),
+            line_start: true,
+            terminal: io::stderr().is_terminal(),
+            plain_stdout: false,
+            pager: None,
+        }
+    }
+
+    // Escape sequences are stripped from stdout, e.g. for outputs of tools
+    // piped to other commands.
+    pub fn strip_stdout(mut self) -> Self {
+        self.plain_stdout = true;
+        self
+    }
+
+    // Stdout is piped to a pager command split by whitespaces.
+    pub fn page(mut self, command: &str) -> Result<Self, io::Error> {
+        let mut arguments = command.split_whitespace();
+
+        self.pager = Some(
+            Command::new(arguments.next().unwrap_or_default())
+                .args(arguments)
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|error| io::Error::new(error.kind(), format!("{error}: {command}")))?,
+        );
+
+        Ok(self)
+    }
+
+    // OSC sequences are written only to terminals as they are garbage in files
+    // or pipes.
+    fn format_osc_sequence(&self, code: usize, text: &str) -> Option<String> {
+        self.terminal.then(|| format!("\x1b]{code};{text}\x07"))
+    }
+
+    async fn write_osc_sequence(&mut self, code: usize, text: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(sequence) = self.format_osc_sequence(code, text) {
+            self.stderr.write_all(sequence.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+
+    // Lines in a log file are prefixed with seconds elapsed since start.
+    async fn write_log_file(&mut self, src: &[u8]) -> Result<(), Box<dyn Error>> {
+        if let Some(file) = &mut self.log_file {
+            let mut buffer = vec![];
+
+            for line in src.split_inclusive(|&byte| byte == b'\n') {
+                if self.line_start {
+                    buffer.extend(
+                        format!("[{:.3}s] ", self.start_time.elapsed().as_secs_f64()).as_bytes(),
+                    );
+                }
+
+                buffer.extend(line);
+                self.line_start = line.ends_with(b"\n");
+            }
+
+            file.write_all(&buffer).await?;
+            // Flush every write as a process can exit any time on failure.
+            file.flush().await?;
         }
+
+        Ok(())
+    }
+}
+
+impl Default for OsConsole {
+    fn default() -> Self {
+        Self::new(None)
     }
 }
 
 #[async_trait]
 impl Console for OsConsole {
     async fn write_stdout(&mut self, src: &[u8]) -> Result<(), Box<dyn Error>> {
-        self.stdout.write_all(src).await?;
+        let src = if self.plain_stdout {
+            strip_escape_sequences(src)
+        } else {
+            src.into()
+        };
+
+        if let Some(stdin) = self.pager.as_mut().and_then(|pager| pager.stdin.as_mut()) {
+            // Pagers can exit before reading everything.
+            match stdin.write_all(&src).await {
+                Err(error) if error.kind() != io::ErrorKind::BrokenPipe => return Err(error.into()),
+                _ => {}
+            }
+        } else {
+            self.stdout.write_all(&src).await?;
+        }
+
+        self.write_log_file(&src).await?;
 
         Ok(())
     }
 
     async fn write_stderr(&mut self, src: &[u8]) -> Result<(), Box<dyn Error>> {
         self.stderr.write_all(src).await?;
+        self.write_log_file(src).await?;
+
+        Ok(())
+    }
+
+    fn width(&self) -> Option<usize> {
+        let stderr = io::stderr();
+
+        if stderr.is_terminal() {
+            terminal_size_of(stderr).map(|(Width(width), _)| width as usize)
+        } else {
+            None
+        }
+    }
+
+    async fn set_title(&mut self, title: &str) -> Result<(), Box<dyn Error>> {
+        self.write_osc_sequence(0, title).await
+    }
+
+    async fn notify(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.write_osc_sequence(9, message).await
+    }
+
+    async fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(mut pager) = self.pager.take() {
+            drop(pager.stdin.take());
+            pager.wait().await?;
+        }
 
         Ok(())
     }
 }
+
+fn strip_escape_sequences(src: &[u8]) -> Cow<'_, [u8]> {
+    ESCAPE_SEQUENCE_PATTERN.replace_all(src, &b""[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn write_log_file() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("log");
+        let mut console = OsConsole::new(Some(File::create(&path).await.unwrap()));
+
+        console.write_stdout(b"foo").await.unwrap();
+        console.write_stdout(b"bar\nbaz\n").await.unwrap();
+        console
+            .write_stderr(b"turtle: build failed\n")
+            .await
+            .unwrap();
+
+        assert!(Regex::new(
+            r"^\[\d+\.\d{3}s\] foobar\n\[\d+\.\d{3}s\] baz\n\[\d+\.\d{3}s\] turtle: build failed\n$"
+        )
+        .unwrap()
+        .is_match(&std::fs::read_to_string(&path).unwrap()));
+    }
+
+    #[test]
+    fn strip_escape_sequences_of_colors() {
+        assert_eq!(
+            strip_escape_sequences(b"\x1b[1;31mfoo\x1b[0m bar\x1b]0;baz\x07\n"),
+            &b"foo bar\n"[..]
+        );
+        assert_eq!(strip_escape_sequences(b"foo"), &b"foo"[..]);
+    }
+
+    #[tokio::test]
+    async fn write_plain_stdout_to_log_file() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("log");
+        let mut console = OsConsole::new(Some(File::create(&path).await.unwrap())).strip_stdout();
+
+        console.write_stdout(b"\x1b[32mfoo\x1b[0m\n").await.unwrap();
+        console.close().await.unwrap();
+
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("] foo\n"));
+    }
+
+    #[test]
+    fn format_osc_sequences_for_terminal() {
+        let mut console = OsConsole::new(None);
+        console.terminal = true;
+
+        assert_eq!(
+            console.format_osc_sequence(0, "[1/2] turtle"),
+            Some("\x1b]0;[1/2] turtle\x07".into())
+        );
+        assert_eq!(
+            console.format_osc_sequence(9, "build succeeded"),
+            Some("\x1b]9;build succeeded\x07".into())
+        );
+    }
+
+    #[test]
+    fn format_no_osc_sequence_without_terminal() {
+        let mut console = OsConsole::new(None);
+        console.terminal = false;
+
+        assert_eq!(console.format_osc_sequence(0, "turtle"), None);
+    }
+}
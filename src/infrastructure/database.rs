@@ -1,38 +1,102 @@
 use crate::{hash_type::HashType, ir::BuildId};
 use async_trait::async_trait;
 use once_cell::sync::OnceCell;
-use std::{error::Error, path::Path, str};
+use sled::{transaction::TransactionError, Transactional};
+use std::{
+    collections::HashSet,
+    error::Error,
+    io::{self, ErrorKind},
+    path::Path,
+    str,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 const TIMESTAMP_HASH_TREE_NAME: &str = "timestamp_hash";
 const CONTENT_HASH_TREE_NAME: &str = "content_hash";
+const DEPENDENCY_HASH_TREE_NAME: &str = "dependency_hash";
+const DEPENDENCY_TREE_NAME: &str = "dependency";
 const OUTPUT_TREE_NAME: &str = "output";
 const SOURCE_TREE_NAME: &str = "source";
+const RUNNING_BUILD_TREE_NAME: &str = "running_build";
+const TARGETS_KEY: &str = "targets";
+const CONFIGURATION_HASH_KEY: &str = "configuration_hash";
+const BUILD_COUNT_KEY: &str = "build_count";
+const DEPENDENCY_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[async_trait]
 pub trait Database {
     fn initialize(&self, path: &Path) -> Result<(), Box<dyn Error>>;
+    // Records are discarded on exit.
+    fn initialize_in_memory(&self) -> Result<(), Box<dyn Error>>;
 
     fn get_hash(&self, r#type: HashType, id: BuildId) -> Result<Option<u64>, Box<dyn Error>>;
     fn set_hash(&self, r#type: HashType, id: BuildId, hash: u64) -> Result<(), Box<dyn Error>>;
 
+    fn get_dependencies(&self, id: BuildId) -> Result<Vec<String>, Box<dyn Error>>;
+    // Dependencies are written durably at most once per interval so that most
+    // of them survive crashes in the middle of builds without flushing on every
+    // build.
+    async fn set_dependencies(
+        &self,
+        id: BuildId,
+        dependencies: &[String],
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn get_build_ids(&self) -> Result<Vec<BuildId>, Box<dyn Error>>;
+    // A number of builds with timestamp hashes kept without scanning records
+    fn get_build_count(&self) -> Result<usize, Box<dyn Error>>;
+    fn remove_builds(&self, ids: &[BuildId]) -> Result<(), Box<dyn Error>>;
+
     fn get_outputs(&self) -> Result<Vec<String>, Box<dyn Error>>;
     fn set_output(&self, path: &str) -> Result<(), Box<dyn Error>>;
 
     fn get_source(&self, output: &str) -> Result<Option<String>, Box<dyn Error>>;
     fn set_source(&self, output: &str, source: &str) -> Result<(), Box<dyn Error>>;
 
+    fn get_targets(&self) -> Result<Vec<String>, Box<dyn Error>>;
+    fn set_targets(&self, targets: &[String]) -> Result<(), Box<dyn Error>>;
+
+    // A hash of a configuration built last
+    fn get_configuration_hash(&self) -> Result<Option<u64>, Box<dyn Error>>;
+    fn set_configuration_hash(&self, hash: u64) -> Result<(), Box<dyn Error>>;
+
+    // Builds running commands which are left on interruption
+    fn get_running_builds(&self) -> Result<Vec<BuildId>, Box<dyn Error>>;
+    fn set_running_build(&self, id: BuildId) -> Result<(), Box<dyn Error>>;
+    fn remove_running_build(&self, id: BuildId) -> Result<(), Box<dyn Error>>;
+
     async fn flush(&self) -> Result<(), Box<dyn Error>>;
 }
 
+// Databases cannot be opened at all on some file systems, e.g. read-only ones,
+// while other errors like lock contention by other processes are fatal.
+pub fn is_unwritable_error(error: &(dyn Error + 'static)) -> bool {
+    let error = match error.downcast_ref::<sled::Error>() {
+        Some(sled::Error::Io(error)) => Some(error),
+        Some(_) => None,
+        None => error.downcast_ref::<io::Error>(),
+    };
+
+    error.is_some_and(|error| {
+        matches!(
+            error.kind(),
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem | ErrorKind::NotADirectory
+        )
+    })
+}
+
 #[derive(Debug)]
 pub struct OsDatabase {
     database: OnceCell<sled::Db>,
+    last_dependency_flush: Mutex<Option<Instant>>,
 }
 
 impl OsDatabase {
     pub fn new() -> Self {
         Self {
             database: Default::default(),
+            last_dependency_flush: Default::default(),
         }
     }
 
@@ -43,10 +107,15 @@ impl OsDatabase {
     fn hash_database(&self, r#type: HashType) -> Result<sled::Tree, Box<dyn Error>> {
         Ok(self.database()?.open_tree(match r#type {
             HashType::Content => CONTENT_HASH_TREE_NAME,
+            HashType::Dependency => DEPENDENCY_HASH_TREE_NAME,
             HashType::Timestamp => TIMESTAMP_HASH_TREE_NAME,
         })?)
     }
 
+    fn dependency_database(&self) -> Result<sled::Tree, Box<dyn Error>> {
+        Ok(self.database()?.open_tree(DEPENDENCY_TREE_NAME)?)
+    }
+
     fn output_database(&self) -> Result<sled::Tree, Box<dyn Error>> {
         Ok(self.database()?.open_tree(OUTPUT_TREE_NAME)?)
     }
@@ -54,6 +123,33 @@ impl OsDatabase {
     fn source_database(&self) -> Result<sled::Tree, Box<dyn Error>> {
         Ok(self.database()?.open_tree(SOURCE_TREE_NAME)?)
     }
+
+    fn running_build_database(&self) -> Result<sled::Tree, Box<dyn Error>> {
+        Ok(self.database()?.open_tree(RUNNING_BUILD_TREE_NAME)?)
+    }
+
+    // Counts are updated only after they are initialized by a first read.
+    fn add_build_count(&self, delta: isize) -> Result<(), Box<dyn Error>> {
+        self.database()?
+            .update_and_fetch(BUILD_COUNT_KEY, |value| {
+                value.map(|value| {
+                    bincode::serialize(
+                        &bincode::deserialize::<usize>(value)
+                            .unwrap_or_default()
+                            .saturating_add_signed(delta),
+                    )
+                    .unwrap_or_default()
+                })
+            })?;
+
+        Ok(())
+    }
+}
+
+impl Default for OsDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
@@ -66,6 +162,14 @@ impl Database for OsDatabase {
         Ok(())
     }
 
+    fn initialize_in_memory(&self) -> Result<(), Box<dyn Error>> {
+        self.database
+            .set(sled::Config::new().temporary(true).open()?)
+            .map_err(|_| "database already initialized")?;
+
+        Ok(())
+    }
+
     fn get_hash(&self, r#type: HashType, id: BuildId) -> Result<Option<u64>, Box<dyn Error>> {
         Ok(self
             .hash_database(r#type)?
@@ -75,12 +179,120 @@ impl Database for OsDatabase {
     }
 
     fn set_hash(&self, r#type: HashType, id: BuildId, hash: u64) -> Result<(), Box<dyn Error>> {
-        self.hash_database(r#type)?
-            .insert(id.to_bytes(), bincode::serialize(&hash)?)?;
+        if self
+            .hash_database(r#type)?
+            .insert(id.to_bytes(), bincode::serialize(&hash)?)?
+            .is_none()
+            && r#type == HashType::Timestamp
+        {
+            self.add_build_count(1)?;
+        }
 
         Ok(())
     }
 
+    fn get_dependencies(&self, id: BuildId) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self
+            .dependency_database()?
+            .get(id.to_bytes())?
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    // Concurrent flushes are serialized by sled and each of them writes all
+    // preceding updates.
+    async fn set_dependencies(
+        &self,
+        id: BuildId,
+        dependencies: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        self.dependency_database()?
+            .insert(id.to_bytes(), bincode::serialize(dependencies)?)?;
+
+        let flushed = {
+            let mut time = self.last_dependency_flush.lock().unwrap();
+
+            if time.is_some_and(|time| time.elapsed() < DEPENDENCY_FLUSH_INTERVAL) {
+                true
+            } else {
+                *time = Some(Instant::now());
+                false
+            }
+        };
+
+        if !flushed {
+            let database = self.database()?;
+            database.flush_async().await?;
+        }
+
+        Ok(())
+    }
+
+    fn get_build_ids(&self) -> Result<Vec<BuildId>, Box<dyn Error>> {
+        let mut ids = HashSet::new();
+
+        for tree in [
+            self.hash_database(HashType::Content)?,
+            self.hash_database(HashType::Dependency)?,
+            self.hash_database(HashType::Timestamp)?,
+            self.dependency_database()?,
+            self.running_build_database()?,
+        ] {
+            for key in tree.iter().keys() {
+                ids.insert(BuildId::from_bytes(key?.as_ref().try_into()?));
+            }
+        }
+
+        Ok(ids.into_iter().collect())
+    }
+
+    fn get_build_count(&self) -> Result<usize, Box<dyn Error>> {
+        let database = self.database()?;
+
+        if let Some(value) = database.get(BUILD_COUNT_KEY)? {
+            return Ok(bincode::deserialize(&value)?);
+        }
+
+        // Databases created by old versions are scanned only once.
+        let count = self.hash_database(HashType::Timestamp)?.len();
+        database.insert(BUILD_COUNT_KEY, bincode::serialize(&count)?)?;
+
+        Ok(count)
+    }
+
+    // Records of builds are removed atomically so that none of them is left
+    // partially on crash.
+    fn remove_builds(&self, ids: &[BuildId]) -> Result<(), Box<dyn Error>> {
+        let count = (
+            &self.hash_database(HashType::Content)?,
+            &self.hash_database(HashType::Dependency)?,
+            &self.hash_database(HashType::Timestamp)?,
+            &self.dependency_database()?,
+            &self.running_build_database()?,
+        )
+            .transaction(
+                |(content, dependency_hash, timestamp, dependency, running)| {
+                    let mut count: isize = 0;
+
+                    for id in ids {
+                        for tree in [content, dependency_hash, dependency, running] {
+                            tree.remove(&id.to_bytes())?;
+                        }
+
+                        if timestamp.remove(&id.to_bytes())?.is_some() {
+                            count += 1;
+                        }
+                    }
+
+                    Ok(count)
+                },
+            )
+            .map_err(|error: TransactionError| error.to_string())?;
+
+        self.add_build_count(-count)
+    }
+
     fn get_outputs(&self) -> Result<Vec<String>, Box<dyn Error>> {
         self.output_database()?
             .iter()
@@ -108,6 +320,57 @@ impl Database for OsDatabase {
         Ok(())
     }
 
+    fn get_targets(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self
+            .database()?
+            .get(TARGETS_KEY)?
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    fn set_targets(&self, targets: &[String]) -> Result<(), Box<dyn Error>> {
+        self.database()?
+            .insert(TARGETS_KEY, bincode::serialize(targets)?)?;
+
+        Ok(())
+    }
+
+    fn get_configuration_hash(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        Ok(self
+            .database()?
+            .get(CONFIGURATION_HASH_KEY)?
+            .map(|value| bincode::deserialize(&value))
+            .transpose()?)
+    }
+
+    fn set_configuration_hash(&self, hash: u64) -> Result<(), Box<dyn Error>> {
+        self.database()?
+            .insert(CONFIGURATION_HASH_KEY, bincode::serialize(&hash)?)?;
+
+        Ok(())
+    }
+
+    fn get_running_builds(&self) -> Result<Vec<BuildId>, Box<dyn Error>> {
+        self.running_build_database()?
+            .iter()
+            .keys()
+            .map(|key| Ok(BuildId::from_bytes(key?.as_ref().try_into()?)))
+            .collect()
+    }
+
+    fn set_running_build(&self, id: BuildId) -> Result<(), Box<dyn Error>> {
+        self.running_build_database()?.insert(id.to_bytes(), &[])?;
+
+        Ok(())
+    }
+
+    fn remove_running_build(&self, id: BuildId) -> Result<(), Box<dyn Error>> {
+        self.running_build_database()?.remove(id.to_bytes())?;
+
+        Ok(())
+    }
+
     async fn flush(&self) -> Result<(), Box<dyn Error>> {
         let database = self.database()?;
         database.flush_async().await?;
@@ -127,6 +390,44 @@ mod tests {
         database.initialize(tempdir().unwrap().path()).unwrap();
     }
 
+    #[test]
+    fn initialize_in_memory() {
+        let database = OsDatabase::new();
+        database.initialize_in_memory().unwrap();
+        database
+            .set_hash(HashType::Content, BuildId::new(0), 42)
+            .unwrap();
+
+        assert_eq!(
+            database
+                .get_hash(HashType::Content, BuildId::new(0))
+                .unwrap(),
+            Some(42)
+        );
+    }
+
+    #[test]
+    fn detect_unwritable_directory() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("foo");
+        std::fs::write(&path, "").unwrap();
+
+        assert!(is_unwritable_error(
+            &*OsDatabase::new().initialize(&path.join("bar")).unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn detect_locked_database() {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+
+        assert!(!is_unwritable_error(
+            &*OsDatabase::new().initialize(directory.path()).unwrap_err()
+        ));
+    }
+
     #[tokio::test]
     async fn flush() {
         let database = OsDatabase::new();
@@ -180,6 +481,164 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn get_dependencies() {
+        let database = OsDatabase::new();
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        assert_eq!(
+            database.get_dependencies(BuildId::new(0)).unwrap(),
+            Vec::<String>::new()
+        );
+
+        database
+            .set_dependencies(BuildId::new(0), &["foo.h".into()])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            database.get_dependencies(BuildId::new(0)).unwrap(),
+            vec!["foo.h"]
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_dependencies_on_crash() {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+
+        database
+            .set_dependencies(BuildId::new(0), &["foo.h".into()])
+            .await
+            .unwrap();
+
+        // A crash leaves only files written so far without flushing on drop.
+        let copy = tempdir().unwrap();
+
+        for entry in find_paths(directory.path()) {
+            let path = copy
+                .path()
+                .join(entry.strip_prefix(directory.path()).unwrap());
+
+            if entry.is_dir() {
+                std::fs::create_dir_all(path).unwrap();
+            } else {
+                std::fs::copy(&entry, path).unwrap();
+            }
+        }
+
+        std::mem::forget(database);
+
+        let database = OsDatabase::new();
+        database.initialize(copy.path()).unwrap();
+
+        assert_eq!(
+            database.get_dependencies(BuildId::new(0)).unwrap(),
+            vec!["foo.h"]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_dependency_flushes() {
+        let database = OsDatabase::new();
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        database
+            .set_dependencies(BuildId::new(0), &["foo.h".into()])
+            .await
+            .unwrap();
+        let time = *database.last_dependency_flush.lock().unwrap();
+        database
+            .set_dependencies(BuildId::new(1), &["bar.h".into()])
+            .await
+            .unwrap();
+
+        assert!(time.is_some());
+        assert_eq!(*database.last_dependency_flush.lock().unwrap(), time);
+        assert_eq!(
+            database.get_dependencies(BuildId::new(1)).unwrap(),
+            vec!["bar.h"]
+        );
+    }
+
+    fn find_paths(path: &Path) -> Vec<std::path::PathBuf> {
+        let mut paths = vec![];
+
+        for entry in std::fs::read_dir(path).unwrap() {
+            let path = entry.unwrap().path();
+
+            paths.push(path.clone());
+
+            if path.is_dir() {
+                paths.extend(find_paths(&path));
+            }
+        }
+
+        paths
+    }
+
+    #[tokio::test]
+    async fn remove_builds() {
+        let database = OsDatabase::new();
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        for id in [BuildId::new(0), BuildId::new(1)] {
+            database.set_hash(HashType::Timestamp, id, 42).unwrap();
+            database.set_hash(HashType::Content, id, 42).unwrap();
+        }
+        database
+            .set_dependencies(BuildId::new(0), &["foo.h".into()])
+            .await
+            .unwrap();
+
+        let mut ids = database.get_build_ids().unwrap();
+        ids.sort_by_key(|id| id.to_bytes());
+        assert_eq!(ids, vec![BuildId::new(0), BuildId::new(1)]);
+
+        database.remove_builds(&[BuildId::new(0)]).unwrap();
+
+        assert_eq!(database.get_build_ids().unwrap(), vec![BuildId::new(1)]);
+        assert_eq!(
+            database
+                .get_hash(HashType::Content, BuildId::new(0))
+                .unwrap(),
+            None
+        );
+        assert!(database
+            .get_dependencies(BuildId::new(0))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn count_builds() {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+
+        database
+            .set_hash(HashType::Timestamp, BuildId::new(0), 42)
+            .unwrap();
+        assert_eq!(database.get_build_count().unwrap(), 1);
+
+        database
+            .set_hash(HashType::Timestamp, BuildId::new(0), 0)
+            .unwrap();
+        database
+            .set_hash(HashType::Timestamp, BuildId::new(1), 42)
+            .unwrap();
+        database
+            .set_hash(HashType::Content, BuildId::new(2), 42)
+            .unwrap();
+        assert_eq!(database.get_build_count().unwrap(), 2);
+
+        database
+            .remove_builds(&[BuildId::new(0), BuildId::new(2)])
+            .unwrap();
+        assert_eq!(database.get_build_count().unwrap(), 1);
+    }
+
     #[test]
     fn set_output() {
         let database = OsDatabase::new();
@@ -215,4 +674,47 @@ mod tests {
 
         assert_eq!(database.get_source("foo").unwrap(), Some("bar".into()));
     }
+
+    #[test]
+    fn get_targets() {
+        let database = OsDatabase::new();
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        assert_eq!(database.get_targets().unwrap(), Vec::<String>::new());
+
+        database.set_targets(&["foo".into(), "bar".into()]).unwrap();
+
+        assert_eq!(database.get_targets().unwrap(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn get_configuration_hash() {
+        let database = OsDatabase::new();
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        assert_eq!(database.get_configuration_hash().unwrap(), None);
+
+        database.set_configuration_hash(42).unwrap();
+
+        assert_eq!(database.get_configuration_hash().unwrap(), Some(42));
+    }
+
+    #[test]
+    fn get_running_builds() {
+        let database = OsDatabase::new();
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        database.set_running_build(BuildId::new(0)).unwrap();
+        database.set_running_build(BuildId::new(1)).unwrap();
+        database.remove_running_build(BuildId::new(0)).unwrap();
+
+        assert_eq!(
+            database.get_running_builds().unwrap(),
+            vec![BuildId::new(1)]
+        );
+
+        database.remove_builds(&[BuildId::new(1)]).unwrap();
+
+        assert_eq!(database.get_running_builds().unwrap(), vec![]);
+    }
 }
@@ -0,0 +1,123 @@
+use super::{CommandError, CommandRunner, ResourceUsage};
+use async_trait::async_trait;
+use std::{
+    collections::HashSet,
+    process::{ExitStatus, Output},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::sleep;
+
+type Environment = Vec<(String, String)>;
+
+// A command runner which records commands in order of their completion
+// without running them.
+#[derive(Debug, Default)]
+pub struct FakeCommandRunner {
+    failing_commands: HashSet<String>,
+    delay: Duration,
+    commands: Arc<Mutex<Vec<String>>>,
+    // Shells of command lists where `None` is a default shell
+    shells: Arc<Mutex<Vec<Option<Vec<String>>>>>,
+    environments: Arc<Mutex<Vec<Environment>>>,
+    running_count: AtomicUsize,
+    max_running_count: Arc<AtomicUsize>,
+}
+
+impl FakeCommandRunner {
+    pub fn new(failing_commands: &[&str], delay: Duration) -> Self {
+        Self {
+            failing_commands: failing_commands
+                .iter()
+                .map(|&command| command.into())
+                .collect(),
+            delay,
+            commands: Default::default(),
+            shells: Default::default(),
+            environments: Default::default(),
+            running_count: Default::default(),
+            max_running_count: Default::default(),
+        }
+    }
+
+    pub fn commands(&self) -> Arc<Mutex<Vec<String>>> {
+        self.commands.clone()
+    }
+
+    pub fn shells(&self) -> Arc<Mutex<Vec<Option<Vec<String>>>>> {
+        self.shells.clone()
+    }
+
+    pub fn environments(&self) -> Arc<Mutex<Vec<Environment>>> {
+        self.environments.clone()
+    }
+
+    // Returns a maximum number of command lists run concurrently.
+    pub fn max_running_count(&self) -> Arc<AtomicUsize> {
+        self.max_running_count.clone()
+    }
+
+    async fn run_commands(&self, commands: &[String]) -> Result<Output, CommandError> {
+        for command in commands {
+            if self.failing_commands.contains(command) {
+                self.commands.lock().unwrap().push(command.into());
+
+                return Err(CommandError::Exit(Output {
+                    status: failure_status(),
+                    stdout: vec![],
+                    stderr: format!("{command}: failed\n").into_bytes(),
+                }));
+            }
+
+            sleep(self.delay).await;
+            self.commands.lock().unwrap().push(command.into());
+        }
+
+        Ok(Output {
+            status: ExitStatus::default(),
+            stdout: vec![],
+            stderr: vec![],
+        })
+    }
+}
+
+#[async_trait]
+impl CommandRunner for FakeCommandRunner {
+    async fn run(
+        &self,
+        commands: &[String],
+        environment: &[(String, String)],
+        shell: Option<&[String]>,
+    ) -> Result<(Output, Option<ResourceUsage>), CommandError> {
+        self.shells
+            .lock()
+            .unwrap()
+            .push(shell.map(|shell| shell.to_vec()));
+        self.environments.lock().unwrap().push(environment.to_vec());
+        let count = self.running_count.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running_count.fetch_max(count, Ordering::SeqCst);
+
+        let result = self.run_commands(commands).await;
+
+        self.running_count.fetch_sub(1, Ordering::SeqCst);
+
+        result.map(|output| (output, None))
+    }
+}
+
+#[cfg(unix)]
+fn failure_status() -> ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    ExitStatus::from_raw(1 << 8)
+}
+
+#[cfg(windows)]
+fn failure_status() -> ExitStatus {
+    use std::os::windows::process::ExitStatusExt;
+
+    ExitStatus::from_raw(1)
+}
@@ -0,0 +1,209 @@
+use super::{FileSystem, Metadata};
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+// An in-memory file system where directories exist implicitly as ancestors of
+// files or explicitly by creation.
+#[derive(Debug, Default)]
+pub struct FakeFileSystem {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+    directories: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl FakeFileSystem {
+    pub fn new(files: &[(&str, &str)]) -> Self {
+        Self {
+            files: Mutex::new(
+                files
+                    .iter()
+                    .map(|&(path, content)| (path.into(), content.into()))
+                    .collect(),
+            ),
+            directories: Default::default(),
+        }
+    }
+
+    fn is_directory(&self, path: &Path) -> bool {
+        self.directories.lock().unwrap().contains(path)
+            || self
+                .files
+                .lock()
+                .unwrap()
+                .keys()
+                .any(|file| file.starts_with(path) && file != path)
+    }
+
+    fn not_found(path: &Path) -> Box<dyn Error> {
+        format!("not found: {}", path.display()).into()
+    }
+}
+
+#[async_trait]
+impl FileSystem for FakeFileSystem {
+    async fn read_file(&self, path: &Path, buffer: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        buffer.extend(
+            self.files
+                .lock()
+                .unwrap()
+                .get(path)
+                .ok_or_else(|| Self::not_found(path))?,
+        );
+
+        Ok(())
+    }
+
+    async fn read_file_to_string(
+        &self,
+        path: &Path,
+        buffer: &mut String,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut bytes = vec![];
+
+        self.read_file(path, &mut bytes).await?;
+        buffer.push_str(&String::from_utf8(bytes)?);
+
+        Ok(())
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<Metadata, Box<dyn Error>> {
+        let size = self
+            .files
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(|content| content.len() as u64);
+
+        if let Some(size) = size {
+            Ok(Metadata::new(SystemTime::UNIX_EPOCH, false, size, 1))
+        } else if self.is_directory(path) {
+            Ok(Metadata::new(SystemTime::UNIX_EPOCH, true, 0, 1))
+        } else {
+            Err(Self::not_found(path))
+        }
+    }
+
+    async fn write_file(&self, path: &Path, content: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.into(), content.into());
+
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.files
+            .lock()
+            .unwrap()
+            .remove(path)
+            .ok_or_else(|| Self::not_found(path))?;
+
+        Ok(())
+    }
+
+    // Modification times are not recorded in memory.
+    async fn set_modified_time(&self, path: &Path, _: SystemTime) -> Result<(), Box<dyn Error>> {
+        if !self.files.lock().unwrap().contains_key(path) {
+            return Err(Self::not_found(path));
+        }
+
+        Ok(())
+    }
+
+    // Files are copied as links are indistinguishable in memory.
+    async fn hard_link(&self, source: &Path, destination: &Path) -> Result<(), Box<dyn Error>> {
+        let mut files = self.files.lock().unwrap();
+        let content = files
+            .get(source)
+            .ok_or_else(|| Self::not_found(source))?
+            .clone();
+
+        files.insert(destination.into(), content);
+
+        Ok(())
+    }
+
+    async fn create_directory(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if self.files.lock().unwrap().contains_key(path) {
+            return Err(format!("file exists: {}", path.display()).into());
+        }
+
+        self.directories.lock().unwrap().insert(path.into());
+
+        Ok(())
+    }
+
+    async fn remove_directory(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if !self.is_directory(path) {
+            return Err(Self::not_found(path));
+        }
+
+        self.files
+            .lock()
+            .unwrap()
+            .retain(|file, _| !file.starts_with(path));
+        self.directories
+            .lock()
+            .unwrap()
+            .retain(|directory| !directory.starts_with(path));
+
+        Ok(())
+    }
+
+    async fn canonicalize_path(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        self.metadata(path).await?;
+
+        Ok(path.into())
+    }
+
+    async fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        if !self.is_directory(path) {
+            return Err(Self::not_found(path));
+        }
+
+        let mut paths = self
+            .files
+            .lock()
+            .unwrap()
+            .keys()
+            .chain(self.directories.lock().unwrap().iter())
+            .filter_map(|entry| {
+                entry
+                    .strip_prefix(path)
+                    .ok()?
+                    .components()
+                    .next()
+                    .map(|component| path.join(component))
+            })
+            .collect::<Vec<_>>();
+
+        paths.sort();
+        paths.dedup();
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_dir() {
+        let file_system =
+            FakeFileSystem::new(&[("foo/bar", ""), ("foo/baz/qux", ""), ("blah", "")]);
+
+        assert_eq!(
+            file_system.read_dir("foo".as_ref()).await.unwrap(),
+            vec![PathBuf::from("foo/bar"), PathBuf::from("foo/baz")]
+        );
+        assert!(file_system.read_dir("foo/bar".as_ref()).await.is_err());
+        assert!(file_system.read_dir("missing".as_ref()).await.is_err());
+    }
+}
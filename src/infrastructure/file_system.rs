@@ -2,20 +2,23 @@ mod metadata;
 
 use async_trait::async_trait;
 use dashmap::DashSet;
-use metadata::Metadata;
+pub use metadata::Metadata;
 use std::{
     error::Error,
     fmt::Debug,
     io,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 use tokio::{
     fs::{self, File},
     io::AsyncReadExt,
     sync::Semaphore,
-    task::yield_now,
+    task::{spawn_blocking, yield_now},
 };
 
+const TEMPORARY_LINK_SUFFIX: &str = ".turtle-link";
+
 #[async_trait]
 pub trait FileSystem {
     async fn read_file(&self, path: &Path, buffer: &mut Vec<u8>) -> Result<(), Box<dyn Error>>;
@@ -25,8 +28,17 @@ pub trait FileSystem {
         buffer: &mut String,
     ) -> Result<(), Box<dyn Error>>;
     async fn metadata(&self, path: &Path) -> Result<Metadata, Box<dyn Error>>;
+    async fn write_file(&self, path: &Path, content: &[u8]) -> Result<(), Box<dyn Error>>;
+    async fn remove_file(&self, path: &Path) -> Result<(), Box<dyn Error>>;
+    async fn set_modified_time(&self, path: &Path, time: SystemTime) -> Result<(), Box<dyn Error>>;
+    // A destination file is replaced with a hard link to a source file.
+    async fn hard_link(&self, source: &Path, destination: &Path) -> Result<(), Box<dyn Error>>;
     async fn create_directory(&self, path: &Path) -> Result<(), Box<dyn Error>>;
+    // A directory is removed with its contents.
+    async fn remove_directory(&self, path: &Path) -> Result<(), Box<dyn Error>>;
     async fn canonicalize_path(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>>;
+    // Entries are sorted and symbolic links in them are not followed.
+    async fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>>;
 }
 
 #[derive(Debug)]
@@ -69,6 +81,19 @@ impl OsFileSystem {
         Ok(())
     }
 
+    async fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, io::Error> {
+        let mut entries = fs::read_dir(path).await?;
+        let mut paths = vec![];
+
+        while let Some(entry) = entries.next_entry().await? {
+            paths.push(entry.path());
+        }
+
+        paths.sort();
+
+        Ok(paths)
+    }
+
     fn error(error: io::Error, path: &Path) -> String {
         format!("{}: {}", error, path.display())
     }
@@ -115,6 +140,58 @@ impl FileSystem for OsFileSystem {
             .try_into()?)
     }
 
+    async fn write_file(&self, path: &Path, content: &[u8]) -> Result<(), Box<dyn Error>> {
+        let permit = self.semaphore.acquire().await?;
+        let result = fs::write(path, content).await;
+        drop(permit);
+
+        Ok(result.map_err(|error| Self::error(error, path))?)
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::remove_file(path)
+            .await
+            .map_err(|error| Self::error(error, path))?;
+
+        Ok(())
+    }
+
+    async fn set_modified_time(&self, path: &Path, time: SystemTime) -> Result<(), Box<dyn Error>> {
+        let file = File::options()
+            .write(true)
+            .open(path)
+            .await
+            .map_err(|error| Self::error(error, path))?
+            .into_std()
+            .await;
+
+        spawn_blocking(move || file.set_modified(time))
+            .await?
+            .map_err(|error| Self::error(error, path))?;
+
+        Ok(())
+    }
+
+    async fn hard_link(&self, source: &Path, destination: &Path) -> Result<(), Box<dyn Error>> {
+        // Links are renamed over destinations not to leave them missing on
+        // failure.
+        let mut temporary = destination.as_os_str().to_owned();
+        temporary.push(TEMPORARY_LINK_SUFFIX);
+        let temporary = PathBuf::from(temporary);
+
+        fs::hard_link(source, &temporary)
+            .await
+            .map_err(|error| Self::error(error, destination))?;
+
+        if let Err(error) = fs::rename(&temporary, destination).await {
+            let _ = fs::remove_file(&temporary).await;
+
+            return Err(Self::error(error, destination).into());
+        }
+
+        Ok(())
+    }
+
     async fn create_directory(&self, path: &Path) -> Result<(), Box<dyn Error>> {
         fs::create_dir_all(path)
             .await
@@ -123,9 +200,119 @@ impl FileSystem for OsFileSystem {
         Ok(())
     }
 
+    async fn remove_directory(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::remove_dir_all(path)
+            .await
+            .map_err(|error| Self::error(error, path))?;
+
+        Ok(())
+    }
+
     async fn canonicalize_path(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
         Ok(fs::canonicalize(path)
             .await
             .map_err(|error| Self::error(error, path))?)
     }
+
+    async fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let permit = self.semaphore.acquire().await?;
+        let result = self.read_dir(path).await;
+        drop(permit);
+
+        Ok(result.map_err(|error| Self::error(error, path))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn read_dir() {
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        std::fs::write(path.join("foo"), "").unwrap();
+        std::fs::create_dir(path.join("bar")).unwrap();
+
+        assert_eq!(
+            FileSystem::read_dir(&OsFileSystem::new(1), path)
+                .await
+                .unwrap(),
+            vec![path.join("bar"), path.join("foo")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn read_dir_with_symbolic_links() {
+        use std::os::unix::fs::symlink;
+
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        std::fs::create_dir(path.join("foo")).unwrap();
+        std::fs::write(path.join("foo/bar"), "").unwrap();
+        symlink(path.join("foo"), path.join("baz")).unwrap();
+        symlink(path.join("missing"), path.join("broken")).unwrap();
+
+        assert_eq!(
+            FileSystem::read_dir(&OsFileSystem::new(1), path)
+                .await
+                .unwrap(),
+            vec![path.join("baz"), path.join("broken"), path.join("foo")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn fail_to_canonicalize_symbolic_link_loop() {
+        use std::os::unix::fs::symlink;
+
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        symlink(path.join("foo"), path.join("bar")).unwrap();
+        symlink(path.join("bar"), path.join("foo")).unwrap();
+
+        assert!(
+            FileSystem::canonicalize_path(&OsFileSystem::new(1), &path.join("foo"))
+                .await
+                .unwrap_err()
+                .to_string()
+                .ends_with(&path.join("foo").display().to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hard_link() {
+        use std::os::unix::fs::MetadataExt;
+
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        std::fs::write(path.join("foo"), "foo").unwrap();
+        std::fs::write(path.join("bar"), "bar").unwrap();
+
+        FileSystem::hard_link(&OsFileSystem::new(1), &path.join("foo"), &path.join("bar"))
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(path.join("bar")).unwrap(), "foo");
+        assert_eq!(
+            std::fs::metadata(path.join("foo")).unwrap().ino(),
+            std::fs::metadata(path.join("bar")).unwrap().ino()
+        );
+        assert!(!path.join("bar.turtle-link").exists());
+    }
+
+    #[tokio::test]
+    async fn fail_to_read_missing_dir() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("foo");
+
+        assert!(FileSystem::read_dir(&OsFileSystem::new(1), &path)
+            .await
+            .unwrap_err()
+            .to_string()
+            .ends_with(&path.display().to_string()));
+    }
 }
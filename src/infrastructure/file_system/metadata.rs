@@ -3,13 +3,19 @@ use std::{fs, io, time::SystemTime};
 pub struct Metadata {
     modified_time: SystemTime,
     directory: bool,
+    // A size in bytes
+    size: u64,
+    // A number of hard links to a file
+    link_count: u64,
 }
 
 impl Metadata {
-    pub fn new(modified_time: SystemTime, directory: bool) -> Self {
+    pub fn new(modified_time: SystemTime, directory: bool, size: u64, link_count: u64) -> Self {
         Self {
             modified_time,
             directory,
+            size,
+            link_count,
         }
     }
 
@@ -20,12 +26,28 @@ impl Metadata {
     pub fn is_file(&self) -> bool {
         !self.directory
     }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn link_count(&self) -> u64 {
+        self.link_count
+    }
 }
 
 impl TryFrom<fs::Metadata> for Metadata {
     type Error = io::Error;
 
     fn try_from(metadata: fs::Metadata) -> Result<Self, Self::Error> {
-        Ok(Metadata::new(metadata.modified()?, metadata.is_dir()))
+        Ok(Metadata::new(
+            metadata.modified()?,
+            metadata.is_dir(),
+            metadata.len(),
+            #[cfg(unix)]
+            std::os::unix::fs::MetadataExt::nlink(&metadata),
+            #[cfg(not(unix))]
+            1,
+        ))
     }
 }
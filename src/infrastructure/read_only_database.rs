@@ -0,0 +1,202 @@
+use super::Database;
+use crate::{hash_type::HashType, ir::BuildId};
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use std::{error::Error, fs, io, path::Path};
+use tempfile::TempDir;
+
+// A database which ignores writes for modes only inspecting builds, such as
+// dry runs, so that they never change build records. It opens a copy of a
+// database as databases write their files even on reads.
+#[derive(Debug, Default)]
+pub struct ReadOnlyDatabase<D> {
+    database: D,
+    directory: OnceCell<TempDir>,
+}
+
+impl<D> ReadOnlyDatabase<D> {
+    pub fn new(database: D) -> Self {
+        Self {
+            database,
+            directory: Default::default(),
+        }
+    }
+}
+
+fn copy_directory(source: &Path, destination: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(destination)?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let path = destination.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_directory(&entry.path(), &path)?;
+        } else {
+            fs::copy(entry.path(), path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl<D: Database + Send + Sync> Database for ReadOnlyDatabase<D> {
+    fn initialize(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let directory = tempfile::tempdir()?;
+
+        if path.exists() {
+            copy_directory(path, directory.path())?;
+        }
+
+        self.database.initialize(directory.path())?;
+        self.directory
+            .set(directory)
+            .map_err(|_| "database already initialized")?;
+
+        Ok(())
+    }
+
+    fn initialize_in_memory(&self) -> Result<(), Box<dyn Error>> {
+        self.database.initialize_in_memory()
+    }
+
+    fn get_hash(&self, r#type: HashType, id: BuildId) -> Result<Option<u64>, Box<dyn Error>> {
+        self.database.get_hash(r#type, id)
+    }
+
+    fn set_hash(&self, _: HashType, _: BuildId, _: u64) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_dependencies(&self, id: BuildId) -> Result<Vec<String>, Box<dyn Error>> {
+        self.database.get_dependencies(id)
+    }
+
+    async fn set_dependencies(&self, _: BuildId, _: &[String]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_build_ids(&self) -> Result<Vec<BuildId>, Box<dyn Error>> {
+        self.database.get_build_ids()
+    }
+
+    fn get_build_count(&self) -> Result<usize, Box<dyn Error>> {
+        self.database.get_build_count()
+    }
+
+    fn remove_builds(&self, _: &[BuildId]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_outputs(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.database.get_outputs()
+    }
+
+    fn set_output(&self, _: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_source(&self, output: &str) -> Result<Option<String>, Box<dyn Error>> {
+        self.database.get_source(output)
+    }
+
+    fn set_source(&self, _: &str, _: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_targets(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.database.get_targets()
+    }
+
+    fn set_targets(&self, _: &[String]) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_configuration_hash(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        self.database.get_configuration_hash()
+    }
+
+    fn set_configuration_hash(&self, _: u64) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn get_running_builds(&self) -> Result<Vec<BuildId>, Box<dyn Error>> {
+        self.database.get_running_builds()
+    }
+
+    fn set_running_build(&self, _: BuildId) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn remove_running_build(&self, _: BuildId) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::OsDatabase;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn ignore_writes() {
+        let database = ReadOnlyDatabase::new(OsDatabase::new());
+        database.initialize(tempdir().unwrap().path()).unwrap();
+
+        database
+            .set_hash(HashType::Content, BuildId::new(0), 42)
+            .unwrap();
+        database
+            .set_dependencies(BuildId::new(0), &["foo.h".into()])
+            .await
+            .unwrap();
+        database.set_output("foo").unwrap();
+        database.set_source("foo", "bar").unwrap();
+        database.set_targets(&["foo".into()]).unwrap();
+        database.flush().await.unwrap();
+
+        assert_eq!(
+            database
+                .get_hash(HashType::Content, BuildId::new(0))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            database.get_dependencies(BuildId::new(0)).unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(database.get_outputs().unwrap(), Vec::<String>::new());
+        assert_eq!(database.get_source("foo").unwrap(), None);
+        assert_eq!(database.get_targets().unwrap(), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn read_records() {
+        let directory = tempdir().unwrap();
+
+        {
+            let database = OsDatabase::new();
+            database.initialize(directory.path()).unwrap();
+            database
+                .set_hash(HashType::Content, BuildId::new(0), 42)
+                .unwrap();
+            database.flush().await.unwrap();
+        }
+
+        let database = ReadOnlyDatabase::new(OsDatabase::new());
+        database.initialize(directory.path()).unwrap();
+
+        assert_eq!(
+            database
+                .get_hash(HashType::Content, BuildId::new(0))
+                .unwrap(),
+            Some(42)
+        );
+    }
+}
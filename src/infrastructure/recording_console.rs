@@ -0,0 +1,64 @@
+use super::Console;
+use async_trait::async_trait;
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+// A console which records outputs, titles, and notifications for assertions.
+#[derive(Debug, Default)]
+pub struct RecordingConsole {
+    stdout: Arc<Mutex<Vec<u8>>>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    titles: Arc<Mutex<Vec<String>>>,
+    notifications: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingConsole {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stdout(&self) -> Arc<Mutex<Vec<u8>>> {
+        self.stdout.clone()
+    }
+
+    pub fn stderr(&self) -> Arc<Mutex<Vec<u8>>> {
+        self.stderr.clone()
+    }
+
+    pub fn titles(&self) -> Arc<Mutex<Vec<String>>> {
+        self.titles.clone()
+    }
+
+    pub fn notifications(&self) -> Arc<Mutex<Vec<String>>> {
+        self.notifications.clone()
+    }
+}
+
+#[async_trait]
+impl Console for RecordingConsole {
+    async fn write_stdout(&mut self, buffer: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.stdout.lock().unwrap().extend(buffer);
+
+        Ok(())
+    }
+
+    async fn write_stderr(&mut self, buffer: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.stderr.lock().unwrap().extend(buffer);
+
+        Ok(())
+    }
+
+    async fn set_title(&mut self, title: &str) -> Result<(), Box<dyn Error>> {
+        self.titles.lock().unwrap().push(title.into());
+
+        Ok(())
+    }
+
+    async fn notify(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.notifications.lock().unwrap().push(message.into());
+
+        Ok(())
+    }
+}
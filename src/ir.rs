@@ -2,10 +2,14 @@ mod build;
 mod configuration;
 mod dynamic_build;
 mod dynamic_configuration;
+mod pool;
+mod response_file;
 mod rule;
 
 pub use build::*;
 pub use configuration::*;
 pub use dynamic_build::*;
 pub use dynamic_configuration::*;
+pub use pool::*;
+pub use response_file::*;
 pub use rule::*;
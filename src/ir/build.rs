@@ -13,6 +13,10 @@ impl BuildId {
         Self(id)
     }
 
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self(u64::from_le_bytes(bytes))
+    }
+
     pub fn to_bytes(self) -> [u8; 8] {
         self.0.to_le_bytes()
     }
@@ -28,6 +32,8 @@ pub struct Build {
     rule: Option<Rule>,
     inputs: Vec<Arc<str>>,
     order_only_inputs: Vec<Arc<str>>,
+    // Outputs built whenever this build is built without blocking it
+    validations: Vec<Arc<str>>,
     dynamic_module: Option<Arc<str>>,
 }
 
@@ -38,6 +44,7 @@ impl Build {
         rule: Option<Rule>,
         inputs: Vec<Arc<str>>,
         order_only_inputs: Vec<Arc<str>>,
+        validations: Vec<Arc<str>>,
         dynamic_module: Option<Arc<str>>,
     ) -> Self {
         Self {
@@ -47,6 +54,7 @@ impl Build {
             rule,
             inputs,
             order_only_inputs,
+            validations,
             dynamic_module,
         }
     }
@@ -75,6 +83,10 @@ impl Build {
         &self.order_only_inputs
     }
 
+    pub fn validations(&self) -> &[Arc<str>] {
+        &self.validations
+    }
+
     pub fn dynamic_module(&self) -> Option<&Arc<str>> {
         self.dynamic_module.as_ref()
     }
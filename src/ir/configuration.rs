@@ -1,4 +1,4 @@
-use super::Build;
+use super::{Build, BuildId};
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
@@ -9,6 +9,8 @@ pub struct Configuration {
     outputs: HashMap<Arc<str>, Arc<Build>>,
     default_outputs: HashSet<Arc<str>>,
     source_map: HashMap<Arc<str>, Arc<str>>,
+    // Tags of builds to select them
+    tags: HashMap<BuildId, Vec<Arc<str>>>,
     build_directory: Option<Arc<str>>,
 }
 
@@ -17,12 +19,14 @@ impl Configuration {
         outputs: HashMap<Arc<str>, Arc<Build>>,
         default_outputs: HashSet<Arc<str>>,
         source_map: HashMap<Arc<str>, Arc<str>>,
+        tags: HashMap<BuildId, Vec<Arc<str>>>,
         build_directory: Option<Arc<str>>,
     ) -> Self {
         Self {
             outputs,
             default_outputs,
             source_map,
+            tags,
             build_directory,
         }
     }
@@ -39,6 +43,10 @@ impl Configuration {
         &self.source_map
     }
 
+    pub fn tags(&self) -> &HashMap<BuildId, Vec<Arc<str>>> {
+        &self.tags
+    }
+
     pub fn build_directory(&self) -> Option<&Arc<str>> {
         self.build_directory.as_ref()
     }
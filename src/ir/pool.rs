@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pool {
+    name: Arc<str>,
+    depth: usize,
+}
+
+impl Pool {
+    pub fn new(name: impl Into<Arc<str>>, depth: usize) -> Self {
+        Self {
+            name: name.into(),
+            depth,
+        }
+    }
+
+    pub fn name(&self) -> &Arc<str> {
+        &self.name
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
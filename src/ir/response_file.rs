@@ -0,0 +1,22 @@
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ResponseFile {
+    path: String,
+    content: String,
+}
+
+impl ResponseFile {
+    pub fn new(path: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            content: content.into(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
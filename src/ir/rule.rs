@@ -1,22 +1,135 @@
+use super::{Pool, ResponseFile};
+use encoding_rs::Encoding;
+
+// A placeholder of `$tmpdir` in commands replaced with temporary directories of
+// each run so that commands are the same across runs.
+pub const TEMPORARY_DIRECTORY_PLACEHOLDER: &str = "\0tmpdir\0";
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Rule {
-    command: String,
-    description: Option<String>,
+    name: String,
+    // Commands run sequentially until any of them fails
+    commands: Vec<String>,
+    options: RuleOptions,
+}
+
+// Bindings of rules other than commands. Most rules use only a few of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleOptions {
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub depfile: Option<String>,
+    pub msvc_deps_prefix: Option<String>,
+    pub environment: Vec<(String, String)>,
+    pub output_encoding: Option<&'static Encoding>,
+    // Run always when requested explicitly
+    pub always: bool,
+    // Create parent directories of outputs before running commands
+    pub mkdir: bool,
+    pub pool: Option<Pool>,
+    pub response_file: Option<ResponseFile>,
+    // Seconds since the UNIX epoch set to modification times of outputs after
+    // builds for reproducible artifacts
+    pub pinned_modified_time: Option<u64>,
+    // Run only for side effects without creating outputs
+    pub no_output: bool,
+    // Words of a shell command run with commands instead of a default shell
+    pub shell: Option<Vec<String>>,
+    // Names of environment variables whose values are hashed with commands
+    pub environment_dependencies: Vec<String>,
+}
+
+impl Default for RuleOptions {
+    fn default() -> Self {
+        Self {
+            description: None,
+            status: None,
+            depfile: None,
+            msvc_deps_prefix: None,
+            environment: vec![],
+            output_encoding: None,
+            always: false,
+            mkdir: true,
+            pool: None,
+            response_file: None,
+            pinned_modified_time: None,
+            no_output: false,
+            shell: None,
+            environment_dependencies: vec![],
+        }
+    }
 }
 
 impl Rule {
-    pub fn new(command: impl Into<String>, description: Option<String>) -> Self {
+    pub fn new(name: String, commands: Vec<String>, options: RuleOptions) -> Self {
         Self {
-            command: command.into(),
-            description,
+            name,
+            commands,
+            options,
         }
     }
 
-    pub fn command(&self) -> &str {
-        &self.command
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
     }
 
     pub fn description(&self) -> Option<&str> {
-        self.description.as_deref()
+        self.options.description.as_deref()
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.options.status.as_deref()
+    }
+
+    pub fn depfile(&self) -> Option<&str> {
+        self.options.depfile.as_deref()
+    }
+
+    pub fn msvc_deps_prefix(&self) -> Option<&str> {
+        self.options.msvc_deps_prefix.as_deref()
+    }
+
+    pub fn environment(&self) -> &[(String, String)] {
+        &self.options.environment
+    }
+
+    pub fn output_encoding(&self) -> Option<&'static Encoding> {
+        self.options.output_encoding
+    }
+
+    pub fn always(&self) -> bool {
+        self.options.always
+    }
+
+    pub fn mkdir(&self) -> bool {
+        self.options.mkdir
+    }
+
+    pub fn pool(&self) -> Option<&Pool> {
+        self.options.pool.as_ref()
+    }
+
+    pub fn response_file(&self) -> Option<&ResponseFile> {
+        self.options.response_file.as_ref()
+    }
+
+    pub fn pinned_modified_time(&self) -> Option<u64> {
+        self.options.pinned_modified_time
+    }
+
+    pub fn no_output(&self) -> bool {
+        self.options.no_output
+    }
+
+    pub fn shell(&self) -> Option<&[String]> {
+        self.options.shell.as_deref()
+    }
+
+    pub fn environment_dependencies(&self) -> &[String] {
+        &self.options.environment_dependencies
     }
 }
@@ -1,13 +1,18 @@
 pub mod arguments;
 pub mod ast;
 pub mod build_graph;
+pub mod compdb;
 pub mod compile;
 pub mod context;
+#[cfg(unix)]
+pub mod daemon;
 pub mod error;
+pub mod fnv;
 pub mod hash_type;
 pub mod infrastructure;
 pub mod ir;
 pub mod module_dependency;
 pub mod parse;
+pub mod remote;
 pub mod run;
 pub mod tool;
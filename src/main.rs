@@ -1,140 +1,1027 @@
-use clap::Parser;
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use futures::future::try_join_all;
 use std::{
-    collections::HashMap,
-    env::set_current_dir,
+    collections::{HashMap, HashSet},
+    env::{args, current_dir, set_current_dir, var, var_os},
+    io::{stderr, stdout, IsTerminal},
+    mem::replace,
     path::{Path, PathBuf},
     process::exit,
     sync::Arc,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    fs::{create_dir_all, File},
+    io::{self, AsyncReadExt},
+    select,
+    signal::ctrl_c,
+    time::{sleep, Instant},
+};
+use turtle_build::arguments::{
+    apply_project_configuration, expand_arguments, format_configuration, read_environment_file,
+    Arguments, Color, JobLimit, Tool,
 };
-use tokio::time::sleep;
-use turtle_build::arguments::{Arguments, Tool};
 use turtle_build::ast::{Module, Statement};
-use turtle_build::compile::compile;
+use turtle_build::compile::{
+    self, compile_lazily, compile_with_errors, trace_command, CompileError, ExpansionLayer,
+};
 use turtle_build::context::Context;
 use turtle_build::error::ApplicationError;
-use turtle_build::infrastructure::{OsCommandRunner, OsConsole, OsDatabase, OsFileSystem};
+use turtle_build::infrastructure::{
+    is_unwritable_error, OsCommandRunner, OsConsole, OsDatabase, OsFileSystem, ReadOnlyDatabase,
+};
+use turtle_build::ir::Configuration;
+use turtle_build::log;
 use turtle_build::module_dependency::ModuleDependencyMap;
 use turtle_build::parse::parse;
+use turtle_build::run::{ErrorFormat, ProfileFormat};
 
 const DEFAULT_BUILD_FILE: &str = "build.ninja";
+const STDIN_BUILD_FILE: &str = "-";
+const RC_FILE: &str = ".turtlerc";
+const PROJECT_CONFIGURATION_FILE: &str = "turtle.toml";
+const NO_COLOR_VARIABLE: &str = "NO_COLOR";
+const PAGER_VARIABLE: &str = "PAGER";
 const DATABASE_DIRECTORY: &str = ".turtle";
+const PROVENANCE_FILE: &str = "provenance.jsonl";
+const PROFILE_FILE: &str = "profile.json";
+const TRACE_FILE: &str = "trace.json";
+const TEMPORARY_DIRECTORY: &str = "tmp";
+const DAEMON_SOCKET_FILE: &str = "daemon.sock";
+const REMOTE_DIRECTORY: &str = "remote";
+const COMPILATION_DATABASE_DIRECTORY: &str = "compdb";
 const OPEN_FILE_LIMIT: usize = if cfg!(target_os = "macos") { 256 } else { 1024 };
 const DEFAULT_FILE_COUNT_PER_PROCESS: usize = 3; // stdin, stdout, and stderr
 
 #[tokio::main]
 async fn main() {
-    let arguments = Arguments::parse();
-    let job_limit = arguments.job_limit.unwrap_or_else(num_cpus::get);
-    let context = Context::new(
-        OsCommandRunner::new(job_limit),
-        OsConsole::new(),
-        OsDatabase::new(),
-        OsFileSystem::new(
-            OPEN_FILE_LIMIT
-                .saturating_sub(DEFAULT_FILE_COUNT_PER_PROCESS * (job_limit + 1))
-                .max(1),
-        ),
+    let command_line_arguments = args().collect::<Vec<_>>();
+    let expanded_arguments = expand_arguments(command_line_arguments.clone(), RC_FILE.as_ref())
+        .unwrap_or_else(|error| Arguments::command().error(ErrorKind::Io, error).exit());
+    let raw_arguments = apply_project_configuration(
+        expanded_arguments.clone(),
+        PROJECT_CONFIGURATION_FILE.as_ref(),
     )
-    .into();
+    .unwrap_or_else(|error| Arguments::command().error(ErrorKind::Io, error).exit());
+    let arguments = Arguments::parse_from(&raw_arguments);
 
-    if let Err(error) = execute(&context, &arguments).await {
-        if !arguments.quiet || !matches!(error, ApplicationError::Build) {
-            context
-                .console()
-                .lock()
-                .await
-                .write_stderr(
-                    format!(
-                        "{}{}\n",
-                        if let Some(prefix) = &arguments.log_prefix {
-                            prefix
-                        } else {
-                            ""
-                        },
-                        error
-                    )
-                    .as_bytes(),
-                )
+    if arguments.print_config {
+        print!(
+            "{}",
+            format_configuration(&command_line_arguments, &expanded_arguments, &raw_arguments)
+        );
+        return;
+    }
+
+    // Builds are forwarded to a daemon if it is running.
+    #[cfg(unix)]
+    if !arguments.daemon && arguments.file.as_deref() != Some(STDIN_BUILD_FILE) {
+        if let Some(code) = turtle_build::daemon::forward(
+            &Path::new(arguments.directory.as_deref().unwrap_or("."))
+                .join(DATABASE_DIRECTORY)
+                .join(DAEMON_SOCKET_FILE),
+            &turtle_build::daemon::Request {
+                arguments: raw_arguments.clone(),
+                color: is_stderr_colored(),
+            },
+            &mut io::stdout(),
+            &mut io::stderr(),
+        )
+        .await
+        .unwrap_or_else(|error| Arguments::command().error(ErrorKind::Io, error).exit())
+        {
+            exit(code)
+        }
+    }
+
+    let job_limit = resolve_job_limit(&arguments);
+    let environment = arguments
+        .env_file
+        .as_deref()
+        .map(|path| read_environment_file(path.as_ref()))
+        .transpose()
+        .unwrap_or_else(|error| Arguments::command().error(ErrorKind::Io, error).exit())
+        .unwrap_or_default();
+    let log_file = if let Some(path) = &arguments.log_file {
+        Some(
+            File::create(path)
                 .await
-                .unwrap();
+                .map_err(|error| io::Error::new(error.kind(), format!("{error}: {path}")))
+                .unwrap_or_else(|error| Arguments::command().error(ErrorKind::Io, error).exit()),
+        )
+    } else {
+        None
+    };
+    let command_runner = OsCommandRunner::new(
+        job_limit,
+        arguments.clear_env,
+        environment,
+        arguments
+            .shell
+            .as_deref()
+            .map(|shell| shell.split_whitespace().map(From::from).collect()),
+    )
+    .limit_capture(arguments.max_capture);
+    let command_runner = if let Some(rate) = arguments.spawn_rate {
+        command_runner.limit_spawn_rate(rate)
+    } else {
+        command_runner
+    };
+    let console = OsConsole::new(log_file);
+    // Outputs of listing tools follow color settings of stdout rather than
+    // stderr.
+    let console = if arguments.is_listing_tool()
+        && match arguments.color {
+            Color::Auto => !stdout().is_terminal() || var_os(NO_COLOR_VARIABLE).is_some(),
+            Color::Always => false,
+            Color::Never => true,
+        } {
+        console.strip_stdout()
+    } else {
+        console
+    };
+    let console = if let Some(pager) =
+        arguments.pager(stdout().is_terminal(), var(PAGER_VARIABLE).ok().as_deref())
+    {
+        console
+            .page(&pager)
+            .unwrap_or_else(|error| Arguments::command().error(ErrorKind::Io, error).exit())
+    } else {
+        console
+    };
+    let file_system = OsFileSystem::new(
+        OPEN_FILE_LIMIT
+            .saturating_sub(DEFAULT_FILE_COUNT_PER_PROCESS * (job_limit + 1))
+            .max(1),
+    );
+    let context = if is_read_only(&arguments) {
+        Context::new(
+            command_runner,
+            console,
+            ReadOnlyDatabase::new(OsDatabase::new()),
+            file_system,
+        )
+    } else {
+        Context::new(command_runner, console, OsDatabase::new(), file_system)
+    }
+    .into();
+
+    let result = select! {
+        result = execute(&context, &arguments) => result,
+        _ = ctrl_c() => Err(ApplicationError::Interrupted),
+    };
+    let _ = context.console().lock().await.close().await;
+
+    if let Err(error) = result {
+        // Records of running builds are kept to rebuild them next time.
+        if matches!(error, ApplicationError::Interrupted) {
+            let _ = context.database().flush().await;
         }
 
+        report_error(&context, &arguments, &error).await;
+
         // Delay for the error message to be written completely hopefully.
         sleep(Duration::from_millis(1)).await;
 
-        exit(1)
+        exit(error.exit_code())
+    }
+}
+
+struct BuildFiles {
+    configuration: Arc<Configuration>,
+    // Configurations compiled lazily contain only builds reachable from
+    // requested outputs.
+    partial: bool,
+    root_module_path: PathBuf,
+    modules: HashMap<PathBuf, Module>,
+    errors: Vec<CompileError>,
+    // Modification times of build files to detect their changes
+    modified_times: Vec<(PathBuf, SystemTime)>,
+    options: LoadOptions,
+    // Expansion of a command traced for `--explain-command`
+    expansion_layers: Option<Vec<ExpansionLayer>>,
+}
+
+impl BuildFiles {
+    // Build files are reloaded also when options given to load them change or
+    // requests need side effects of loading, e.g. fetching remote build files.
+    async fn is_changed(&self, context: &Context, arguments: &Arguments) -> bool {
+        if arguments.refresh || arguments.trace_parse || self.options != LoadOptions::new(arguments)
+        {
+            return true;
+        }
+
+        for (path, time) in &self.modified_times {
+            match context.file_system().metadata(path).await {
+                Ok(metadata) if metadata.modified_time() == *time => {}
+                _ => return true,
+            }
+        }
+
+        false
+    }
+}
+
+// Options changing configurations compiled from build files
+#[derive(Debug, Eq, PartialEq)]
+struct LoadOptions {
+    file: Option<String>,
+    from_compdb: Option<String>,
+    output_root: Option<String>,
+    max_parse_depth: usize,
+    fail_fast_parse: bool,
+    warning_error: bool,
+    compile: compile::Options,
+    // Outputs for configurations compiled lazily
+    lazy_outputs: Option<Vec<String>>,
+    explain_command: Option<String>,
+    variables: Vec<(String, String)>,
+}
+
+impl LoadOptions {
+    fn new(arguments: &Arguments) -> Self {
+        let (outputs, variables) = arguments.split_outputs();
+
+        Self {
+            file: arguments.file.clone(),
+            from_compdb: arguments.from_compdb.clone(),
+            output_root: arguments.output_root.clone(),
+            max_parse_depth: arguments.max_parse_depth,
+            fail_fast_parse: arguments.fail_fast_parse,
+            warning_error: arguments.is_warning_error(),
+            compile: arguments.compile_options(),
+            lazy_outputs: (arguments.lazy && !outputs.is_empty()).then(|| {
+                outputs
+                    .into_iter()
+                    .chain(arguments.keep_going_until.clone())
+                    .chain(arguments.rebuild.clone())
+                    .collect()
+            }),
+            explain_command: arguments.explain_command.clone(),
+            variables,
+        }
     }
 }
 
+fn resolve_job_limit(arguments: &Arguments) -> usize {
+    arguments
+        .job_limit
+        .unwrap_or(JobLimit::Auto)
+        .resolve(num_cpus::get())
+}
+
+fn is_stderr_colored() -> bool {
+    stderr().is_terminal() && var_os(NO_COLOR_VARIABLE).is_none()
+}
+
+// Tools inspecting build files themselves run without build databases.
+fn inspects_build_files(arguments: &Arguments) -> bool {
+    matches!(arguments.tool, Some(Tool::Check | Tool::Unused))
+}
+
+// Build records are never written in modes which only inspect builds.
+fn is_read_only(arguments: &Arguments) -> bool {
+    arguments.dry_run
+        || arguments.check_up_to_date
+        || arguments.explain_json
+        || arguments.dump_database
+        || arguments.explain_command.is_some()
+        || arguments.doctor
+        || arguments.why.is_some()
+        || arguments.print_inputs.is_some()
+        || arguments.dependency_report.is_some()
+        || arguments.print_default_targets
+        || matches!(
+            arguments.tool,
+            Some(
+                Tool::Check
+                    | Tool::Clean
+                    | Tool::CleanDead
+                    | Tool::Dead
+                    | Tool::Edges
+                    | Tool::Expand
+                    | Tool::ExportSqlite
+                    | Tool::Graph
+                    | Tool::Inputs
+                    | Tool::Makefile
+                    | Tool::NinjaDeps
+                    | Tool::Query
+                    | Tool::Targets
+                    | Tool::Unused
+            )
+        )
+}
+
 async fn execute(context: &Arc<Context>, arguments: &Arguments) -> Result<(), ApplicationError> {
     if let Some(directory) = &arguments.directory {
-        set_current_dir(directory)?;
+        set_current_dir(directory)
+            .map_err(|error| ApplicationError::Other(format!("{error}: {directory}")))?;
     }
 
-    let root_module_path = context
-        .file_system()
-        .canonicalize_path(
-            arguments
-                .file
-                .as_deref()
-                .unwrap_or(DEFAULT_BUILD_FILE)
-                .as_ref(),
+    if arguments.doctor {
+        return doctor(context, arguments).await;
+    }
+
+    let build_files = load(context, arguments).await?;
+
+    if inspects_build_files(arguments) {
+        return dispatch(context, arguments, &build_files, None).await;
+    } else if let Some(error) = build_files.errors.first() {
+        return Err(error.clone().into());
+    } else if let Some(output) = &arguments.explain_command {
+        return turtle_build::tool::explain_command(
+            context,
+            output,
+            build_files.expansion_layers.as_deref(),
         )
-        .await?;
-    let (modules, dependencies) = parse_modules(context, &root_module_path).await?;
+        .await;
+    }
+
+    // Builds can still run without records of previous ones, e.g. on read-only
+    // file systems.
+    let database_persistent = if let Err(error) = context.database().initialize(
+        &database_directory(arguments, &build_files)
+            .join(env!("CARGO_PKG_VERSION").replace('.', "_")),
+    ) {
+        if arguments.require_database || !is_unwritable_error(&*error) {
+            return Err(error.into());
+        }
+
+        context.database().initialize_in_memory()?;
+
+        let mut console = context.console().lock().await;
+
+        log!(
+            console,
+            "warning: cannot open a build database ({}); rebuilding everything without records",
+            error
+        );
+
+        false
+    } else {
+        true
+    };
+
+    if arguments.compaction_threshold > 0 && !matches!(arguments.tool, Some(Tool::Recompact)) {
+        if let Some(count) = turtle_build::tool::compact(
+            context,
+            &build_files.configuration,
+            arguments.compaction_threshold,
+            build_files.partial,
+        )
+        .await?
+        {
+            let mut console = context.console().lock().await;
 
+            log!(
+                console,
+                "compacted a build database by removing {} stale builds",
+                count
+            );
+        }
+    }
+
+    if arguments.daemon {
+        serve(context, arguments, build_files).await
+    } else {
+        dispatch(
+            context,
+            arguments,
+            &build_files,
+            database_persistent.then(|| database_directory(arguments, &build_files)),
+        )
+        .await
+    }
+}
+
+fn database_directory(arguments: &Arguments, build_files: &BuildFiles) -> PathBuf {
+    arguments
+        .database_directory(&build_files.root_module_path)
+        .unwrap_or_else(|| {
+            let directory = build_files
+                .configuration
+                .build_directory()
+                .map(|string| string.as_ref().as_ref())
+                // Outputs are relative to a working directory and so is a database.
+                .unwrap_or_else(|| Path::new("."))
+                .join(DATABASE_DIRECTORY);
+
+            // Builds from compilation databases are recorded separately so that
+            // compaction never removes records of builds in build files.
+            if arguments.from_compdb.is_some() {
+                directory.join(COMPILATION_DATABASE_DIRECTORY)
+            } else {
+                directory
+            }
+        })
+}
+
+async fn doctor(context: &Context, arguments: &Arguments) -> Result<(), ApplicationError> {
+    let build_files = load(context, arguments).await;
+    let database_directory = build_files.as_ref().map_or_else(
+        |_| Path::new(".").join(DATABASE_DIRECTORY),
+        |build_files| database_directory(arguments, build_files),
+    );
+    let build_file = build_files.and_then(|build_files| match build_files.errors.first() {
+        Some(error) => Err(error.clone().into()),
+        None => Ok(()),
+    });
+    let job_limit = resolve_job_limit(arguments);
+
+    turtle_build::tool::doctor(
+        context,
+        build_file,
+        &database_directory,
+        OPEN_FILE_LIMIT.max(DEFAULT_FILE_COUNT_PER_PROCESS * (job_limit + 1) + 1),
+    )
+    .await
+}
+
+async fn load(context: &Context, arguments: &Arguments) -> Result<BuildFiles, ApplicationError> {
+    if let Some(path) = &arguments.from_compdb {
+        return load_compilation_database(context, arguments, path.as_ref()).await;
+    }
+
+    let stdin = arguments.file.as_deref() == Some(STDIN_BUILD_FILE);
+    // Relative paths in a build file from stdin are resolved against a current
+    // directory.
+    let root_module_path = if stdin {
+        current_dir()?.join(STDIN_BUILD_FILE)
+    } else {
+        context
+            .file_system()
+            .canonicalize_path(
+                arguments
+                    .file
+                    .as_deref()
+                    .unwrap_or(DEFAULT_BUILD_FILE)
+                    .as_ref(),
+            )
+            .await?
+    };
+    let start_time = Instant::now();
+    let (modules, dependencies) = parse_modules(
+        context,
+        &root_module_path,
+        stdin,
+        arguments.max_parse_depth,
+        arguments.refresh,
+        arguments.fail_fast_parse,
+    )
+    .await?;
+    let parse_duration = start_time.elapsed();
+
+    let start_time = Instant::now();
     turtle_build::module_dependency::validate(&dependencies)?;
+    let validate_duration = start_time.elapsed();
 
-    let configuration = Arc::new(compile(&modules, &dependencies, &root_module_path)?);
+    let start_time = Instant::now();
+    let options = LoadOptions::new(arguments);
+    let (configuration, errors) = if let Some(outputs) = &options.lazy_outputs {
+        compile_lazily(
+            &modules,
+            &dependencies,
+            &root_module_path,
+            &options.variables,
+            outputs,
+            options.compile,
+        )?
+    } else {
+        compile_with_errors(
+            &modules,
+            &dependencies,
+            &root_module_path,
+            &options.variables,
+            options.output_root.as_deref().map(Path::new),
+            options.compile,
+        )?
+    };
+    let compile_duration = start_time.elapsed();
+    let expansion_layers = if let Some(output) = &arguments.explain_command {
+        trace_command(
+            &modules,
+            &dependencies,
+            &root_module_path,
+            &options.variables,
+            options.output_root.as_deref().map(Path::new),
+            output,
+            options.compile,
+        )?
+    } else {
+        None
+    };
 
-    context.database().initialize(
-        &configuration
-            .build_directory()
-            .map(|string| string.as_ref().as_ref())
-            .unwrap_or_else(|| root_module_path.parent().unwrap())
-            .join(DATABASE_DIRECTORY)
-            .join(env!("CARGO_PKG_VERSION").replace('.', "_")),
-    )?;
+    // Warnings are shown on load unless they are promoted to errors.
+    let (errors, warnings) = errors.into_iter().partition::<Vec<_>, _>(|error| {
+        !error.is_warning()
+            || matches!(error, CompileError::OutputNotReferenced(..))
+                && arguments.is_warning_error()
+    });
 
-    if let Some(tool) = &arguments.tool {
-        match tool {
-            Tool::CleanDead => turtle_build::tool::clean_dead(context, &configuration).await?,
+    if !warnings.is_empty() {
+        let mut console = context.console().lock().await;
+
+        for warning in &warnings {
+            log!(console, "warning: {}", warning);
         }
+    }
+
+    if arguments.trace_parse {
+        let mut console = context.console().lock().await;
+
+        log!(
+            console,
+            "parse: {}ms ({} files)",
+            parse_duration.as_millis(),
+            modules.len()
+        );
+        log!(console, "validate: {}ms", validate_duration.as_millis());
+        log!(
+            console,
+            "compile: {}ms ({} builds)",
+            compile_duration.as_millis(),
+            configuration
+                .outputs()
+                .values()
+                .map(|build| build.id())
+                .collect::<HashSet<_>>()
+                .len()
+        );
+    }
+
+    let mut modified_times = vec![];
+
+    for path in modules.keys().filter(|_| !stdin) {
+        modified_times.push((
+            path.clone(),
+            context.file_system().metadata(path).await?.modified_time(),
+        ));
+    }
+
+    Ok(BuildFiles {
+        configuration: configuration.into(),
+        partial: options.lazy_outputs.is_some(),
+        root_module_path,
+        modules,
+        errors,
+        modified_times,
+        options,
+        expansion_layers,
+    })
+}
+
+async fn load_compilation_database(
+    context: &Context,
+    arguments: &Arguments,
+    path: &Path,
+) -> Result<BuildFiles, ApplicationError> {
+    let mut source = String::new();
+    context
+        .file_system()
+        .read_file_to_string(path, &mut source)
+        .await?;
+
+    Ok(BuildFiles {
+        configuration: turtle_build::compdb::compile(&source, &current_dir()?)?.into(),
+        partial: false,
+        root_module_path: context.file_system().canonicalize_path(path).await?,
+        modules: Default::default(),
+        errors: vec![],
+        modified_times: vec![(
+            path.into(),
+            context.file_system().metadata(path).await?.modified_time(),
+        )],
+        options: LoadOptions::new(arguments),
+        expansion_layers: None,
+    })
+}
+
+async fn dispatch(
+    context: &Arc<Context>,
+    arguments: &Arguments,
+    build_files: &BuildFiles,
+    // A database directory usable for temporary files
+    database_directory: Option<PathBuf>,
+) -> Result<(), ApplicationError> {
+    let configuration = build_files.configuration.clone();
+    let outputs = if arguments.again && !inspects_build_files(arguments) {
+        context.database().get_targets()?
     } else {
-        turtle_build::run::run(
+        arguments.split_outputs().0
+    };
+    let options = turtle_build::run::Options {
+        color: match arguments.color {
+            Color::Auto => is_stderr_colored(),
+            Color::Always => true,
+            Color::Never => false,
+        },
+        debug: arguments.debug,
+        profile: arguments.profile_format(),
+        profile_file: match arguments.profile_format() {
+            Some(ProfileFormat::Json) => Some(PROFILE_FILE),
+            Some(ProfileFormat::Chrome) => Some(TRACE_FILE),
+            Some(ProfileFormat::Text) | None => None,
+        }
+        .map(|file| {
+            configuration
+                .build_directory()
+                .map(|string| string.as_ref().as_ref())
+                .unwrap_or_else(|| Path::new("."))
+                .join(DATABASE_DIRECTORY)
+                .join(file)
+        }),
+        time_report: arguments.time_report,
+        dry_run: arguments.dry_run,
+        touch: false,
+        stall_timeout: Duration::from_secs(arguments.stall_timeout),
+        abort_on_stall: arguments.abort_on_stall,
+        keep_going: if arguments.no_stop {
+            0
+        } else if arguments.stop_on_first_error {
+            1
+        } else {
+            arguments.keep_going
+        },
+        keep_going_until: arguments.keep_going_until.clone(),
+        no_deps: arguments.no_deps,
+        no_deps_record: arguments.no_deps_record,
+        missing_ok: arguments.missing_ok,
+        allow_missing_outputs: arguments.allow_missing_outputs,
+        shard: arguments.shard,
+        excluded_outputs: arguments.exclude.clone(),
+        tags: arguments.tag.clone(),
+        forced_outputs: arguments.rebuild.clone(),
+        force_dependencies: arguments.rebuild_deps,
+        subdirectory: arguments.subdir.as_ref().map(PathBuf::from),
+        shuffle: arguments.shuffle.map(|seed| {
+            seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64
+            })
+        }),
+        detect_undeclared_outputs: arguments.detect_undeclared_outputs,
+        detect_modified_inputs: arguments.detect_modified_inputs || arguments.strict_inputs,
+        strict_inputs: arguments.strict_inputs,
+        keep_response_files: arguments.keep_rsp,
+        collapse_errors: arguments.collapse_errors,
+        provenance_file: arguments.provenance.then(|| {
+            configuration
+                .build_directory()
+                .map(|string| string.as_ref().as_ref())
+                .unwrap_or_else(|| Path::new("."))
+                .join(DATABASE_DIRECTORY)
+                .join(PROVENANCE_FILE)
+        }),
+        terminal_title: arguments.terminal_title,
+        max_memory: arguments.max_memory.map(|size| size * 1024 * 1024),
+        max_output_bytes: arguments.max_output_bytes,
+        rule_job_limits: arguments
+            .jobs_for_rule
+            .iter()
+            .map(|limit| (limit.rule.clone(), limit.count))
+            .collect(),
+        dedup_outputs: arguments.dedup_outputs,
+        temporary_directory: database_directory
+            .map(|directory| directory.join(TEMPORARY_DIRECTORY)),
+        schedule: arguments.schedule,
+        job_limit: resolve_job_limit(arguments),
+        output_mode: arguments.output_mode,
+        lazy: arguments.lazy,
+        post_build: arguments.post_build.clone(),
+        error_format: arguments.error_format,
+        pause_on_signals: true,
+    };
+
+    if let Some(path) = &arguments.import_ninja_log {
+        let count = turtle_build::run::import_ninja_log(
             context,
             configuration.clone(),
-            &arguments.outputs,
-            turtle_build::run::Options {
-                debug: arguments.debug,
-                profile: arguments.profile,
-            },
+            path,
+            options.clone(),
         )
         .await?;
+        let mut console = context.console().lock().await;
+
+        log!(console, "imported {} builds from {}", count, path.display());
+    }
+
+    if let Some(output) = &arguments.why {
+        turtle_build::run::why(context, configuration, output, options).await?;
+    } else if let Some(output) = &arguments.print_inputs {
+        turtle_build::tool::print_inputs(context, &configuration, output).await?;
+    } else if let Some(file) = &arguments.dependency_report {
+        turtle_build::tool::dependency_report(context, &configuration, file).await?;
+    } else if arguments.print_default_targets {
+        turtle_build::tool::print_default_targets(context, &configuration).await?;
+    } else if arguments.check_up_to_date {
+        turtle_build::run::check_up_to_date(context, configuration, &outputs, options).await?;
+    } else if arguments.explain_json {
+        turtle_build::run::explain_json(context, configuration, &outputs, options).await?;
+    } else if arguments.verify_deterministic {
+        turtle_build::run::verify_deterministic(context, configuration, &outputs, options).await?;
+    } else if arguments.dump_database {
+        turtle_build::tool::dump_database(context, &configuration).await?;
+    } else if let Some(tool) = &arguments.tool {
+        match tool {
+            Tool::Check => {
+                turtle_build::tool::check(context, &configuration, build_files.errors.clone())
+                    .await?
+            }
+            Tool::Clean => turtle_build::tool::clean(&configuration, &outputs).await?,
+            Tool::CleanDead => turtle_build::tool::clean_dead(context, &configuration).await?,
+            Tool::Dead => turtle_build::tool::dead(context, &configuration).await?,
+            Tool::Edges => turtle_build::tool::edges(context, &configuration, &outputs).await?,
+            Tool::Expand => turtle_build::tool::expand(context, &configuration).await?,
+            Tool::ExportSqlite => {
+                turtle_build::tool::export_sqlite(&configuration, &outputs).await?
+            }
+            Tool::Graph => {
+                turtle_build::tool::graph(context, &configuration, &outputs, arguments.mermaid)
+                    .await?
+            }
+            Tool::Inputs => {
+                turtle_build::tool::inputs(
+                    context,
+                    &configuration,
+                    &outputs,
+                    arguments.dependency_order,
+                )
+                .await?
+            }
+            Tool::Makefile => turtle_build::tool::makefile(context, &configuration).await?,
+            Tool::NinjaDeps => turtle_build::tool::ninja_deps(context, &configuration).await?,
+            Tool::Query => {
+                turtle_build::tool::query(context, &configuration, &outputs, arguments.transitive)
+                    .await?
+            }
+            Tool::Recompact => {
+                turtle_build::tool::recompact(context, &configuration, build_files.partial).await?
+            }
+            Tool::Targets => turtle_build::tool::targets(context, &configuration, &outputs).await?,
+            Tool::Touch => {
+                turtle_build::tool::touch(context, configuration, &outputs, options).await?
+            }
+            Tool::Unused => {
+                if let Some(error) = build_files.errors.first() {
+                    return Err(error.clone().into());
+                }
+
+                turtle_build::tool::unused(context, &configuration, &build_files.modules).await?
+            }
+        }
+    } else {
+        if !arguments.dry_run {
+            context.database().set_targets(&outputs)?;
+        }
+
+        turtle_build::run::run(context, configuration, &outputs, options).await?;
     }
 
     Ok(())
 }
 
+// A daemon runs builds requested by clients one by one with build files
+// reloaded only when they change. Console outputs are sent to the clients
+// during builds.
+#[cfg(unix)]
+async fn serve(
+    context: &Arc<Context>,
+    daemon_arguments: &Arguments,
+    mut build_files: BuildFiles,
+) -> Result<(), ApplicationError> {
+    if build_files.modified_times.is_empty() {
+        return Err(ApplicationError::Other(
+            "daemon cannot read build files from stdin".into(),
+        ));
+    }
+
+    create_dir_all(DATABASE_DIRECTORY).await?;
+
+    let server =
+        turtle_build::daemon::Server::bind(&Path::new(DATABASE_DIRECTORY).join(DAEMON_SOCKET_FILE))
+            .await?;
+
+    loop {
+        let (request, connection) = match server.accept().await {
+            Ok(request) => request,
+            Err(error) => {
+                let mut console = context.console().lock().await;
+
+                log!(console, "failed to accept a daemon request: {}", error);
+                continue;
+            }
+        };
+        let console = replace(
+            &mut *context.console().lock().await,
+            Box::new(connection.console()),
+        );
+        let code = match Arguments::try_parse_from(request.arguments) {
+            Ok(mut arguments) => {
+                if arguments.color == Color::Auto {
+                    arguments.color = if request.color {
+                        Color::Always
+                    } else {
+                        Color::Never
+                    };
+                }
+
+                if let Err(error) =
+                    serve_request(context, daemon_arguments, &arguments, &mut build_files).await
+                {
+                    report_error(context, &arguments, &error).await;
+                    error.exit_code()
+                } else {
+                    0
+                }
+            }
+            Err(error) => {
+                let _ = context
+                    .console()
+                    .lock()
+                    .await
+                    .write_stderr(error.to_string().as_bytes())
+                    .await;
+                1
+            }
+        };
+
+        *context.console().lock().await = console;
+
+        // Clients can be gone already.
+        let _ = connection.exit(code).await;
+    }
+}
+
+// Options configuring command runners and consoles are fixed on startup of
+// daemons.
+#[cfg(unix)]
+fn find_different_fixed_options(
+    daemon_arguments: &Arguments,
+    arguments: &Arguments,
+) -> Vec<&'static str> {
+    [
+        (
+            "-j",
+            resolve_job_limit(daemon_arguments) != resolve_job_limit(arguments),
+        ),
+        ("--shell", daemon_arguments.shell != arguments.shell),
+        (
+            "--env-file",
+            daemon_arguments.env_file != arguments.env_file,
+        ),
+        (
+            "--clear-env",
+            daemon_arguments.clear_env != arguments.clear_env,
+        ),
+        (
+            "--max-capture",
+            daemon_arguments.max_capture != arguments.max_capture,
+        ),
+        (
+            "--spawn-rate",
+            daemon_arguments.spawn_rate != arguments.spawn_rate,
+        ),
+        (
+            "--log-file",
+            daemon_arguments.log_file != arguments.log_file,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(option, different)| different.then_some(option))
+    .collect()
+}
+
+#[cfg(not(unix))]
+async fn serve(_: &Arc<Context>, _: &Arguments, _: BuildFiles) -> Result<(), ApplicationError> {
+    Err(ApplicationError::Other(
+        "daemon not supported on this platform".into(),
+    ))
+}
+
+#[cfg(unix)]
+async fn serve_request(
+    context: &Arc<Context>,
+    daemon_arguments: &Arguments,
+    arguments: &Arguments,
+    build_files: &mut BuildFiles,
+) -> Result<(), ApplicationError> {
+    let options = find_different_fixed_options(daemon_arguments, arguments);
+
+    if !options.is_empty() {
+        return Err(ApplicationError::Other(format!(
+            "options {} differ from ones of a daemon; restart it with them",
+            options.join(", ")
+        )));
+    }
+
+    if build_files.is_changed(context, arguments).await {
+        *build_files = load(context, arguments).await?;
+    }
+
+    if inspects_build_files(arguments) {
+        return dispatch(context, arguments, build_files, None).await;
+    } else if let Some(error) = build_files.errors.first() {
+        return Err(error.clone().into());
+    }
+
+    dispatch(
+        context,
+        arguments,
+        build_files,
+        Some(database_directory(arguments, build_files)),
+    )
+    .await
+}
+
+async fn report_error(context: &Context, arguments: &Arguments, error: &ApplicationError) {
+    // Outputs of failed commands are reported already.
+    if !(arguments.quiet || arguments.error_format == ErrorFormat::Short)
+        || !matches!(error, ApplicationError::Build)
+    {
+        context
+            .console()
+            .lock()
+            .await
+            .write_stderr(
+                format!(
+                    "{}{}\n",
+                    if let Some(prefix) = &arguments.log_prefix {
+                        prefix
+                    } else {
+                        ""
+                    },
+                    error
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+    }
+}
+
 async fn parse_modules(
     context: &Context,
-    path: &Path,
+    root_path: &Path,
+    stdin: bool,
+    max_depth: usize,
+    refresh: bool,
+    fail_fast: bool,
 ) -> Result<(HashMap<PathBuf, Module>, ModuleDependencyMap), ApplicationError> {
-    let mut paths = vec![context.file_system().canonicalize_path(path).await?];
+    let mut paths = vec![(
+        if stdin {
+            root_path.into()
+        } else {
+            context.file_system().canonicalize_path(root_path).await?
+        },
+        vec![],
+    )];
     let mut modules = HashMap::new();
     let mut dependencies = HashMap::new();
+    let mut errors = vec![];
+    let mut broken_paths = HashSet::new();
 
-    while let Some(path) = paths.pop() {
+    // Chains of parent modules are tracked to report too deep inclusion.
+    while let Some((path, mut parents)) = paths.pop() {
+        if modules.contains_key(&path) || broken_paths.contains(&path) {
+            continue;
+        } else if parents.len() > max_depth {
+            return Err(ApplicationError::Other(format!(
+                "build files nested deeper than {max_depth}: {}",
+                parents
+                    .iter()
+                    .chain([&path])
+                    .map(|path| relative_path(path).map(|path| path.display().to_string()))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(" -> ")
+            )));
+        }
+
+        let stdin = stdin && path == root_path;
         let mut source = String::new();
 
-        context
-            .file_system()
-            .read_file_to_string(&path, &mut source)
-            .await?;
+        if stdin {
+            io::stdin().read_to_string(&mut source).await?;
+        } else {
+            context
+                .file_system()
+                .read_file_to_string(&path, &mut source)
+                .await?;
+        }
 
-        let module = parse(&source)?;
+        // Build files included by broken ones are not parsed as their paths
+        // are unknown.
+        let module = match parse(&source) {
+            Ok(module) => module,
+            Err(error) if fail_fast => return Err(error.into()),
+            Err(error) => {
+                errors.push((relative_path(&path)?.display().to_string(), error));
+                broken_paths.insert(path);
+                continue;
+            }
+        };
+        let module_path = &path;
 
         let submodule_paths = try_join_all(
             module
@@ -145,32 +1032,81 @@ async fn parse_modules(
                     Statement::Submodule(submodule) => Some(submodule.path()),
                     _ => None,
                 })
-                .map(|submodule_path| resolve_submodule_path(context, &path, submodule_path))
+                .map(|submodule_path| async move {
+                    resolve_submodule_path(context, module_path, submodule_path, refresh)
+                        .await
+                        .map_err(|error| {
+                            if stdin {
+                                ApplicationError::Other(format!(
+                                    "failed to resolve \"{submodule_path}\" in a build file from stdin relative to a current directory: {error}"
+                                ))
+                            } else {
+                                error
+                            }
+                        })
+                })
                 .collect::<Vec<_>>(),
         )
         .await?
         .into_iter()
         .collect::<HashMap<_, _>>();
 
-        paths.extend(submodule_paths.values().cloned());
+        parents.push(path.clone());
+        paths.extend(
+            submodule_paths
+                .values()
+                .map(|submodule_path| (submodule_path.clone(), parents.clone())),
+        );
 
         modules.insert(path.clone(), module);
         dependencies.insert(path, submodule_paths);
     }
 
-    Ok((modules, dependencies))
+    errors.sort_by(|(one, _), (other, _)| one.cmp(other));
+
+    match errors.len() {
+        0 => Ok((modules, dependencies)),
+        1 => Err(errors.remove(0).1.into()),
+        _ => Err(ApplicationError::Parses(errors)),
+    }
 }
 
 async fn resolve_submodule_path(
     context: &Context,
     module_path: &Path,
     submodule_path: &str,
+    refresh: bool,
 ) -> Result<(String, PathBuf), ApplicationError> {
-    Ok((
-        submodule_path.into(),
-        context
-            .file_system()
-            .canonicalize_path(&module_path.parent().unwrap().join(submodule_path))
-            .await?,
-    ))
+    let path = if turtle_build::remote::is_url(submodule_path) {
+        turtle_build::remote::fetch(
+            submodule_path,
+            &Path::new(DATABASE_DIRECTORY).join(REMOTE_DIRECTORY),
+            refresh,
+        )
+        .await?
+    } else {
+        relative_path(module_path.parent().unwrap())?.join(submodule_path)
+    };
+
+    // Canonicalization fails on missing files or loops of symbolic links.
+    let canonical_path = match context.file_system().canonicalize_path(&path).await {
+        Ok(path) => path,
+        Err(error) => {
+            return Err(ApplicationError::ModuleNotResolved(
+                submodule_path.into(),
+                relative_path(module_path)?.display().to_string(),
+                error.to_string(),
+            ))
+        }
+    };
+
+    Ok((submodule_path.into(), canonical_path))
+}
+
+// Show paths relative to a working directory in error messages.
+fn relative_path(path: &Path) -> Result<PathBuf, ApplicationError> {
+    Ok(path
+        .strip_prefix(current_dir()?)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| path.into()))
 }
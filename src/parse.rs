@@ -1,3 +1,4 @@
+mod depfile;
 mod error;
 mod parser;
 
@@ -5,10 +6,73 @@ pub use self::error::ParseError;
 use self::parser::{dynamic_module, module};
 use crate::ast::{DynamicModule, Module};
 
+const FORMAT_HEADER: &str = "# turtle-format:";
+const SUPPORTED_FORMAT_VERSION: u64 = 1;
+
 pub fn parse(source: &str) -> Result<Module, ParseError> {
+    check_format_version(source)?;
+
     Ok(module(source).map(|(_, module)| module)?)
 }
 
 pub fn parse_dynamic(source: &str) -> Result<DynamicModule, ParseError> {
     Ok(dynamic_module(source).map(|(_, module)| module)?)
 }
+
+pub fn parse_depfile(source: &str) -> Vec<String> {
+    depfile::dependencies(source)
+}
+
+// A format header is looked up in comments and blank lines at the beginning of
+// a build file. Build files of newer formats are rejected rather than parsed
+// mistakenly.
+fn check_format_version(source: &str) -> Result<(), ParseError> {
+    let Some(version) = source
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with('#'))
+        .find_map(|line| line.strip_prefix(FORMAT_HEADER))
+        .map(str::trim)
+    else {
+        return Ok(());
+    };
+
+    match version.parse::<u64>() {
+        Ok(number) if number <= SUPPORTED_FORMAT_VERSION => Ok(()),
+        _ => Err(ParseError::new(format!(
+            "unsupported build file format {version:?} (up to {SUPPORTED_FORMAT_VERSION} supported)"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_without_format_header() {
+        assert!(parse("# foo\nx = 1\n").is_ok());
+    }
+
+    #[test]
+    fn parse_supported_format() {
+        assert!(parse("# turtle-format: 1\nx = 1\n").is_ok());
+        assert!(parse("\n# foo\n  # turtle-format:1\nx = 1\n").is_ok());
+    }
+
+    #[test]
+    fn fail_to_parse_unsupported_format() {
+        assert_eq!(
+            parse("# turtle-format: 2\nx = 1\n"),
+            Err(ParseError::new(
+                "unsupported build file format \"2\" (up to 1 supported)"
+            ))
+        );
+        assert!(parse("# turtle-format: foo\n").is_err());
+    }
+
+    #[test]
+    fn ignore_format_header_after_statements() {
+        assert!(parse("x = 1\n# turtle-format: 2\n").is_ok());
+    }
+}
@@ -0,0 +1,97 @@
+// Parse Makefile-style dependency files generated by compilers.
+pub fn dependencies(source: &str) -> Vec<String> {
+    let mut dependencies = vec![];
+    let mut token = String::new();
+    let mut target = true;
+    let mut characters = source.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.peek() {
+                Some('\n') => {
+                    characters.next();
+                    push_token(&mut dependencies, &mut token, &mut target);
+                }
+                Some('\r') => {
+                    characters.next();
+                    characters.next_if_eq(&'\n');
+                    push_token(&mut dependencies, &mut token, &mut target);
+                }
+                Some(&character @ (' ' | '#')) => {
+                    characters.next();
+                    token.push(character);
+                }
+                _ => token.push('\\'),
+            },
+            '$' if characters.next_if_eq(&'$').is_some() => token.push('$'),
+            '\n' => {
+                push_token(&mut dependencies, &mut token, &mut target);
+                target = true;
+            }
+            character if character.is_whitespace() => {
+                push_token(&mut dependencies, &mut token, &mut target)
+            }
+            character => token.push(character),
+        }
+    }
+
+    push_token(&mut dependencies, &mut token, &mut target);
+
+    dependencies
+}
+
+fn push_token(dependencies: &mut Vec<String>, token: &mut String, target: &mut bool) {
+    if *target {
+        if token.ends_with(':') {
+            *target = false;
+        }
+    } else if !token.is_empty() {
+        dependencies.push(token.clone());
+    }
+
+    token.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(dependencies(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn parse_dependencies() {
+        assert_eq!(dependencies("foo.o: foo.c foo.h\n"), vec!["foo.c", "foo.h"]);
+    }
+
+    #[test]
+    fn parse_line_continuation() {
+        assert_eq!(
+            dependencies("foo.o: foo.c \\\n  foo.h \\\r\n  bar.h\n"),
+            vec!["foo.c", "foo.h", "bar.h"]
+        );
+    }
+
+    #[test]
+    fn parse_escaped_characters() {
+        assert_eq!(
+            dependencies("foo.o: foo\\ bar.c $$baz.h\n"),
+            vec!["foo bar.c", "$baz.h"]
+        );
+    }
+
+    #[test]
+    fn parse_multiple_targets() {
+        assert_eq!(
+            dependencies("foo.o bar.o: foo.h\nbaz.o: baz.h\n"),
+            vec!["foo.h", "baz.h"]
+        );
+    }
+
+    #[test]
+    fn parse_separated_colon() {
+        assert_eq!(dependencies("foo.o : foo.h\n"), vec!["foo.h"]);
+    }
+}
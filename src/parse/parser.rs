@@ -1,18 +1,22 @@
 use crate::ast::{
-    Build, DefaultOutput, DynamicBuild, DynamicModule, Include, Module, Rule, Statement, Submodule,
-    VariableDefinition,
+    Build, DefaultOutput, DynamicBuild, DynamicModule, Include, Module, Pool, Rule, Statement,
+    Submodule, VariableDefinition,
 };
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric1, line_ending, none_of, one_of, space1},
-    combinator::{all_consuming, into, map, not, opt, peek, recognize, value},
+    character::complete::{alpha1, alphanumeric1, digit1, line_ending, none_of, one_of, space1},
+    combinator::{all_consuming, into, map, map_opt, map_res, not, opt, peek, recognize, value},
     multi::{many0, many0_count, many1, many1_count},
-    sequence::{delimited, preceded, terminated, tuple},
+    sequence::{preceded, terminated, tuple},
     IResult, Parser,
 };
+use std::collections::BTreeMap;
 
 const OPERATOR_CHARACTERS: &str = "|:";
+// Signs are not followed by these characters so that `|` does not match `||` or
+// `|@`.
+const SIGN_TERMINATOR_CHARACTERS: &str = "|:@";
 const DYNAMIC_MODULE_VERSION_VARIABLE: &str = "ninja_dyndep_version";
 
 pub fn module(input: &str) -> IResult<&str, Module> {
@@ -38,6 +42,7 @@ fn statement(input: &str) -> IResult<&str, Statement> {
         into(build),
         into(default),
         into(include),
+        into(pool),
         into(rule),
         into(submodule),
         into(variable_definition),
@@ -46,8 +51,21 @@ fn statement(input: &str) -> IResult<&str, Statement> {
 
 fn variable_definition(input: &str) -> IResult<&str, VariableDefinition> {
     map(
-        tuple((identifier, sign("="), opt(string_line), line_break)),
-        |(name, _, value, _)| VariableDefinition::new(name, value.unwrap_or_default()),
+        tuple((
+            variable_name,
+            alt((value(true, sign(":=")), value(false, sign("=")))),
+            opt(string_line),
+            line_break,
+        )),
+        |(name, immediate, value, _)| {
+            let value = value.unwrap_or_default();
+
+            if immediate {
+                VariableDefinition::new_immediate(name, value)
+            } else {
+                VariableDefinition::new(name, value)
+            }
+        },
     )(input)
 }
 
@@ -63,24 +81,98 @@ fn dynamic_module_version(input: &str) -> IResult<&str, &str> {
     )(input)
 }
 
+fn pool(input: &str) -> IResult<&str, Pool> {
+    map_opt(
+        tuple((
+            keyword("pool"),
+            identifier,
+            line_break,
+            many1(preceded(indent, variable_definition)),
+        )),
+        |(_, name, _, variable_definitions)| match variable_definitions.as_slice() {
+            [definition] if definition.name() == "depth" => {
+                Some(Pool::new(name, definition.value()))
+            }
+            _ => None,
+        },
+    )(input)
+}
+
 fn rule(input: &str) -> IResult<&str, Rule> {
-    map(
+    map_opt(
         tuple((
             keyword("rule"),
             identifier,
+            opt(preceded(sign(":"), identifier)),
             line_break,
-            delimited(
-                tuple((indent, keyword("command"), sign("="))),
-                string_line,
-                line_break,
-            ),
-            opt(delimited(
-                tuple((indent, keyword("description"), sign("="))),
-                string_line,
-                line_break,
+            many0(preceded(
+                indent,
+                alt((
+                    map(indexed_command, |(index, command)| (Some(index), command)),
+                    map(variable_definition, |definition| (None, definition)),
+                )),
             )),
         )),
-        |(_, name, _, command, description)| Rule::new(name, command, description.map(From::from)),
+        |(_, name, base, _, variable_definitions)| {
+            let mut command = None;
+            let mut indexed_commands = BTreeMap::new();
+            let mut description = None;
+            let mut other_definitions = vec![];
+
+            for (index, definition) in variable_definitions {
+                // Commands and descriptions depend on variables of builds.
+                if definition.is_immediate()
+                    && matches!(definition.name(), "command" | "description")
+                {
+                    return None;
+                }
+
+                match (index, definition.name()) {
+                    (Some(index), _) => {
+                        indexed_commands.insert(index, definition.value().to_owned());
+                    }
+                    (None, "command") => command = Some(definition.value().to_owned()),
+                    (None, "description") => description = Some(definition.value().to_owned()),
+                    _ => other_definitions.push(definition),
+                }
+            }
+
+            // Extensions inherit commands of their bases unless they define
+            // ones.
+            let commands = match (command, indexed_commands.is_empty()) {
+                (Some(command), true) => vec![command],
+                (None, false) => indexed_commands.into_values().collect(),
+                (None, true) if base.is_some() => vec![],
+                _ => return None,
+            };
+
+            Some(if let Some(base) = base {
+                Rule::new_extension(name, base, commands, description, other_definitions)
+            } else {
+                Rule::new(name, commands, description, other_definitions)
+            })
+        },
+    )(input)
+}
+
+// Commands of `command[0]`, `command[1]`, ... run in order of their indices.
+fn indexed_command(input: &str) -> IResult<&str, (usize, VariableDefinition)> {
+    map(
+        tuple((
+            keyword("command"),
+            tag("["),
+            map_res(digit1, str::parse),
+            sign("]"),
+            sign("="),
+            opt(string_line),
+            line_break,
+        )),
+        |(_, _, index, _, _, value, _)| {
+            (
+                index,
+                VariableDefinition::new("command", value.unwrap_or_default()),
+            )
+        },
     )(input)
 }
 
@@ -95,6 +187,7 @@ fn build(input: &str) -> IResult<&str, Build> {
             many0(string_literal),
             opt(preceded(sign("|"), many1(string_literal))),
             opt(preceded(sign("||"), many1(string_literal))),
+            opt(preceded(sign("|@"), many1(string_literal))),
             line_break,
             many0(preceded(indent, variable_definition)),
         )),
@@ -107,6 +200,7 @@ fn build(input: &str) -> IResult<&str, Build> {
             inputs,
             implicit_inputs,
             order_only_inputs,
+            validations,
             _,
             variable_definitions,
         )| {
@@ -117,6 +211,7 @@ fn build(input: &str) -> IResult<&str, Build> {
                 inputs,
                 implicit_inputs.unwrap_or_default(),
                 order_only_inputs.unwrap_or_default(),
+                validations.unwrap_or_default(),
                 variable_definitions,
             )
         },
@@ -142,7 +237,7 @@ pub fn dynamic_build(input: &str) -> IResult<&str, DynamicBuild> {
 fn default(input: &str) -> IResult<&str, DefaultOutput> {
     map(
         tuple((keyword("default"), many1(string_literal), line_break)),
-        |(_, outputs, _)| DefaultOutput::new(outputs.into_iter().map(From::from).collect()),
+        |(_, outputs, _)| DefaultOutput::new(outputs),
     )(input)
 }
 
@@ -186,13 +281,22 @@ fn identifier(input: &str) -> IResult<&str, &str> {
     ))))(input)
 }
 
+// Names of variables can contain dots, e.g. `shell.bash`, unlike references to
+// them.
+fn variable_name(input: &str) -> IResult<&str, &str> {
+    token(recognize(tuple((
+        alt((alpha1, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_"), tag(".")))),
+    ))))(input)
+}
+
 fn sign(sign: &'static str) -> impl Fn(&str) -> IResult<&str, ()> {
     move |input| {
         value(
             (),
             token(terminated(
                 tag(sign),
-                peek(not(one_of(OPERATOR_CHARACTERS))),
+                peek(not(one_of(SIGN_TERMINATOR_CHARACTERS))),
             )),
         )(input)
     }
@@ -241,6 +345,7 @@ mod tests {
             inputs,
             vec![],
             vec![],
+            vec![],
             variable_definitions,
         )
     }
@@ -262,15 +367,17 @@ mod tests {
         );
         assert_eq!(
             module("rule foo\n command = bar\n").unwrap().1,
-            Module::new(vec![Rule::new("foo", "bar", None).into()])
+            Module::new(vec![
+                Rule::new("foo", vec!["bar".into()], None, vec![]).into()
+            ])
         );
         assert_eq!(
             module("rule foo\n command = bar\nrule baz\n command = blah\n")
                 .unwrap()
                 .1,
             Module::new(vec![
-                Rule::new("foo", "bar", None).into(),
-                Rule::new("baz", "blah", None).into(),
+                Rule::new("foo", vec!["bar".into()], None, vec![]).into(),
+                Rule::new("baz", vec!["blah".into()], None, vec![]).into(),
             ],)
         );
         assert_eq!(
@@ -320,6 +427,28 @@ mod tests {
             variable_definition("x = \n").unwrap().1,
             VariableDefinition::new("x", "")
         );
+        assert_eq!(
+            variable_definition("shell.bash = bash -c\n").unwrap().1,
+            VariableDefinition::new("shell.bash", "bash -c")
+        );
+    }
+
+    #[test]
+    fn parse_immediate_variable_definition() {
+        assert_eq!(
+            variable_definition("x := $y\n").unwrap().1,
+            VariableDefinition::new_immediate("x", "$y")
+        );
+        assert_eq!(
+            variable_definition("x :=\n").unwrap().1,
+            VariableDefinition::new_immediate("x", "")
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_immediate_command() {
+        assert!(rule("rule foo\n  command := bar\n").is_err());
+        assert!(rule("rule foo\n  command = bar\n  description := baz\n").is_err());
     }
 
     #[test]
@@ -336,14 +465,79 @@ mod tests {
     fn parse_rule() {
         assert_eq!(
             rule("rule foo\n command = bar\n").unwrap().1,
-            Rule::new("foo", "bar", None)
+            Rule::new("foo", vec!["bar".into()], None, vec![])
         );
         assert_eq!(
             rule("rule foo\n command = bar\n description = baz\n")
                 .unwrap()
                 .1,
-            Rule::new("foo", "bar", Some("baz".into()))
+            Rule::new("foo", vec!["bar".into()], Some("baz".into()), vec![])
         );
+        assert_eq!(
+            rule("rule foo\n description = baz\n command = bar\n")
+                .unwrap()
+                .1,
+            Rule::new("foo", vec!["bar".into()], Some("baz".into()), vec![])
+        );
+        assert_eq!(
+            rule("rule foo\n command = bar\n raw = 1\n").unwrap().1,
+            Rule::new(
+                "foo",
+                vec!["bar".into()],
+                None,
+                vec![VariableDefinition::new("raw", "1")]
+            )
+        );
+        assert!(rule("rule foo\n description = baz\n").is_err());
+    }
+
+    #[test]
+    fn parse_pool() {
+        assert_eq!(
+            pool("pool foo\n depth = 4\n").unwrap().1,
+            Pool::new("foo", "4")
+        );
+        assert_eq!(
+            pool("pool foo\n depth = $jobs\n").unwrap().1,
+            Pool::new("foo", "$jobs")
+        );
+        assert!(pool("pool foo\n").is_err());
+        assert!(pool("pool foo\n command = bar\n").is_err());
+        assert!(pool("pool foo\n depth = 1\n depth = 2\n").is_err());
+    }
+
+    #[test]
+    fn parse_rule_extension() {
+        assert_eq!(
+            rule("rule foo : bar\n x = 1\n").unwrap().1,
+            Rule::new_extension(
+                "foo",
+                "bar",
+                vec![],
+                None,
+                vec![VariableDefinition::new("x", "1")]
+            )
+        );
+        assert_eq!(
+            rule("rule foo: bar\n command = baz\n").unwrap().1,
+            Rule::new_extension("foo", "bar", vec!["baz".into()], None, vec![])
+        );
+        assert_eq!(
+            rule("rule foo : bar\n").unwrap().1,
+            Rule::new_extension("foo", "bar", vec![], None, vec![])
+        );
+        assert!(rule("rule foo\n").is_err());
+    }
+
+    #[test]
+    fn parse_rule_with_indexed_commands() {
+        assert_eq!(
+            rule("rule foo\n command[1] = baz\n command[0] = bar\n")
+                .unwrap()
+                .1,
+            Rule::new("foo", vec!["bar".into(), "baz".into()], None, vec![])
+        );
+        assert!(rule("rule foo\n command = bar\n command[0] = baz\n").is_err());
     }
 
     #[test]
@@ -399,6 +593,7 @@ mod tests {
                 vec![],
                 vec![],
                 vec![],
+                vec![],
                 vec![]
             )
         );
@@ -411,6 +606,7 @@ mod tests {
                 vec![],
                 vec![],
                 vec![],
+                vec![],
                 vec![]
             )
         );
@@ -423,6 +619,7 @@ mod tests {
                 vec![],
                 vec!["x2".into()],
                 vec![],
+                vec![],
                 vec![]
             )
         );
@@ -435,6 +632,7 @@ mod tests {
                 vec![],
                 vec!["x2".into(), "x3".into()],
                 vec![],
+                vec![],
                 vec![]
             )
         );
@@ -448,6 +646,7 @@ mod tests {
                 vec![],
                 vec!["x2".into()],
                 vec![],
+                vec![],
             )
         );
         assert_eq!(
@@ -459,6 +658,33 @@ mod tests {
                 vec![],
                 vec![],
                 vec!["x2".into(), "x3".into()],
+                vec![],
+                vec![]
+            )
+        );
+        assert_eq!(
+            build("build x1: rule x2 | x3 || x4 |@ x5 x6\n").unwrap().1,
+            Build::new(
+                vec!["x1".into()],
+                vec![],
+                "rule",
+                vec!["x2".into()],
+                vec!["x3".into()],
+                vec!["x4".into()],
+                vec!["x5".into(), "x6".into()],
+                vec![]
+            )
+        );
+        assert_eq!(
+            build("build x1: rule |@ x2\n").unwrap().1,
+            Build::new(
+                vec!["x1".into()],
+                vec![],
+                "rule",
+                vec![],
+                vec![],
+                vec![],
+                vec!["x2".into()],
                 vec![]
             )
         );
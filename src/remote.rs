@@ -0,0 +1,161 @@
+use crate::fnv;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+use tokio::fs::{create_dir_all, try_exists, write};
+
+const URL_SCHEMES: &[&str] = &["http://", "https://"];
+
+pub fn is_url(path: &str) -> bool {
+    URL_SCHEMES.iter().any(|scheme| path.starts_with(scheme))
+}
+
+// Remote build files are cached under a directory keyed by hashes of their
+// URLs. Caches are used on network failures even if refreshing is requested.
+pub async fn fetch(url: &str, directory: &Path, refresh: bool) -> Result<PathBuf, Box<dyn Error>> {
+    let path = directory.join(format!("{:016x}.ninja", fnv::hash(url.as_bytes())));
+    let cached = try_exists(&path).await?;
+
+    if cached && !refresh {
+        return Ok(path);
+    }
+
+    match download(url).await {
+        Ok(source) => {
+            create_dir_all(directory).await?;
+            write(&path, source).await?;
+        }
+        Err(_) if cached => {}
+        Err(error) => return Err(format!("failed to fetch {url}: {error}").into()),
+    }
+
+    Ok(path)
+}
+
+async fn download(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(reqwest::get(url)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?
+        .to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::{
+        fs::read_to_string,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    // This serves a build file to a request and returns its URL.
+    async fn serve(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let _ = stream.read(&mut buffer).await.unwrap();
+
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+        });
+
+        format!("http://{address}/rules.ninja")
+    }
+
+    // This returns a URL where nothing is served.
+    async fn unavailable_url() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        drop(listener);
+
+        format!("http://{address}/rules.ninja")
+    }
+
+    #[test]
+    fn detect_urls() {
+        assert!(is_url("https://example.com/rules.ninja"));
+        assert!(is_url("http://example.com/rules.ninja"));
+        assert!(!is_url("rules.ninja"));
+        assert!(!is_url("/https/rules.ninja"));
+    }
+
+    #[tokio::test]
+    async fn fetch_remote_file() {
+        let directory = tempdir().unwrap();
+        let url = serve("rule foo\n  command = foo\n").await;
+
+        let path = fetch(&url, directory.path(), false).await.unwrap();
+
+        assert!(path.starts_with(directory.path()));
+        assert_eq!(
+            read_to_string(&path).await.unwrap(),
+            "rule foo\n  command = foo\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn use_cache() {
+        let directory = tempdir().unwrap();
+        // Cache file names must not change across Rust versions.
+        let path = directory.path().join("a0c072e798cc4365.ninja");
+        write(&path, "foo").await.unwrap();
+
+        assert_eq!(
+            fetch("http://foo", directory.path(), false).await.unwrap(),
+            path
+        );
+        assert_eq!(read_to_string(&path).await.unwrap(), "foo");
+    }
+
+    #[tokio::test]
+    async fn refresh_cache() {
+        let directory = tempdir().unwrap();
+        let url = serve("bar").await;
+        let path = directory
+            .path()
+            .join(format!("{:016x}.ninja", fnv::hash(url.as_bytes())));
+        write(&path, "foo").await.unwrap();
+
+        fetch(&url, directory.path(), true).await.unwrap();
+
+        assert_eq!(read_to_string(&path).await.unwrap(), "bar");
+    }
+
+    #[tokio::test]
+    async fn fall_back_to_cache_on_network_failure() {
+        let directory = tempdir().unwrap();
+        let url = unavailable_url().await;
+        let path = directory
+            .path()
+            .join(format!("{:016x}.ninja", fnv::hash(url.as_bytes())));
+        write(&path, "foo").await.unwrap();
+
+        assert_eq!(fetch(&url, directory.path(), true).await.unwrap(), path);
+        assert_eq!(read_to_string(&path).await.unwrap(), "foo");
+    }
+
+    #[tokio::test]
+    async fn fail_to_fetch_without_cache() {
+        let directory = tempdir().unwrap();
+
+        assert!(fetch(&unavailable_url().await, directory.path(), false)
+            .await
+            .is_err());
+    }
+}
@@ -1,7 +1,30 @@
 mod context;
+mod dedup;
+mod determinism;
+mod error_format;
+mod error_output;
+mod event;
 mod hash;
 mod log;
+mod memory;
+mod modified_inputs;
+mod ninja_log;
 mod options;
+mod output_mode;
+mod output_size;
+mod pause;
+mod pool;
+mod post_build;
+mod profile;
+mod provenance;
+mod scheduler;
+mod shard;
+mod status;
+mod time_report;
+mod undeclared_outputs;
+mod up_to_date;
+mod watchdog;
+mod why;
 
 use self::context::Context as RunContext;
 use crate::{
@@ -10,17 +33,39 @@ use crate::{
     context::Context,
     debug,
     error::ApplicationError,
+    fnv,
     hash_type::HashType,
-    ir::{Build, Configuration, Rule},
-    parse::parse_dynamic,
+    infrastructure::{is_truncated, CommandError, Console, ResourceUsage},
+    ir::{Build, Configuration, Rule, TEMPORARY_DIRECTORY_PLACEHOLDER},
+    log,
+    parse::{parse_depfile, parse_dynamic},
     profile,
 };
 use async_recursion::async_recursion;
-use futures::future::{try_join_all, FutureExt, Shared};
+pub use determinism::verify_deterministic;
+use encoding_rs::Encoding;
+pub use error_format::{ErrorFormat, ErrorFormatError};
+pub use event::{BuildEvent, EventHandler};
+use futures::future::{join_all, try_join_all, FutureExt, Shared};
 use itertools::Itertools;
+pub use ninja_log::import_ninja_log;
 pub use options::Options;
-use std::{future::Future, path::Path, pin::Pin, sync::Arc};
-use tokio::{spawn, time::Instant, try_join};
+pub use output_mode::{OutputMode, OutputModeError};
+pub use profile::{ProfileFormat, ProfileFormatError};
+pub use scheduler::{Schedule, ScheduleError};
+pub use shard::{Shard, ShardError};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    future::Future,
+    path::Path,
+    pin::{pin, Pin},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, UNIX_EPOCH},
+};
+use tokio::{select, spawn, time::Instant};
+pub use up_to_date::{check_up_to_date, explain_json};
+pub use why::why;
 
 type RawBuildFuture = Pin<Box<dyn Future<Output = Result<(), ApplicationError>> + Send>>;
 type BuildFuture = Shared<RawBuildFuture>;
@@ -30,13 +75,52 @@ pub async fn run(
     configuration: Arc<Configuration>,
     outputs: &[String],
     options: Options,
+) -> Result<(), ApplicationError> {
+    run_with_handler(context, configuration, outputs, options, None).await
+}
+
+// Build events are sent to a given handler instead of descriptions of rules on
+// a console.
+pub async fn run_with_event_handler(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+    handler: impl Fn(BuildEvent) + Send + Sync + 'static,
+) -> Result<(), ApplicationError> {
+    run_with_handler(
+        context,
+        configuration,
+        outputs,
+        options,
+        Some(Arc::new(handler)),
+    )
+    .await
+}
+
+async fn run_with_handler(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+    event_handler: Option<EventHandler>,
 ) -> Result<(), ApplicationError> {
     let graph = BuildGraph::new(configuration.outputs());
+    // Failures of builds other than a focused one never stop builds.
+    let options = Options {
+        keep_going: if options.keep_going_until.is_some() {
+            0
+        } else {
+            options.keep_going
+        },
+        ..options
+    };
     let context = Arc::new(RunContext::new(
         context.clone(),
         configuration,
         graph,
         options,
+        event_handler,
     ));
 
     context
@@ -46,44 +130,516 @@ pub async fn run(
         .validate()
         .map_err(|error| map_build_graph_error(&context, &error))?;
 
-    if outputs.is_empty() {
-        for output in context.configuration().default_outputs() {
-            trigger_build(
-                context.clone(),
+    let builds = if outputs.is_empty() {
+        context
+            .configuration()
+            .default_outputs()
+            .iter()
+            .map(|output| {
                 context
                     .configuration()
                     .outputs()
                     .get(output.as_ref())
-                    .ok_or_else(|| ApplicationError::DefaultOutputNotFound(output.clone()))?,
-            )
-            .await?;
-        }
+                    .cloned()
+                    .ok_or_else(|| ApplicationError::DefaultOutputNotFound(output.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
     } else {
-        for output in outputs {
-            trigger_build(
-                context.clone(),
+        outputs
+            .iter()
+            .map(|output| {
                 context
                     .configuration()
                     .outputs()
                     .get(output.as_str())
-                    .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))?,
-            )
-            .await?;
+                    .cloned()
+                    .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    if !context.options().dry_run && !context.options().touch {
+        clean_interrupted_builds(&context).await?;
+    }
+
+    let builds = if let Some(directory) = &context.options().subdirectory {
+        select_builds(&context, &builds, |build| {
+            build
+                .outputs()
+                .iter()
+                .chain(build.implicit_outputs())
+                .any(|output| Path::new(output.as_ref()).starts_with(directory))
+        })
+    } else {
+        builds
+    };
+
+    let builds = if let Some(shard) = context.options().shard {
+        builds
+            .into_iter()
+            .filter(|build| shard.contains(&build.outputs()[0]))
+            .collect()
+    } else {
+        builds
+    };
+
+    let builds = if context.options().tags.is_empty() {
+        builds
+    } else {
+        select_builds(&context, &builds, |build| {
+            context
+                .configuration()
+                .tags()
+                .get(&build.id())
+                .into_iter()
+                .flatten()
+                .any(|tag| context.options().tags.iter().any(|other| **other == **tag))
+        })
+    };
+
+    let builds = if context.options().excluded_outputs.is_empty() {
+        builds
+    } else {
+        exclude_builds(&context, &builds).await?
+    };
+
+    if !context.options().missing_ok {
+        check_inputs(&context, &builds).await?;
+    }
+
+    if !outputs.is_empty() {
+        for build in &builds {
+            context.requested_builds().insert(build.id());
         }
     }
 
-    // Do not inline this to avoid borrowing a lock of builds.
-    let futures = context
-        .build_futures()
+    force_builds(&context)?;
+    // Partial configurations differ from full ones and dry runs record nothing.
+    if !context.options().lazy && !context.options().dry_run {
+        check_configuration_hash(&context).await?;
+    }
+
+    let focused_build = context
+        .options()
+        .keep_going_until
+        .as_ref()
+        .map(|output| {
+            context
+                .configuration()
+                .outputs()
+                .get(output.as_str())
+                .cloned()
+                .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))
+        })
+        .transpose()?;
+
+    if context.options().max_memory.is_some() && memory::available_memory().is_none() {
+        let mut console = context.application().console().lock().await;
+
+        log!(
+            console,
+            "memory information not available; ignoring memory limit"
+        );
+    }
+
+    if let Some(capacity) = context
+        .pool_capacity()
+        .filter(|&capacity| capacity < context.options().job_limit)
+    {
+        let mut console = context.application().console().lock().await;
+
+        log!(
+            console,
+            "note: running at most {} jobs of total depth of pools below a job limit of {}",
+            capacity,
+            context.options().job_limit
+        );
+    }
+
+    // Depths of pools beyond a job limit are never used and can be
+    // misconfiguration.
+    let oversized_pools = context
+        .pools()
         .iter()
-        .map(|r#ref| r#ref.value().clone())
+        .filter(|(_, pool)| pool.depth() > context.options().job_limit)
+        .map(|(name, pool)| (name, pool.depth()))
+        .sorted()
         .collect::<Vec<_>>();
 
-    let result = try_join_all(futures).await;
+    if !oversized_pools.is_empty() {
+        let mut console = context.application().console().lock().await;
+
+        for (name, depth) in oversized_pools {
+            log!(
+                console,
+                "warning: depth {} of pool \"{}\" exceeds a job limit of {}; consider aligning them",
+                depth,
+                name,
+                context.options().job_limit
+            );
+        }
+    }
+
+    if let Some(seed) = context.options().shuffle {
+        let mut console = context.application().console().lock().await;
+
+        log!(console, "shuffle seed: {}", seed);
+    }
+
+    let mut builds = builds;
+
+    shuffle(&context, &mut builds, |build| &build.outputs()[0]);
+
+    let file_system = context.application().file_system();
+    let runs_commands = !context.options().dry_run && !context.options().touch;
+
+    if runs_commands {
+        file_system
+            .create_directory(context.temporary_directory())
+            .await?;
+    }
+
+    let pause = pause::watch(&context)?;
+    let result = async {
+        for build in builds.iter().chain(&focused_build) {
+            trigger_build(context.clone(), build).await?;
+        }
+
+        // Do not inline this to avoid borrowing a lock of builds.
+        let futures = builds
+            .iter()
+            .map(|build| context.build_futures().get(&build.id()).unwrap().clone())
+            .collect::<Vec<_>>();
+
+        select! {
+            result = join_requested_builds(&context, futures) => result,
+            _ = context.failure_limit().notified(), if context.options().keep_going != 1 => {
+                Err(ApplicationError::Build)
+            }
+            result = join_focused_build(&context, focused_build.as_deref()), if focused_build.is_some() => result,
+            result = watchdog::watch(&context) => result,
+            result = pause => result,
+            result = output_mode::watch(&context), if context.options().output_mode != OutputMode::Plain => result,
+        }
+    }
+    .await;
+
+    output_mode::render(&context).await?;
+
+    let result = if context.options().detect_modified_inputs {
+        result.and(modified_inputs::check(&context).await)
+    } else {
+        result
+    };
+
+    if runs_commands {
+        // Temporary files are disposable and failures of their removal are
+        // ignored.
+        let _ = file_system
+            .remove_directory(context.temporary_directory())
+            .await;
+    }
 
     context.application().database().flush().await?;
 
-    result.map(|_| ())
+    send_event(
+        &context,
+        BuildEvent::BuildFinished {
+            success: result.is_ok(),
+        },
+    );
+
+    if context.options().terminal_title {
+        context
+            .application()
+            .console()
+            .lock()
+            .await
+            .notify(if result.is_ok() {
+                "turtle: build succeeded"
+            } else {
+                "turtle: build failed"
+            })
+            .await?;
+    }
+
+    if context.options().collapse_errors {
+        error_output::report(&context).await?;
+    }
+
+    if result.is_ok() {
+        report_groups(&context, outputs).await?;
+    }
+
+    if context.options().time_report {
+        time_report::report(&context).await?;
+    }
+
+    profile::report(&context).await?;
+
+    if runs_commands {
+        post_build::run(&context, result.is_ok()).await?;
+    }
+
+    result
+}
+
+// Builds satisfying a predicate, e.g. ones with outputs under a directory, are
+// selected from ones reachable from requested builds.
+fn select_builds(
+    context: &RunContext,
+    builds: &[Arc<Build>],
+    predicate: impl Fn(&Build) -> bool,
+) -> Vec<Arc<Build>> {
+    let mut pending = builds.to_vec();
+    let mut visited = HashSet::new();
+    let mut selected = vec![];
+
+    while let Some(build) = pending.pop() {
+        if !visited.insert(build.id()) {
+            continue;
+        }
+
+        if predicate(&build) {
+            selected.push(build.clone());
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(build) = context.configuration().outputs().get(input) {
+                pending.push(build.clone());
+            }
+        }
+    }
+
+    selected.sort_by(|one, other| one.outputs()[0].cmp(&other.outputs()[0]));
+
+    selected
+}
+
+// Excluded builds are pruned from requested builds and phony builds grouping
+// them. The ones which other selected builds depend on are built anyway.
+async fn exclude_builds(
+    context: &RunContext,
+    builds: &[Arc<Build>],
+) -> Result<Vec<Arc<Build>>, ApplicationError> {
+    let mut pending = builds.to_vec();
+    let mut visited = HashSet::new();
+    let mut selected = vec![];
+
+    while let Some(build) = pending.pop() {
+        if !visited.insert(build.id()) || is_excluded(context, &build) {
+            continue;
+        } else if build.rule().is_some() {
+            selected.push(build);
+            continue;
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(build) = context.configuration().outputs().get(input) {
+                pending.push(build.clone());
+            }
+        }
+    }
+
+    selected.sort_by(|one, other| one.outputs()[0].cmp(&other.outputs()[0]));
+
+    let mut pending = selected.clone();
+    let mut visited = HashSet::new();
+    let mut warned = HashSet::new();
+    let mut console = context.application().console().lock().await;
+
+    while let Some(build) = pending.pop() {
+        if !visited.insert(build.id()) {
+            continue;
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(input) = context.configuration().outputs().get(input) {
+                if input.rule().is_some()
+                    && is_excluded(context, input)
+                    && warned.insert(input.id())
+                {
+                    log!(
+                        console,
+                        "warning: building excluded output {} required by {}",
+                        input.outputs()[0],
+                        build.outputs()[0]
+                    );
+                }
+
+                pending.push(input.clone());
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+fn is_excluded(context: &RunContext, build: &Build) -> bool {
+    build
+        .outputs()
+        .iter()
+        .chain(build.implicit_outputs())
+        .any(|output| {
+            context
+                .options()
+                .excluded_outputs
+                .iter()
+                .any(|pattern| pattern.matches(output))
+        })
+}
+
+fn force_builds(context: &RunContext) -> Result<(), ApplicationError> {
+    let mut pending = context
+        .options()
+        .forced_outputs
+        .iter()
+        .map(|output| {
+            context
+                .configuration()
+                .outputs()
+                .get(output.as_str())
+                .cloned()
+                .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    while let Some(build) = pending.pop() {
+        if !context.forced_builds().insert(build.id()) || !context.options().force_dependencies {
+            continue;
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(build) = context.configuration().outputs().get(input) {
+                pending.push(build.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Check inputs not built by any builds before running any commands.
+async fn check_inputs(context: &RunContext, builds: &[Arc<Build>]) -> Result<(), ApplicationError> {
+    let mut builds = builds.to_vec();
+    let mut visited_builds = HashSet::new();
+    let mut visited_inputs = HashSet::new();
+    let mut inputs = vec![];
+
+    while let Some(build) = builds.pop() {
+        if !visited_builds.insert(build.id()) {
+            continue;
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(build) = context.configuration().outputs().get(input) {
+                builds.push(build.clone());
+            } else if visited_inputs.insert(input.clone()) {
+                inputs.push((input.clone(), build.clone()));
+            }
+        }
+    }
+
+    try_join_all(inputs.iter().map(|(input, build)| async move {
+        context
+            .application()
+            .file_system()
+            .metadata(input.as_ref().as_ref())
+            .await
+            .map_err(|_| {
+                let output = &build.outputs()[0];
+
+                ApplicationError::MissingInput(
+                    input.to_string(),
+                    context
+                        .configuration()
+                        .source_map()
+                        .get(output)
+                        .unwrap_or(output)
+                        .to_string(),
+                )
+            })
+    }))
+    .await?;
+
+    Ok(())
+}
+
+// Builds interrupted while running commands might have left partial outputs
+// looking up to date. So their outputs and records are removed to rebuild them.
+async fn clean_interrupted_builds(context: &RunContext) -> Result<(), ApplicationError> {
+    let ids = context
+        .application()
+        .database()
+        .get_running_builds()?
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut builds = context
+        .configuration()
+        .outputs()
+        .values()
+        .filter(|build| ids.contains(&build.id()))
+        .collect::<Vec<_>>();
+
+    builds.sort_by_key(|build| build.outputs()[0].clone());
+    builds.dedup_by_key(|build| build.id());
+
+    for build in builds {
+        for output in build.outputs().iter().chain(build.implicit_outputs()) {
+            // Outputs might not be written yet.
+            let _ = context
+                .application()
+                .file_system()
+                .remove_file(output.as_ref().as_ref())
+                .await;
+        }
+    }
+
+    context
+        .application()
+        .database()
+        .remove_builds(&ids.into_iter().collect::<Vec<_>>())?;
+
+    Ok(())
+}
+
+// Groups are phony builds requested explicitly.
+async fn report_groups(context: &RunContext, outputs: &[String]) -> Result<(), ApplicationError> {
+    let mut console = context.application().console().lock().await;
+
+    for output in outputs {
+        if let Some(build) = context.configuration().outputs().get(output.as_str()) {
+            if build.rule().is_none() && !build.inputs().is_empty() {
+                log!(
+                    console,
+                    "{}: {} of {} rebuilt",
+                    output,
+                    build
+                        .inputs()
+                        .iter()
+                        .filter(|input| is_rebuilt(context, input))
+                        .count(),
+                    build.inputs().len()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_rebuilt(context: &RunContext, output: &str) -> bool {
+    context
+        .configuration()
+        .outputs()
+        .get(output)
+        .map(|build| context.rebuilt_builds().contains(&build.id()))
+        .unwrap_or_default()
 }
 
 #[async_recursion]
@@ -95,7 +651,11 @@ async fn trigger_build(
         .build_futures()
         .entry(build.id())
         .or_insert_with(|| {
-            let future: RawBuildFuture = Box::pin(spawn_build(context.clone(), build.clone()));
+            if build.rule().is_some() {
+                context.triggered_count().fetch_add(1, Ordering::SeqCst);
+            }
+
+            let future: RawBuildFuture = Box::pin(finish_build(context.clone(), build.clone()));
 
             future.shared()
         });
@@ -103,15 +663,39 @@ async fn trigger_build(
     Ok(())
 }
 
+// Progress is updated after builds finish whether they run or not.
+async fn finish_build(context: Arc<RunContext>, build: Arc<Build>) -> Result<(), ApplicationError> {
+    spawn_build(context.clone(), build.clone()).await?;
+
+    if build.rule().is_some() && context.options().terminal_title {
+        report_progress(context);
+    }
+
+    Ok(())
+}
+
 async fn spawn_build(context: Arc<RunContext>, build: Arc<Build>) -> Result<(), ApplicationError> {
     spawn(async move {
+        for validation in build.validations() {
+            if let Some(build) = context.configuration().outputs().get(validation) {
+                trigger_build(context.clone(), build).await?;
+            }
+        }
+
+        let mut inputs = build
+            .inputs()
+            .iter()
+            .chain(build.order_only_inputs())
+            .collect::<Vec<_>>();
         let mut futures = vec![];
 
-        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+        shuffle(&context, &mut inputs, |input| input);
+
+        for input in inputs {
             futures.push(build_input(context.clone(), input).await?);
         }
 
-        try_join_all(futures).await?;
+        join_builds(&context, futures).await?;
 
         // TODO Consider caching dynamic modules.
         let dynamic_configuration = if let Some(dynamic_module) = build.dynamic_module() {
@@ -146,39 +730,55 @@ async fn spawn_build(context: Arc<RunContext>, build: Arc<Build>) -> Result<(),
             &[]
         };
 
+        let mut inputs = dynamic_inputs.iter().collect::<Vec<_>>();
         let mut futures = vec![];
 
-        for input in dynamic_inputs {
+        shuffle(&context, &mut inputs, |input| input);
+
+        for input in inputs {
             futures.push(build_input(context.clone(), input).await?);
         }
 
-        try_join_all(futures).await?;
+        join_builds(&context, futures).await?;
 
-        let outputs_exist = try_join_all(
-            build
-                .outputs()
+        if build.rule().is_none()
+            && build
+                .inputs()
                 .iter()
-                .chain(build.implicit_outputs())
-                .map(|path| check_file_existence(&context, path)),
-        )
-        .await
-        .is_ok();
-        let (file_inputs, phony_inputs) = build
-            .inputs()
-            .iter()
-            .chain(dynamic_inputs)
-            .map(|string| string.as_ref())
-            .partition::<Vec<_>, _>(|&input| {
-                if let Some(build) = context.configuration().outputs().get(input) {
-                    build.rule().is_some()
-                } else {
-                    true
-                }
-            });
+                .any(|input| is_rebuilt(&context, input))
+        {
+            context.rebuilt_builds().insert(build.id());
+        }
+
+        // Outputs of builds run dry do not exist and cannot be hashed.
+        if context.options().dry_run
+            && build
+                .inputs()
+                .iter()
+                .chain(dynamic_inputs)
+                .any(|input| is_rebuilt(&context, input))
+        {
+            if let Some(rule) = build.rule() {
+                run_dry(&context, &build, rule).await?;
+            }
+
+            return Ok(());
+        }
+
+        let outputs_exist = check_outputs_existence(&context, &build).await;
+        let (file_inputs, phony_inputs) =
+            partition_inputs(&context, build.inputs().iter().chain(dynamic_inputs));
+        let dependencies_changed =
+            !context.options().no_deps && are_dependencies_changed(&context, &build).await?;
+        let always = (build.rule().map(Rule::always).unwrap_or_default()
+            && context.requested_builds().contains(&build.id()))
+            || context.forced_builds().contains(&build.id());
         let timestamp_hash =
             hash::calculate_timestamp_hash(&context, &build, &file_inputs, &phony_inputs).await?;
 
         if outputs_exist
+            && !always
+            && !dependencies_changed
             && Some(timestamp_hash)
                 == context
                     .application()
@@ -192,6 +792,8 @@ async fn spawn_build(context: Arc<RunContext>, build: Arc<Build>) -> Result<(),
             hash::calculate_content_hash(&context, &build, &file_inputs, &phony_inputs).await?;
 
         if outputs_exist
+            && !always
+            && !dependencies_changed
             && Some(content_hash)
                 == context
                     .application()
@@ -199,20 +801,172 @@ async fn spawn_build(context: Arc<RunContext>, build: Arc<Build>) -> Result<(),
                     .get_hash(HashType::Content, build.id())?
         {
             return Ok(());
-        } else if let Some(rule) = build.rule() {
-            try_join_all(
-                build
-                    .outputs()
-                    .iter()
-                    .chain(build.implicit_outputs())
-                    .map(|path| prepare_directory(&context, path.as_ref())),
-            )
-            .await?;
-
-            run_rule(&context, rule).await?;
+        } else if context.options().dry_run {
+            if let Some(rule) = build.rule() {
+                run_dry(&context, &build, rule).await?;
+            }
 
-            for output in build.outputs() {
-                context.application().database().set_output(output)?;
+            // Dry runs never record builds even in writable databases, e.g. of
+            // daemons.
+            return Ok(());
+        } else if let Some(rule) = build.rule() {
+            if !context.options().touch {
+                if rule.mkdir() && !rule.no_output() {
+                    try_join_all(
+                        build
+                            .outputs()
+                            .iter()
+                            .chain(build.implicit_outputs())
+                            .map(|path| prepare_directory(&context, path.as_ref())),
+                    )
+                    .await?;
+                }
+
+                // Builds in pools wait for their slots before jobs start.
+                let _permit = if let Some(pool) = rule.pool() {
+                    Some(
+                        context.pools()[pool.name()]
+                            .acquire()
+                            .await
+                            .map_err(|_| CommandError::SemaphoreClosed)?,
+                    )
+                } else {
+                    None
+                };
+                let _rule_permit = if let Some(pool) = context.rule_pools().get(rule.name()) {
+                    Some(
+                        pool.acquire()
+                            .await
+                            .map_err(|_| CommandError::SemaphoreClosed)?,
+                    )
+                } else {
+                    None
+                };
+                let _job_permit = if let Some(scheduler) = context.scheduler() {
+                    Some(scheduler.acquire(rule.name(), build.id()).await)
+                } else {
+                    None
+                };
+
+                pause::wait(&context).await;
+                memory::wait(&context).await;
+                context.watchdog().start(&build);
+                send_event(
+                    &context,
+                    BuildEvent::JobStarted {
+                        output: build.outputs()[0].to_string(),
+                        description: rule.description().map(|description| {
+                            expand_temporary_directory(&context, description).into()
+                        }),
+                    },
+                );
+                if context.options().detect_modified_inputs {
+                    modified_inputs::record(&context, &file_inputs).await;
+                }
+
+                dedup::unlink(&context, &build).await?;
+
+                let snapshot = if context.options().detect_undeclared_outputs {
+                    Some(undeclared_outputs::snapshot(&context, &build).await?)
+                } else {
+                    None
+                };
+                let start_time = Instant::now();
+                context
+                    .application()
+                    .database()
+                    .set_running_build(build.id())?;
+                let result = match run_rule(&context, &build, rule).await {
+                    Ok(value) if !context.options().allow_missing_outputs => {
+                        check_outputs_creation(&context, &build)
+                            .await
+                            .map(|_| value)
+                    }
+                    result => result,
+                };
+                context
+                    .application()
+                    .database()
+                    .remove_running_build(build.id())?;
+                context
+                    .timings()
+                    .insert(build.id(), (start_time, Instant::now()));
+
+                if let Ok((_, Some(usage))) = &result {
+                    context.resource_usages().insert(build.id(), *usage);
+                }
+
+                context.watchdog().finish(&build);
+                send_event(
+                    &context,
+                    BuildEvent::JobFinished {
+                        output: build.outputs()[0].to_string(),
+                        success: result.is_ok(),
+                    },
+                );
+
+                if result.is_err() {
+                    record_failure(&context);
+                }
+
+                let (dependencies, _) = result?;
+
+                if let Some(limit) = context.options().max_output_bytes {
+                    output_size::record(&context, &build, limit).await?;
+                }
+
+                if let Some(snapshot) = &snapshot {
+                    undeclared_outputs::report(&context, &build, snapshot).await?;
+                }
+
+                // Rebuild checks do not depend on modification times of outputs.
+                if let Some(time) = rule.pinned_modified_time().filter(|_| !rule.no_output()) {
+                    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+                        context
+                            .application()
+                            .file_system()
+                            .set_modified_time(
+                                output.as_ref().as_ref(),
+                                UNIX_EPOCH + Duration::from_secs(time),
+                            )
+                            .await?;
+                    }
+                }
+
+                if !context.options().no_deps_record {
+                    if let Some(depfile) = rule.depfile() {
+                        record_dependencies(
+                            &context,
+                            &build,
+                            &read_depfile(&context, depfile).await?,
+                        )
+                        .await?;
+                    } else if rule.msvc_deps_prefix().is_some() {
+                        record_dependencies(&context, &build, &dependencies).await?;
+                    }
+                }
+
+                if let Some(path) = &context.options().provenance_file {
+                    provenance::record(
+                        &context,
+                        path,
+                        &build,
+                        &expand_commands(&context, rule),
+                        &file_inputs,
+                    )
+                    .await?;
+                }
+
+                if context.options().dedup_outputs {
+                    dedup::deduplicate(&context, &build).await;
+                }
+
+                output_mode::record(&context, &build).await;
+                context.rebuilt_builds().insert(build.id());
+            }
+
+            for output in build.outputs().iter().filter(|_| !rule.no_output()) {
+                context.application().database().set_output(output)?;
 
                 if let Some(source) = context.configuration().source_map().get(output) {
                     context
@@ -238,6 +992,296 @@ async fn spawn_build(context: Arc<RunContext>, build: Arc<Build>) -> Result<(),
     .await?
 }
 
+// Progress is shown as counts of rule builds finished and triggered so far
+// including up-to-date ones. Titles are written in background not to block
+// builds while a console is locked by commands.
+fn report_progress(context: Arc<RunContext>) {
+    context.finished_count().fetch_add(1, Ordering::SeqCst);
+
+    spawn(async move {
+        let mut console = context.application().console().lock().await;
+        // Counts are read after locking as updates can be reordered.
+        let finished = context.finished_count().load(Ordering::SeqCst);
+        let triggered = context.triggered_count().load(Ordering::SeqCst);
+
+        // Titles are cosmetic and their failures are ignored.
+        let _ = console
+            .set_title(&format!("[{finished}/{triggered}] turtle"))
+            .await;
+    });
+}
+
+// Inputs are partitioned into files and outputs of phony builds. Outputs of
+// side-effect-only builds are not files and go with the latter.
+fn partition_inputs<'a>(
+    context: &RunContext,
+    inputs: impl IntoIterator<Item = &'a Arc<str>>,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    inputs
+        .into_iter()
+        .map(|string| string.as_ref())
+        .partition::<Vec<_>, _>(|&input| {
+            if let Some(build) = context.configuration().outputs().get(input) {
+                build.rule().is_some_and(|rule| !rule.no_output())
+            } else {
+                true
+            }
+        })
+}
+
+// Shuffle builds deterministically by a seed to reveal undeclared dependencies
+// which happen to be built earlier in a stable order. Hashes are stable across
+// Turtle builds so that seeds reproduce orders anywhere.
+fn shuffle<T>(context: &RunContext, items: &mut [T], key: impl Fn(&T) -> &str) {
+    if let Some(seed) = context.options().shuffle {
+        items.sort_by_cached_key(|item| {
+            fnv::hash(&[&seed.to_le_bytes(), key(item).as_bytes()].concat())
+        });
+    }
+}
+
+async fn write_description(
+    context: &RunContext,
+    console: &mut (dyn Console + Send + Sync),
+    rule: &Rule,
+) -> Result<(), ApplicationError> {
+    if let Some(description) = rule.description().filter(|_| {
+        context.event_handler().is_none()
+            && context.options().output_mode == OutputMode::Plain
+            && context.options().error_format == ErrorFormat::Full
+    }) {
+        let description = expand_temporary_directory(context, description);
+        let description = status::format_description(
+            &description,
+            rule.status()
+                .map(|status| expand_temporary_directory(context, status))
+                .as_deref(),
+            context.options().color,
+        );
+        let description = if let Some(width) = console.width() {
+            status::elide_description(&description, width)
+        } else {
+            description
+        };
+
+        console.write_stderr(description.as_bytes()).await?;
+        console.write_stderr(b"\n").await?;
+    }
+
+    Ok(())
+}
+
+// Dry runs show descriptions of rules as if their commands succeeded.
+async fn run_dry(context: &RunContext, build: &Build, rule: &Rule) -> Result<(), ApplicationError> {
+    write_description(
+        context,
+        &mut **context.application().console().lock().await,
+        rule,
+    )
+    .await?;
+    output_mode::record(context, build).await;
+    context.rebuilt_builds().insert(build.id());
+
+    Ok(())
+}
+
+// Outputs are passed through as they are unless their encoding is specified.
+fn decode_output<'a>(encoding: Option<&'static Encoding>, output: &'a [u8]) -> Cow<'a, [u8]> {
+    match encoding.map(|encoding| encoding.decode(output).0) {
+        None | Some(Cow::Borrowed(_)) => Cow::Borrowed(output),
+        Some(Cow::Owned(string)) => Cow::Owned(string.into_bytes()),
+    }
+}
+
+fn send_event(context: &RunContext, event: BuildEvent) {
+    if let Some(handler) = context.event_handler() {
+        handler(event);
+    }
+}
+
+async fn are_dependencies_changed(
+    context: &RunContext,
+    build: &Build,
+) -> Result<bool, ApplicationError> {
+    let dependencies = context
+        .application()
+        .database()
+        .get_dependencies(build.id())?;
+
+    Ok(!dependencies.is_empty()
+        && Some(hash::calculate_dependency_hash(context, &dependencies).await?)
+            != context
+                .application()
+                .database()
+                .get_hash(HashType::Dependency, build.id())?)
+}
+
+fn expand_temporary_directory<'a>(context: &RunContext, string: &'a str) -> Cow<'a, str> {
+    if string.contains(TEMPORARY_DIRECTORY_PLACEHOLDER) {
+        string
+            .replace(
+                TEMPORARY_DIRECTORY_PLACEHOLDER,
+                &context.temporary_directory().display().to_string(),
+            )
+            .into()
+    } else {
+        string.into()
+    }
+}
+
+// Commands are passed to command runners exactly as expanded here.
+fn expand_commands(context: &RunContext, rule: &Rule) -> Vec<String> {
+    rule.commands()
+        .iter()
+        .map(|command| expand_temporary_directory(context, command).into_owned())
+        .collect()
+}
+
+// Missing depfiles are regarded as empty.
+async fn read_depfile(
+    context: &RunContext,
+    depfile: &str,
+) -> Result<Vec<String>, ApplicationError> {
+    let file_system = context.application().file_system();
+
+    Ok(if file_system.metadata(depfile.as_ref()).await.is_ok() {
+        let mut source = String::new();
+        file_system
+            .read_file_to_string(depfile.as_ref(), &mut source)
+            .await?;
+
+        parse_depfile(&source)
+    } else {
+        vec![]
+    })
+}
+
+async fn record_dependencies(
+    context: &RunContext,
+    build: &Build,
+    dependencies: &[String],
+) -> Result<(), ApplicationError> {
+    context.application().database().set_hash(
+        HashType::Dependency,
+        build.id(),
+        hash::calculate_dependency_hash(context, dependencies).await?,
+    )?;
+    context
+        .application()
+        .database()
+        .set_dependencies(build.id(), dependencies)
+        .await?;
+
+    Ok(())
+}
+
+// Unless stopping on the first failure, wait for all builds so that
+// independent ones can finish.
+// Validations are triggered by other builds but not awaited by them. So this
+// awaits every triggered build until no more build is triggered.
+async fn join_requested_builds(
+    context: &RunContext,
+    futures: Vec<BuildFuture>,
+) -> Result<(), ApplicationError> {
+    join_builds(context, futures).await?;
+
+    let mut joined_builds = HashSet::new();
+
+    loop {
+        let futures = context
+            .build_futures()
+            .iter()
+            .filter(|entry| joined_builds.insert(*entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect::<Vec<_>>();
+
+        if futures.is_empty() {
+            return Ok(());
+        }
+
+        join_builds(context, futures).await?;
+    }
+}
+
+async fn join_builds(
+    context: &RunContext,
+    futures: Vec<BuildFuture>,
+) -> Result<(), ApplicationError> {
+    if context.options().shuffle.is_some() {
+        // Run builds one by one so that the same seed reproduces the same order.
+        let mut result = Ok(());
+
+        for future in futures {
+            if let Err(error) = future.await {
+                if context.options().keep_going == 1 {
+                    return Err(error);
+                } else if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+
+        result?;
+    } else if context.options().keep_going == 1 {
+        try_join_all(futures).await?;
+    } else {
+        join_all(futures)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+
+    Ok(())
+}
+
+// Recipes of builds are hashed on every rebuild check anyway. This only tells
+// why rebuilds cascade after edits of build files.
+async fn check_configuration_hash(context: &RunContext) -> Result<(), ApplicationError> {
+    let database = context.application().database();
+    let hash = hash::calculate_configuration_hash(context.configuration());
+
+    if database
+        .get_configuration_hash()?
+        .is_some_and(|previous_hash| previous_hash != hash)
+    {
+        let mut console = context.application().console().lock().await;
+
+        log!(
+            console,
+            "note: build files changed since the last build; rebuilding outputs whose recipes changed"
+        );
+    }
+
+    database.set_configuration_hash(hash)?;
+
+    Ok(())
+}
+
+// Builds stop as soon as a focused build finishes. They fail if any builds
+// failed so far.
+async fn join_focused_build(
+    context: &RunContext,
+    build: Option<&Build>,
+) -> Result<(), ApplicationError> {
+    if let Some(build) = build {
+        let future = context.build_futures().get(&build.id()).unwrap().clone();
+
+        future.await?;
+    }
+
+    if context.failure_count().load(Ordering::SeqCst) > 0 {
+        return Err(ApplicationError::Build);
+    }
+
+    Ok(())
+}
+
+fn record_failure(context: &RunContext) {
+    if context.failure_count().fetch_add(1, Ordering::SeqCst) + 1 == context.options().keep_going {
+        context.failure_limit().notify_one();
+    }
+}
+
 async fn build_input(
     context: Arc<RunContext>,
     input: &str,
@@ -256,6 +1300,53 @@ async fn build_input(
     )
 }
 
+// Outputs of side-effect-only builds are regarded as existent.
+async fn check_outputs_existence(context: &RunContext, build: &Build) -> bool {
+    build.rule().is_some_and(Rule::no_output)
+        || try_join_all(
+            build
+                .outputs()
+                .iter()
+                .chain(build.implicit_outputs())
+                .map(|path| check_file_existence(context, path)),
+        )
+        .await
+        .is_ok()
+}
+
+// Commands succeeding without creating outputs are regarded as failures
+// because their dependents would fail confusingly later.
+async fn check_outputs_creation(
+    context: &RunContext,
+    build: &Build,
+) -> Result<(), ApplicationError> {
+    if build.rule().is_some_and(Rule::no_output) {
+        return Ok(());
+    }
+
+    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+        if context
+            .application()
+            .file_system()
+            .metadata(output.as_ref().as_ref())
+            .await
+            .is_err()
+        {
+            let mut console = context.application().console().lock().await;
+
+            log!(
+                console,
+                "output \"{}\" not created by its command (use --allow-missing-outputs to ignore)",
+                output
+            );
+
+            return Err(ApplicationError::Build);
+        }
+    }
+
+    Ok(())
+}
+
 async fn check_file_existence(context: &RunContext, path: &str) -> Result<(), ApplicationError> {
     if context
         .application()
@@ -291,38 +1382,132 @@ async fn prepare_directory(
     Ok(())
 }
 
-async fn run_rule(context: &RunContext, rule: &Rule) -> Result<(), ApplicationError> {
-    let ((output, duration), mut console) = try_join!(
-        async {
-            let start_time = Instant::now();
-            let output = context
-                .application()
-                .command_runner()
-                .run(rule.command())
-                .await?;
+// Returns dependencies in command outputs for `deps = msvc` and resource usage
+// of commands.
+async fn run_rule(
+    context: &RunContext,
+    build: &Build,
+    rule: &Rule,
+) -> Result<(Vec<String>, Option<ResourceUsage>), ApplicationError> {
+    if let Some(file) = rule.response_file() {
+        prepare_directory(context, file.path()).await?;
+        context
+            .application()
+            .file_system()
+            .write_file(
+                file.path().as_ref(),
+                expand_temporary_directory(context, file.content()).as_bytes(),
+            )
+            .await?;
+    }
 
-            Ok::<_, ApplicationError>((output, Instant::now() - start_time))
-        },
-        async {
-            let mut console = context.application().console().lock().await;
+    let commands = expand_commands(context, rule);
+    let mut command = pin!(async {
+        let start_time = Instant::now();
+        let result = context
+            .application()
+            .command_runner()
+            .run(&commands, rule.environment(), rule.shell())
+            .await;
 
-            if let Some(description) = rule.description() {
-                console.write_stderr(description.as_bytes()).await?;
-                console.write_stderr(b"\n").await?;
-            }
+        (result, Instant::now() - start_time)
+    });
+    let mut console = pin!(async {
+        let mut console = context.application().console().lock().await;
 
-            debug!(context, console, "command: {}", rule.command());
+        write_description(context, &mut **console, rule).await?;
 
-            Ok(console)
+        for command in &commands {
+            debug!(context, console, "command: {}", command);
         }
-    )?;
+
+        Ok::<_, ApplicationError>(console)
+    });
+
+    let ((result, duration), mut console) = select! {
+        output = &mut command => (output, console.await?),
+        console = &mut console => {
+            let mut console = console?;
+
+            (watchdog::wait(context, &mut **console, command).await?, console)
+        }
+    };
 
     profile!(context, console, "duration: {}ms", duration.as_millis());
 
-    console.write_stdout(&output.stdout).await?;
-    console.write_stderr(&output.stderr).await?;
+    let usage = result.as_ref().ok().and_then(|(_, usage)| *usage);
+
+    if let Some(usage) = usage {
+        profile!(
+            context,
+            console,
+            "cpu time: {}ms, max rss: {}KiB",
+            usage.cpu_time.as_millis(),
+            usage.max_rss / 1024
+        );
+    }
+
+    let output = match &result {
+        Ok((output, _)) => output,
+        Err(error) => error.output().ok_or_else(|| error.clone())?,
+    };
+    // Headers are not in formats of locations of diagnostics in quickfix lists.
+    if result.is_err() && context.options().error_format == ErrorFormat::Short {
+        console
+            .write_stderr(format!("FAILED: {}\n", build.outputs().join(" ")).as_bytes())
+            .await?;
+    }
+
+    let output_stdout = decode_output(rule.output_encoding(), &output.stdout);
+    let output_stderr = decode_output(rule.output_encoding(), &output.stderr);
+
+    let mut dependencies = vec![];
+
+    if let Some(prefix) = rule.msvc_deps_prefix() {
+        let mut stdout = vec![];
+
+        for line in output_stdout.split_inclusive(|&byte| byte == b'\n') {
+            if let Some(path) = line.strip_prefix(prefix.as_bytes()) {
+                dependencies.push(String::from_utf8_lossy(path).trim().into());
+            } else {
+                stdout.extend(line);
+            }
+        }
+
+        console.write_stdout(&stdout).await?;
+
+        if is_truncated(&output.stdout) {
+            log!(
+                console,
+                "warning: dependencies of \"{}\" may be incomplete as its stdout was truncated; consider raising --max-capture",
+                build.outputs()[0]
+            );
+        }
+    } else {
+        console.write_stdout(&output_stdout).await?;
+    }
+
+    if output_stderr.is_empty()
+        || !context.options().collapse_errors
+        || error_output::record(context, &output_stderr)
+    {
+        console.write_stderr(&output_stderr).await?;
+    }
+
+    // Response files are kept on failures for debugging like Ninja.
+    if let Some(file) = rule.response_file() {
+        if context.options().keep_response_files {
+            log!(console, "response file kept: {}", file.path());
+        } else if result.is_ok() {
+            context
+                .application()
+                .file_system()
+                .remove_file(file.path().as_ref())
+                .await?;
+        }
+    }
 
-    if !output.status.success() {
+    if result.is_err() {
         debug!(
             context,
             console,
@@ -337,7 +1522,7 @@ async fn run_rule(context: &RunContext, rule: &Rule) -> Result<(), ApplicationEr
         return Err(ApplicationError::Build);
     }
 
-    Ok(())
+    Ok((dependencies, usage))
 }
 
 fn map_build_graph_error(context: &RunContext, error: &BuildGraphError) -> ApplicationError {
@@ -366,3 +1551,2218 @@ fn map_build_graph_error(context: &RunContext, error: &BuildGraphError) -> Appli
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compile::{compile, compile_with_errors},
+        infrastructure::{
+            Console, Database, FakeCommandRunner, OsCommandRunner, OsConsole, OsDatabase,
+            OsFileSystem, ReadOnlyDatabase, RecordingConsole,
+        },
+        parse::parse,
+    };
+    use glob::Pattern;
+    use std::{env, path::PathBuf, time::Duration};
+    use tempfile::{tempdir, TempDir};
+    use tokio::{spawn, time::sleep};
+
+    const DELAY: Duration = Duration::from_millis(100);
+
+    fn compile_configuration(source: &str) -> Arc<Configuration> {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn create_context(command_runner: FakeCommandRunner) -> (Arc<Context>, TempDir) {
+        create_context_with_console(command_runner, OsConsole::new(None))
+    }
+
+    fn create_context_with_console(
+        command_runner: FakeCommandRunner,
+        console: impl Console + Send + Sync + 'static,
+    ) -> (Arc<Context>, TempDir) {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+
+        (
+            Context::new(command_runner, console, database, OsFileSystem::new(1)).into(),
+            directory,
+        )
+    }
+
+    // Commands run in a shell actually and write their outputs.
+    #[cfg(unix)]
+    fn create_shell_context(
+        console: impl Console + Send + Sync + 'static,
+        directory: &Path,
+    ) -> Arc<Context> {
+        let database = OsDatabase::new();
+        database.initialize(directory).unwrap();
+
+        Context::new(
+            OsCommandRunner::new(1, false, vec![], Some(vec!["sh".into(), "-c".into()])),
+            console,
+            database,
+            OsFileSystem::new(1),
+        )
+        .into()
+    }
+
+    fn options(keep_going: usize) -> Options {
+        Options {
+            color: false,
+            debug: false,
+            profile: None,
+            profile_file: None,
+            time_report: false,
+            detect_undeclared_outputs: false,
+            detect_modified_inputs: false,
+            strict_inputs: false,
+            keep_response_files: false,
+            collapse_errors: false,
+            provenance_file: None,
+            terminal_title: false,
+            max_memory: None,
+            max_output_bytes: None,
+            rule_job_limits: Default::default(),
+            dedup_outputs: false,
+            temporary_directory: None,
+            schedule: Schedule::Fifo,
+            job_limit: 1,
+            output_mode: OutputMode::Plain,
+            lazy: false,
+            post_build: None,
+            error_format: Default::default(),
+            pause_on_signals: false,
+            dry_run: false,
+            touch: false,
+            stall_timeout: Duration::from_secs(60),
+            abort_on_stall: false,
+            keep_going,
+            keep_going_until: None,
+            no_deps: false,
+            no_deps_record: false,
+            missing_ok: false,
+            // Fake commands do not create outputs.
+            allow_missing_outputs: true,
+            shard: None,
+            subdirectory: None,
+            excluded_outputs: vec![],
+            tags: vec![],
+            forced_outputs: vec![],
+            force_dependencies: false,
+            shuffle: None,
+        }
+    }
+
+    const FAILING_CONFIGURATION: &str = "
+rule fail
+  command = fail
+rule slow
+  command = slow
+rule next
+  command = next
+build foo: fail
+build bar: slow
+build baz: next || bar
+build all: phony foo baz
+";
+
+    #[tokio::test]
+    async fn stop_on_first_error() {
+        let runner = FakeCommandRunner::new(&["fail"], DELAY);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(FAILING_CONFIGURATION),
+                &["all".into()],
+                options(1),
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+        assert!(!commands.lock().unwrap().contains(&"next".into()));
+    }
+
+    #[tokio::test]
+    async fn run_post_build_command() {
+        for (source, result, success, build_count, failure_count) in [
+            (
+                "rule echo\n  command = echo\nbuild foo: echo\n",
+                Ok(()),
+                "success",
+                "1",
+                "0",
+            ),
+            (
+                "rule fail\n  command = fail\nbuild foo: fail\n",
+                Err(ApplicationError::Build),
+                "failure",
+                "1",
+                "1",
+            ),
+        ] {
+            let runner = FakeCommandRunner::new(&["fail"], Duration::ZERO);
+            let commands = runner.commands();
+            let environments = runner.environments();
+            let (context, _directory) = create_context(runner);
+
+            assert_eq!(
+                run(
+                    &context,
+                    compile_configuration(source),
+                    &[],
+                    Options {
+                        post_build: Some("notify".into()),
+                        ..options(1)
+                    },
+                )
+                .await,
+                result
+            );
+            assert_eq!(commands.lock().unwrap().last(), Some(&"notify".into()));
+            assert_eq!(
+                environments.lock().unwrap().last(),
+                Some(&vec![
+                    ("TURTLE_BUILD_RESULT".into(), success.into()),
+                    ("TURTLE_BUILD_COUNT".into(), build_count.into()),
+                    ("TURTLE_FAILURE_COUNT".into(), failure_count.into()),
+                ])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn keep_going_without_stop() {
+        let runner = FakeCommandRunner::new(&["fail"], DELAY);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(FAILING_CONFIGURATION),
+                &["all".into()],
+                options(0),
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+        assert!(commands.lock().unwrap().contains(&"next".into()));
+    }
+
+    #[tokio::test]
+    async fn keep_going_until_focused_build() {
+        let runner = FakeCommandRunner::new(&["fail"], DELAY);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(
+                    "
+rule fail
+  command = fail
+rule cc
+  command = cc $out
+build foo: fail
+build a: cc
+build b: cc || a
+build c: cc || b
+build focus: cc || a
+build all: phony foo c focus
+"
+                ),
+                &["all".into()],
+                Options {
+                    keep_going_until: Some("focus".into()),
+                    ..options(1)
+                },
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+
+        let commands = commands.lock().unwrap();
+
+        assert!(commands.contains(&"cc focus".into()));
+        assert!(!commands.contains(&"cc c".into()));
+    }
+
+    #[tokio::test]
+    async fn reproduce_shuffled_order() {
+        async fn run_shuffled(seed: u64) -> Vec<String> {
+            let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+            let commands = runner.commands();
+            let (context, _directory) = create_context(runner);
+
+            run(
+                &context,
+                compile_configuration(
+                    "rule echo\n  command = echo $out\nbuild a: echo\nbuild b: echo\nbuild c: echo\nbuild d: echo\n",
+                ),
+                &["a".into(), "b".into(), "c".into(), "d".into()],
+                Options {
+                    shuffle: Some(seed),
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            let order = commands.lock().unwrap().clone();
+
+            order
+        }
+
+        assert_eq!(
+            run_shuffled(42).await,
+            ["echo d", "echo c", "echo b", "echo a"]
+        );
+        assert_eq!(
+            run_shuffled(42).await,
+            ["echo d", "echo c", "echo b", "echo a"]
+        );
+        assert_eq!(
+            run_shuffled(7).await,
+            ["echo b", "echo c", "echo a", "echo d"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_builds_in_subdirectory() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+        let configuration = compile_configuration(
+            "rule echo\n  command = echo $out\nbuild lib/util.o: echo\nbuild src/net/socket.o: echo || lib/util.o\nbuild src/net/http.o: echo\nbuild src/ui/window.o: echo\nbuild all: phony src/net/socket.o src/net/http.o src/ui/window.o\ndefault all\n",
+        );
+        let options = Options {
+            subdirectory: Some("src/net".into()),
+            ..options(1)
+        };
+
+        run(&context, configuration.clone(), &[], options.clone())
+            .await
+            .unwrap();
+
+        let mut commands = commands.lock().unwrap().clone();
+        commands.sort();
+
+        assert_eq!(
+            commands,
+            vec![
+                "echo lib/util.o",
+                "echo src/net/http.o",
+                "echo src/net/socket.o"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_no_builds_outside_subdirectory() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        run(
+            &context,
+            compile_configuration(
+                "rule echo\n  command = echo $out\nbuild src/net/http.o: echo\nbuild src/ui/window.o: echo\n",
+            ),
+            &["src/ui/window.o".into()],
+            Options {
+                subdirectory: Some("src/net".into()),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(commands.lock().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn report_group_summary() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+        let path = |name: &str| directory.path().join(name).display().to_string();
+
+        for name in ["foo", "bar"] {
+            std::fs::write(path(name), name).unwrap();
+        }
+
+        run(
+            &context,
+            compile_configuration(&format!(
+                "rule echo\n  command = echo $out\n  description = echo $out\nbuild {}: echo\nbuild {}: echo\nbuild all: phony {} {}\n",
+                path("foo"),
+                path("bar"),
+                path("foo"),
+                path("bar"),
+            )),
+            &["all".into()],
+            options(1),
+        )
+        .await
+        .unwrap();
+
+        let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+        assert!(stderr.contains(&format!("echo {}\n", path("foo"))));
+        assert!(stderr.contains(&format!("echo {}\n", path("bar"))));
+        assert!(stderr.ends_with("turtle: all: 2 of 2 rebuilt\n"));
+    }
+
+    #[tokio::test]
+    async fn notify_build_completion() {
+        let console = RecordingConsole::new();
+        let notifications = console.notifications();
+        let (context, _directory) =
+            create_context_with_console(FakeCommandRunner::new(&["fail"], Duration::ZERO), console);
+
+        run(
+            &context,
+            compile_configuration("rule echo\n  command = echo $out\nbuild foo: echo\n"),
+            &[],
+            Options {
+                terminal_title: true,
+                max_memory: None,
+                rule_job_limits: Default::default(),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+        run(
+            &context,
+            compile_configuration("rule fail\n  command = fail\nbuild bar: fail\n"),
+            &[],
+            Options {
+                terminal_title: true,
+                max_memory: None,
+                rule_job_limits: Default::default(),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            *notifications.lock().unwrap(),
+            vec!["turtle: build succeeded", "turtle: build failed"]
+        );
+    }
+
+    #[tokio::test]
+    async fn set_no_terminal_title_if_disabled() {
+        let console = RecordingConsole::new();
+        let titles = console.titles();
+        let notifications = console.notifications();
+        let (context, _directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+
+        run(
+            &context,
+            compile_configuration("rule echo\n  command = echo $out\nbuild foo: echo\n"),
+            &[],
+            options(1),
+        )
+        .await
+        .unwrap();
+
+        assert!(titles.lock().unwrap().is_empty());
+        assert!(notifications.lock().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    mod verify_deterministic {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[tokio::test]
+        async fn detect_nondeterministic_output() {
+            let directory = tempdir().unwrap();
+            let console = RecordingConsole::new();
+            let stderr = console.stderr();
+            let context = create_shell_context(console, directory.path());
+            let path = |name: &str| directory.path().join(name).display().to_string();
+
+            // A counter outside of a build graph changes like timestamps.
+            assert_eq!(
+                verify_deterministic(
+                    &context,
+                    compile_configuration(&format!(
+                        "rule stamp\n  command = echo x >> {} && cat {} > $out\nrule echo\n  command = echo hello > $out\nbuild {}: stamp\nbuild {}: echo\nbuild all: phony {} {}\n",
+                        path("counter"),
+                        path("counter"),
+                        path("foo"),
+                        path("bar"),
+                        path("foo"),
+                        path("bar"),
+                    )),
+                    &["all".into()],
+                    options(0),
+                )
+                .await,
+                Err(ApplicationError::Other(
+                    "1 outputs are not deterministic".into()
+                ))
+            );
+
+            let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+            assert!(stderr.contains(&format!("output \"{}\" differs", path("foo"))));
+            assert!(!stderr.contains(&format!("output \"{}\" differs", path("bar"))));
+        }
+
+        #[tokio::test]
+        async fn verify_deterministic_output() {
+            let directory = tempdir().unwrap();
+            let context = create_shell_context(RecordingConsole::new(), directory.path());
+            let path = directory.path().join("foo").display().to_string();
+
+            verify_deterministic(
+                &context,
+                compile_configuration(&format!(
+                    "rule echo\n  command = echo hello > $out\nbuild {path}: echo\n"
+                )),
+                &[],
+                options(0),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read_to_string(path).unwrap(), "hello\n");
+        }
+    }
+
+    mod dry_run {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use std::{collections::BTreeMap, fs};
+
+        const CONFIGURATION: &str = "rule cc\n  command = cc $in\n  description = cc $out\nrule link\n  command = link $in\n  description = link $out\nbuild {foo.o}: cc {foo.c}\nbuild {bar}: link {foo.o}\n";
+
+        fn create_context(
+            command_runner: FakeCommandRunner,
+            console: RecordingConsole,
+            directory: &Path,
+        ) -> Arc<Context> {
+            let database = ReadOnlyDatabase::new(OsDatabase::new());
+            database.initialize(directory).unwrap();
+
+            Context::new(command_runner, console, database, OsFileSystem::new(1)).into()
+        }
+
+        fn configuration(directory: &Path) -> Arc<Configuration> {
+            let mut source = CONFIGURATION.to_owned();
+
+            for name in ["foo.o", "foo.c", "bar"] {
+                source = source.replace(
+                    &format!("{{{name}}}"),
+                    &directory.join(name).display().to_string(),
+                );
+            }
+
+            compile_configuration(&source)
+        }
+
+        fn read_files(directory: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+            let mut files = BTreeMap::new();
+
+            for entry in fs::read_dir(directory).unwrap() {
+                let path = entry.unwrap().path();
+
+                if path.is_dir() {
+                    files.extend(read_files(&path));
+                } else {
+                    files.insert(path.clone(), fs::read(&path).unwrap());
+                }
+            }
+
+            files
+        }
+
+        #[tokio::test]
+        async fn show_builds_without_running_commands() {
+            let directory = tempdir().unwrap();
+            let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+            let commands = runner.commands();
+            let console = RecordingConsole::new();
+            let stderr = console.stderr();
+            let context = create_context(runner, console, &directory.path().join("database"));
+            fs::write(directory.path().join("foo.c"), "foo").unwrap();
+
+            run(
+                &context,
+                configuration(directory.path()),
+                &[],
+                Options {
+                    dry_run: true,
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(commands.lock().unwrap().len(), 0);
+
+            let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+            assert!(stderr.contains(&format!(
+                "cc {}\n",
+                directory.path().join("foo.o").display()
+            )));
+            assert!(stderr.contains(&format!(
+                "link {}\n",
+                directory.path().join("bar").display()
+            )));
+        }
+
+        #[tokio::test]
+        async fn keep_database_unchanged() {
+            let directory = tempdir().unwrap();
+            let database_directory = directory.path().join("database");
+
+            for name in ["foo.c", "foo.o", "bar"] {
+                fs::write(directory.path().join(name), name).unwrap();
+            }
+
+            {
+                let database = OsDatabase::new();
+                database.initialize(&database_directory).unwrap();
+                let context = Arc::new(Context::new(
+                    FakeCommandRunner::new(&[], Duration::ZERO),
+                    RecordingConsole::new(),
+                    database,
+                    OsFileSystem::new(1),
+                ));
+
+                run(&context, configuration(directory.path()), &[], options(1))
+                    .await
+                    .unwrap();
+            }
+
+            let files = read_files(&database_directory);
+
+            {
+                let context = create_context(
+                    FakeCommandRunner::new(&[], Duration::ZERO),
+                    RecordingConsole::new(),
+                    &database_directory,
+                );
+                fs::write(directory.path().join("foo.c"), "changed").unwrap();
+
+                run(
+                    &context,
+                    configuration(directory.path()),
+                    &[],
+                    Options {
+                        dry_run: true,
+                        ..options(1)
+                    },
+                )
+                .await
+                .unwrap();
+            }
+
+            assert_eq!(read_files(&database_directory), files);
+        }
+
+        // Daemons run dry runs with writable databases.
+        #[tokio::test]
+        async fn rebuild_after_dry_run_with_writable_database() {
+            let directory = tempdir().unwrap();
+            let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+            let commands = runner.commands();
+            let database = OsDatabase::new();
+            database
+                .initialize(&directory.path().join("database"))
+                .unwrap();
+            let context = Arc::new(Context::new(
+                runner,
+                RecordingConsole::new(),
+                database,
+                OsFileSystem::new(1),
+            ));
+
+            for name in ["foo.c", "foo.o", "bar"] {
+                fs::write(directory.path().join(name), name).unwrap();
+            }
+
+            run(&context, configuration(directory.path()), &[], options(1))
+                .await
+                .unwrap();
+            fs::write(directory.path().join("foo.c"), "changed").unwrap();
+            run(
+                &context,
+                configuration(directory.path()),
+                &[],
+                Options {
+                    dry_run: true,
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(commands.lock().unwrap().len(), 2);
+
+            run(&context, configuration(directory.path()), &[], options(1))
+                .await
+                .unwrap();
+
+            // Fake commands leave an intermediate output unchanged.
+            assert_eq!(commands.lock().unwrap().len(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn collapse_repeated_error_outputs() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, _directory) =
+            create_context_with_console(FakeCommandRunner::new(&["fail"], Duration::ZERO), console);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(
+                    "rule fail\n  command = fail\nbuild foo: fail\nbuild bar: fail\nbuild baz: fail\n",
+                ),
+                &[],
+                Options {
+                    collapse_errors: true,
+                    ..options(0)
+                },
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+
+        let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+        assert_eq!(stderr.matches("fail: failed\n").count(), 1);
+        assert!(stderr.ends_with("turtle: fail: failed (repeated 3 times)\n"));
+    }
+
+    #[tokio::test]
+    async fn detect_inputs_modified_during_builds() {
+        for strict in [false, true] {
+            let console = RecordingConsole::new();
+            let stderr = console.stderr();
+            let (context, directory) = create_context_with_console(
+                FakeCommandRunner::new(&[], Duration::from_millis(500)),
+                console,
+            );
+            let input = directory.path().join("foo.c");
+
+            std::fs::write(&input, "").unwrap();
+
+            let modification = spawn({
+                let input = input.clone();
+
+                async move {
+                    sleep(Duration::from_millis(100)).await;
+                    std::fs::File::options()
+                        .write(true)
+                        .open(&input)
+                        .unwrap()
+                        .set_modified(UNIX_EPOCH + Duration::from_secs(42))
+                        .unwrap();
+                }
+            });
+
+            let result = run(
+                &context,
+                compile_configuration(&format!(
+                    "rule cc\n  command = cc $in\nbuild foo.o: cc {}\n",
+                    input.display()
+                )),
+                &[],
+                Options {
+                    detect_modified_inputs: true,
+                    strict_inputs: strict,
+                    ..options(1)
+                },
+            )
+            .await;
+
+            modification.await.unwrap();
+
+            assert_eq!(result.is_err(), strict);
+            assert!(String::from_utf8(stderr.lock().unwrap().clone())
+                .unwrap()
+                .contains(&format!(
+                    "input \"{}\" modified during build",
+                    input.display()
+                )));
+        }
+    }
+
+    #[tokio::test]
+    async fn write_profiles() {
+        for (format, key) in [
+            (ProfileFormat::Json, "output"),
+            (ProfileFormat::Chrome, "name"),
+        ] {
+            let (context, directory) = create_context(FakeCommandRunner::new(&[], Duration::ZERO));
+            let profile_file = directory.path().join(".turtle/profile.json");
+
+            run(
+                &context,
+                compile_configuration(
+                    "rule cc\n  command = cc $out\nbuild foo: cc\nbuild bar: cc || foo\n",
+                ),
+                &[],
+                Options {
+                    profile: Some(format),
+                    profile_file: Some(profile_file.clone()),
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            let profile = serde_json::from_str::<serde_json::Value>(
+                &std::fs::read_to_string(&profile_file).unwrap(),
+            )
+            .unwrap();
+            let events = if format == ProfileFormat::Json {
+                &profile
+            } else {
+                &profile["traceEvents"]
+            };
+
+            assert_eq!(
+                events
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|event| event[key].as_str().unwrap())
+                    .collect::<Vec<_>>(),
+                vec!["foo", "bar"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn show_profile_summary() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, _directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+
+        run(
+            &context,
+            compile_configuration("rule cc\n  command = cc $out\nbuild foo: cc\n"),
+            &[],
+            Options {
+                profile: Some(ProfileFormat::Text),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+        assert!(stderr.contains("turtle: duration: "));
+        assert!(stderr.contains("turtle: profile: 1 builds, "));
+        assert!(stderr.contains("ms foo (cc)\n"));
+    }
+
+    #[tokio::test]
+    async fn record_provenance() {
+        let (context, directory) = create_context(FakeCommandRunner::new(&[], Duration::ZERO));
+        let path = |name: &str| directory.path().join(name).display().to_string();
+        let provenance_file = directory.path().join(".turtle/provenance.jsonl");
+
+        for name in ["foo.c", "foo.o", "foo.d", "bar"] {
+            std::fs::write(path(name), name).unwrap();
+        }
+
+        run(
+            &context,
+            compile_configuration(&format!(
+                "rule cc\n  command = cc $in\nbuild {} | {}: cc {}\nbuild {}: cc {}\n",
+                path("foo.o"),
+                path("foo.d"),
+                path("foo.c"),
+                path("bar"),
+                path("foo.o"),
+            )),
+            &[],
+            Options {
+                provenance_file: Some(provenance_file.clone()),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut entries = std::fs::read_to_string(&provenance_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry["output"].as_str().unwrap().to_owned());
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry["output"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec![path("bar"), path("foo.d"), path("foo.o")]
+        );
+
+        for entry in &entries {
+            assert!(entry["timestamp"].as_u64().is_some());
+        }
+
+        assert_eq!(
+            entries[0]["commands"],
+            serde_json::json!([format!("cc {}", path("foo.o"))])
+        );
+        assert_eq!(entries[0]["hash_algorithm"], "fnv1a64");
+        assert_eq!(entries[0]["inputs"][0]["path"], path("foo.o"));
+        assert_eq!(
+            entries[0]["inputs"][0]["hash"],
+            format!("{:016x}", crate::fnv::hash(b"foo.o"))
+        );
+        assert_eq!(entries[1]["inputs"], entries[2]["inputs"]);
+    }
+
+    #[tokio::test]
+    async fn record_provenance_with_temporary_directories() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let provenance_file = directory.path().join(".turtle/provenance.jsonl");
+
+        run(
+            &context,
+            compile_configuration("rule foo\n  command = foo $tmpdir/$out\nbuild bar: foo\n"),
+            &[],
+            Options {
+                provenance_file: Some(provenance_file.clone()),
+                temporary_directory: Some(directory.path().join("tmp")),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let entry = serde_json::from_str::<serde_json::Value>(
+            &std::fs::read_to_string(&provenance_file).unwrap(),
+        )
+        .unwrap();
+
+        assert!(!entry["commands"][0].as_str().unwrap().contains('\0'));
+        assert_eq!(
+            entry["commands"],
+            serde_json::json!(*commands.lock().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn stop_commands_on_first_failure() {
+        let runner = FakeCommandRunner::new(&["fail"], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(
+                    "rule foo\n  command[0] = first\n  command[1] = fail\n  command[2] = last\nbuild bar: foo\n"
+                ),
+                &[],
+                options(1),
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec!["first".to_string(), "fail".into()]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_builds_in_pool_with_depth_overridden() {
+        for (variables, count) in [(vec![], 1), (vec![("link_jobs".into(), "2".into())], 2)] {
+            let runner = FakeCommandRunner::new(&[], Duration::from_millis(50));
+            let max_running_count = runner.max_running_count();
+            let (context, _directory) = create_context(runner);
+            let path = PathBuf::from("build.ninja");
+
+            run(
+                &context,
+                compile_with_errors(
+                    &[(
+                        path.clone(),
+                        parse(
+                            "link_jobs = 1\npool link\n  depth = $link_jobs\nrule link\n  command = link $out\n  pool = link\nbuild a: link\nbuild b: link\n",
+                        )
+                        .unwrap(),
+                    )]
+                    .into_iter()
+                    .collect(),
+                    &[(path.clone(), Default::default())].into_iter().collect(),
+                    &path,
+                    &variables,
+                    None, Default::default(),
+                )
+                .unwrap()
+                .0
+                .into(),
+                &[],
+                options(1),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(max_running_count.load(Ordering::SeqCst), count);
+        }
+    }
+
+    #[tokio::test]
+    async fn select_builds_by_tags() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        run(
+            &context,
+            compile_configuration(
+                "rule cc\n  command = cc $out\nbuild a: cc\n  tags = ci\nbuild b: cc || c\n  tags = fast ci\nbuild c: cc\nbuild d: cc\n  tags = fast\n",
+            ),
+            &[],
+            Options {
+                tags: vec!["ci".into()],
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut commands = commands.lock().unwrap().clone();
+        commands.sort();
+
+        assert_eq!(
+            commands,
+            vec!["cc a".to_string(), "cc b".into(), "cc c".into()]
+        );
+    }
+
+    #[tokio::test]
+    async fn exclude_outputs() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context_with_console(runner, console);
+
+        run(
+            &context,
+            compile_configuration(
+                "rule cc\n  command = cc $out\nbuild util: cc\nbuild app: cc || util\nbuild app_bench: cc || util\nbuild tool_bench: cc\nbuild all: phony app app_bench tool_bench\n",
+            ),
+            &["all".into()],
+            Options {
+                excluded_outputs: vec![Pattern::new("*_bench").unwrap(), Pattern::new("util").unwrap()],
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut commands = commands.lock().unwrap().clone();
+        commands.sort();
+
+        assert_eq!(commands, vec!["cc app".to_string(), "cc util".into()]);
+        assert!(String::from_utf8(stderr.lock().unwrap().clone())
+            .unwrap()
+            .contains("turtle: warning: building excluded output util required by app\n"));
+    }
+
+    #[tokio::test]
+    async fn interleave_rules_with_fair_schedule() {
+        let runner = FakeCommandRunner::new(&[], Duration::from_millis(10));
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        run(
+            &context,
+            compile_configuration(
+                "rule a\n  command = a $out\nrule b\n  command = b $out\nbuild a1: a\nbuild a2: a\nbuild a3: a\nbuild b1: b\nbuild b2: b\nbuild b3: b\n",
+            ),
+            &["a1", "a2", "a3", "b1", "b2", "b3"].map(String::from),
+            Options {
+                schedule: Schedule::Fair,
+                job_limit: 1,
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let commands = commands.lock().unwrap();
+
+        assert_eq!(commands.len(), 6);
+        assert!(commands
+            .windows(2)
+            .all(|commands| commands[0][..1] != commands[1][..1]));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pause_and_resume_builds_on_signals() {
+        let runner = FakeCommandRunner::new(&[], Duration::from_millis(100));
+        let commands = runner.commands();
+        let shells = runner.shells();
+        let (context, _directory) = create_context(runner);
+        let handle = spawn(async move {
+            run(
+                &context,
+                compile_configuration(
+                    "rule a\n  command = a $out\nbuild a1: a\nbuild a2: a || a1\nbuild a3: a || a2\n",
+                ),
+                &["a3".into()],
+                Options {
+                    pause_on_signals: true,
+                    // Paused builds are not stalled.
+                    stall_timeout: Duration::from_millis(200),
+                    abort_on_stall: true,
+                    allow_missing_outputs: true,
+                    ..options(1)
+                },
+            )
+            .await
+        });
+
+        // Signal handlers are installed before any command starts.
+        while shells.lock().unwrap().is_empty() {
+            sleep(Duration::from_millis(1)).await;
+        }
+
+        // SAFETY: Signals are handled by a build above.
+        unsafe { libc::kill(libc::getpid(), libc::SIGUSR1) };
+        sleep(Duration::from_millis(500)).await;
+
+        assert_eq!(*commands.lock().unwrap(), vec!["a a1".to_string()]);
+
+        // SAFETY: Signals are handled by a build above.
+        unsafe { libc::kill(libc::getpid(), libc::SIGUSR2) };
+        handle.await.unwrap().unwrap();
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec!["a a1".to_string(), "a a2".into(), "a a3".into()]
+        );
+    }
+
+    #[tokio::test]
+    async fn show_failures_in_short_error_format() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, _directory) =
+            create_context_with_console(FakeCommandRunner::new(&["fail"], Duration::ZERO), console);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(
+                    "rule ok\n  command = ok\n  description = OK $out\nrule fail\n  command = fail\n  description = FAIL $out\nbuild foo: ok\nbuild bar: fail\n"
+                ),
+                &["foo".into(), "bar".into()],
+                Options {
+                    error_format: ErrorFormat::Short,
+                    allow_missing_outputs: true,
+                    ..options(0)
+                },
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+
+        assert_eq!(
+            String::from_utf8(stderr.lock().unwrap().clone()).unwrap(),
+            "FAILED: bar\nfail: failed\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn warn_about_pools_deeper_than_job_limit() {
+        for (depth, warned) in [(4, false), (5, true)] {
+            let console = RecordingConsole::new();
+            let stderr = console.stderr();
+            let (context, _directory) =
+                create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+
+            run(
+                &context,
+                compile_configuration(&format!(
+                    "pool a\n  depth = {depth}\nrule a\n  command = a $out\n  pool = a\nbuild a1: a\n"
+                )),
+                &[],
+                Options {
+                    job_limit: 4,
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                String::from_utf8(stderr.lock().unwrap().clone())
+                    .unwrap()
+                    .contains(&format!(
+                        "warning: depth {depth} of pool \"a\" exceeds a job limit of 4; consider aligning them\n"
+                    )),
+                warned
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn bound_jobs_by_total_depth_of_pools() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let runner = FakeCommandRunner::new(&[], Duration::from_millis(50));
+        let max_running_count = runner.max_running_count();
+        let (context, _directory) = create_context_with_console(runner, console);
+
+        run(
+            &context,
+            compile_configuration(
+                "pool a\n  depth = 1\npool b\n  depth = 1\nrule a\n  command = a $out\n  pool = a\nrule b\n  command = b $out\n  pool = b\nbuild a1: a\nbuild a2: a\nbuild b1: b\nbuild b2: b\n",
+            ),
+            &[],
+            Options {
+                job_limit: 8,
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(max_running_count.load(Ordering::SeqCst), 2);
+        assert!(String::from_utf8(stderr.lock().unwrap().clone())
+            .unwrap()
+            .contains(
+                "note: running at most 2 jobs of total depth of pools below a job limit of 8\n"
+            ));
+    }
+
+    #[tokio::test]
+    async fn rebuild_interrupted_build() {
+        let runner = FakeCommandRunner::new(&[], Duration::from_millis(100));
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let input = directory.path().join("foo.c");
+        let output = directory.path().join("foo.o");
+        let configuration = compile_configuration(&format!(
+            "rule cc\n  command = cc\nbuild {}: cc {}\n",
+            output.display(),
+            input.display()
+        ));
+        let id = configuration.outputs()[output.to_str().unwrap()].id();
+        std::fs::write(&input, "foo").unwrap();
+        std::fs::write(&output, "").unwrap();
+
+        let handle = spawn({
+            let context = context.clone();
+            let configuration = configuration.clone();
+
+            async move { run(&context, configuration, &[], options(1)).await }
+        });
+
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(context.database().get_running_builds().unwrap(), vec![id]);
+        handle.await.unwrap().unwrap();
+        assert!(context.database().get_running_builds().unwrap().is_empty());
+
+        // An interruption leaves a record of a running build and its partial
+        // output looking up to date.
+        context.database().set_running_build(id).unwrap();
+        std::fs::write(&output, "partial").unwrap();
+
+        run(&context, configuration, &[], options(1)).await.unwrap();
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec!["cc".to_string(), "cc".into()]
+        );
+        assert!(!output.exists());
+        assert!(context.database().get_running_builds().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn limit_jobs_of_rule() {
+        for (limits, count) in [
+            (vec![], 3),
+            (vec![("link", 2)], 2),
+            (vec![("link", 1)], 1),
+            (vec![("cc", 1)], 3),
+        ] {
+            let runner = FakeCommandRunner::new(&[], Duration::from_millis(50));
+            let max_running_count = runner.max_running_count();
+            let (context, _directory) = create_context(runner);
+
+            run(
+                &context,
+                compile_configuration(
+                    "rule link\n  command = link $out\nbuild a: link\nbuild b: link\nbuild c: link\n",
+                ),
+                &[],
+                Options {
+                    rule_job_limits: limits
+                        .into_iter()
+                        .map(|(rule, count)| (rule.into(), count))
+                        .collect(),
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(max_running_count.load(Ordering::SeqCst), count);
+        }
+    }
+
+    #[tokio::test]
+    async fn report_saturated_pools_on_stall() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, _directory) = create_context_with_console(
+            FakeCommandRunner::new(&[], Duration::from_millis(300)),
+            console,
+        );
+
+        run(
+            &context,
+            compile_configuration(
+                "pool link\n  depth = 1\nrule link\n  command = link $out\n  pool = link\nbuild a: link\nbuild b: link\n",
+            ),
+            &[],
+            Options {
+                stall_timeout: Duration::from_millis(100),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+        assert!(stderr.contains("pool link: 1 of 1 running, 1 waiting\n"));
+    }
+
+    #[tokio::test]
+    async fn report_stall_of_console_build_before_abort() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, _directory) = create_context_with_console(
+            FakeCommandRunner::new(&[], Duration::from_millis(300)),
+            console,
+        );
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(
+                    "rule foo\n  command = foo $out\n  pool = console\nbuild foo: foo\n",
+                ),
+                &[],
+                Options {
+                    stall_timeout: Duration::from_millis(100),
+                    abort_on_stall: true,
+                    ..options(1)
+                },
+            )
+            .await,
+            Err(ApplicationError::Stalled)
+        );
+
+        let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+        assert!(stderr.contains("running foo for 0s\n"), "{stderr}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn deduplicate_identical_outputs() {
+        use std::os::unix::fs::MetadataExt;
+
+        let (context, directory) = create_context(FakeCommandRunner::new(&[], Duration::ZERO));
+        let path = |name: &str| directory.path().join(name).display().to_string();
+
+        for (name, content) in [("foo", "same"), ("bar", "same"), ("baz", "other")] {
+            std::fs::write(path(name), content).unwrap();
+        }
+
+        run(
+            &context,
+            compile_configuration(&format!(
+                "rule echo\n  command = echo $out\nbuild {}: echo\nbuild {}: echo\nbuild {}: echo\n",
+                path("foo"),
+                path("bar"),
+                path("baz"),
+            )),
+            &[],
+            Options {
+                dedup_outputs: true,
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let inode = |name: &str| std::fs::metadata(path(name)).unwrap().ino();
+
+        assert_eq!(inode("foo"), inode("bar"));
+        assert_ne!(inode("foo"), inode("baz"));
+        assert_eq!(std::fs::read_to_string(path("bar")).unwrap(), "same");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unlink_deduplicated_outputs_before_rebuilds() {
+        use std::os::unix::fs::MetadataExt;
+
+        let directory = tempdir().unwrap();
+        let context =
+            create_shell_context(RecordingConsole::new(), &directory.path().join("database"));
+        let path = |name: &str| directory.path().join(name).display().to_string();
+        let configuration = compile_configuration(&format!(
+            "rule cat\n  command = cat $in > $out\nbuild {}: cat {}\nbuild {}: cat {}\n",
+            path("foo"),
+            path("foo.in"),
+            path("bar"),
+            path("bar.in"),
+        ));
+        let options = Options {
+            dedup_outputs: true,
+            ..options(1)
+        };
+
+        for name in ["foo.in", "bar.in"] {
+            std::fs::write(path(name), "same").unwrap();
+        }
+
+        run(&context, configuration.clone(), &[], options.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::metadata(path("foo")).unwrap().ino(),
+            std::fs::metadata(path("bar")).unwrap().ino()
+        );
+
+        std::fs::write(path("foo.in"), "other").unwrap();
+        run(&context, configuration, &[], options).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(path("foo")).unwrap(), "other");
+        assert_eq!(std::fs::read_to_string(path("bar")).unwrap(), "same");
+    }
+
+    #[tokio::test]
+    async fn create_and_remove_temporary_directory() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let root = directory.path().join("tmp");
+        let written = Arc::new(std::sync::Mutex::new(false));
+
+        run_with_event_handler(
+            &context,
+            compile_configuration("rule foo\n  command = foo $tmpdir/$out\nbuild bar: foo\n"),
+            &[],
+            Options {
+                temporary_directory: Some(root.clone()),
+                ..options(1)
+            },
+            {
+                let root = root.clone();
+                let written = written.clone();
+
+                move |event| {
+                    if let BuildEvent::JobStarted { .. } = event {
+                        let directories = std::fs::read_dir(&root)
+                            .unwrap()
+                            .map(|entry| entry.unwrap().path())
+                            .collect::<Vec<_>>();
+
+                        assert_eq!(directories.len(), 1);
+                        std::fs::write(directories[0].join("bar"), "").unwrap();
+                        *written.lock().unwrap() = true;
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        let command = commands.lock().unwrap()[0].clone();
+
+        assert!(*written.lock().unwrap());
+        assert!(command.starts_with(&format!("foo {}", root.join("turtle-").display())));
+        assert!(command.ends_with("/bar"));
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn expand_temporary_directory_in_descriptions() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+        let root = directory.path().join("tmp");
+
+        run(
+            &context,
+            compile_configuration(
+                "rule foo\n  command = foo $tmpdir/$out\n  description = FOO $tmpdir/$out\nbuild bar: foo\n",
+            ),
+            &[],
+            Options {
+                temporary_directory: Some(root.clone()),
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        let stderr = String::from_utf8(stderr.lock().unwrap().clone()).unwrap();
+
+        assert!(!stderr.contains('\0'));
+        assert!(stderr.starts_with(&format!("FOO {}", root.join("turtle-").display())));
+        assert!(stderr.ends_with("/bar\n"));
+    }
+
+    #[test]
+    fn decode_shift_jis_output() {
+        assert_eq!(
+            decode_output(
+                Some(encoding_rs::SHIFT_JIS),
+                b"Note: including file: \x93\xfa\x96\x7b\\foo.h\n"
+            ),
+            "Note: including file: 日本\\foo.h\n".as_bytes()
+        );
+    }
+
+    #[test]
+    fn pass_through_output_without_encoding() {
+        assert_eq!(decode_output(None, b"\x93\xfa"), &b"\x93\xfa"[..]);
+    }
+
+    #[tokio::test]
+    async fn run_always_rule_requested_explicitly() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let foo = directory.path().join("foo").display().to_string();
+        let bar = directory.path().join("bar").display().to_string();
+        std::fs::write(&foo, "").unwrap();
+        std::fs::write(&bar, "").unwrap();
+        let configuration = compile_configuration(&format!(
+            "rule always\n  command = always\n  always = 1\nrule once\n  command = once\nbuild {foo}: always\nbuild {bar}: once\n"
+        ));
+
+        for _ in 0..2 {
+            run(
+                &context,
+                configuration.clone(),
+                &[foo.clone(), bar.clone()],
+                options(1),
+            )
+            .await
+            .unwrap();
+        }
+
+        run(&context, configuration, &[], options(1)).await.unwrap();
+
+        let mut commands = commands.lock().unwrap().clone();
+        commands.sort();
+        assert_eq!(commands, vec!["always", "always", "once"]);
+    }
+
+    #[tokio::test]
+    async fn rebuild_forced_outputs() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let foo = directory.path().join("foo").display().to_string();
+        let bar = directory.path().join("bar").display().to_string();
+        std::fs::write(&foo, "").unwrap();
+        std::fs::write(&bar, "").unwrap();
+        let configuration = compile_configuration(&format!(
+            "rule cc\n  command = cc $out\nbuild {foo}: cc || {bar}\nbuild {bar}: cc\n"
+        ));
+
+        run(&context, configuration.clone(), &[], options(1))
+            .await
+            .unwrap();
+        commands.lock().unwrap().clear();
+
+        for force_dependencies in [false, true] {
+            run(
+                &context,
+                configuration.clone(),
+                &[],
+                Options {
+                    forced_outputs: vec![foo.clone()],
+                    force_dependencies,
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec![
+                format!("cc {foo}"),
+                format!("cc {bar}"),
+                format!("cc {foo}")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_side_effect_builds_on_input_changes() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let input = directory.path().join("input").display().to_string();
+        let configuration = compile_configuration(&format!(
+            "rule deploy\n  command = deploy $in\n  no_output = 1\nrule notify\n  command = notify $in\n  no_output = 1\nbuild deploy: deploy {input}\nbuild notify: notify deploy\n"
+        ));
+
+        for content in ["foo", "foo", "bar"] {
+            std::fs::write(&input, content).unwrap();
+            run(&context, configuration.clone(), &[], options(1))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec![
+                format!("deploy {input}"),
+                "notify deploy".into(),
+                format!("deploy {input}"),
+                "notify deploy".into(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_rules_in_their_shells() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let shells = runner.shells();
+        let (context, _directory) = create_context(runner);
+
+        run(
+            &context,
+            compile_configuration(
+                "shell.bash = /bin/bash -c\nshell.sh = /bin/sh -ec\nrule foo\n  command = foo $out\n  shell = bash\nrule bar\n  command = bar $out\n  shell = sh\nrule baz\n  command = baz $out\nbuild foo: foo\nbuild bar: bar || foo\nbuild baz: baz || bar\n",
+            ),
+            &[],
+            options(1),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *shells.lock().unwrap(),
+            vec![
+                Some(vec!["/bin/bash".into(), "-c".into()]),
+                Some(vec!["/bin/sh".into(), "-ec".into()]),
+                None
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pin_modified_times_of_outputs() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let foo = directory.path().join("foo").display().to_string();
+        std::fs::write(&foo, "").unwrap();
+        let configuration = compile_configuration(&format!(
+            "rule cc\n  command = cc $out\n  pin_mtime = 1000000000\nbuild {foo}: cc\n"
+        ));
+
+        for _ in 0..2 {
+            run(&context, configuration.clone(), &[], options(1))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            std::fs::metadata(&foo).unwrap().modified().unwrap(),
+            UNIX_EPOCH + Duration::from_secs(1000000000)
+        );
+        assert_eq!(*commands.lock().unwrap(), vec![format!("cc {foo}")]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rebuild_consumers_of_pinned_outputs() {
+        let directory = tempdir().unwrap();
+        let context =
+            create_shell_context(RecordingConsole::new(), &directory.path().join("database"));
+        let path = |name: &str| directory.path().join(name).display().to_string();
+        let configuration = compile_configuration(&format!(
+            "rule pin\n  command = cp $in $out\n  pin_mtime = 1000\nrule cp\n  command = cp $in $out\nbuild {}: pin {}\nbuild {}: cp {}\n",
+            path("mid"),
+            path("in"),
+            path("out"),
+            path("mid"),
+        ));
+
+        for content in ["1", "1", "2", "2"] {
+            std::fs::write(path("in"), content).unwrap();
+
+            run(&context, configuration.clone(), &[], options(1))
+                .await
+                .unwrap();
+
+            assert_eq!(std::fs::read_to_string(path("out")).unwrap(), content);
+        }
+    }
+
+    #[tokio::test]
+    async fn render_builds_in_output_modes() {
+        for (mode, expected) in [
+            (
+                OutputMode::Plain,
+                "compile out/foo.o\ncompile out/bar.o\nlink app\n",
+            ),
+            (OutputMode::Tree, "app\nout/\n  bar.o\n  foo.o\n"),
+            (
+                OutputMode::Grouped,
+                "cc (2)\n  out/bar.o\n  out/foo.o\nlink (1)\n  app\n",
+            ),
+        ] {
+            let console = RecordingConsole::new();
+            let stderr = console.stderr();
+            let (context, _directory) =
+                create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+
+            run(
+                &context,
+                compile_configuration(
+                    "rule cc\n  command = cc $out\n  description = compile $out\nrule link\n  command = link $out\n  description = link $out\nbuild out/bar.o: cc || out/foo.o\nbuild out/foo.o: cc\nbuild app: link || out/bar.o\n",
+                ),
+                &[],
+                Options {
+                    output_mode: mode,
+                    ..options(1)
+                },
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                String::from_utf8(stderr.lock().unwrap().clone()).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn import_ninja_log_before_build() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let path = |name: &str| directory.path().join(name).display().to_string();
+        let (input, foo, bar) = (path("input"), path("foo"), path("bar"));
+
+        for file in [&input, &foo, &bar] {
+            std::fs::write(file, "").unwrap();
+        }
+
+        let configuration = compile_configuration(&format!(
+            "rule cc\n  command = cc $in $out\nbuild {foo}: cc {input}\nbuild {bar}: cc {input}\n"
+        ));
+        let modified_time = |file: &str| {
+            std::fs::metadata(file)
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        };
+        let log = directory.path().join(".ninja_log");
+        std::fs::write(
+            &log,
+            format!(
+                "# ninja log v5\n0\t1\t{}\t{foo}\t{:x}\n0\t1\t{}\t{bar}\t0\n",
+                modified_time(&foo),
+                ninja_log::hash_murmur(format!("cc {input} {foo}").as_bytes()),
+                modified_time(&bar),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            import_ninja_log(&context, configuration.clone(), &log, options(1))
+                .await
+                .unwrap(),
+            1
+        );
+
+        run(&context, configuration, &[], options(1)).await.unwrap();
+
+        assert_eq!(*commands.lock().unwrap(), vec![format!("cc {input} {bar}")]);
+    }
+
+    #[tokio::test]
+    async fn note_changed_configuration() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+        let foo = directory.path().join("foo").display().to_string();
+        let configuration = |command: &str| {
+            compile_configuration(&format!(
+                "rule cc\n  command = {command} $out\nbuild {foo}: cc\n"
+            ))
+        };
+
+        assert_ne!(
+            hash::calculate_configuration_hash(&configuration("cc")),
+            hash::calculate_configuration_hash(&configuration("gcc"))
+        );
+
+        for command in ["cc", "cc", "gcc"] {
+            std::fs::write(&foo, "").unwrap();
+            run(&context, configuration(command), &[], options(1))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(stderr.lock().unwrap().clone())
+                .unwrap()
+                .matches("note: build files changed since the last build")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn dump_database_after_build() {
+        let console = RecordingConsole::new();
+        let stdout = console.stdout();
+        let (context, directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+        let foo = directory.path().join("foo").display().to_string();
+        std::fs::write(&foo, "").unwrap();
+        let configuration =
+            compile_configuration(&format!("rule cc\n  command = cc $out\nbuild {foo}: cc\n"));
+
+        run(&context, configuration.clone(), &[], options(1))
+            .await
+            .unwrap();
+        crate::tool::dump_database(context.as_ref(), &configuration)
+            .await
+            .unwrap();
+
+        let dump = String::from_utf8(stdout.lock().unwrap().clone()).unwrap();
+
+        assert!(dump.contains(&format!("build {foo}\n  timestamp hash: ")));
+        assert!(dump.contains(&format!("output {foo}\n")));
+    }
+
+    #[tokio::test]
+    async fn run_compilation_database() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let path = directory.path().display().to_string();
+        std::fs::write(directory.path().join("foo.c"), "").unwrap();
+        std::fs::write(directory.path().join("bar.c"), "").unwrap();
+
+        run(
+            &context,
+            crate::compdb::compile(
+                &format!(
+                    r#"[
+                        {{ "directory": "{path}", "command": "cc -c foo.c", "file": "foo.c", "output": "foo.o" }},
+                        {{ "directory": "{path}", "arguments": ["cc", "-c", "bar.c"], "file": "bar.c", "output": "bar.o" }}
+                    ]"#
+                ),
+                &std::env::current_dir().unwrap(),
+            )
+            .unwrap()
+            .into(),
+            &[],
+            options(1),
+        )
+        .await
+        .unwrap();
+
+        let mut commands = commands.lock().unwrap().clone();
+        commands.sort();
+        assert_eq!(
+            commands,
+            vec![
+                format!("cd {path} && cc -c bar.c"),
+                format!("cd {path} && cc -c foo.c")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_validation() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        run(
+            &context,
+            compile_configuration(
+                "rule build\n  command = build\nrule check\n  command = check\nbuild foo: build |@ bar\nbuild bar: check\nbuild baz: build\n",
+            ),
+            &["foo".into()],
+            options(1),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*commands.lock().unwrap(), vec!["build", "check"]);
+    }
+
+    #[tokio::test]
+    async fn fail_with_failed_validation() {
+        let (context, _directory) =
+            create_context(FakeCommandRunner::new(&["check"], Duration::ZERO));
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(
+                    "rule build\n  command = build\nrule check\n  command = check\nbuild foo: build |@ bar\nbuild bar: check\n",
+                ),
+                &["foo".into()],
+                options(1),
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_response_file() {
+        let (context, directory) = create_context(FakeCommandRunner::new(&[], Duration::ZERO));
+        let path = directory.path().join("foo.rsp");
+
+        run(
+            &context,
+            compile_configuration(&format!(
+                "rule foo\n  command = foo\n  rspfile = {}\n  rspfile_content = bar\nbuild foo: foo\n",
+                path.display()
+            )),
+            &[],
+            options(1),
+        )
+        .await
+        .unwrap();
+
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn keep_response_file() {
+        let (context, directory) = create_context(FakeCommandRunner::new(&[], Duration::ZERO));
+        let file = |name: &str| directory.path().join(name).display().to_string();
+        std::fs::write(file("bar"), "").unwrap();
+        std::fs::write(file("baz"), "").unwrap();
+
+        run(
+            &context,
+            compile_configuration(&format!(
+                "rule foo\n  command = foo\n  rspfile = {}\n  rspfile_content = $in_newline\nbuild foo: foo {} {}\n",
+                file("foo.rsp"),
+                file("bar"),
+                file("baz"),
+            )),
+            &[],
+            Options {
+                keep_response_files: true,
+                ..options(1)
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(file("foo.rsp")).unwrap(),
+            format!("{}\n{}", file("bar"), file("baz"))
+        );
+    }
+
+    #[tokio::test]
+    async fn rebuild_on_recipe_changes() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let file = |name: &str| directory.path().join(name).display().to_string();
+        std::fs::write(file("foo"), "").unwrap();
+        let configuration = |description: &str, content: &str| {
+            compile_configuration(&format!(
+                "rule foo\n  command = foo\n  description = {description}\n  rspfile = {}\n  rspfile_content = {content}\nbuild {}: foo\n",
+                file("foo.rsp"),
+                file("foo"),
+            ))
+        };
+
+        for (description, content, count) in [
+            ("foo", "foo", 1),
+            ("foo", "foo", 1),
+            ("bar", "foo", 1),
+            ("bar", "bar", 2),
+        ] {
+            run(
+                &context,
+                configuration(description, content),
+                &[],
+                options(1),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(commands.lock().unwrap().len(), count);
+        }
+    }
+
+    #[tokio::test]
+    async fn rebuild_on_environment_dependency_changes() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context(runner);
+        let file = directory.path().join("foo").display().to_string();
+        std::fs::write(&file, "").unwrap();
+        let configuration = compile_configuration(&format!(
+            "rule foo\n  command = foo\n  env_deps = TURTLE_TEST_TRACKED\nbuild {file}: foo\n"
+        ));
+
+        for (name, value, count) in [
+            ("TURTLE_TEST_TRACKED", "foo", 1),
+            ("TURTLE_TEST_TRACKED", "foo", 1),
+            ("TURTLE_TEST_UNTRACKED", "foo", 1),
+            ("TURTLE_TEST_TRACKED", "bar", 2),
+        ] {
+            env::set_var(name, value);
+
+            run(&context, configuration.clone(), &[], options(1))
+                .await
+                .unwrap();
+
+            assert_eq!(commands.lock().unwrap().len(), count);
+        }
+    }
+
+    #[tokio::test]
+    async fn fail_with_missing_input() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration("rule cp\n  command = cp\nbuild foo: cp missing.c\n"),
+                &[],
+                options(1),
+            )
+            .await,
+            Err(ApplicationError::MissingInput(
+                "missing.c".into(),
+                "foo".into()
+            ))
+        );
+        assert!(commands.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fail_on_outputs_not_created() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let (context, directory) =
+            create_context_with_console(FakeCommandRunner::new(&[], Duration::ZERO), console);
+        let path = |name: &str| directory.path().join(name).display().to_string();
+
+        std::fs::write(path("foo"), "").unwrap();
+
+        let configuration = compile_configuration(&format!(
+            "rule touch\n  command = touch\nbuild {}: touch\nbuild {}: touch\n",
+            path("foo"),
+            path("bar"),
+        ));
+        let options = Options {
+            allow_missing_outputs: false,
+            ..options(1)
+        };
+
+        run(
+            &context,
+            configuration.clone(),
+            &[path("foo")],
+            options.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            run(&context, configuration, &[path("bar")], options).await,
+            Err(ApplicationError::Build)
+        );
+        assert!(String::from_utf8(stderr.lock().unwrap().clone())
+            .unwrap()
+            .contains(&format!(
+                "output \"{}\" not created by its command",
+                path("bar")
+            )));
+    }
+
+    #[tokio::test]
+    async fn stop_builds_exceeding_output_size() {
+        let console = RecordingConsole::new();
+        let stderr = console.stderr();
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, directory) = create_context_with_console(runner, console);
+        let path = |name: &str| directory.path().join(name).display().to_string();
+
+        for name in ["foo", "bar", "baz"] {
+            std::fs::write(path(name), "0123456789").unwrap();
+        }
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration(&format!(
+                    "rule touch\n  command = touch $out\nbuild {}: touch\nbuild {}: touch || {}\nbuild {}: touch || {}\n",
+                    path("foo"),
+                    path("bar"),
+                    path("foo"),
+                    path("baz"),
+                    path("bar"),
+                )),
+                &[path("baz")],
+                Options {
+                    max_output_bytes: Some(15),
+                    ..options(0)
+                },
+            )
+            .await,
+            Err(ApplicationError::Build)
+        );
+        assert_eq!(
+            *commands.lock().unwrap(),
+            vec![
+                format!("touch {}", path("foo")),
+                format!("touch {}", path("bar"))
+            ]
+        );
+        assert!(String::from_utf8(stderr.lock().unwrap().clone())
+            .unwrap()
+            .contains(&format!(
+                "outputs of 20 bytes exceeded a limit of 15 bytes at \"{}\"",
+                path("bar")
+            )));
+    }
+
+    #[tokio::test]
+    async fn skip_input_check_with_missing_ok() {
+        let runner = FakeCommandRunner::new(&[], Duration::ZERO);
+        let commands = runner.commands();
+        let (context, _directory) = create_context(runner);
+
+        assert_eq!(
+            run(
+                &context,
+                compile_configuration("rule cp\n  command = cp\nbuild foo: cp || missing.c\n"),
+                &[],
+                Options {
+                    missing_ok: true,
+                    ..options(1)
+                },
+            )
+            .await,
+            Err(ApplicationError::FileNotFound("missing.c".into()))
+        );
+        assert!(commands.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_build_events() {
+        let (context, _directory) = create_context(FakeCommandRunner::new(&[], Duration::ZERO));
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+
+        run_with_event_handler(
+            &context,
+            compile_configuration(
+                "rule foo\n  command = foo\n  description = foo $out\nbuild bar: foo\nbuild baz: foo || bar\n",
+            ),
+            &["baz".into()],
+            options(1),
+            {
+                let events = events.clone();
+                move |event| events.lock().unwrap().push(event)
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                BuildEvent::JobStarted {
+                    output: "bar".into(),
+                    description: Some("foo bar".into())
+                },
+                BuildEvent::JobFinished {
+                    output: "bar".into(),
+                    success: true
+                },
+                BuildEvent::JobStarted {
+                    output: "baz".into(),
+                    description: Some("foo baz".into())
+                },
+                BuildEvent::JobFinished {
+                    output: "baz".into(),
+                    success: true
+                },
+                BuildEvent::BuildFinished { success: true },
+            ]
+        );
+    }
+}
@@ -1,19 +1,76 @@
-use super::{options::Options, BuildFuture};
+use super::{
+    event::EventHandler,
+    options::Options,
+    pause::Pause,
+    pool::Pool,
+    scheduler::{Schedule, Scheduler},
+    watchdog::Watchdog,
+    BuildFuture,
+};
 use crate::{
     build_graph::BuildGraph,
     context::Context as ApplicationContext,
+    infrastructure::ResourceUsage,
     ir::{BuildId, Configuration},
 };
-use dashmap::DashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use dashmap::{DashMap, DashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    env::temp_dir,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::{Mutex, Notify},
+    time::Instant,
+};
 
 pub struct Context {
     application: Arc<ApplicationContext>,
     configuration: Arc<Configuration>,
     build_futures: DashMap<BuildId, BuildFuture>,
+    rebuilt_builds: DashSet<BuildId>,
+    requested_builds: DashSet<BuildId>,
+    // Builds run regardless of whether they are up to date
+    forced_builds: DashSet<BuildId>,
+    pools: HashMap<Arc<str>, Pool>,
+    // Implicit pools of rules limited by options
+    rule_pools: HashMap<String, Pool>,
+    // Start and end times of commands
+    timings: DashMap<BuildId, (Instant, Instant)>,
+    // Resource usage of commands on supported platforms
+    resource_usages: DashMap<BuildId, ResourceUsage>,
+    // Modification times of inputs when commands consuming them started
+    input_times: DashMap<String, SystemTime>,
     build_graph: Mutex<BuildGraph>,
     options: Options,
+    watchdog: Watchdog,
+    pause: Pause,
+    failure_count: AtomicUsize,
+    // A total size of outputs built in bytes
+    output_size: AtomicU64,
+    // Rule builds triggered and finished for progress
+    triggered_count: AtomicUsize,
+    finished_count: AtomicUsize,
+    failure_limit: Notify,
+    event_handler: Option<EventHandler>,
+    // Summaries and counts of error outputs of commands by their hashes
+    error_outputs: DashMap<u64, (String, usize)>,
+    // First outputs with contents by their hashes for deduplication
+    output_contents: DashMap<u64, Arc<str>>,
+    // A directory of `$tmpdir` unique to a run
+    temporary_directory: PathBuf,
+    // Job slots are left to a command runner in FIFO order.
+    scheduler: Option<Scheduler>,
+    // A total depth of pools if builds of all rules are in any pools
+    pool_capacity: Option<usize>,
+    // Rules and first outputs of builds finished since the last render
+    finished_builds: Mutex<Vec<(String, Arc<str>)>>,
 }
 
 impl Context {
@@ -22,13 +79,76 @@ impl Context {
         configuration: Arc<Configuration>,
         build_graph: BuildGraph,
         options: Options,
+        event_handler: Option<EventHandler>,
     ) -> Self {
+        let pools = configuration
+            .outputs()
+            .values()
+            .flat_map(|build| build.rule().and_then(|rule| rule.pool()))
+            .map(|pool| (pool.name().clone(), Pool::new(pool.depth())))
+            .collect();
+
+        let temporary_directory = options
+            .temporary_directory
+            .clone()
+            .unwrap_or_else(temp_dir)
+            .join(format!(
+                "turtle-{}-{}",
+                process::id(),
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos()
+            ));
+
+        let rule_pools = options
+            .rule_job_limits
+            .iter()
+            .map(|(rule, &count)| (rule.clone(), Pool::new(count)))
+            .collect();
+
+        let pool_capacity = calculate_pool_capacity(&configuration, &pools, &rule_pools);
+
+        // Pools bound jobs already and schedulers only need their total depth
+        // of slots.
+        let scheduler = (options.schedule != Schedule::Fifo).then(|| {
+            Scheduler::new(
+                options.schedule,
+                pool_capacity.map_or(options.job_limit, |capacity| {
+                    capacity.min(options.job_limit)
+                }),
+                &configuration,
+            )
+        });
+
         Self {
             application,
+            pools,
+            rule_pools,
             build_graph: build_graph.into(),
             configuration,
             build_futures: DashMap::new(),
+            rebuilt_builds: DashSet::new(),
+            requested_builds: DashSet::new(),
+            forced_builds: DashSet::new(),
+            timings: DashMap::new(),
+            resource_usages: DashMap::new(),
+            input_times: DashMap::new(),
             options,
+            watchdog: Watchdog::new(),
+            pause: Pause::new(),
+            failure_count: AtomicUsize::new(0),
+            output_size: AtomicU64::new(0),
+            triggered_count: AtomicUsize::new(0),
+            finished_count: AtomicUsize::new(0),
+            failure_limit: Notify::new(),
+            event_handler,
+            error_outputs: DashMap::new(),
+            output_contents: DashMap::new(),
+            temporary_directory,
+            scheduler,
+            pool_capacity,
+            finished_builds: Default::default(),
         }
     }
 
@@ -44,6 +164,38 @@ impl Context {
         &self.build_futures
     }
 
+    pub fn rebuilt_builds(&self) -> &DashSet<BuildId> {
+        &self.rebuilt_builds
+    }
+
+    pub fn requested_builds(&self) -> &DashSet<BuildId> {
+        &self.requested_builds
+    }
+
+    pub fn forced_builds(&self) -> &DashSet<BuildId> {
+        &self.forced_builds
+    }
+
+    pub fn pools(&self) -> &HashMap<Arc<str>, Pool> {
+        &self.pools
+    }
+
+    pub fn rule_pools(&self) -> &HashMap<String, Pool> {
+        &self.rule_pools
+    }
+
+    pub fn timings(&self) -> &DashMap<BuildId, (Instant, Instant)> {
+        &self.timings
+    }
+
+    pub fn resource_usages(&self) -> &DashMap<BuildId, ResourceUsage> {
+        &self.resource_usages
+    }
+
+    pub fn input_times(&self) -> &DashMap<String, SystemTime> {
+        &self.input_times
+    }
+
     pub fn build_graph(&self) -> &Mutex<BuildGraph> {
         &self.build_graph
     }
@@ -51,4 +203,95 @@ impl Context {
     pub fn options(&self) -> &Options {
         &self.options
     }
+
+    pub fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
+    pub fn pause(&self) -> &Pause {
+        &self.pause
+    }
+
+    pub fn failure_count(&self) -> &AtomicUsize {
+        &self.failure_count
+    }
+
+    pub fn output_size(&self) -> &AtomicU64 {
+        &self.output_size
+    }
+
+    pub fn triggered_count(&self) -> &AtomicUsize {
+        &self.triggered_count
+    }
+
+    pub fn finished_count(&self) -> &AtomicUsize {
+        &self.finished_count
+    }
+
+    pub fn failure_limit(&self) -> &Notify {
+        &self.failure_limit
+    }
+
+    pub fn event_handler(&self) -> Option<&EventHandler> {
+        self.event_handler.as_ref()
+    }
+
+    pub fn error_outputs(&self) -> &DashMap<u64, (String, usize)> {
+        &self.error_outputs
+    }
+
+    pub fn output_contents(&self) -> &DashMap<u64, Arc<str>> {
+        &self.output_contents
+    }
+
+    pub fn temporary_directory(&self) -> &Path {
+        &self.temporary_directory
+    }
+
+    pub fn scheduler(&self) -> Option<&Scheduler> {
+        self.scheduler.as_ref()
+    }
+
+    pub fn pool_capacity(&self) -> Option<usize> {
+        self.pool_capacity
+    }
+
+    pub fn finished_builds(&self) -> &Mutex<Vec<(String, Arc<str>)>> {
+        &self.finished_builds
+    }
+}
+
+// Rules limited by options count as implicit pools unless they are in pools.
+fn calculate_pool_capacity(
+    configuration: &Configuration,
+    pools: &HashMap<Arc<str>, Pool>,
+    rule_pools: &HashMap<String, Pool>,
+) -> Option<usize> {
+    let mut unpooled_rules = HashSet::new();
+
+    for rule in configuration
+        .outputs()
+        .values()
+        .filter_map(|build| build.rule())
+    {
+        if rule.pool().is_none() {
+            if !rule_pools.contains_key(rule.name()) {
+                return None;
+            }
+
+            unpooled_rules.insert(rule.name());
+        }
+    }
+
+    if pools.is_empty() && unpooled_rules.is_empty() {
+        return None;
+    }
+
+    Some(
+        pools.values().map(Pool::depth).sum::<usize>()
+            + unpooled_rules
+                .iter()
+                .map(|&rule| rule_pools[rule].depth())
+                .sum::<usize>(),
+    )
 }
@@ -0,0 +1,75 @@
+use super::context::Context;
+use crate::{error::ApplicationError, ir::Build};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+// This replaces outputs of a build identical to outputs of builds finished
+// before with hard links to the first copies. Outputs are kept as they are if
+// they cannot be read or linked, e.g. on file systems without hard links.
+pub async fn deduplicate(context: &Context, build: &Build) {
+    let file_system = context.application().file_system();
+    let mut buffer = vec![];
+    let mut other_buffer = vec![];
+
+    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+        buffer.clear();
+
+        if file_system
+            .read_file(Path::new(output.as_ref()), &mut buffer)
+            .await
+            .is_err()
+        {
+            continue;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        buffer.hash(&mut hasher);
+
+        let original = context
+            .output_contents()
+            .entry(hasher.finish())
+            .or_insert_with(|| output.clone())
+            .clone();
+
+        if &original == output {
+            continue;
+        }
+
+        // Contents are compared as hashes can collide.
+        other_buffer.clear();
+
+        if file_system
+            .read_file(Path::new(original.as_ref()), &mut other_buffer)
+            .await
+            .is_ok()
+            && other_buffer == buffer
+        {
+            let _ = file_system
+                .hard_link(Path::new(original.as_ref()), Path::new(output.as_ref()))
+                .await;
+        }
+    }
+}
+
+// Outputs linked to other files are unlinked before their commands run so that
+// commands writing them in place never change outputs of other builds.
+pub async fn unlink(context: &Context, build: &Build) -> Result<(), ApplicationError> {
+    let file_system = context.application().file_system();
+
+    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+        let path = Path::new(output.as_ref());
+
+        if file_system
+            .metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file() && metadata.link_count() > 1)
+        {
+            file_system.remove_file(path).await?;
+        }
+    }
+
+    Ok(())
+}
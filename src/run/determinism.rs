@@ -0,0 +1,133 @@
+use super::{run, Options};
+use crate::{context::Context, error::ApplicationError, ir::Configuration, log};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    path::Path,
+    sync::Arc,
+};
+
+// This builds outputs twice from scratch and reports ones whose contents
+// differ between the builds, e.g. because of embedded timestamps.
+pub async fn verify_deterministic(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+) -> Result<(), ApplicationError> {
+    let files = find_outputs(&configuration, outputs)?;
+
+    run(context, configuration.clone(), outputs, options.clone()).await?;
+    let hashes = hash_files(context, &files).await;
+
+    for file in &files {
+        // Outputs not built, e.g. optional ones, do not exist.
+        let _ = context
+            .file_system()
+            .remove_file(Path::new(file.as_ref()))
+            .await;
+    }
+
+    run(context, configuration, outputs, options).await?;
+
+    let other_hashes = hash_files(context, &files).await;
+    let differences = files
+        .iter()
+        .filter(|file| hashes.get(*file) != other_hashes.get(*file))
+        .collect::<Vec<_>>();
+
+    if differences.is_empty() {
+        return Ok(());
+    }
+
+    let mut console = context.console().lock().await;
+
+    for file in &differences {
+        log!(console, "output \"{}\" differs between builds", file);
+    }
+
+    Err(ApplicationError::Other(format!(
+        "{} outputs are not deterministic",
+        differences.len()
+    )))
+}
+
+// Outputs of rule builds reachable from requested ones through inputs and
+// order-only inputs are verified. Phony builds have no contents of their own.
+fn find_outputs(
+    configuration: &Configuration,
+    outputs: &[String],
+) -> Result<Vec<Arc<str>>, ApplicationError> {
+    let mut pending = if outputs.is_empty() {
+        configuration
+            .default_outputs()
+            .iter()
+            .map(|output| {
+                configuration
+                    .outputs()
+                    .get(output)
+                    .ok_or_else(|| ApplicationError::DefaultOutputNotFound(output.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        outputs
+            .iter()
+            .map(|output| {
+                configuration
+                    .outputs()
+                    .get(output.as_str())
+                    .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let mut visited = HashSet::new();
+    let mut files = vec![];
+
+    while let Some(build) = pending.pop() {
+        if !visited.insert(build.id()) {
+            continue;
+        }
+
+        if build.rule().is_some() {
+            files.extend(
+                build
+                    .outputs()
+                    .iter()
+                    .chain(build.implicit_outputs())
+                    .cloned(),
+            );
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(build) = configuration.outputs().get(input) {
+                pending.push(build);
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+async fn hash_files(context: &Context, files: &[Arc<str>]) -> HashMap<Arc<str>, u64> {
+    let mut hashes = HashMap::new();
+    let mut buffer = vec![];
+
+    for file in files {
+        buffer.clear();
+
+        if context
+            .file_system()
+            .read_file(Path::new(file.as_ref()), &mut buffer)
+            .await
+            .is_ok()
+        {
+            let mut hasher = DefaultHasher::new();
+            buffer.hash(&mut hasher);
+            hashes.insert(file.clone(), hasher.finish());
+        }
+    }
+
+    hashes
+}
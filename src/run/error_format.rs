@@ -0,0 +1,51 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+// A format of failures of builds on a console.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+    // Descriptions of builds are shown with outputs of their commands.
+    #[default]
+    Full,
+    // Only outputs of commands are shown with a header of each failed build
+    // for quickfix lists of editors.
+    Short,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = ErrorFormatError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "full" => Ok(Self::Full),
+            "short" => Ok(Self::Short),
+            _ => Err(ErrorFormatError),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ErrorFormatError;
+
+impl Error for ErrorFormatError {}
+
+impl Display for ErrorFormatError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "error format must be full or short")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_format() {
+        assert_eq!("full".parse(), Ok(ErrorFormat::Full));
+        assert_eq!("short".parse(), Ok(ErrorFormat::Short));
+        assert_eq!("foo".parse::<ErrorFormat>(), Err(ErrorFormatError));
+    }
+}
@@ -0,0 +1,68 @@
+use super::context::Context;
+use crate::{error::ApplicationError, log};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+// This records an error output of a command and returns true if it is seen for
+// the first time. Outputs are identified by their hashes.
+pub fn record(context: &Context, output: &[u8]) -> bool {
+    let mut hasher = DefaultHasher::new();
+
+    output.hash(&mut hasher);
+
+    let mut entry = context
+        .error_outputs()
+        .entry(hasher.finish())
+        .or_insert_with(|| (summarize(output), 0));
+
+    entry.1 += 1;
+
+    entry.1 == 1
+}
+
+// Repeated outputs are shown with their first lines and counts.
+pub async fn report(context: &Context) -> Result<(), ApplicationError> {
+    let mut outputs = context
+        .error_outputs()
+        .iter()
+        .map(|entry| entry.value().clone())
+        .filter(|(_, count)| *count > 1)
+        .collect::<Vec<_>>();
+
+    if outputs.is_empty() {
+        return Ok(());
+    }
+
+    outputs.sort();
+
+    let mut console = context.application().console().lock().await;
+
+    for (summary, count) in outputs {
+        log!(console, "{} (repeated {} times)", summary, count);
+    }
+
+    Ok(())
+}
+
+fn summarize(output: &[u8]) -> String {
+    String::from_utf8_lossy(output)
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default()
+        .trim()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_output() {
+        assert_eq!(summarize(b""), "");
+        assert_eq!(summarize(b"foo\nbar\n"), "foo");
+        assert_eq!(summarize(b"\n  foo  \nbar\n"), "foo");
+    }
+}
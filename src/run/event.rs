@@ -0,0 +1,18 @@
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BuildEvent {
+    JobStarted {
+        output: String,
+        description: Option<String>,
+    },
+    JobFinished {
+        output: String,
+        success: bool,
+    },
+    BuildFinished {
+        success: bool,
+    },
+}
+
+pub type EventHandler = Arc<dyn Fn(BuildEvent) + Send + Sync>;
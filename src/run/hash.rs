@@ -2,10 +2,11 @@ use super::context::Context;
 use crate::{
     error::ApplicationError,
     hash_type::HashType,
-    ir::{Build, Rule},
+    ir::{Build, Configuration},
 };
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashSet},
+    env,
     hash::{Hash, Hasher},
 };
 
@@ -23,16 +24,20 @@ pub async fn calculate_timestamp_hash(
 
     let mut hasher = DefaultHasher::new();
 
-    hash_command(build, &mut hasher);
+    hash_recipe(build, &mut hasher);
 
-    for input in file_inputs {
-        context
-            .application()
-            .file_system()
-            .metadata(input.as_ref())
-            .await?
-            .modified_time()
-            .hash(&mut hasher);
+    for &input in file_inputs {
+        if is_pinned(context, input) {
+            get_build_hash(context, HashType::Timestamp, input)?.hash(&mut hasher);
+        } else {
+            context
+                .application()
+                .file_system()
+                .metadata(input.as_ref())
+                .await?
+                .modified_time()
+                .hash(&mut hasher);
+        }
     }
 
     for &input in phony_inputs {
@@ -54,7 +59,7 @@ pub async fn calculate_content_hash(
 
     let mut hasher = DefaultHasher::new();
 
-    hash_command(build, &mut hasher);
+    hash_recipe(build, &mut hasher);
 
     let mut buffer = Vec::with_capacity(BUFFER_CAPACITY);
 
@@ -75,6 +80,71 @@ pub async fn calculate_content_hash(
     Ok(hasher.finish())
 }
 
+// Dependencies discovered by depfiles can be missing after they are recorded.
+pub async fn calculate_dependency_hash(
+    context: &Context,
+    dependencies: &[String],
+) -> Result<u64, ApplicationError> {
+    let mut hasher = DefaultHasher::new();
+
+    for dependency in dependencies {
+        dependency.hash(&mut hasher);
+
+        if is_pinned(context, dependency) {
+            get_build_hash(context, HashType::Timestamp, dependency)
+                .ok()
+                .hash(&mut hasher);
+            continue;
+        }
+
+        context
+            .application()
+            .file_system()
+            .metadata(dependency.as_ref())
+            .await
+            .ok()
+            .map(|metadata| metadata.modified_time())
+            .hash(&mut hasher);
+    }
+
+    Ok(hasher.finish())
+}
+
+// Builds are hashed in an order of their outputs so that hashes are stable
+// across runs.
+pub fn calculate_configuration_hash(configuration: &Configuration) -> u64 {
+    let mut builds = configuration.outputs().values().collect::<Vec<_>>();
+    let mut ids = HashSet::new();
+
+    builds.sort_by_key(|build| build.outputs()[0].clone());
+    builds.retain(|build| ids.insert(build.id()));
+
+    let mut hasher = DefaultHasher::new();
+
+    for build in builds {
+        build.id().hash(&mut hasher);
+        build.outputs().hash(&mut hasher);
+        build.implicit_outputs().hash(&mut hasher);
+        build.inputs().hash(&mut hasher);
+        build.order_only_inputs().hash(&mut hasher);
+        hash_recipe(build, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+// Modification times of pinned outputs never change on rebuilds. Hashes of
+// builds producing them are used instead.
+fn is_pinned(context: &Context, path: &str) -> bool {
+    context
+        .configuration()
+        .outputs()
+        .get(path)
+        .and_then(|build| build.rule())
+        .and_then(|rule| rule.pinned_modified_time())
+        .is_some()
+}
+
 fn get_build_hash(
     context: &Context,
     r#type: HashType,
@@ -103,6 +173,33 @@ fn calculate_phony_hash(build: &Build, file_inputs: &[&str], phony_inputs: &[&st
     }
 }
 
-fn hash_command(build: &Build, hasher: &mut impl Hasher) {
-    build.rule().map(Rule::command).hash(hasher);
+// Only variables changing behavior of commands are hashed so that edits of
+// cosmetic ones like `description` do not trigger rebuilds.
+fn hash_recipe(build: &Build, hasher: &mut impl Hasher) {
+    build
+        .rule()
+        .map(|rule| {
+            (
+                rule.commands(),
+                rule.environment(),
+                rule.depfile(),
+                rule.msvc_deps_prefix(),
+                rule.response_file(),
+                rule.output_encoding().map(|encoding| encoding.name()),
+                rule.mkdir(),
+                rule.no_output(),
+                rule.shell(),
+            )
+        })
+        .hash(hasher);
+
+    // Values of environment variables which commands read are tracked only if
+    // rules declare them.
+    for name in build
+        .rule()
+        .into_iter()
+        .flat_map(|rule| rule.environment_dependencies())
+    {
+        (name, env::var_os(name)).hash(hasher);
+    }
 }
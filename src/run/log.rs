@@ -10,7 +10,7 @@ macro_rules! debug {
 #[macro_export]
 macro_rules! profile {
     ($context:expr, $console:expr, $template:literal, $($value:expr),+) => {
-        if $context.options().profile {
+        if $context.options().profile == Some($crate::run::ProfileFormat::Text) {
             $crate::log!($console, $template, $($value),+);
         }
     };
@@ -18,9 +18,9 @@ macro_rules! profile {
 
 #[macro_export]
 macro_rules! log {
-    ($console:expr, $template:literal, $($value:expr),+) => {
+    ($console:expr, $template:literal $(, $value:expr)*) => {
         $console.write_stderr(
-            ("turtle: ".to_owned() + &format!($template, $($value),+)).as_bytes(),
+            ("turtle: ".to_owned() + &format!($template $(, $value)*)).as_bytes(),
         ).await?;
         $console.write_stderr("\n".as_bytes()).await?;
     };
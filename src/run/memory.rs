@@ -0,0 +1,91 @@
+use super::context::Context;
+use std::{fs::read_to_string, time::Duration};
+use tokio::time::sleep;
+
+const MEMORY_INFO_FILE: &str = "/proc/meminfo";
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+// This returns available memory of a system in bytes or `None` if a platform
+// does not provide it.
+pub fn available_memory() -> Option<u64> {
+    parse_available_memory(&read_to_string(MEMORY_INFO_FILE).ok()?)
+}
+
+fn parse_available_memory(info: &str) -> Option<u64> {
+    info.lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|size| size * 1024)
+}
+
+pub async fn wait(context: &Context) {
+    if let Some(threshold) = context.options().max_memory {
+        wait_for_memory(threshold, available_memory, || {
+            context.watchdog().running_count() == 0
+        })
+        .await
+    }
+}
+
+// Jobs start anyway if no other jobs are running since nothing would free
+// memory for them.
+async fn wait_for_memory(threshold: u64, probe: impl Fn() -> Option<u64>, idle: impl Fn() -> bool) {
+    while !idle() && probe().is_some_and(|memory| memory < threshold) {
+        sleep(PROBE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn parse_memory_info() {
+        assert_eq!(
+            parse_available_memory(
+                "MemTotal:       16318412 kB\nMemFree:         1442632 kB\nMemAvailable:    9627644 kB\n"
+            ),
+            Some(9627644 * 1024)
+        );
+    }
+
+    #[test]
+    fn parse_memory_info_without_available_memory() {
+        assert_eq!(parse_available_memory("MemTotal: 16318412 kB\n"), None);
+    }
+
+    #[tokio::test]
+    async fn defer_jobs_while_memory_is_low() {
+        let count = AtomicUsize::new(0);
+
+        wait_for_memory(
+            2,
+            || {
+                Some(if count.fetch_add(1, Ordering::SeqCst) < 3 {
+                    1
+                } else {
+                    2
+                })
+            },
+            || false,
+        )
+        .await;
+
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn start_jobs_if_idle() {
+        wait_for_memory(2, || Some(1), || true).await;
+    }
+
+    #[tokio::test]
+    async fn start_jobs_without_memory_information() {
+        wait_for_memory(2, || None, || false).await;
+    }
+}
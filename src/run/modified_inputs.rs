@@ -0,0 +1,70 @@
+use super::context::Context;
+use crate::{error::ApplicationError, log};
+
+// This records modification times of inputs when commands consuming them
+// start. Inputs consumed by several builds keep their first times.
+pub async fn record(context: &Context, inputs: &[&str]) {
+    for &input in inputs {
+        if context.input_times().contains_key(input) {
+            continue;
+        }
+
+        if let Ok(metadata) = context
+            .application()
+            .file_system()
+            .metadata(input.as_ref())
+            .await
+        {
+            context
+                .input_times()
+                .entry(input.into())
+                .or_insert(metadata.modified_time());
+        }
+    }
+}
+
+// Inputs modified during builds make their outputs inconsistent. Builds using
+// them run again on next builds as their hashes were calculated with the
+// original modification times.
+pub async fn check(context: &Context) -> Result<(), ApplicationError> {
+    let mut inputs = vec![];
+
+    for entry in context.input_times().iter() {
+        if context
+            .application()
+            .file_system()
+            .metadata(entry.key().as_ref())
+            .await
+            .map_or(true, |metadata| metadata.modified_time() != *entry.value())
+        {
+            inputs.push(entry.key().clone());
+        }
+    }
+
+    if inputs.is_empty() {
+        return Ok(());
+    }
+
+    inputs.sort();
+
+    let strict = context.options().strict_inputs;
+    let mut console = context.application().console().lock().await;
+
+    for input in &inputs {
+        if strict {
+            log!(console, "input \"{}\" modified during build", input);
+        } else {
+            log!(
+                console,
+                "warning: input \"{}\" modified during build",
+                input
+            );
+        }
+    }
+
+    if strict {
+        Err(ApplicationError::Build)
+    } else {
+        Ok(())
+    }
+}
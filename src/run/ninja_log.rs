@@ -0,0 +1,307 @@
+use super::{
+    context::Context as RunContext, hash, partition_inputs, read_depfile, record_dependencies,
+    Options,
+};
+use crate::{
+    build_graph::BuildGraph,
+    context::Context,
+    error::ApplicationError,
+    hash_type::HashType,
+    ir::{Build, Configuration, Rule, TEMPORARY_DIRECTORY_PLACEHOLDER},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SIGNATURES: &[&str] = &["# ninja log v5", "# ninja log v6"];
+const HASH_SEED: u64 = 0xDECAFBADDECAFBAD;
+const HASH_MULTIPLIER: u64 = 0xc6a4a7935bd1e995;
+const HASH_SHIFT: u32 = 47;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Entry {
+    modified_time: u64,
+    command_hash: u64,
+}
+
+// This records builds in a Ninja log as up to date in a build database if
+// their outputs, inputs, and commands have not changed since Ninja ran them.
+// The other builds are left to run on a next build. It returns a number of
+// builds imported.
+pub async fn import_ninja_log(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    path: &Path,
+    options: Options,
+) -> Result<usize, ApplicationError> {
+    let mut source = String::new();
+    context
+        .file_system()
+        .read_file_to_string(path, &mut source)
+        .await?;
+    let entries = parse_log(&source).ok_or_else(|| {
+        ApplicationError::Other(format!("{}: unsupported Ninja log", path.display()))
+    })?;
+    let context = RunContext::new(
+        context.clone(),
+        configuration.clone(),
+        BuildGraph::new(configuration.outputs()),
+        options,
+        None,
+    );
+    let mut ids = HashSet::new();
+    let mut builds = configuration
+        .outputs()
+        .values()
+        .filter(|build| ids.insert(build.id()))
+        .collect::<Vec<_>>();
+
+    builds.sort_by_key(|build| build.outputs().first().cloned());
+
+    let mut count = 0;
+
+    for build in builds {
+        if import_build(&context, build, &entries).await? {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+async fn import_build(
+    context: &RunContext,
+    build: &Build,
+    entries: &HashMap<&str, Entry>,
+) -> Result<bool, ApplicationError> {
+    let Some(rule) = build.rule() else {
+        return Ok(false);
+    };
+    // Ninja runs only one command per build.
+    let [command] = rule.commands() else {
+        return Ok(false);
+    };
+
+    // Dependencies found by dynamic modules or in command outputs are not
+    // recovered from Ninja logs.
+    if rule.always()
+        || rule.msvc_deps_prefix().is_some()
+        || build.dynamic_module().is_some()
+        || command.contains(TEMPORARY_DIRECTORY_PLACEHOLDER)
+    {
+        return Ok(false);
+    }
+
+    let (file_inputs, phony_inputs) = partition_inputs(context, build.inputs());
+
+    if !phony_inputs.is_empty() {
+        return Ok(false);
+    }
+
+    let command_hash = hash_command(command, rule);
+    let mut modified_time = u64::MAX;
+
+    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+        match (
+            entries.get(output.as_ref()),
+            read_modified_time(context, output).await,
+        ) {
+            (Some(entry), Some(time))
+                if entry.command_hash == command_hash && entry.modified_time == time =>
+            {
+                modified_time = modified_time.min(time);
+            }
+            _ => return Ok(false),
+        }
+    }
+
+    let dependencies = if let Some(depfile) = rule.depfile() {
+        // Missing depfiles are regarded as empty on builds but dependencies
+        // must be known here.
+        if context
+            .application()
+            .file_system()
+            .metadata(depfile.as_ref())
+            .await
+            .is_err()
+        {
+            return Ok(false);
+        }
+
+        Some(read_depfile(context, depfile).await?)
+    } else {
+        None
+    };
+
+    for input in file_inputs
+        .iter()
+        .copied()
+        .chain(dependencies.iter().flatten().map(String::as_str))
+    {
+        if read_modified_time(context, input)
+            .await
+            .is_none_or(|time| time > modified_time)
+        {
+            return Ok(false);
+        }
+    }
+
+    if let Some(dependencies) = &dependencies {
+        record_dependencies(context, build, dependencies).await?;
+    }
+
+    let database = context.application().database();
+
+    for output in build.outputs() {
+        database.set_output(output)?;
+
+        if let Some(source) = context.configuration().source_map().get(output) {
+            database.set_source(output, source)?;
+        }
+    }
+
+    database.set_hash(
+        HashType::Timestamp,
+        build.id(),
+        hash::calculate_timestamp_hash(context, build, &file_inputs, &phony_inputs).await?,
+    )?;
+    database.set_hash(
+        HashType::Content,
+        build.id(),
+        hash::calculate_content_hash(context, build, &file_inputs, &phony_inputs).await?,
+    )?;
+
+    Ok(true)
+}
+
+// Later entries of the same outputs supersede earlier ones as Ninja appends
+// entries to logs. Malformed lines are skipped as Ninja does.
+fn parse_log(source: &str) -> Option<HashMap<&str, Entry>> {
+    let mut lines = source.lines();
+
+    if !SIGNATURES.contains(&lines.next()?.trim_end()) {
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+
+    for line in lines {
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        if let [_, _, modified_time, output, command_hash] = fields[..] {
+            if let (Ok(modified_time), Ok(command_hash)) =
+                (modified_time.parse(), u64::from_str_radix(command_hash, 16))
+            {
+                entries.insert(
+                    output,
+                    Entry {
+                        modified_time,
+                        command_hash,
+                    },
+                );
+            }
+        }
+    }
+
+    Some(entries)
+}
+
+// Ninja hashes commands with contents of response files.
+fn hash_command(command: &str, rule: &Rule) -> u64 {
+    if let Some(file) = rule.response_file() {
+        hash_murmur(format!("{command};rspfile={}", file.content()).as_bytes())
+    } else {
+        hash_murmur(command.as_bytes())
+    }
+}
+
+// MurmurHash64A with a seed of Ninja
+pub(super) fn hash_murmur(bytes: &[u8]) -> u64 {
+    let mut hash = HASH_SEED ^ (bytes.len() as u64).wrapping_mul(HASH_MULTIPLIER);
+    let mut chunks = bytes.chunks_exact(8);
+
+    for chunk in &mut chunks {
+        let mut value = u64::from_le_bytes(chunk.try_into().unwrap());
+
+        value = value.wrapping_mul(HASH_MULTIPLIER);
+        value ^= value >> HASH_SHIFT;
+        value = value.wrapping_mul(HASH_MULTIPLIER);
+
+        hash ^= value;
+        hash = hash.wrapping_mul(HASH_MULTIPLIER);
+    }
+
+    let remainder = chunks.remainder();
+
+    if !remainder.is_empty() {
+        for (index, &byte) in remainder.iter().enumerate() {
+            hash ^= (byte as u64) << (8 * index);
+        }
+
+        hash = hash.wrapping_mul(HASH_MULTIPLIER);
+    }
+
+    hash ^= hash >> HASH_SHIFT;
+    hash = hash.wrapping_mul(HASH_MULTIPLIER);
+    hash ^= hash >> HASH_SHIFT;
+
+    hash
+}
+
+// Ninja records modification times in nanoseconds.
+async fn read_modified_time(context: &RunContext, path: &str) -> Option<u64> {
+    unix_nanoseconds(
+        context
+            .application()
+            .file_system()
+            .metadata(path.as_ref())
+            .await
+            .ok()?
+            .modified_time(),
+    )
+}
+
+fn unix_nanoseconds(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos()
+        .try_into()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_commands_as_ninja() {
+        assert_eq!(hash_murmur(b""), 0x87c2bc0beaf1d91d);
+        assert_eq!(hash_murmur(b"cc foo"), 0x3340e5cfe5a434af);
+        assert_eq!(hash_murmur(b"cc -c foo.c -o foo.o"), 0xc1cfc0967c85181b);
+    }
+
+    #[test]
+    fn parse_entries() {
+        assert_eq!(
+            parse_log("# ninja log v5\n0\t1\t42\tfoo\tff\n1\t2\t43\tfoo\tfe\nbar\n").unwrap(),
+            [(
+                "foo",
+                Entry {
+                    modified_time: 43,
+                    command_hash: 0xfe
+                }
+            )]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_unknown_version() {
+        assert_eq!(parse_log("# ninja log v4\n"), None);
+        assert_eq!(parse_log(""), None);
+    }
+}
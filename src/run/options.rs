@@ -1,5 +1,71 @@
+use super::{ErrorFormat, OutputMode, ProfileFormat, Schedule, Shard};
+use glob::Pattern;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Options {
+    pub color: bool,
     pub debug: bool,
-    pub profile: bool,
+    pub profile: Option<ProfileFormat>,
+    // A path of a profile written in formats other than text
+    pub profile_file: Option<PathBuf>,
+    pub time_report: bool,
+    pub dry_run: bool,
+    pub touch: bool,
+    pub stall_timeout: Duration,
+    pub abort_on_stall: bool,
+    // A number of failed jobs before stopping builds where 0 means infinity
+    pub keep_going: usize,
+    // An output whose completion or failure stops builds regardless of
+    // failures of other builds
+    pub keep_going_until: Option<String>,
+    pub no_deps: bool,
+    pub no_deps_record: bool,
+    pub missing_ok: bool,
+    // Do not fail builds whose commands succeed without creating outputs
+    pub allow_missing_outputs: bool,
+    pub shard: Option<Shard>,
+    // A directory of outputs to build
+    pub subdirectory: Option<PathBuf>,
+    // Patterns of outputs not to build unless other builds depend on them
+    pub excluded_outputs: Vec<Pattern>,
+    // Tags of builds to build
+    pub tags: Vec<String>,
+    // Outputs rebuilt regardless of whether they are up to date
+    pub forced_outputs: Vec<String>,
+    // Rebuild transitive dependencies of forced outputs as well
+    pub force_dependencies: bool,
+    // A seed to shuffle an order of builds
+    pub shuffle: Option<u64>,
+    pub detect_undeclared_outputs: bool,
+    pub detect_modified_inputs: bool,
+    // Fail builds if inputs are modified during them
+    pub strict_inputs: bool,
+    pub keep_response_files: bool,
+    pub collapse_errors: bool,
+    // A path of a provenance log of built outputs
+    pub provenance_file: Option<PathBuf>,
+    pub terminal_title: bool,
+    // Available memory in bytes below which jobs are deferred
+    pub max_memory: Option<u64>,
+    // A total size of outputs in bytes above which builds stop
+    pub max_output_bytes: Option<u64>,
+    // Numbers of jobs of rules running concurrently by their names
+    pub rule_job_limits: HashMap<String, usize>,
+    // Hard-link byte-identical outputs to their first copies
+    pub dedup_outputs: bool,
+    // A directory where temporary directories of runs are created (a system
+    // temporary directory by default)
+    pub temporary_directory: Option<PathBuf>,
+    pub schedule: Schedule,
+    // A number of jobs run concurrently with schedules other than FIFO
+    pub job_limit: usize,
+    pub output_mode: OutputMode,
+    pub error_format: ErrorFormat,
+    // Pause and resume builds on signals
+    pub pause_on_signals: bool,
+    // Build files are compiled only for requested outputs.
+    pub lazy: bool,
+    // A command run after builds finish regardless of their results
+    pub post_build: Option<String>,
 }
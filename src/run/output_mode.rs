@@ -0,0 +1,187 @@
+use super::context::Context;
+use crate::{error::ApplicationError, ir::Build};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    mem::take,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::sleep;
+
+const RENDER_INTERVAL: Duration = Duration::from_secs(1);
+
+// A layout of builds shown on a console while they run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputMode {
+    // Descriptions of builds are shown one per line.
+    #[default]
+    Plain,
+    // Outputs of builds are shown in trees of their directories.
+    Tree,
+    // Outputs of builds are shown by their rules with counts.
+    Grouped,
+}
+
+impl FromStr for OutputMode {
+    type Err = OutputModeError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "plain" => Ok(Self::Plain),
+            "tree" => Ok(Self::Tree),
+            "grouped" => Ok(Self::Grouped),
+            _ => Err(OutputModeError),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OutputModeError;
+
+impl Error for OutputModeError {}
+
+impl Display for OutputModeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "output mode must be plain, tree, or grouped")
+    }
+}
+
+pub async fn record(context: &Context, build: &Build) {
+    if let Some(rule) = build.rule().filter(|_| context.event_handler().is_none()) {
+        context
+            .finished_builds()
+            .lock()
+            .await
+            .push((rule.name().into(), build.outputs()[0].clone()));
+    }
+}
+
+// This future never completes and renders builds finished since the last
+// render periodically.
+pub async fn watch(context: &Context) -> Result<(), ApplicationError> {
+    loop {
+        sleep(RENDER_INTERVAL).await;
+        render(context).await?;
+    }
+}
+
+pub async fn render(context: &Context) -> Result<(), ApplicationError> {
+    let builds = take(&mut *context.finished_builds().lock().await);
+
+    if builds.is_empty() {
+        return Ok(());
+    }
+
+    let string = match context.options().output_mode {
+        OutputMode::Plain => return Ok(()),
+        OutputMode::Tree => format_tree(builds.iter().map(|(_, output)| output.as_ref())),
+        OutputMode::Grouped => format_groups(&builds),
+    };
+
+    context
+        .application()
+        .console()
+        .lock()
+        .await
+        .write_stderr(string.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Directory(BTreeMap<String, Directory>);
+
+fn format_tree<'a>(outputs: impl IntoIterator<Item = &'a str>) -> String {
+    let mut root = Directory::default();
+
+    for output in outputs {
+        let mut directory = &mut root;
+
+        for component in output.split('/') {
+            directory = directory.0.entry(component.into()).or_default();
+        }
+    }
+
+    let mut string = String::new();
+
+    format_directory(&root, 0, &mut string);
+
+    string
+}
+
+fn format_directory(directory: &Directory, depth: usize, string: &mut String) {
+    for (name, entry) in &directory.0 {
+        string.push_str(&"  ".repeat(depth));
+        string.push_str(name);
+
+        if !entry.0.is_empty() {
+            string.push('/');
+        }
+
+        string.push('\n');
+
+        format_directory(entry, depth + 1, string);
+    }
+}
+
+fn format_groups(builds: &[(String, Arc<str>)]) -> String {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+
+    for (rule, output) in builds {
+        groups
+            .entry(rule.as_str())
+            .or_default()
+            .push(output.as_ref());
+    }
+
+    let mut string = String::new();
+
+    for (rule, mut outputs) in groups {
+        outputs.sort();
+
+        string.push_str(&format!("{rule} ({})\n", outputs.len()));
+
+        for output in outputs {
+            string.push_str(&format!("  {output}\n"));
+        }
+    }
+
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_output_mode() {
+        assert_eq!("plain".parse(), Ok(OutputMode::Plain));
+        assert_eq!("tree".parse(), Ok(OutputMode::Tree));
+        assert_eq!("grouped".parse(), Ok(OutputMode::Grouped));
+        assert_eq!("foo".parse::<OutputMode>(), Err(OutputModeError));
+    }
+
+    #[test]
+    fn format_nested_directories() {
+        assert_eq!(
+            format_tree(["src/foo.o", "bin/foo", "src/bar/baz.o", "qux"]),
+            "bin/\n  foo\nqux\nsrc/\n  bar/\n    baz.o\n  foo.o\n"
+        );
+    }
+
+    #[test]
+    fn format_builds_by_rules() {
+        assert_eq!(
+            format_groups(&[
+                ("link".into(), "foo".into()),
+                ("cc".into(), "foo.o".into()),
+                ("cc".into(), "bar.o".into()),
+            ]),
+            "cc (2)\n  bar.o\n  foo.o\nlink (1)\n  foo\n"
+        );
+    }
+}
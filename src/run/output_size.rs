@@ -0,0 +1,41 @@
+use super::context::Context;
+use crate::{error::ApplicationError, ir::Build, log};
+use std::{path::Path, sync::atomic::Ordering};
+
+// This adds sizes of outputs of a build to a total of the run and stops builds
+// when the total exceeds a limit. Only the build crossing the limit is
+// reported.
+pub async fn record(context: &Context, build: &Build, limit: u64) -> Result<(), ApplicationError> {
+    let mut size = 0;
+
+    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+        if let Ok(metadata) = context
+            .application()
+            .file_system()
+            .metadata(Path::new(output.as_ref()))
+            .await
+        {
+            size += metadata.size();
+        }
+    }
+
+    let previous_size = context.output_size().fetch_add(size, Ordering::SeqCst);
+
+    if previous_size + size <= limit {
+        return Ok(());
+    } else if previous_size <= limit {
+        let mut console = context.application().console().lock().await;
+
+        log!(
+            console,
+            "outputs of {} bytes exceeded a limit of {} bytes at \"{}\"",
+            previous_size + size,
+            limit,
+            build.outputs()[0]
+        );
+    }
+
+    context.failure_limit().notify_one();
+
+    Err(ApplicationError::Build)
+}
@@ -0,0 +1,122 @@
+use super::context::Context;
+use crate::error::ApplicationError;
+use std::future::Future;
+use tokio::sync::watch;
+
+// Builds running already finish while builds are paused but new ones do not
+// start until they are resumed.
+#[derive(Debug)]
+pub struct Pause {
+    paused: watch::Sender<bool>,
+}
+
+impl Pause {
+    pub fn new() -> Self {
+        Self {
+            paused: watch::channel(false).0,
+        }
+    }
+
+    // This returns `true` if a state changes.
+    pub fn set(&self, paused: bool) -> bool {
+        self.paused.send_if_modified(|value| {
+            let changed = *value != paused;
+            *value = paused;
+            changed
+        })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    async fn wait(&self) {
+        // A sender is kept in a context and never dropped while waiting.
+        let _ = self.paused.subscribe().wait_for(|paused| !paused).await;
+    }
+}
+
+pub async fn wait(context: &Context) {
+    context.pause().wait().await
+}
+
+// `SIGUSR1` pauses builds and `SIGUSR2` resumes them. `SIGTSTP` and `SIGCONT`
+// are left to job control of shells. Signal handlers are installed before this
+// returns so that no signal is missed by builds started afterward. The future
+// returned never completes.
+#[cfg(unix)]
+pub fn watch(
+    context: &Context,
+) -> Result<impl Future<Output = Result<(), ApplicationError>> + '_, ApplicationError> {
+    use crate::log;
+    use std::future::pending;
+    use tokio::{
+        select,
+        signal::unix::{signal, SignalKind},
+    };
+
+    let signals = if context.options().pause_on_signals {
+        Some((
+            signal(SignalKind::user_defined1())?,
+            signal(SignalKind::user_defined2())?,
+        ))
+    } else {
+        None
+    };
+
+    Ok(async move {
+        let Some((mut pause, mut resume)) = signals else {
+            return pending().await;
+        };
+
+        loop {
+            let paused = select! {
+                _ = pause.recv() => true,
+                _ = resume.recv() => false,
+            };
+
+            if context.pause().set(paused) {
+                let mut console = context.application().console().lock().await;
+
+                if paused {
+                    log!(
+                        console,
+                        "paused: running builds finish but no new ones start until SIGUSR2"
+                    );
+                } else {
+                    log!(console, "resumed");
+                }
+            }
+        }
+    })
+}
+
+#[cfg(not(unix))]
+pub fn watch(
+    _context: &Context,
+) -> Result<impl Future<Output = Result<(), ApplicationError>> + '_, ApplicationError> {
+    Ok(std::future::pending())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn wait_until_resumed() {
+        let pause = Pause::new();
+
+        assert!(pause.set(true));
+        assert!(!pause.set(true));
+        assert!(pause.is_paused());
+        assert!(timeout(Duration::from_millis(10), pause.wait())
+            .await
+            .is_err());
+
+        assert!(pause.set(false));
+        assert!(!pause.is_paused());
+        pause.wait().await;
+    }
+}
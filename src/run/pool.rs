@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{AcquireError, Semaphore, SemaphorePermit};
+
+// A pool counts jobs waiting for its slots to diagnose if it is a bottleneck.
+#[derive(Debug)]
+pub struct Pool {
+    depth: usize,
+    semaphore: Semaphore,
+    waiting_count: AtomicUsize,
+}
+
+impl Pool {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            semaphore: Semaphore::new(depth),
+            waiting_count: AtomicUsize::new(0),
+        }
+    }
+
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        let _guard = WaitingGuard::new(&self.waiting_count);
+
+        self.semaphore.acquire().await
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.depth - self.semaphore.available_permits()
+    }
+
+    pub fn waiting_count(&self) -> usize {
+        self.waiting_count.load(Ordering::SeqCst)
+    }
+}
+
+// A count is decremented even if waiting jobs are cancelled.
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl<'a> WaitingGuard<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+
+        Self(count)
+    }
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, time::Duration};
+    use tokio::{spawn, time::sleep};
+
+    #[tokio::test]
+    async fn count_running_and_waiting_jobs() {
+        let pool = Arc::new(Pool::new(2));
+        let _first = pool.acquire().await.unwrap();
+        let _second = pool.acquire().await.unwrap();
+
+        let handle = spawn({
+            let pool = pool.clone();
+
+            async move {
+                let _permit = pool.acquire().await.unwrap();
+            }
+        });
+
+        sleep(Duration::from_millis(10)).await;
+
+        assert_eq!(pool.depth(), 2);
+        assert_eq!(pool.running_count(), 2);
+        assert_eq!(pool.waiting_count(), 1);
+
+        handle.abort();
+        let _ = handle.await;
+
+        assert_eq!(pool.waiting_count(), 0);
+    }
+}
@@ -0,0 +1,51 @@
+use super::context::Context;
+use crate::{error::ApplicationError, log};
+use std::{slice, sync::atomic::Ordering};
+
+const RESULT_VARIABLE: &str = "TURTLE_BUILD_RESULT";
+const BUILD_COUNT_VARIABLE: &str = "TURTLE_BUILD_COUNT";
+const FAILURE_COUNT_VARIABLE: &str = "TURTLE_FAILURE_COUNT";
+
+// A post-build command is a lifecycle hook rather than a build. It is given a
+// result and numbers of builds whose commands ran and failed. Its failures are
+// only warned and never change results of builds.
+pub async fn run(context: &Context, success: bool) -> Result<(), ApplicationError> {
+    let Some(command) = &context.options().post_build else {
+        return Ok(());
+    };
+    let environment = [
+        (
+            RESULT_VARIABLE,
+            if success { "success" } else { "failure" }.to_owned(),
+        ),
+        (BUILD_COUNT_VARIABLE, context.timings().len().to_string()),
+        (
+            FAILURE_COUNT_VARIABLE,
+            context.failure_count().load(Ordering::SeqCst).to_string(),
+        ),
+    ]
+    .map(|(name, value)| (name.to_owned(), value));
+    let result = context
+        .application()
+        .command_runner()
+        .run(slice::from_ref(command), &environment, None)
+        .await;
+    let mut console = context.application().console().lock().await;
+
+    match &result {
+        Ok((output, _)) => {
+            console.write_stdout(&output.stdout).await?;
+            console.write_stderr(&output.stderr).await?;
+        }
+        Err(error) => {
+            if let Some(output) = error.output() {
+                console.write_stdout(&output.stdout).await?;
+                console.write_stderr(&output.stderr).await?;
+            }
+
+            log!(console, "warning: post-build command failed: {}", error);
+        }
+    }
+
+    Ok(())
+}
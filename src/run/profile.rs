@@ -0,0 +1,290 @@
+use super::context::Context;
+use crate::{error::ApplicationError, log};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+use tokio::time::Instant;
+
+const SLOWEST_BUILD_COUNT: usize = 5;
+
+// A representation of profiling data of builds run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProfileFormat {
+    // Timings are shown on a console for each build and summarized after
+    // builds.
+    #[default]
+    Text,
+    // Records of builds are written to a JSON file.
+    Json,
+    // Builds are written to a trace file of the Chrome trace event format.
+    Chrome,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = ProfileFormatError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "chrome" => Ok(Self::Chrome),
+            _ => Err(ProfileFormatError),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProfileFormatError;
+
+impl Error for ProfileFormatError {}
+
+impl Display for ProfileFormatError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "profile format must be text, json, or chrome")
+    }
+}
+
+// A build which ran its command. Times are in microseconds since the first
+// command started. Resource usage is known only on supported platforms.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct Record {
+    output: String,
+    rule: String,
+    start: u64,
+    end: u64,
+    cpu_time: Option<u64>,
+    max_rss: Option<u64>,
+}
+
+// An event of the Chrome trace event format. Lanes of concurrent builds are
+// shown as threads.
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    cat: &'a str,
+    ph: &'static str,
+    ts: u64,
+    dur: u64,
+    pid: u64,
+    tid: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: Vec<TraceEvent<'a>>,
+}
+
+pub async fn report(context: &Context) -> Result<(), ApplicationError> {
+    let Some(format) = context.options().profile else {
+        return Ok(());
+    };
+    let records = collect_records(context);
+
+    if format == ProfileFormat::Text {
+        let mut console = context.application().console().lock().await;
+
+        for line in format_summary(&records) {
+            log!(console, "{}", line);
+        }
+
+        return Ok(());
+    }
+
+    let Some(path) = &context.options().profile_file else {
+        return Ok(());
+    };
+    let content = if format == ProfileFormat::Json {
+        serde_json::to_vec_pretty(&records)
+    } else {
+        serde_json::to_vec(&format_trace(&records))
+    }
+    .map_err(|error| ApplicationError::Other(error.to_string()))?;
+    let file_system = context.application().file_system();
+
+    if let Some(directory) = path.parent() {
+        file_system.create_directory(directory).await?;
+    }
+
+    file_system.write_file(path, &content).await?;
+
+    Ok(())
+}
+
+fn collect_records(context: &Context) -> Vec<Record> {
+    let mut ids = HashSet::new();
+    let timings = context
+        .configuration()
+        .outputs()
+        .values()
+        .filter(|build| ids.insert(build.id()))
+        .filter_map(|build| Some((*context.timings().get(&build.id())?, build)))
+        .collect::<Vec<_>>();
+    let Some(first_time) = timings.iter().map(|((start_time, _), _)| *start_time).min() else {
+        return vec![];
+    };
+    let microseconds = |time: Instant| (time - first_time).as_micros() as u64;
+    let mut records = timings
+        .into_iter()
+        .map(|((start_time, end_time), build)| {
+            let usage = context
+                .resource_usages()
+                .get(&build.id())
+                .map(|entry| *entry);
+
+            Record {
+                output: build.outputs()[0].to_string(),
+                rule: build
+                    .rule()
+                    .map(|rule| rule.name())
+                    .unwrap_or("phony")
+                    .into(),
+                start: microseconds(start_time),
+                end: microseconds(end_time),
+                cpu_time: usage.map(|usage| usage.cpu_time.as_micros() as u64),
+                max_rss: usage.map(|usage| usage.max_rss),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    records.sort_by(|one, other| (one.start, &one.output).cmp(&(other.start, &other.output)));
+
+    records
+}
+
+fn format_summary(records: &[Record]) -> Vec<String> {
+    let total = records
+        .iter()
+        .map(|record| record.end - record.start)
+        .sum::<u64>();
+    let mut lines = vec![format!(
+        "profile: {} builds, {}ms in commands, {}ms wall-clock time",
+        records.len(),
+        total / 1000,
+        records.iter().map(|record| record.end).max().unwrap_or(0) / 1000
+    )];
+    let mut records = records.iter().collect::<Vec<_>>();
+
+    records.sort_by(|one, other| {
+        (other.end - other.start)
+            .cmp(&(one.end - one.start))
+            .then_with(|| one.output.cmp(&other.output))
+    });
+
+    for record in records.into_iter().take(SLOWEST_BUILD_COUNT) {
+        lines.push(format!(
+            "  {}ms {} ({})",
+            (record.end - record.start) / 1000,
+            record.output,
+            record.rule
+        ));
+    }
+
+    lines
+}
+
+// Builds are placed in the first lanes free when they start.
+fn format_trace(records: &[Record]) -> Trace<'_> {
+    let mut lanes = Vec::<u64>::new();
+
+    Trace {
+        trace_events: records
+            .iter()
+            .map(|record| {
+                let lane = if let Some(lane) = lanes.iter().position(|&end| end <= record.start) {
+                    lanes[lane] = record.end;
+                    lane
+                } else {
+                    lanes.push(record.end);
+                    lanes.len() - 1
+                };
+
+                TraceEvent {
+                    name: &record.output,
+                    cat: &record.rule,
+                    ph: "X",
+                    ts: record.start,
+                    dur: record.end - record.start,
+                    pid: 1,
+                    tid: lane,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
+
+    fn record(output: &str, start: u64, end: u64) -> Record {
+        Record {
+            output: output.into(),
+            rule: "cc".into(),
+            start,
+            end,
+            cpu_time: None,
+            max_rss: None,
+        }
+    }
+
+    #[test]
+    fn parse_profile_format() {
+        assert_eq!("text".parse(), Ok(ProfileFormat::Text));
+        assert_eq!("json".parse(), Ok(ProfileFormat::Json));
+        assert_eq!("chrome".parse(), Ok(ProfileFormat::Chrome));
+        assert_eq!("foo".parse::<ProfileFormat>(), Err(ProfileFormatError));
+    }
+
+    #[test]
+    fn format_summary_of_slowest_builds() {
+        assert_eq!(
+            format_summary(&[record("foo", 0, 1000), record("bar", 0, 3000)]),
+            vec![
+                "profile: 2 builds, 4ms in commands, 3ms wall-clock time",
+                "  3ms bar (cc)",
+                "  1ms foo (cc)",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_trace_in_lanes() {
+        assert_eq!(
+            serde_json::to_value(format_trace(&[
+                record("foo", 0, 10),
+                record("bar", 5, 20),
+                record("baz", 10, 30),
+            ]))
+            .unwrap(),
+            json!({
+                "traceEvents": [
+                    {"name": "foo", "cat": "cc", "ph": "X", "ts": 0, "dur": 10, "pid": 1, "tid": 0},
+                    {"name": "bar", "cat": "cc", "ph": "X", "ts": 5, "dur": 15, "pid": 1, "tid": 1},
+                    {"name": "baz", "cat": "cc", "ph": "X", "ts": 10, "dur": 20, "pid": 1, "tid": 0},
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_records() {
+        assert_eq!(
+            serde_json::to_value([record("foo", 0, 10)]).unwrap(),
+            Value::Array(vec![json!({
+                "output": "foo",
+                "rule": "cc",
+                "start": 0,
+                "end": 10,
+                "cpu_time": null,
+                "max_rss": null,
+            })])
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use super::context::Context;
+use crate::{error::ApplicationError, fnv, ir::Build};
+use serde::Serialize;
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+const HASH_ALGORITHM: &str = "fnv1a64";
+
+// An entry of a provenance log in JSON Lines. Hashes are hexadecimal 64-bit
+// FNV-1a hashes of file contents stable across Rust versions and are not
+// cryptographic.
+#[derive(Serialize)]
+struct Entry<'a> {
+    output: &'a str,
+    // Commands as run with variables and temporary directories expanded
+    commands: &'a [String],
+    hash_algorithm: &'a str,
+    inputs: &'a [Input<'a>],
+    // Seconds since the Unix epoch
+    timestamp: u64,
+}
+
+#[derive(Serialize)]
+struct Input<'a> {
+    path: &'a str,
+    hash: String,
+}
+
+// This appends entries for outputs of a build to a provenance log. The log is
+// never rewritten so that entries of past builds are kept.
+pub async fn record(
+    context: &Context,
+    path: &Path,
+    build: &Build,
+    commands: &[String],
+    inputs: &[&str],
+) -> Result<(), ApplicationError> {
+    let file_system = context.application().file_system();
+    let mut hashed_inputs = vec![];
+    let mut buffer = vec![];
+
+    for &input in inputs {
+        file_system.read_file(input.as_ref(), &mut buffer).await?;
+
+        hashed_inputs.push(Input {
+            path: input,
+            hash: format!("{:016x}", fnv::hash(&buffer)),
+        });
+        buffer.clear();
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut lines = String::new();
+
+    for output in build.outputs().iter().chain(build.implicit_outputs()) {
+        lines += &serde_json::to_string(&Entry {
+            output,
+            commands,
+            hash_algorithm: HASH_ALGORITHM,
+            inputs: &hashed_inputs,
+            timestamp,
+        })
+        .map_err(|error| ApplicationError::Other(error.to_string()))?;
+        lines.push('\n');
+    }
+
+    if let Some(directory) = path.parent() {
+        file_system.create_directory(directory).await?;
+    }
+
+    // Entries of a build are appended at once not to be interleaved with others.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?
+        .write_all(lines.as_bytes())
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use crate::ir::{BuildId, Configuration};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::Mutex,
+};
+use tokio::sync::oneshot::{self, Receiver, Sender};
+
+// A policy to choose which of jobs waiting for job slots run next.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Schedule {
+    // Jobs run in order of their readiness.
+    #[default]
+    Fifo,
+    // Jobs of rules which ran least recently run first.
+    Fair,
+    // Jobs with the longest chains of builds depending on them run first.
+    Critical,
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "fifo" => Ok(Self::Fifo),
+            "fair" => Ok(Self::Fair),
+            "critical" => Ok(Self::Critical),
+            _ => Err(ScheduleError),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScheduleError;
+
+impl Error for ScheduleError {}
+
+impl Display for ScheduleError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "schedule must be fifo, fair, or critical")
+    }
+}
+
+#[derive(Debug)]
+pub struct Scheduler {
+    schedule: Schedule,
+    priorities: HashMap<BuildId, usize>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    available_count: usize,
+    // A logical clock incremented on every start of jobs
+    time: u64,
+    // Last times when jobs of rules started
+    rule_times: HashMap<String, u64>,
+    waiters: Vec<Waiter>,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    rule: String,
+    priority: usize,
+    sender: Sender<()>,
+}
+
+impl Scheduler {
+    pub fn new(schedule: Schedule, job_limit: usize, configuration: &Configuration) -> Self {
+        Self {
+            schedule,
+            priorities: if schedule == Schedule::Critical {
+                calculate_priorities(configuration)
+            } else {
+                Default::default()
+            },
+            state: Mutex::new(State {
+                available_count: job_limit,
+                time: 0,
+                rule_times: Default::default(),
+                waiters: vec![],
+            }),
+        }
+    }
+
+    pub async fn acquire(&self, rule: &str, id: BuildId) -> Permit<'_> {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+
+            if state.available_count > 0 && state.waiters.is_empty() {
+                state.available_count -= 1;
+                state.start(rule);
+
+                return Permit(self);
+            }
+
+            let (sender, receiver) = oneshot::channel();
+
+            state.waiters.push(Waiter {
+                rule: rule.into(),
+                priority: self.priorities.get(&id).copied().unwrap_or_default(),
+                sender,
+            });
+
+            receiver
+        };
+        let mut waiting = Waiting {
+            scheduler: self,
+            receiver: Some(receiver),
+        };
+
+        // Senders are never dropped without sending while receivers are alive.
+        let _ = waiting.receiver.as_mut().unwrap().await;
+        waiting.receiver = None;
+
+        Permit(self)
+    }
+
+    // Slots are passed to waiting jobs directly so that no others take them.
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        while let Some(index) = state.select(self.schedule) {
+            let waiter = state.waiters.remove(index);
+
+            if waiter.sender.send(()).is_ok() {
+                state.start(&waiter.rule);
+                return;
+            }
+        }
+
+        state.available_count += 1;
+    }
+}
+
+impl State {
+    fn start(&mut self, rule: &str) {
+        self.time += 1;
+        self.rule_times.insert(rule.into(), self.time);
+    }
+
+    // Ties are broken by orders of waiters.
+    fn select(&self, schedule: Schedule) -> Option<usize> {
+        let waiters = self.waiters.iter().enumerate();
+
+        match schedule {
+            Schedule::Fifo => waiters.map(|(index, _)| index).next(),
+            Schedule::Fair => waiters
+                .min_by_key(|(index, waiter)| {
+                    (
+                        self.rule_times
+                            .get(&waiter.rule)
+                            .copied()
+                            .unwrap_or_default(),
+                        *index,
+                    )
+                })
+                .map(|(index, _)| index),
+            Schedule::Critical => waiters
+                .max_by_key(|(index, waiter)| (waiter.priority, usize::MAX - index))
+                .map(|(index, _)| index),
+        }
+    }
+}
+
+pub struct Permit<'a>(&'a Scheduler);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+// Slots passed to cancelled jobs are released.
+struct Waiting<'a> {
+    scheduler: &'a Scheduler,
+    receiver: Option<Receiver<()>>,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+
+            if receiver.try_recv().is_ok() {
+                self.scheduler.release();
+            }
+        }
+    }
+}
+
+// Priorities of builds are lengths of the longest chains of builds depending on
+// them.
+fn calculate_priorities(configuration: &Configuration) -> HashMap<BuildId, usize> {
+    let mut dependents = HashMap::<BuildId, Vec<BuildId>>::new();
+
+    for build in configuration.outputs().values() {
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(input) = configuration.outputs().get(input) {
+                dependents.entry(input.id()).or_default().push(build.id());
+            }
+        }
+    }
+
+    let mut priorities = HashMap::new();
+
+    for build in configuration.outputs().values() {
+        calculate_priority(build.id(), &dependents, &mut priorities);
+    }
+
+    priorities
+}
+
+fn calculate_priority(
+    id: BuildId,
+    dependents: &HashMap<BuildId, Vec<BuildId>>,
+    priorities: &mut HashMap<BuildId, usize>,
+) -> usize {
+    if let Some(&priority) = priorities.get(&id) {
+        return priority;
+    }
+
+    // Cycles are reported elsewhere and only need to terminate here.
+    priorities.insert(id, 0);
+
+    let priority = dependents
+        .get(&id)
+        .into_iter()
+        .flatten()
+        .map(|&dependent| calculate_priority(dependent, dependents, priorities) + 1)
+        .max()
+        .unwrap_or_default();
+
+    priorities.insert(id, priority);
+
+    priority
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use std::{path::PathBuf, sync::Arc};
+    use tokio::{spawn, task::yield_now};
+
+    fn compile_configuration(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_schedule() {
+        assert_eq!("fifo".parse(), Ok(Schedule::Fifo));
+        assert_eq!("fair".parse(), Ok(Schedule::Fair));
+        assert_eq!("critical".parse(), Ok(Schedule::Critical));
+        assert_eq!("foo".parse::<Schedule>(), Err(ScheduleError));
+    }
+
+    #[test]
+    fn calculate_lengths_of_dependent_chains() {
+        let configuration = compile_configuration(
+            "rule cc\n  command = cc $out\nbuild a: cc\nbuild b: cc a\nbuild c: cc b\nbuild d: cc a\n",
+        );
+        let priorities = calculate_priorities(&configuration);
+        let priority = |output: &str| priorities[&configuration.outputs()[output].id()];
+
+        assert_eq!(priority("a"), 2);
+        assert_eq!(priority("b"), 1);
+        assert_eq!(priority("c"), 0);
+        assert_eq!(priority("d"), 0);
+    }
+
+    #[tokio::test]
+    async fn release_slot_of_cancelled_job() {
+        let configuration = compile_configuration("");
+        let scheduler = Arc::new(Scheduler::new(Schedule::Fair, 1, &configuration));
+        let id = BuildId::new(0);
+        let permit = scheduler.acquire("foo", id).await;
+
+        let handle = spawn({
+            let scheduler = scheduler.clone();
+
+            async move {
+                scheduler.acquire("bar", id).await;
+            }
+        });
+
+        yield_now().await;
+        handle.abort();
+        let _ = handle.await;
+        drop(permit);
+
+        scheduler.acquire("baz", id).await;
+    }
+}
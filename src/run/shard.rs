@@ -0,0 +1,111 @@
+use crate::fnv;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+// A shard of outputs where its index starts from 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Shard {
+    index: usize,
+    count: usize,
+}
+
+impl Shard {
+    pub fn new(index: usize, count: usize) -> Option<Self> {
+        if index > 0 && index <= count {
+            Some(Self { index, count })
+        } else {
+            None
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    // Outputs are partitioned by stable hashes of their paths so that every
+    // worker gets the same partitions even with different Turtle builds.
+    pub fn contains(&self, output: &str) -> bool {
+        fnv::hash(output.as_bytes()) % self.count as u64 == (self.index - 1) as u64
+    }
+}
+
+impl FromStr for Shard {
+    type Err = ShardError;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (index, count) = string.split_once('/').ok_or(ShardError)?;
+
+        Self::new(
+            index.parse().map_err(|_| ShardError)?,
+            count.parse().map_err(|_| ShardError)?,
+        )
+        .ok_or(ShardError)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ShardError;
+
+impl Error for ShardError {}
+
+impl Display for ShardError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "shard must be i/n where i is between 1 and n inclusively"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("1/3".parse(), Ok(Shard::new(1, 3).unwrap()));
+        assert_eq!("3/3".parse(), Ok(Shard::new(3, 3).unwrap()));
+    }
+
+    #[test]
+    fn fail_to_parse() {
+        for string in ["", "1", "0/3", "4/3", "1/0", "a/3", "1/b"] {
+            assert_eq!(string.parse::<Shard>(), Err(ShardError));
+        }
+    }
+
+    #[test]
+    fn partition_outputs_stably() {
+        assert!(Shard::new(1, 2).unwrap().contains("a"));
+        assert!(Shard::new(2, 2).unwrap().contains("b"));
+    }
+
+    #[test]
+    fn partition_outputs() {
+        let outputs = (0..100)
+            .map(|index| format!("foo{index}"))
+            .collect::<Vec<_>>();
+
+        for output in &outputs {
+            assert_eq!(
+                (1..=3)
+                    .filter(|&index| Shard::new(index, 3).unwrap().contains(output))
+                    .count(),
+                1
+            );
+        }
+
+        for index in 1..=3 {
+            assert!(outputs
+                .iter()
+                .any(|output| Shard::new(index, 3).unwrap().contains(output)));
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+
+const RESET_SEQUENCE: &str = "\x1b[0m";
+const ESCAPE_CHARACTER: char = '\x1b';
+const ELLIPSIS: &str = "...";
+
+pub fn format_description<'a>(
+    description: &'a str,
+    status: Option<&str>,
+    color: bool,
+) -> Cow<'a, str> {
+    match status
+        .filter(|_| color)
+        .and_then(|status| Some((status, status_color(status)?)))
+    {
+        Some((status, sequence)) => {
+            format!("{sequence}{status}{RESET_SEQUENCE} {description}").into()
+        }
+        None => description.into(),
+    }
+}
+
+// This elides the middle of a description to fit it in a width of a terminal.
+// Escape sequences of colors are kept as they have no width.
+pub fn elide_description(description: &str, width: usize) -> Cow<'_, str> {
+    let tokens = split_escape_sequences(description);
+    let length = tokens.iter().filter(|(_, escape)| !escape).count();
+
+    if length <= width {
+        return description.into();
+    }
+
+    let visible_length = width.saturating_sub(ELLIPSIS.len());
+    let head_length = visible_length.div_ceil(2);
+    let tail_start = length - (visible_length - head_length);
+    let mut elided = String::with_capacity(description.len());
+    let mut index = 0;
+
+    for (token, escape) in tokens {
+        if escape {
+            elided.push_str(token);
+            continue;
+        }
+
+        if index < head_length || index >= tail_start {
+            elided.push_str(token);
+        } else if index == head_length && width >= ELLIPSIS.len() {
+            elided.push_str(ELLIPSIS);
+        }
+
+        index += 1;
+    }
+
+    elided.into()
+}
+
+// Each token is either an escape sequence or a character.
+fn split_escape_sequences(string: &str) -> Vec<(&str, bool)> {
+    let mut tokens = vec![];
+    let mut characters = string.char_indices().peekable();
+
+    while let Some((start, character)) = characters.next() {
+        let mut end = start + character.len_utf8();
+
+        if character == ESCAPE_CHARACTER && characters.next_if(|&(_, next)| next == '[').is_some() {
+            end += 1;
+
+            for (index, character) in characters.by_ref() {
+                end = index + character.len_utf8();
+
+                if ('@'..='~').contains(&character) {
+                    break;
+                }
+            }
+
+            tokens.push((&string[start..end], true));
+        } else {
+            tokens.push((&string[start..end], false));
+        }
+    }
+
+    tokens
+}
+
+fn status_color(status: &str) -> Option<&'static str> {
+    Some(match status {
+        "compile" => "\x1b[1;34m",
+        "link" => "\x1b[1;32m",
+        "test" => "\x1b[1;33m",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_description_without_status() {
+        assert_eq!(format_description("foo", None, true), "foo");
+    }
+
+    #[test]
+    fn format_description_with_status() {
+        assert_eq!(
+            format_description("foo", Some("compile"), true),
+            "\x1b[1;34mcompile\x1b[0m foo"
+        );
+        assert_eq!(
+            format_description("foo", Some("link"), true),
+            "\x1b[1;32mlink\x1b[0m foo"
+        );
+        assert_eq!(
+            format_description("foo", Some("test"), true),
+            "\x1b[1;33mtest\x1b[0m foo"
+        );
+    }
+
+    #[test]
+    fn format_description_with_unknown_status() {
+        assert_eq!(format_description("foo", Some("bar"), true), "foo");
+    }
+
+    #[test]
+    fn format_description_without_color() {
+        assert_eq!(format_description("foo", Some("compile"), false), "foo");
+    }
+
+    #[test]
+    fn keep_short_description() {
+        assert_eq!(elide_description("CC foo.o", 80), "CC foo.o");
+        assert_eq!(elide_description("CC foo.o", 8), "CC foo.o");
+    }
+
+    #[test]
+    fn elide_long_description() {
+        assert_eq!(
+            elide_description("CC really/long/path/foo.o", 15),
+            "CC rea.../foo.o"
+        );
+        assert_eq!(
+            elide_description("CC really/long/path/foo.o", 10),
+            "CC r...o.o"
+        );
+        assert_eq!(elide_description("CC really/long/path/foo.o", 4), "C...");
+        assert_eq!(elide_description("CC really/long/path/foo.o", 2), "");
+    }
+
+    #[test]
+    fn elide_long_description_with_colors() {
+        let description = format_description("really/long/path/foo.o", Some("compile"), true);
+
+        assert_eq!(
+            elide_description(&description, 20),
+            "\x1b[1;34mcompile\x1b[0m r...th/foo.o"
+        );
+        assert_eq!(
+            elide_description(&description, 10),
+            "\x1b[1;34mcomp...\x1b[0mo.o"
+        );
+        assert_eq!(elide_description(&description, 30), description);
+    }
+}
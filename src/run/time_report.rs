@@ -0,0 +1,190 @@
+use super::context::Context;
+use crate::{error::ApplicationError, infrastructure::ResourceUsage, ir::BuildId, log};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+use tokio::time::Instant;
+
+// This shows a critical path of builds which limited wall-clock time. Each
+// build in it starts after its predecessor finishes.
+pub async fn report(context: &Context) -> Result<(), ApplicationError> {
+    let timings = context
+        .timings()
+        .iter()
+        .map(|entry| (*entry.key(), *entry.value()))
+        .collect::<HashMap<_, _>>();
+    let path = find_critical_path(&timings, |id| find_timed_inputs(context, &timings, id));
+
+    let (Some(first), Some(last)) = (path.first(), path.last()) else {
+        return Ok(());
+    };
+    let builds = context
+        .configuration()
+        .outputs()
+        .values()
+        .map(|build| (build.id(), build))
+        .collect::<HashMap<_, _>>();
+    let mut console = context.application().console().lock().await;
+
+    log!(
+        console,
+        "critical path: {}ms",
+        (timings[last].1 - timings[first].0).as_millis()
+    );
+
+    for id in &path {
+        let (start_time, end_time) = timings[id];
+
+        log!(
+            console,
+            "  {}ms {}{}",
+            (end_time - start_time).as_millis(),
+            builds[id].outputs()[0],
+            context
+                .resource_usages()
+                .get(id)
+                .map(|usage| format_usage(&usage))
+                .unwrap_or_default()
+        );
+    }
+
+    // Memory-hungry builds are candidates for pools.
+    if let Some(entry) = context
+        .resource_usages()
+        .iter()
+        .max_by_key(|entry| entry.value().max_rss)
+    {
+        log!(
+            console,
+            "max rss: {}KiB {}",
+            entry.value().max_rss / 1024,
+            builds[entry.key()].outputs()[0]
+        );
+    }
+
+    Ok(())
+}
+
+fn format_usage(usage: &ResourceUsage) -> String {
+    format!(
+        " (cpu time: {}ms, max rss: {}KiB)",
+        usage.cpu_time.as_millis(),
+        usage.max_rss / 1024
+    )
+}
+
+// Builds without timings (e.g. phony or up-to-date ones) are skipped through.
+fn find_timed_inputs(
+    context: &Context,
+    timings: &HashMap<BuildId, (Instant, Instant)>,
+    id: BuildId,
+) -> Vec<BuildId> {
+    let builds = context.configuration().outputs();
+    let mut pending = builds
+        .values()
+        .find(|build| build.id() == id)
+        .into_iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    let mut inputs = vec![];
+
+    while let Some(build) = pending.pop() {
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if let Some(input) = builds.get(input) {
+                if !visited.insert(input.id()) {
+                    continue;
+                } else if timings.contains_key(&input.id()) {
+                    inputs.push(input.id());
+                } else {
+                    pending.push(input.clone());
+                }
+            }
+        }
+    }
+
+    inputs
+}
+
+// A path ends with the build finished last and continues to the inputs
+// finished last before their successors started.
+fn find_critical_path<K: Copy + Eq + Hash>(
+    timings: &HashMap<K, (Instant, Instant)>,
+    inputs: impl Fn(K) -> Vec<K>,
+) -> Vec<K> {
+    let mut path = vec![];
+    let mut current = timings
+        .iter()
+        .max_by_key(|(_, (_, end_time))| *end_time)
+        .map(|(key, _)| *key);
+
+    while let Some(key) = current {
+        path.push(key);
+
+        let start_time = timings[&key].0;
+
+        current = inputs(key)
+            .into_iter()
+            .filter(|input| timings[input].1 <= start_time)
+            .max_by_key(|input| timings[input].1);
+    }
+
+    path.reverse();
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn format_resource_usage() {
+        assert_eq!(
+            format_usage(&ResourceUsage {
+                cpu_time: Duration::from_millis(42),
+                max_rss: 2048,
+            }),
+            " (cpu time: 42ms, max rss: 2KiB)"
+        );
+    }
+
+    #[test]
+    fn find_path_in_empty_graph() {
+        assert_eq!(
+            find_critical_path::<&str>(&HashMap::new(), |_| vec![]),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn find_path_in_synthetic_graph() {
+        let time = Instant::now();
+        let timing = |start: u64, end: u64| {
+            (
+                time + Duration::from_millis(start),
+                time + Duration::from_millis(end),
+            )
+        };
+        // a (0-10) and b (0-30) are inputs of c (30-40). d (10-35) is
+        // independent. e (40-50) depends on c and d.
+        let timings = [
+            ("a", timing(0, 10)),
+            ("b", timing(0, 30)),
+            ("c", timing(30, 40)),
+            ("d", timing(10, 35)),
+            ("e", timing(40, 50)),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let inputs = |key| match key {
+            "c" => vec!["a", "b"],
+            "e" => vec!["c", "d"],
+            _ => vec![],
+        };
+
+        assert_eq!(find_critical_path(&timings, inputs), vec!["b", "c", "e"]);
+    }
+}
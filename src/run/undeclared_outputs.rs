@@ -0,0 +1,185 @@
+use super::context::Context;
+use crate::{error::ApplicationError, ir::Build, log};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+// This takes modification times of files in directories of outputs. It is
+// heuristic as commands can write files anywhere.
+pub async fn snapshot(context: &Context, build: &Build) -> Result<Snapshot, ApplicationError> {
+    let file_system = context.application().file_system();
+    let mut snapshot = Snapshot::new();
+
+    for directory in find_directories(build) {
+        let Ok(paths) = file_system
+            .read_dir(if directory.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                &directory
+            })
+            .await
+        else {
+            continue;
+        };
+
+        for path in paths {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+
+            if let Ok(metadata) = file_system.metadata(&path).await {
+                if metadata.is_file() {
+                    snapshot.insert(directory.join(name), metadata.modified_time());
+                }
+            }
+        }
+    }
+
+    Ok(snapshot)
+}
+
+pub async fn report(
+    context: &Context,
+    build: &Build,
+    snapshot: &Snapshot,
+) -> Result<(), ApplicationError> {
+    let paths = find_undeclared_outputs(build, snapshot, &self::snapshot(context, build).await?);
+
+    if !paths.is_empty() {
+        let mut console = context.application().console().lock().await;
+
+        log!(
+            console,
+            "undeclared outputs written by \"{}\": {}",
+            build.outputs()[0],
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+fn find_directories(build: &Build) -> BTreeSet<PathBuf> {
+    build
+        .outputs()
+        .iter()
+        .chain(build.implicit_outputs())
+        .filter_map(|output| Path::new(output.as_ref()).parent())
+        .map(PathBuf::from)
+        .collect()
+}
+
+// Files created or modified are undeclared outputs unless they are declared
+// outputs or depfiles.
+fn find_undeclared_outputs(build: &Build, before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let declared = build
+        .outputs()
+        .iter()
+        .chain(build.implicit_outputs())
+        .map(|output| PathBuf::from(output.as_ref()))
+        .chain(
+            build
+                .rule()
+                .and_then(|rule| rule.depfile())
+                .map(PathBuf::from),
+        )
+        .collect::<HashSet<_>>();
+
+    after
+        .iter()
+        .filter(|(path, time)| before.get(*path) != Some(time) && !declared.contains(*path))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{Rule, RuleOptions};
+    use std::time::Duration;
+
+    fn build(outputs: &[&str], depfile: Option<&str>) -> Build {
+        Build::new(
+            outputs.iter().map(|&output| output.into()).collect(),
+            vec![],
+            Some(Rule::new(
+                "foo".into(),
+                vec!["".into()],
+                RuleOptions {
+                    depfile: depfile.map(From::from),
+                    ..Default::default()
+                },
+            )),
+            vec![],
+            vec![],
+            vec![],
+            None,
+        )
+    }
+
+    fn snapshot(paths: &[(&str, u64)]) -> Snapshot {
+        paths
+            .iter()
+            .map(|&(path, time)| {
+                (
+                    path.into(),
+                    SystemTime::UNIX_EPOCH + Duration::from_secs(time),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_directories_of_outputs() {
+        assert_eq!(
+            find_directories(&build(&["foo", "bar/baz", "bar/qux"], None)),
+            [PathBuf::from(""), PathBuf::from("bar")]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn find_extra_file() {
+        assert_eq!(
+            find_undeclared_outputs(
+                &build(&["out/foo"], None),
+                &snapshot(&[("out/bar", 1)]),
+                &snapshot(&[("out/foo", 2), ("out/bar", 1), ("out/extra", 2)]),
+            ),
+            vec![PathBuf::from("out/extra")]
+        );
+    }
+
+    #[test]
+    fn find_modified_file() {
+        assert_eq!(
+            find_undeclared_outputs(
+                &build(&["foo"], None),
+                &snapshot(&[("foo", 1), ("bar", 1)]),
+                &snapshot(&[("foo", 2), ("bar", 2)]),
+            ),
+            vec![PathBuf::from("bar")]
+        );
+    }
+
+    #[test]
+    fn ignore_depfile() {
+        assert_eq!(
+            find_undeclared_outputs(
+                &build(&["foo"], Some("foo.d")),
+                &snapshot(&[]),
+                &snapshot(&[("foo", 1), ("foo.d", 1)]),
+            ),
+            Vec::<PathBuf>::new()
+        );
+    }
+}
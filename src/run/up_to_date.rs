@@ -0,0 +1,297 @@
+use super::{
+    context::Context as RunContext,
+    why::{explain_build, DirtyReason},
+    Options,
+};
+use crate::{
+    build_graph::BuildGraph, context::Context, error::ApplicationError, ir::Configuration,
+};
+use serde::Serialize;
+use std::{collections::HashMap, sync::Arc};
+
+// This lists builds which would run and fails without running any commands if
+// there are any.
+pub async fn check_up_to_date(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+) -> Result<(), ApplicationError> {
+    let outputs = find_stale_outputs(context, configuration, outputs, options).await?;
+
+    if outputs.is_empty() {
+        return Ok(());
+    }
+
+    let mut console = context.console().lock().await;
+
+    for (output, reason) in &outputs {
+        console
+            .write_stdout(format!("{output}: dirty ({reason})\n").as_bytes())
+            .await?;
+    }
+
+    Err(ApplicationError::OutOfDate(outputs.len()))
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    output: &'a str,
+    reason: &'a DirtyReason,
+}
+
+// This prints builds which would run with reasons as a JSON array without
+// running any commands.
+pub async fn explain_json(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+) -> Result<(), ApplicationError> {
+    let json = format_json(&find_stale_outputs(context, configuration, outputs, options).await?)?;
+
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(json.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+fn format_json(outputs: &[(Arc<str>, DirtyReason)]) -> Result<String, ApplicationError> {
+    Ok(serde_json::to_string(
+        &outputs
+            .iter()
+            .map(|(output, reason)| Entry { output, reason })
+            .collect::<Vec<_>>(),
+    )
+    .map_err(|error| ApplicationError::Other(error.to_string()))?
+        + "\n")
+}
+
+async fn find_stale_outputs(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+) -> Result<Vec<(Arc<str>, DirtyReason)>, ApplicationError> {
+    let builds = if outputs.is_empty() {
+        configuration
+            .default_outputs()
+            .iter()
+            .map(|output| {
+                configuration
+                    .outputs()
+                    .get(output)
+                    .cloned()
+                    .ok_or_else(|| ApplicationError::DefaultOutputNotFound(output.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        outputs
+            .iter()
+            .map(|output| {
+                configuration
+                    .outputs()
+                    .get(output.as_str())
+                    .cloned()
+                    .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    let context = RunContext::new(
+        context.clone(),
+        configuration.clone(),
+        BuildGraph::new(configuration.outputs()),
+        options,
+        None,
+    );
+    let mut reasons = HashMap::new();
+
+    for build in &builds {
+        explain_build(&context, build, &mut reasons).await?;
+    }
+
+    let mut outputs = reasons
+        .into_iter()
+        .filter_map(|(output, reason)| Some((output, reason?)))
+        // Phony builds never run.
+        .filter(|(output, _)| {
+            configuration
+                .outputs()
+                .get(output)
+                .and_then(|build| build.rule())
+                .is_some()
+        })
+        .collect::<Vec<_>>();
+
+    outputs.sort_by(|(one, _), (other, _)| one.cmp(other));
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compile::compile,
+        infrastructure::{Database, FakeCommandRunner, OsConsole, OsDatabase, OsFileSystem},
+        parse::parse,
+        run::{run, OutputMode, Schedule},
+    };
+    use pretty_assertions::assert_eq;
+    use std::{
+        fs::{write, File},
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn check_stale_and_clean_trees() {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+        let context = Arc::new(Context::new(
+            FakeCommandRunner::new(&[], Duration::ZERO),
+            OsConsole::new(None),
+            database,
+            OsFileSystem::new(1),
+        ));
+        let path = |name: &str| directory.path().join(name).display().to_string();
+
+        for name in ["a.c", "b.c", "a.o", "b.o"] {
+            write(path(name), name).unwrap();
+        }
+
+        let module_path = PathBuf::from("build.ninja");
+        let configuration = Arc::new(
+            compile(
+                &[(
+                    module_path.clone(),
+                    parse(&format!(
+                        "rule cc\n  command = cc\nbuild {}: cc {}\nbuild {}: cc {}\nbuild all: phony {} {}\n",
+                        path("a.o"),
+                        path("a.c"),
+                        path("b.o"),
+                        path("b.c"),
+                        path("a.o"),
+                        path("b.o"),
+                    ))
+                    .unwrap(),
+                )]
+                .into_iter()
+                .collect(),
+                &[(module_path.clone(), Default::default())]
+                    .into_iter()
+                    .collect(),
+                &module_path,
+            )
+            .unwrap(),
+        );
+        let options = Options {
+            color: false,
+            debug: false,
+            profile: None,
+            profile_file: None,
+            time_report: false,
+            detect_undeclared_outputs: false,
+            detect_modified_inputs: false,
+            strict_inputs: false,
+            keep_response_files: false,
+            collapse_errors: false,
+            provenance_file: None,
+            terminal_title: false,
+            max_memory: None,
+            max_output_bytes: None,
+            rule_job_limits: Default::default(),
+            dedup_outputs: false,
+            temporary_directory: None,
+            schedule: Schedule::Fifo,
+            job_limit: 1,
+            output_mode: OutputMode::Plain,
+            lazy: false,
+            post_build: None,
+            error_format: Default::default(),
+            pause_on_signals: false,
+            dry_run: false,
+            touch: false,
+            stall_timeout: Duration::from_secs(60),
+            abort_on_stall: false,
+            keep_going: 1,
+            keep_going_until: None,
+            no_deps: false,
+            no_deps_record: false,
+            missing_ok: false,
+            allow_missing_outputs: false,
+            shard: None,
+            subdirectory: None,
+            excluded_outputs: vec![],
+            tags: vec![],
+            forced_outputs: vec![],
+            force_dependencies: false,
+            shuffle: None,
+        };
+
+        assert_eq!(
+            check_up_to_date(&context, configuration.clone(), &[], options.clone()).await,
+            Err(ApplicationError::OutOfDate(2))
+        );
+
+        run(&context, configuration.clone(), &[], options.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            check_up_to_date(&context, configuration.clone(), &[], options.clone()).await,
+            Ok(())
+        );
+
+        write(path("a.c"), "changed").unwrap();
+        File::options()
+            .write(true)
+            .open(path("a.c"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+
+        let outputs = find_stale_outputs(&context, configuration, &["all".into()], options)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outputs,
+            vec![(path("a.o").into(), DirtyReason::InputsChanged)]
+        );
+        assert_eq!(
+            format_json(&outputs).unwrap(),
+            format!(
+                "[{{\"output\":{:?},\"reason\":{{\"type\":\"inputs_changed\"}}}}]\n",
+                path("a.o")
+            )
+        );
+    }
+
+    #[test]
+    fn format_reasons_in_json() {
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(
+                &format_json(&[
+                    ("foo.o".into(), DirtyReason::InputsChanged),
+                    ("foo".into(), DirtyReason::InputDirty("foo.o".into())),
+                ])
+                .unwrap()
+            )
+            .unwrap(),
+            serde_json::json!([
+                { "output": "foo.o", "reason": { "type": "inputs_changed" } },
+                {
+                    "output": "foo",
+                    "reason": { "type": "input_dirty", "input": "foo.o" }
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use super::{context::Context, pause};
+use crate::{error::ApplicationError, infrastructure::Console, ir::Build, log};
+use dashmap::DashMap;
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    select,
+    sync::Notify,
+    time::{sleep_until, Instant},
+};
+
+#[derive(Debug)]
+pub struct Watchdog {
+    last_activity: Mutex<Instant>,
+    running_builds: DashMap<Arc<str>, Instant>,
+    stall: Notify,
+    reported: Notify,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self {
+            last_activity: Mutex::new(Instant::now()),
+            running_builds: Default::default(),
+            stall: Notify::new(),
+            reported: Notify::new(),
+        }
+    }
+
+    pub fn start(&self, build: &Build) {
+        self.running_builds
+            .insert(build.outputs()[0].clone(), Instant::now());
+        self.touch();
+    }
+
+    pub fn finish(&self, build: &Build) {
+        self.running_builds.remove(&build.outputs()[0]);
+        self.touch();
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.running_builds.len()
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    fn last_activity(&self) -> Instant {
+        *self.last_activity.lock().unwrap()
+    }
+
+    fn running_builds(&self) -> Vec<(Arc<str>, Duration)> {
+        let mut builds = self
+            .running_builds
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().elapsed()))
+            .collect::<Vec<_>>();
+
+        builds.sort();
+
+        builds
+    }
+}
+
+// This future never completes unless builds are aborted on stall.
+pub async fn watch(context: &Context) -> Result<(), ApplicationError> {
+    let mut last_report = None::<Instant>;
+
+    loop {
+        let last_activity = context.watchdog().last_activity();
+        let deadline = last_report.map_or(last_activity, |time| time.max(last_activity))
+            + context.options().stall_timeout;
+
+        if Instant::now() < deadline {
+            sleep_until(deadline).await;
+            continue;
+        }
+
+        // Time while builds are paused is not counted as stall.
+        if context.pause().is_paused() {
+            pause::wait(context).await;
+            context.watchdog().touch();
+            continue;
+        }
+
+        // A build job keeps the console locked while its command runs. In that
+        // case, the job reports the stall instead and builds are aborted only
+        // after that. The console is left to this if the job finishes meanwhile.
+        if let Ok(mut console) = context.application().console().try_lock() {
+            report(context, &mut **console).await?;
+        } else {
+            let mut reported = pin!(context.watchdog().reported.notified());
+
+            reported.as_mut().enable();
+            context.watchdog().stall.notify_waiters();
+
+            if context.options().abort_on_stall {
+                select! {
+                    biased;
+                    _ = reported => {}
+                    mut console = context.application().console().lock() => {
+                        report(context, &mut **console).await?;
+                    }
+                }
+            }
+        }
+
+        if context.options().abort_on_stall {
+            return Err(ApplicationError::Stalled);
+        }
+
+        last_report = Some(Instant::now());
+    }
+}
+
+// Waits for a future while reporting stalls to a console locked by a caller.
+pub async fn wait<T>(
+    context: &Context,
+    console: &mut (dyn Console + Send + Sync),
+    future: impl Future<Output = T>,
+) -> Result<T, ApplicationError> {
+    let mut future = pin!(future);
+
+    loop {
+        select! {
+            value = &mut future => return Ok(value),
+            _ = context.watchdog().stall.notified() => {
+                report(context, console).await?;
+                context.watchdog().reported.notify_waiters();
+
+                if context.options().abort_on_stall {
+                    return Err(ApplicationError::Stalled);
+                }
+            }
+        }
+    }
+}
+
+async fn report(
+    context: &Context,
+    console: &mut (dyn Console + Send + Sync),
+) -> Result<(), ApplicationError> {
+    log!(
+        console,
+        "no build started or finished for {}s",
+        context.watchdog().last_activity().elapsed().as_secs()
+    );
+
+    for (output, duration) in context.watchdog().running_builds() {
+        log!(console, "running {} for {}s", output, duration.as_secs());
+    }
+
+    // Saturated pools with waiting jobs tell which pools serialize builds.
+    let mut pools = context
+        .pools()
+        .iter()
+        .map(|(name, pool)| (format!("pool {name}"), pool))
+        .chain(
+            context
+                .rule_pools()
+                .iter()
+                .map(|(name, pool)| (format!("rule {name}"), pool)),
+        )
+        .filter(|(_, pool)| pool.running_count() > 0 || pool.waiting_count() > 0)
+        .collect::<Vec<_>>();
+
+    pools.sort_by(|(one, _), (other, _)| one.cmp(other));
+
+    for (name, pool) in pools {
+        log!(
+            console,
+            "{}: {} of {} running, {} waiting",
+            name,
+            pool.running_count(),
+            pool.depth(),
+            pool.waiting_count()
+        );
+    }
+
+    Ok(())
+}
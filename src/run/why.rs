@@ -0,0 +1,327 @@
+use super::{
+    are_dependencies_changed, check_file_existence, check_outputs_existence,
+    context::Context as RunContext, hash, partition_inputs, Options,
+};
+use crate::{
+    build_graph::BuildGraph,
+    context::Context,
+    error::ApplicationError,
+    hash_type::HashType,
+    ir::{Build, Configuration},
+};
+use async_recursion::async_recursion;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", content = "input", rename_all = "snake_case")]
+pub(super) enum DirtyReason {
+    DependenciesChanged,
+    InputDirty(Arc<str>),
+    InputMissing(Arc<str>),
+    InputsChanged,
+    NotBuilt,
+    OutputMissing,
+}
+
+impl Display for DirtyReason {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::DependenciesChanged => write!(formatter, "dependencies changed"),
+            Self::InputDirty(input) => write!(formatter, "input \"{input}\" dirty"),
+            Self::InputMissing(input) => write!(formatter, "input \"{input}\" missing"),
+            Self::InputsChanged => write!(formatter, "inputs or command changed"),
+            Self::NotBuilt => write!(formatter, "never built"),
+            Self::OutputMissing => write!(formatter, "output missing"),
+        }
+    }
+}
+
+// This shows why an output is dirty as a tree of builds without running any
+// commands. Subtrees of clean builds are omitted. Dynamic dependencies are not
+// considered.
+pub async fn why(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    output: &str,
+    options: Options,
+) -> Result<(), ApplicationError> {
+    let tree = explain(context, configuration, output, options).await?;
+    let mut console = context.console().lock().await;
+
+    console.write_stdout(tree.as_bytes()).await?;
+
+    Ok(())
+}
+
+async fn explain(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    output: &str,
+    options: Options,
+) -> Result<String, ApplicationError> {
+    let build = configuration
+        .outputs()
+        .get(output)
+        .cloned()
+        .ok_or_else(|| ApplicationError::OutputNotFound(output.into()))?;
+    let context = RunContext::new(
+        context.clone(),
+        configuration.clone(),
+        BuildGraph::new(configuration.outputs()),
+        options,
+        None,
+    );
+    let mut reasons = HashMap::new();
+    let mut tree = String::new();
+
+    explain_build(&context, &build, &mut reasons).await?;
+    render(&context, &build, &reasons, 0, &mut tree);
+
+    Ok(tree)
+}
+
+#[async_recursion]
+pub(super) async fn explain_build(
+    context: &RunContext,
+    build: &Arc<Build>,
+    reasons: &mut HashMap<Arc<str>, Option<DirtyReason>>,
+) -> Result<Option<DirtyReason>, ApplicationError> {
+    let output = &build.outputs()[0];
+
+    if let Some(reason) = reasons.get(output) {
+        return Ok(reason.clone());
+    }
+
+    let mut input_reason = None;
+
+    for input in build.inputs().iter().chain(build.order_only_inputs()) {
+        if let Some(input_build) = context.configuration().outputs().get(input) {
+            if explain_build(context, input_build, reasons)
+                .await?
+                .is_some()
+                && input_reason.is_none()
+                && !build.order_only_inputs().contains(input)
+            {
+                input_reason = Some(DirtyReason::InputDirty(input.clone()));
+            }
+        } else if check_file_existence(context, input).await.is_err() && input_reason.is_none() {
+            input_reason = Some(DirtyReason::InputMissing(input.clone()));
+        }
+    }
+
+    let reason = if input_reason.is_some() {
+        input_reason
+    } else if build.rule().is_none() {
+        None
+    } else {
+        explain_rule_build(context, build).await?
+    };
+
+    reasons.insert(output.clone(), reason.clone());
+
+    Ok(reason)
+}
+
+async fn explain_rule_build(
+    context: &RunContext,
+    build: &Build,
+) -> Result<Option<DirtyReason>, ApplicationError> {
+    let database = context.application().database();
+    if !check_outputs_existence(context, build).await {
+        return Ok(Some(DirtyReason::OutputMissing));
+    } else if database.get_hash(HashType::Content, build.id())?.is_none() {
+        return Ok(Some(DirtyReason::NotBuilt));
+    } else if !context.options().no_deps && are_dependencies_changed(context, build).await? {
+        return Ok(Some(DirtyReason::DependenciesChanged));
+    }
+
+    let (file_inputs, phony_inputs) = partition_inputs(context, build.inputs());
+
+    Ok(
+        if Some(hash::calculate_timestamp_hash(context, build, &file_inputs, &phony_inputs).await?)
+            == database.get_hash(HashType::Timestamp, build.id())?
+            || Some(
+                hash::calculate_content_hash(context, build, &file_inputs, &phony_inputs).await?,
+            ) == database.get_hash(HashType::Content, build.id())?
+        {
+            None
+        } else {
+            Some(DirtyReason::InputsChanged)
+        },
+    )
+}
+
+fn render(
+    context: &RunContext,
+    build: &Build,
+    reasons: &HashMap<Arc<str>, Option<DirtyReason>>,
+    depth: usize,
+    tree: &mut String,
+) {
+    let output = &build.outputs()[0];
+    let reason = &reasons[output];
+
+    tree.push_str(&"  ".repeat(depth));
+    tree.push_str(&match reason {
+        Some(reason) => format!("{output}: dirty ({reason})\n"),
+        None => format!("{output}: clean\n"),
+    });
+
+    if reason.is_none() {
+        return;
+    }
+
+    for input in build.inputs().iter().chain(build.order_only_inputs()) {
+        if let Some(build) = context.configuration().outputs().get(input) {
+            render(context, build, reasons, depth + 1, tree);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compile::compile,
+        infrastructure::{Database, FakeCommandRunner, OsConsole, OsDatabase, OsFileSystem},
+        parse::parse,
+        run::{run, OutputMode, Schedule},
+    };
+    use pretty_assertions::assert_eq;
+    use std::{
+        fs::{write, File},
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn explain_changed_leaf() {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+        let context = Arc::new(Context::new(
+            FakeCommandRunner::new(&[], Duration::ZERO),
+            OsConsole::new(None),
+            database,
+            OsFileSystem::new(1),
+        ));
+        let path = |name: &str| directory.path().join(name).display().to_string();
+
+        for name in ["a.c", "b.c", "a.o", "b.o", "app"] {
+            write(path(name), name).unwrap();
+        }
+
+        let module_path = PathBuf::from("build.ninja");
+        let configuration = Arc::new(
+            compile(
+                &[(
+                    module_path.clone(),
+                    parse(&format!(
+                        "rule cc\n  command = cc\nbuild {}: cc {}\nbuild {}: cc {}\nbuild {}: cc {} {}\n",
+                        path("a.o"),
+                        path("a.c"),
+                        path("b.o"),
+                        path("b.c"),
+                        path("app"),
+                        path("a.o"),
+                        path("b.o"),
+                    ))
+                    .unwrap(),
+                )]
+                .into_iter()
+                .collect(),
+                &[(module_path.clone(), Default::default())]
+                    .into_iter()
+                    .collect(),
+                &module_path,
+            )
+            .unwrap(),
+        );
+        let options = Options {
+            color: false,
+            debug: false,
+            profile: None,
+            profile_file: None,
+            time_report: false,
+            detect_undeclared_outputs: false,
+            detect_modified_inputs: false,
+            strict_inputs: false,
+            keep_response_files: false,
+            collapse_errors: false,
+            provenance_file: None,
+            terminal_title: false,
+            max_memory: None,
+            max_output_bytes: None,
+            rule_job_limits: Default::default(),
+            dedup_outputs: false,
+            temporary_directory: None,
+            schedule: Schedule::Fifo,
+            job_limit: 1,
+            output_mode: OutputMode::Plain,
+            lazy: false,
+            post_build: None,
+            error_format: Default::default(),
+            pause_on_signals: false,
+            dry_run: false,
+            touch: false,
+            stall_timeout: Duration::from_secs(60),
+            abort_on_stall: false,
+            keep_going: 1,
+            keep_going_until: None,
+            no_deps: false,
+            no_deps_record: false,
+            missing_ok: false,
+            allow_missing_outputs: false,
+            shard: None,
+            subdirectory: None,
+            excluded_outputs: vec![],
+            tags: vec![],
+            forced_outputs: vec![],
+            force_dependencies: false,
+            shuffle: None,
+        };
+
+        run(&context, configuration.clone(), &[], options.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            explain(
+                &context,
+                configuration.clone(),
+                &path("app"),
+                options.clone()
+            )
+            .await
+            .unwrap(),
+            format!("{}: clean\n", path("app"))
+        );
+
+        write(path("a.c"), "changed").unwrap();
+        File::options()
+            .write(true)
+            .open(path("a.c"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+
+        assert_eq!(
+            explain(&context, configuration, &path("app"), options)
+                .await
+                .unwrap(),
+            format!(
+                "{}: dirty (input \"{}\" dirty)\n  {}: dirty (inputs or command changed)\n  {}: clean\n",
+                path("app"),
+                path("a.o"),
+                path("a.o"),
+                path("b.o")
+            )
+        );
+    }
+}
@@ -1,3 +1,41 @@
+mod check;
+mod clean;
 mod clean_dead;
+mod dead;
+mod dependency_report;
+mod doctor;
+mod dump_database;
+mod edges;
+mod expand;
+mod explain_command;
+mod export_sqlite;
+mod graph;
+mod inputs;
+mod makefile;
+mod ninja_deps;
+mod query;
+mod recompact;
+mod targets;
+mod touch;
+mod unused;
 
+pub use check::*;
+pub use clean::*;
 pub use clean_dead::*;
+pub use dead::dead;
+pub use dependency_report::dependency_report;
+pub use doctor::doctor;
+pub use dump_database::dump_database;
+pub use edges::edges;
+pub use expand::*;
+pub use explain_command::explain_command;
+pub use export_sqlite::export_sqlite;
+pub use graph::graph;
+pub use inputs::{inputs, print_inputs};
+pub use makefile::*;
+pub use ninja_deps::*;
+pub use query::query;
+pub use recompact::*;
+pub use targets::{print_default_targets, targets};
+pub use touch::*;
+pub use unused::unused;
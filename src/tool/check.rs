@@ -0,0 +1,95 @@
+use crate::{
+    build_graph::BuildGraph, compile::CompileError, context::Context, error::ApplicationError,
+    ir::Configuration,
+};
+use futures::future::join_all;
+use std::collections::{HashMap, HashSet};
+
+// This checks build files without running any builds and reports all problems
+// found at once.
+pub async fn check(
+    context: &Context,
+    configuration: &Configuration,
+    errors: Vec<CompileError>,
+) -> Result<(), ApplicationError> {
+    let mut problems = errors
+        .into_iter()
+        .map(ApplicationError::from)
+        .collect::<Vec<_>>();
+
+    if let Err(error) = BuildGraph::new(configuration.outputs()).validate() {
+        problems.push(error.into());
+    }
+
+    problems.extend(find_missing_inputs(context, configuration).await);
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let mut console = context.console().lock().await;
+
+    for problem in &problems {
+        console
+            .write_stderr(format!("{problem}\n").as_bytes())
+            .await?;
+    }
+
+    Err(ApplicationError::Other(format!(
+        "{} problem(s) found",
+        problems.len()
+    )))
+}
+
+async fn find_missing_inputs(
+    context: &Context,
+    configuration: &Configuration,
+) -> Vec<ApplicationError> {
+    let mut inputs = HashMap::new();
+    let mut visited_builds = HashSet::new();
+    let mut builds = configuration.outputs().values().collect::<Vec<_>>();
+
+    // Builds are sorted so that the same outputs are reported for inputs
+    // shared by them every time.
+    builds.sort_by_key(|build| build.outputs()[0].clone());
+
+    for build in builds {
+        if !visited_builds.insert(build.id()) {
+            continue;
+        }
+
+        for input in build.inputs().iter().chain(build.order_only_inputs()) {
+            if !configuration.outputs().contains_key(input) {
+                inputs
+                    .entry(input.clone())
+                    .or_insert_with(|| build.outputs()[0].clone());
+            }
+        }
+    }
+
+    let mut inputs = inputs.into_iter().collect::<Vec<_>>();
+
+    inputs.sort();
+
+    join_all(inputs.into_iter().map(|(input, output)| async move {
+        context
+            .file_system()
+            .metadata(input.as_ref().as_ref())
+            .await
+            .err()
+            .map(|_| {
+                ApplicationError::MissingInput(
+                    input.to_string(),
+                    configuration
+                        .source_map()
+                        .get(&output)
+                        .unwrap_or(&output)
+                        .to_string(),
+                )
+            })
+    }))
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
@@ -0,0 +1,25 @@
+use super::targets::find_outputs;
+use crate::ir::Configuration;
+use futures::future::try_join_all;
+use std::{error::Error, io::ErrorKind};
+use tokio::fs::remove_file;
+
+pub async fn clean(
+    configuration: &Configuration,
+    patterns: &[String],
+) -> Result<(), Box<dyn Error>> {
+    try_join_all(
+        find_outputs(configuration, patterns)?
+            .iter()
+            .map(|output| async move {
+                // Outputs not built yet are ignored.
+                match remove_file(output.as_ref()).await {
+                    Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+                    _ => Ok(()),
+                }
+            }),
+    )
+    .await?;
+
+    Ok(())
+}
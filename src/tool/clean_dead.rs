@@ -1,3 +1,4 @@
+use super::dead::find_dead_outputs;
 use crate::{context::Context, ir::Configuration};
 use futures::future::try_join_all;
 use std::error::Error;
@@ -8,29 +9,12 @@ pub async fn clean_dead(
     configuration: &Configuration,
 ) -> Result<(), Box<dyn Error>> {
     try_join_all(
-        context
-            .database()
-            .get_outputs()?
+        find_dead_outputs(context, configuration)
+            .await?
             .iter()
-            .map(|output| remove_output(context, configuration, output)),
+            .map(remove_file),
     )
     .await?;
 
     Ok(())
 }
-
-async fn remove_output(
-    context: &Context,
-    configuration: &Configuration,
-    output: &str,
-) -> Result<(), Box<dyn Error>> {
-    if configuration.outputs().contains_key(output) {
-        return Ok(());
-    } else if let Ok(metadata) = context.file_system().metadata(output.as_ref()).await {
-        if metadata.is_file() {
-            remove_file(output).await?;
-        }
-    }
-
-    Ok(())
-}
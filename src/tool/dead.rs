@@ -0,0 +1,48 @@
+use crate::{context::Context, ir::Configuration};
+use futures::future::try_join_all;
+use std::error::Error;
+
+pub async fn dead(context: &Context, configuration: &Configuration) -> Result<(), Box<dyn Error>> {
+    let outputs = find_dead_outputs(context, configuration).await?;
+    let mut console = context.console().lock().await;
+
+    for output in outputs {
+        console.write_stdout(output.as_bytes()).await?;
+        console.write_stdout(b"\n").await?;
+    }
+
+    Ok(())
+}
+
+// Dead outputs are the ones built previously and still existing on a file
+// system but not defined in build files anymore.
+pub(crate) async fn find_dead_outputs(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut outputs = try_join_all(context.database().get_outputs()?.into_iter().map(
+        |output| async move {
+            Ok::<_, Box<dyn Error>>(
+                is_dead(context, configuration, &output)
+                    .await
+                    .then_some(output),
+            )
+        },
+    ))
+    .await?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    outputs.sort();
+
+    Ok(outputs)
+}
+
+async fn is_dead(context: &Context, configuration: &Configuration, output: &str) -> bool {
+    !configuration.outputs().contains_key(output)
+        && matches!(
+            context.file_system().metadata(output.as_ref()).await,
+            Ok(metadata) if metadata.is_file()
+        )
+}
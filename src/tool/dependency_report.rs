@@ -0,0 +1,198 @@
+use crate::{
+    context::Context,
+    error::ApplicationError,
+    ir::{Build, Configuration},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Write,
+    sync::Arc,
+};
+
+const SAMPLE_COUNT: usize = 10;
+
+// This prints a number of builds which rebuild when a file changes and a
+// sample of their outputs.
+pub async fn dependency_report(
+    context: &Context,
+    configuration: &Configuration,
+    file: &str,
+) -> Result<(), Box<dyn Error>> {
+    let builds = find_dependents(configuration, file)?;
+
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(format_report(file, &builds).as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+// Dependents are builds reachable through inputs and implicit inputs of builds
+// consuming a file. Order-only inputs and validations do not trigger rebuilds.
+fn find_dependents<'a>(
+    configuration: &'a Configuration,
+    file: &str,
+) -> Result<Vec<&'a Build>, ApplicationError> {
+    let mut ids = HashSet::new();
+    let mut consumers = HashMap::<&str, Vec<&Build>>::new();
+
+    for build in configuration
+        .outputs()
+        .values()
+        .filter(|build| ids.insert(build.id()))
+    {
+        for input in build.inputs() {
+            consumers.entry(input).or_default().push(build);
+        }
+    }
+
+    if !consumers.contains_key(file) && !configuration.outputs().contains_key(file) {
+        return Err(ApplicationError::OutputNotFound(file.into()));
+    }
+
+    let mut visited = HashSet::new();
+    let mut files = vec![file];
+    let mut builds = vec![];
+
+    while let Some(file) = files.pop() {
+        for &build in consumers.get(file).into_iter().flatten() {
+            if visited.insert(build.id()) {
+                builds.push(build);
+                files.extend(
+                    build
+                        .outputs()
+                        .iter()
+                        .chain(build.implicit_outputs())
+                        .map(|output| output.as_ref()),
+                );
+            }
+        }
+    }
+
+    builds.sort_by(|one, other| one.outputs().cmp(other.outputs()));
+
+    Ok(builds)
+}
+
+fn format_report(file: &str, builds: &[&Build]) -> String {
+    let mut string = format!("{} builds depend on {}\n", builds.len(), file);
+
+    for build in builds.iter().take(SAMPLE_COUNT) {
+        writeln!(
+            string,
+            "  {}",
+            build
+                .outputs()
+                .iter()
+                .map(Arc::as_ref)
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+        .unwrap();
+    }
+
+    if builds.len() > SAMPLE_COUNT {
+        writeln!(string, "  ... and {} more", builds.len() - SAMPLE_COUNT).unwrap();
+    }
+
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn compile_source(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    fn format(source: &str, file: &str) -> String {
+        format_report(
+            file,
+            &find_dependents(&compile_source(source), file).unwrap(),
+        )
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc $in -o $out
+rule link
+  command = link $in -o $out
+build foo.o: cc foo.c | common.h
+build bar.o: cc bar.c | common.h
+build baz.o: cc baz.c || common.h
+build qux.o: cc qux.c
+build app: link foo.o bar.o baz.o
+build lib.a: link qux.o
+build all: phony app lib.a
+";
+
+    #[test]
+    fn count_dependents_of_central_header() {
+        assert_eq!(
+            format(SOURCE, "common.h"),
+            "4 builds depend on common.h\n  all\n  app\n  bar.o\n  foo.o\n"
+        );
+    }
+
+    #[test]
+    fn count_dependents_of_output() {
+        assert_eq!(
+            format(SOURCE, "qux.o"),
+            "2 builds depend on qux.o\n  all\n  lib.a\n"
+        );
+    }
+
+    #[test]
+    fn count_no_dependents() {
+        assert_eq!(format(SOURCE, "all"), "0 builds depend on all\n");
+    }
+
+    #[test]
+    fn sample_dependents() {
+        let source = (0..12)
+            .map(|index| format!("build {index:02}: phony common.h\n"))
+            .collect::<String>();
+
+        assert_eq!(
+            format(&source, "common.h"),
+            [
+                "12 builds depend on common.h",
+                "  00",
+                "  01",
+                "  02",
+                "  03",
+                "  04",
+                "  05",
+                "  06",
+                "  07",
+                "  08",
+                "  09",
+                "  ... and 2 more",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn fail_to_report_unknown_file() {
+        assert!(find_dependents(&compile_source(SOURCE), "unknown").is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    context::Context,
+    error::ApplicationError,
+    infrastructure::{CommandRunner, FileSystem},
+};
+use std::{fs::read_to_string, path::Path};
+
+const SHELL_PROBE_COMMAND: &str = "exit 0";
+const PROBE_FILE: &str = ".turtle-doctor";
+const LIMITS_FILE: &str = "/proc/self/limits";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Status {
+    Pass,
+    Warning,
+    Failure,
+}
+
+// This checks if an environment runs builds and reports results of all checks
+// at once. Only warnings are reported for unknown or low open file limits as
+// builds might still succeed.
+pub async fn doctor(
+    context: &Context,
+    build_file: Result<(), ApplicationError>,
+    database_directory: &Path,
+    open_file_count: usize,
+) -> Result<(), ApplicationError> {
+    let checks = [
+        (
+            "shell",
+            result_status(check_shell(context.command_runner()).await),
+        ),
+        (
+            "database directory",
+            result_status(check_directory(context.file_system(), database_directory).await),
+        ),
+        (
+            "open file limit",
+            check_open_file_limit(open_file_limit(), open_file_count),
+        ),
+        (
+            "build file",
+            result_status(
+                build_file
+                    .map(|_| "parsed".into())
+                    .map_err(|error| error.to_string()),
+            ),
+        ),
+    ];
+    let mut console = context.console().lock().await;
+
+    for (name, (status, message)) in &checks {
+        console
+            .write_stdout(
+                format!(
+                    "{}: {}: {}\n",
+                    match status {
+                        Status::Pass => "pass",
+                        Status::Warning => "warning",
+                        Status::Failure => "fail",
+                    },
+                    name,
+                    message
+                )
+                .as_bytes(),
+            )
+            .await?;
+    }
+
+    let count = checks
+        .iter()
+        .filter(|(_, (status, _))| *status == Status::Failure)
+        .count();
+
+    if count == 0 {
+        Ok(())
+    } else {
+        Err(ApplicationError::Other(format!("{count} check(s) failed")))
+    }
+}
+
+fn result_status(result: Result<String, String>) -> (Status, String) {
+    match result {
+        Ok(message) => (Status::Pass, message),
+        Err(message) => (Status::Failure, message),
+    }
+}
+
+async fn check_shell(command_runner: &(dyn CommandRunner + Send + Sync)) -> Result<String, String> {
+    command_runner
+        .run(&[SHELL_PROBE_COMMAND.into()], &[], None)
+        .await
+        .map(|_| "runs commands".into())
+        .map_err(|error| error.to_string())
+}
+
+// A directory is created if it does not exist as builds would do so.
+async fn check_directory(
+    file_system: &(dyn FileSystem + Send + Sync),
+    directory: &Path,
+) -> Result<String, String> {
+    let path = directory.join(PROBE_FILE);
+
+    file_system
+        .create_directory(directory)
+        .await
+        .map_err(|error| error.to_string())?;
+    file_system
+        .write_file(&path, &[])
+        .await
+        .map_err(|error| error.to_string())?;
+    file_system
+        .remove_file(&path)
+        .await
+        .map_err(|error| error.to_string())?;
+
+    Ok(format!("{} is writable", directory.display()))
+}
+
+fn check_open_file_limit(limit: Option<usize>, count: usize) -> (Status, String) {
+    match limit {
+        Some(limit) if limit >= count => (Status::Pass, format!("{limit}")),
+        Some(limit) => (
+            Status::Warning,
+            format!("{limit} is lower than {count} files opened by jobs and builds"),
+        ),
+        None => (Status::Warning, "unknown".into()),
+    }
+}
+
+// This returns a soft limit of open files of the current process or `None` if
+// a platform does not provide it.
+fn open_file_limit() -> Option<usize> {
+    parse_open_file_limit(&read_to_string(LIMITS_FILE).ok()?)
+}
+
+fn parse_open_file_limit(limits: &str) -> Option<usize> {
+    limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::{FakeCommandRunner, FakeFileSystem};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn check_running_shell() {
+        assert_eq!(
+            check_shell(&FakeCommandRunner::new(&[], Duration::ZERO)).await,
+            Ok("runs commands".into())
+        );
+        assert!(check_shell(&FakeCommandRunner::new(
+            &[SHELL_PROBE_COMMAND],
+            Duration::ZERO
+        ))
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn check_writable_directory() {
+        let file_system = FakeFileSystem::new(&[]);
+
+        assert_eq!(
+            check_directory(&file_system, Path::new(".turtle")).await,
+            Ok(".turtle is writable".into())
+        );
+        assert!(file_system
+            .metadata(&Path::new(".turtle").join(PROBE_FILE))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn check_directory_blocked_by_file() {
+        assert!(check_directory(
+            &FakeFileSystem::new(&[(".turtle", "")]),
+            Path::new(".turtle")
+        )
+        .await
+        .is_err());
+    }
+
+    #[test]
+    fn check_open_file_limits() {
+        assert_eq!(check_open_file_limit(Some(1024), 1024).0, Status::Pass);
+        assert_eq!(check_open_file_limit(Some(256), 1024).0, Status::Warning);
+        assert_eq!(check_open_file_limit(None, 1024).0, Status::Warning);
+    }
+
+    #[test]
+    fn parse_limits() {
+        assert_eq!(
+            parse_open_file_limit(
+                "Limit                     Soft Limit           Hard Limit           Units\nMax open files            1024                 524288               files\n"
+            ),
+            Some(1024)
+        );
+        assert_eq!(parse_open_file_limit("Max processes 1 1 processes\n"), None);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{context::Context, hash_type::HashType, ir::Configuration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+};
+
+const HASH_TYPES: [(HashType, &str); 3] = [
+    (HashType::Timestamp, "timestamp hash"),
+    (HashType::Content, "content hash"),
+    (HashType::Dependency, "dependency hash"),
+];
+
+pub async fn dump_database(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<(), Box<dyn Error>> {
+    let dump = format_database(context, configuration)?;
+
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(dump.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+// Records are sorted so that dumps are stable across runs. Builds not defined
+// in build files anymore are named by their IDs.
+fn format_database(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<String, Box<dyn Error>> {
+    let database = context.database();
+    let mut names = HashMap::new();
+
+    for build in configuration.outputs().values() {
+        names
+            .entry(build.id())
+            .or_insert_with(|| build.outputs()[0].to_string());
+    }
+
+    let running_ids = database.get_running_builds()?;
+    let mut builds = BTreeMap::new();
+
+    for id in database.get_build_ids()? {
+        builds.insert(
+            names
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| format!("#{:016x}", u64::from_le_bytes(id.to_bytes()))),
+            id,
+        );
+    }
+
+    let mut string = String::new();
+
+    for (name, id) in builds {
+        string.push_str(&format!("build {name}\n"));
+
+        for (r#type, label) in HASH_TYPES {
+            if let Some(hash) = database.get_hash(r#type, id)? {
+                string.push_str(&format!("  {label}: {hash:016x}\n"));
+            }
+        }
+
+        let mut dependencies = database.get_dependencies(id)?;
+        dependencies.sort();
+
+        for dependency in dependencies {
+            string.push_str(&format!("  dependency: {dependency}\n"));
+        }
+
+        if running_ids.contains(&id) {
+            string.push_str("  running\n");
+        }
+    }
+
+    let mut outputs = database.get_outputs()?;
+    outputs.sort();
+
+    for output in outputs {
+        string.push_str(&format!("output {output}\n"));
+
+        if let Some(source) = database.get_source(&output)? {
+            string.push_str(&format!("  source: {source}\n"));
+        }
+    }
+
+    for target in database.get_targets()? {
+        string.push_str(&format!("target {target}\n"));
+    }
+
+    Ok(string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compile::compile,
+        infrastructure::{Database, FakeCommandRunner, OsDatabase, OsFileSystem, RecordingConsole},
+        ir::BuildId,
+        parse::parse,
+    };
+    use pretty_assertions::assert_eq;
+    use std::{path::PathBuf, time::Duration};
+    use tempfile::tempdir;
+
+    fn compile_configuration(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn dump_records() {
+        let directory = tempdir().unwrap();
+        let database = OsDatabase::new();
+        database.initialize(directory.path()).unwrap();
+        let configuration =
+            compile_configuration("rule cc\n  command = cc $out\nbuild foo.o: cc || bar\n");
+        let id = configuration.outputs()["foo.o"].id();
+
+        database.set_hash(HashType::Timestamp, id, 42).unwrap();
+        database.set_hash(HashType::Content, id, 0xff).unwrap();
+        database
+            .set_dependencies(id, &["foo.h".into(), "bar.h".into()])
+            .await
+            .unwrap();
+        database
+            .set_hash(HashType::Timestamp, BuildId::new(1), 1)
+            .unwrap();
+        database.set_running_build(BuildId::new(1)).unwrap();
+        database.set_output("foo.o").unwrap();
+        database.set_source("foo.o", "foo.c").unwrap();
+        database.set_targets(&["foo.o".into()]).unwrap();
+
+        let console = RecordingConsole::new();
+        let stdout = console.stdout();
+        let context = Context::new(
+            FakeCommandRunner::new(&[], Duration::ZERO),
+            console,
+            database,
+            OsFileSystem::new(1),
+        );
+
+        dump_database(&context, &configuration).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(stdout.lock().unwrap().clone()).unwrap(),
+            [
+                "build #0000000000000001",
+                "  timestamp hash: 0000000000000001",
+                "  running",
+                "build foo.o",
+                "  timestamp hash: 000000000000002a",
+                "  content hash: 00000000000000ff",
+                "  dependency: bar.h",
+                "  dependency: foo.h",
+                "output foo.o",
+                "  source: foo.c",
+                "target foo.o",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
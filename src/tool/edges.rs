@@ -0,0 +1,177 @@
+use crate::{
+    context::Context,
+    error::ApplicationError,
+    ir::{Build, Configuration},
+};
+use std::{collections::HashSet, error::Error, fmt::Write, sync::Arc};
+
+// This shows a build producing each file and builds consuming it as inputs,
+// order-only inputs, or validations with their rules and outputs.
+pub async fn edges(
+    context: &Context,
+    configuration: &Configuration,
+    files: &[String],
+) -> Result<(), Box<dyn Error>> {
+    if files.is_empty() {
+        return Err(ApplicationError::Other("files are required".into()).into());
+    }
+
+    let mut string = String::new();
+
+    for file in files {
+        format_edges(configuration, file, &mut string)?;
+    }
+
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(string.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+fn format_edges(
+    configuration: &Configuration,
+    file: &str,
+    string: &mut String,
+) -> Result<(), ApplicationError> {
+    let producer = configuration.outputs().get(file);
+    let mut ids = HashSet::new();
+    let mut consumers = configuration
+        .outputs()
+        .values()
+        .filter(|build| ids.insert(build.id()))
+        .filter_map(|build| Some((build, find_input_kind(build, file)?)))
+        .collect::<Vec<_>>();
+
+    if producer.is_none() && consumers.is_empty() {
+        return Err(ApplicationError::OutputNotFound(file.into()));
+    }
+
+    consumers.sort_by(|(one, _), (other, _)| one.outputs().cmp(other.outputs()));
+
+    writeln!(string, "{file}:").unwrap();
+    string.push_str("  produced by:\n");
+
+    if let Some(build) = producer {
+        format_build(build, None, string);
+    }
+
+    string.push_str("  consumed by:\n");
+
+    for (build, kind) in consumers {
+        format_build(build, kind, string);
+    }
+
+    Ok(())
+}
+
+fn find_input_kind(build: &Build, file: &str) -> Option<Option<&'static str>> {
+    let contains = |inputs: &[Arc<str>]| inputs.iter().any(|input| input.as_ref() == file);
+
+    if contains(build.inputs()) {
+        Some(None)
+    } else if contains(build.order_only_inputs()) {
+        Some(Some("order-only"))
+    } else if contains(build.validations()) {
+        Some(Some("validation"))
+    } else {
+        None
+    }
+}
+
+fn format_build(build: &Build, kind: Option<&str>, string: &mut String) {
+    write!(
+        string,
+        "    {}: {}",
+        build.rule().map(|rule| rule.name()).unwrap_or("phony"),
+        build
+            .outputs()
+            .iter()
+            .chain(build.implicit_outputs())
+            .map(|output| output.as_ref())
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+    .unwrap();
+
+    if let Some(kind) = kind {
+        write!(string, " ({kind})").unwrap();
+    }
+
+    string.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn compile_source(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc $in -o $out
+rule gen
+  command = gen $out
+build common.h | common.d: gen common.def
+build foo.o: cc foo.c common.h
+build bar.o: cc bar.c common.h
+build baz.o: cc baz.c || common.h
+build qux.o: cc qux.c
+";
+
+    #[test]
+    fn format_consumers_of_shared_header() {
+        let mut string = String::new();
+
+        format_edges(&compile_source(SOURCE), "common.h", &mut string).unwrap();
+
+        assert_eq!(
+            string,
+            [
+                "common.h:",
+                "  produced by:",
+                "    gen: common.h common.d",
+                "  consumed by:",
+                "    cc: bar.o",
+                "    cc: baz.o (order-only)",
+                "    cc: foo.o",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn format_source_file() {
+        let mut string = String::new();
+
+        format_edges(&compile_source(SOURCE), "foo.c", &mut string).unwrap();
+
+        assert_eq!(
+            string,
+            "foo.c:\n  produced by:\n  consumed by:\n    cc: foo.o\n"
+        );
+    }
+
+    #[test]
+    fn fail_to_format_unknown_file() {
+        assert!(format_edges(&compile_source(SOURCE), "unknown", &mut String::new()).is_err());
+    }
+}
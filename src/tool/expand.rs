@@ -0,0 +1,250 @@
+use crate::{
+    context::Context,
+    ir::{Configuration, TEMPORARY_DIRECTORY_PLACEHOLDER},
+};
+use std::{collections::HashSet, error::Error, fmt::Write};
+
+const CONSOLE_POOL: &str = "console";
+
+// This prints builds with all build files inlined and all variables resolved
+// in Ninja's syntax. Every build has its own rule of concrete commands.
+pub async fn expand(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<(), Box<dyn Error>> {
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(format_configuration(configuration)?.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+fn format_configuration(configuration: &Configuration) -> Result<String, Box<dyn Error>> {
+    let mut builds = configuration.outputs().values().collect::<Vec<_>>();
+    let mut visited_builds = HashSet::new();
+
+    builds.sort_by_key(|build| build.outputs()[0].clone());
+    builds.retain(|build| visited_builds.insert(build.id()));
+
+    let mut string = String::new();
+
+    if let Some(directory) = configuration.build_directory() {
+        writeln!(string, "builddir = {}\n", escape(directory))?;
+    }
+
+    let mut pools = builds
+        .iter()
+        .flat_map(|build| build.rule().and_then(|rule| rule.pool()))
+        .filter(|pool| pool.name().as_ref() != CONSOLE_POOL)
+        .map(|pool| (pool.name(), pool.depth()))
+        .collect::<Vec<_>>();
+
+    pools.sort();
+    pools.dedup();
+
+    for (name, depth) in pools {
+        writeln!(string, "pool {name}\n  depth = {depth}\n")?;
+    }
+
+    for (index, build) in builds.into_iter().enumerate() {
+        let rule_name = if let Some(rule) = build.rule() {
+            let name = format!("rule{index}");
+
+            writeln!(string, "rule {name}")?;
+
+            match rule.commands() {
+                [command] => writeln!(string, "  command = {}", escape(command))?,
+                commands => {
+                    for (index, command) in commands.iter().enumerate() {
+                        writeln!(string, "  command[{index}] = {}", escape(command))?;
+                    }
+                }
+            }
+
+            for (name, value) in [
+                ("description", rule.description()),
+                ("status", rule.status()),
+                ("depfile", rule.depfile()),
+                ("rspfile", rule.response_file().map(|file| file.path())),
+                (
+                    "rspfile_content",
+                    rule.response_file().map(|file| file.content()),
+                ),
+            ] {
+                if let Some(value) = value {
+                    writeln!(string, "  {name} = {}", escape(value))?;
+                }
+            }
+
+            if let Some(prefix) = rule.msvc_deps_prefix() {
+                writeln!(string, "  deps = msvc")?;
+                writeln!(string, "  msvc_deps_prefix = {}", escape(prefix))?;
+            }
+
+            if !rule.environment().is_empty() {
+                writeln!(
+                    string,
+                    "  env = {}",
+                    rule.environment()
+                        .iter()
+                        .map(|(name, value)| escape(&format!("{name}={value}")))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )?;
+            }
+
+            if let Some(encoding) = rule.output_encoding() {
+                writeln!(string, "  output_encoding = {}", encoding.name())?;
+            }
+
+            if let Some(pool) = rule.pool() {
+                writeln!(string, "  pool = {}", pool.name())?;
+            }
+
+            if rule.always() {
+                writeln!(string, "  always = 1")?;
+            }
+
+            if !rule.mkdir() {
+                writeln!(string, "  mkdir = 0")?;
+            }
+
+            writeln!(string)?;
+
+            name
+        } else {
+            "phony".into()
+        };
+
+        write!(string, "build {}", join_paths(build.outputs()))?;
+
+        if !build.implicit_outputs().is_empty() {
+            write!(string, " | {}", join_paths(build.implicit_outputs()))?;
+        }
+
+        write!(string, ": {rule_name}")?;
+
+        if !build.inputs().is_empty() {
+            write!(string, " {}", join_paths(build.inputs()))?;
+        }
+
+        if !build.order_only_inputs().is_empty() {
+            write!(string, " || {}", join_paths(build.order_only_inputs()))?;
+        }
+
+        if !build.validations().is_empty() {
+            write!(string, " |@ {}", join_paths(build.validations()))?;
+        }
+
+        writeln!(string)?;
+
+        if let Some(module) = build.dynamic_module() {
+            writeln!(string, "  dyndep = {}", escape(module))?;
+        }
+
+        writeln!(string)?;
+    }
+
+    let mut default_outputs = configuration
+        .default_outputs()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    default_outputs.sort();
+
+    if !default_outputs.is_empty() {
+        writeln!(string, "default {}", join_paths(&default_outputs))?;
+    }
+
+    Ok(string)
+}
+
+fn join_paths(paths: &[impl AsRef<str>]) -> String {
+    paths
+        .iter()
+        .map(|path| escape(path.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Temporary directories of runs are left as variables.
+fn escape(string: &str) -> String {
+    string
+        .replace('$', "$$")
+        .replace(TEMPORARY_DIRECTORY_PLACEHOLDER, "$tmpdir")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use std::path::PathBuf;
+
+    #[test]
+    fn expand_multiple_build_files() {
+        let root_path = PathBuf::from("build.ninja");
+        let submodule_path = PathBuf::from("foo.ninja");
+
+        let string = format_configuration(
+            &compile(
+                &[
+                    (
+                        root_path.clone(),
+                        parse(
+                            "
+cflags = -O2
+rule cc
+  command = cc $cflags -c $in -o $out
+include foo.ninja
+build foo.o: cc foo.c
+",
+                        )
+                        .unwrap(),
+                    ),
+                    (
+                        submodule_path.clone(),
+                        parse(
+                            "
+cflags = -O0
+rule ld
+  command = ld $in -o $out
+  description = link $out
+build foo: ld foo.o || bar
+build bar: phony
+",
+                        )
+                        .unwrap(),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+                &[
+                    (
+                        root_path.clone(),
+                        [("foo.ninja".into(), submodule_path.clone())]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    (submodule_path, Default::default()),
+                ]
+                .into_iter()
+                .collect(),
+                &root_path,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert!(string.contains("  command = ld foo.o -o foo\n  description = link foo\n"));
+        assert!(string.contains("  command = cc -O0 -c foo.c -o foo.o\n"));
+        assert!(string.contains("build foo: rule1 foo.o || bar\n"));
+        assert!(string.contains("build foo.o: rule2 foo.c\n"));
+        assert!(string.contains("build bar: phony\n"));
+        assert!(string.ends_with("default bar foo foo.o\n"));
+        assert!(parse(&string).is_ok());
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    compile::ExpansionLayer, context::Context, error::ApplicationError,
+    ir::TEMPORARY_DIRECTORY_PLACEHOLDER,
+};
+
+// Each line shows a command expanded up to a scope labeled at its start.
+// Temporary directories of runs are left as variables.
+pub async fn explain_command(
+    context: &Context,
+    output: &str,
+    layers: Option<&[ExpansionLayer]>,
+) -> Result<(), ApplicationError> {
+    let layers = layers.ok_or_else(|| ApplicationError::OutputNotFound(output.into()))?;
+    let mut console = context.console().lock().await;
+
+    for layer in layers {
+        for command in layer.commands() {
+            console
+                .write_stdout(
+                    format!(
+                        "{}: {}\n",
+                        layer.name(),
+                        command.replace(TEMPORARY_DIRECTORY_PLACEHOLDER, "$tmpdir")
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use crate::{
+    error::ApplicationError,
+    ir::{Configuration, TEMPORARY_DIRECTORY_PLACEHOLDER},
+};
+use rusqlite::{params, Connection};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    error::Error,
+    fs::remove_file,
+    io::ErrorKind,
+    path::Path,
+};
+
+// Builds are edges from input nodes to output nodes. Phony builds have no
+// rules. Input kinds are `input`, `order_only`, and `validation`.
+const SCHEMA: &str = "
+CREATE TABLE rules (
+  id INTEGER PRIMARY KEY,
+  name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE nodes (
+  id INTEGER PRIMARY KEY,
+  path TEXT NOT NULL UNIQUE
+);
+CREATE TABLE edges (
+  id INTEGER PRIMARY KEY,
+  rule_id INTEGER REFERENCES rules (id),
+  command TEXT,
+  description TEXT,
+  pool TEXT
+);
+CREATE TABLE edge_outputs (
+  edge_id INTEGER NOT NULL REFERENCES edges (id),
+  node_id INTEGER NOT NULL REFERENCES nodes (id),
+  implicit INTEGER NOT NULL,
+  PRIMARY KEY (edge_id, node_id)
+);
+CREATE TABLE edge_inputs (
+  edge_id INTEGER NOT NULL REFERENCES edges (id),
+  node_id INTEGER NOT NULL REFERENCES nodes (id),
+  kind TEXT NOT NULL
+);
+CREATE INDEX edge_inputs_node_id ON edge_inputs (node_id);
+";
+
+// This writes a build graph into a new SQLite database at a path replacing an
+// existing file for ad-hoc queries.
+pub async fn export_sqlite(
+    configuration: &Configuration,
+    outputs: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let [path] = outputs else {
+        return Err(
+            ApplicationError::Other("a path of a SQLite database is required".into()).into(),
+        );
+    };
+
+    match remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+        _ => {}
+    }
+
+    export(configuration, &mut Connection::open(Path::new(path))?)?;
+
+    Ok(())
+}
+
+fn export(configuration: &Configuration, connection: &mut Connection) -> rusqlite::Result<()> {
+    let mut ids = HashSet::new();
+    let mut builds = configuration
+        .outputs()
+        .values()
+        .filter(|build| ids.insert(build.id()))
+        .collect::<Vec<_>>();
+
+    builds.sort_by(|one, other| one.outputs().cmp(other.outputs()));
+
+    let rules = builds
+        .iter()
+        .flat_map(|build| build.rule())
+        .map(|rule| rule.name())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .zip(1..)
+        .collect::<BTreeMap<_, i64>>();
+    let nodes = builds
+        .iter()
+        .flat_map(|build| {
+            build
+                .outputs()
+                .iter()
+                .chain(build.implicit_outputs())
+                .chain(build.inputs())
+                .chain(build.order_only_inputs())
+                .chain(build.validations())
+        })
+        .map(|path| path.as_ref())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .zip(1..)
+        .collect::<BTreeMap<_, i64>>();
+
+    let transaction = connection.transaction()?;
+
+    transaction.execute_batch(SCHEMA)?;
+
+    for (name, id) in &rules {
+        transaction.execute("INSERT INTO rules VALUES (?1, ?2)", params![id, name])?;
+    }
+
+    for (path, id) in &nodes {
+        transaction.execute("INSERT INTO nodes VALUES (?1, ?2)", params![id, path])?;
+    }
+
+    for (build, edge_id) in builds.iter().zip(1..) {
+        let rule = build.rule();
+
+        transaction.execute(
+            "INSERT INTO edges VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                edge_id,
+                rule.map(|rule| rules[rule.name()]),
+                rule.map(|rule| show_temporary_directory(&rule.commands().join("\n"))),
+                rule.and_then(|rule| rule.description())
+                    .map(show_temporary_directory),
+                rule.and_then(|rule| rule.pool())
+                    .map(|pool| pool.name().as_ref()),
+            ],
+        )?;
+
+        for (outputs, implicit) in [(build.outputs(), false), (build.implicit_outputs(), true)] {
+            for output in outputs {
+                transaction.execute(
+                    "INSERT OR IGNORE INTO edge_outputs VALUES (?1, ?2, ?3)",
+                    params![edge_id, nodes[output.as_ref()], implicit],
+                )?;
+            }
+        }
+
+        for (inputs, kind) in [
+            (build.inputs(), "input"),
+            (build.order_only_inputs(), "order_only"),
+            (build.validations(), "validation"),
+        ] {
+            for input in inputs {
+                transaction.execute(
+                    "INSERT INTO edge_inputs VALUES (?1, ?2, ?3)",
+                    params![edge_id, nodes[input.as_ref()], kind],
+                )?;
+            }
+        }
+    }
+
+    transaction.commit()
+}
+
+// Temporary directories of runs are left as variables.
+fn show_temporary_directory(string: &str) -> String {
+    string.replace(TEMPORARY_DIRECTORY_PLACEHOLDER, "$tmpdir")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn export_source(source: &str) -> Connection {
+        let path = PathBuf::from("build.ninja");
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        export(
+            &compile(
+                &[(path.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
+                &[(path.clone(), Default::default())].into_iter().collect(),
+                &path,
+            )
+            .unwrap(),
+            &mut connection,
+        )
+        .unwrap();
+
+        connection
+    }
+
+    fn count(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc -c $in -o $out
+rule link
+  command = link $in -o $out
+build foo.o: cc foo.c | common.h
+build bar.o: cc bar.c | common.h
+build app | app.map: link foo.o bar.o || generated
+build generated: phony
+";
+
+    #[test]
+    fn export_rows() {
+        let connection = export_source(SOURCE);
+
+        assert_eq!(count(&connection, "rules"), 2);
+        assert_eq!(count(&connection, "nodes"), 8);
+        assert_eq!(count(&connection, "edges"), 4);
+        assert_eq!(count(&connection, "edge_outputs"), 5);
+        assert_eq!(count(&connection, "edge_inputs"), 7);
+    }
+
+    #[test]
+    fn query_consumers_of_file() {
+        let connection = export_source(SOURCE);
+        let mut statement = connection
+            .prepare(
+                "SELECT output.path, rules.name FROM nodes AS input
+                JOIN edge_inputs ON edge_inputs.node_id = input.id
+                JOIN edges ON edges.id = edge_inputs.edge_id
+                JOIN rules ON rules.id = edges.rule_id
+                JOIN edge_outputs ON edge_outputs.edge_id = edges.id
+                JOIN nodes AS output ON output.id = edge_outputs.node_id
+                WHERE input.path = 'common.h'
+                ORDER BY output.path",
+            )
+            .unwrap();
+
+        assert_eq!(
+            statement
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<Vec<(String, String)>, _>>()
+                .unwrap(),
+            vec![("bar.o".into(), "cc".into()), ("foo.o".into(), "cc".into())]
+        );
+    }
+
+    #[test]
+    fn export_phony_edge_without_rule() {
+        let connection = export_source(SOURCE);
+
+        assert_eq!(
+            connection
+                .query_row(
+                    "SELECT COUNT(*) FROM edges WHERE rule_id IS NULL AND command IS NULL",
+                    [],
+                    |row| row.get::<_, i64>(0)
+                )
+                .unwrap(),
+            1
+        );
+    }
+}
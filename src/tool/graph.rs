@@ -0,0 +1,282 @@
+use crate::{
+    context::Context,
+    error::ApplicationError,
+    ir::{Build, Configuration},
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    path::Path,
+    sync::Arc,
+};
+
+// An edge from an input to an output with a rule name and whether the input is
+// order-only
+type Edge<'a> = (&'a str, &'a str, Option<&'a str>, bool);
+
+// This prints builds which outputs depend on transitively as a graph in DOT or
+// Mermaid. Default outputs are used if none is given.
+pub async fn graph(
+    context: &Context,
+    configuration: &Configuration,
+    outputs: &[String],
+    mermaid: bool,
+) -> Result<(), Box<dyn Error>> {
+    let builds = find_builds(configuration, outputs)?;
+    let string = if mermaid {
+        format_mermaid(&builds)
+    } else {
+        format_dot(&builds)
+    };
+
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(string.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+fn find_builds(
+    configuration: &Configuration,
+    outputs: &[String],
+) -> Result<Vec<Arc<Build>>, ApplicationError> {
+    let mut pending = if outputs.is_empty() {
+        configuration.default_outputs().iter().cloned().collect()
+    } else {
+        outputs
+            .iter()
+            .map(|output| Arc::from(output.as_str()))
+            .collect::<Vec<_>>()
+    };
+
+    for output in &pending {
+        if !configuration.outputs().contains_key(output) {
+            return Err(ApplicationError::OutputNotFound(output.to_string()));
+        }
+    }
+
+    let mut ids = HashSet::new();
+    let mut builds = vec![];
+
+    while let Some(output) = pending.pop() {
+        if let Some(build) = configuration.outputs().get(&output) {
+            if ids.insert(build.id()) {
+                pending.extend(
+                    build
+                        .inputs()
+                        .iter()
+                        .chain(build.order_only_inputs())
+                        .cloned(),
+                );
+                builds.push(build.clone());
+            }
+        }
+    }
+
+    builds.sort_by(|one, other| one.outputs().cmp(other.outputs()));
+
+    Ok(builds)
+}
+
+// Nodes are files with IDs in a sorted order of their paths so that graphs are
+// stable across runs.
+fn collect_graph(builds: &[Arc<Build>]) -> (BTreeMap<&str, usize>, Vec<Edge<'_>>) {
+    let mut edges = vec![];
+
+    for build in builds {
+        let rule = build.rule().map(|rule| rule.name());
+
+        for output in build.outputs().iter().chain(build.implicit_outputs()) {
+            for input in build.inputs() {
+                edges.push((input.as_ref(), output.as_ref(), rule, false));
+            }
+
+            for input in build.order_only_inputs() {
+                edges.push((input.as_ref(), output.as_ref(), rule, true));
+            }
+        }
+    }
+
+    let mut nodes = edges
+        .iter()
+        .flat_map(|&(input, output, _, _)| [(input, 0), (output, 0)])
+        .chain(
+            builds
+                .iter()
+                .flat_map(|build| build.outputs())
+                .map(|output| (output.as_ref(), 0)),
+        )
+        .collect::<BTreeMap<_, _>>();
+
+    for (index, id) in nodes.values_mut().enumerate() {
+        *id = index;
+    }
+
+    (nodes, edges)
+}
+
+fn format_dot(builds: &[Arc<Build>]) -> String {
+    let (nodes, edges) = collect_graph(builds);
+    let mut string = String::from("digraph turtle {\n  rankdir=\"LR\"\n  node [shape=box]\n");
+
+    for (path, id) in &nodes {
+        string.push_str(&format!("  n{id} [label=\"{}\"]\n", escape_dot(path)));
+    }
+
+    for (input, output, rule, order_only) in edges {
+        let mut attributes = vec![];
+
+        if let Some(rule) = rule {
+            attributes.push(format!("label=\"{}\"", escape_dot(rule)));
+        }
+
+        if order_only {
+            attributes.push("style=dotted".into());
+        }
+
+        string.push_str(&format!("  n{} -> n{}", nodes[input], nodes[output]));
+
+        if !attributes.is_empty() {
+            string.push_str(&format!(" [{}]", attributes.join(", ")));
+        }
+
+        string.push('\n');
+    }
+
+    string.push_str("}\n");
+
+    string
+}
+
+// Labels are base names of paths for readability.
+fn format_mermaid(builds: &[Arc<Build>]) -> String {
+    let (nodes, edges) = collect_graph(builds);
+    let mut string = String::from("graph LR\n");
+
+    for (path, id) in &nodes {
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(path);
+
+        string.push_str(&format!("  n{id}[\"{}\"]\n", escape_mermaid(name)));
+    }
+
+    for (input, output, rule, order_only) in edges {
+        string.push_str(&format!(
+            "  n{} {}{} n{}\n",
+            nodes[input],
+            if order_only { "-.->" } else { "-->" },
+            rule.map(|rule| format!("|{}|", escape_mermaid(rule)))
+                .unwrap_or_default(),
+            nodes[output]
+        ));
+    }
+
+    string
+}
+
+fn escape_dot(string: &str) -> String {
+    string.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(string: &str) -> String {
+    string.replace('"', "#quot;").replace('|', "#124;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn find(source: &str, outputs: &[&str]) -> Vec<Arc<Build>> {
+        let path = PathBuf::from("build.ninja");
+
+        find_builds(
+            &compile(
+                &[(path.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
+                &[(path.clone(), Default::default())].into_iter().collect(),
+                &path,
+            )
+            .unwrap(),
+            &outputs
+                .iter()
+                .map(|&output| output.into())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc $out
+build obj/foo.o: cc src/foo.c
+build obj/bar.o: cc src/bar.c || gen/bar.h
+build gen/bar.h: cc
+build bin/foo: cc obj/foo.o obj/bar.o
+build unused: cc
+";
+
+    #[test]
+    fn format_mermaid_graph() {
+        let string = format_mermaid(&find(SOURCE, &["bin/foo"]));
+        let lines = string.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "graph LR");
+        assert_eq!(lines.iter().filter(|line| line.ends_with("\"]")).count(), 6);
+        assert_eq!(lines.iter().filter(|line| line.contains("-->")).count(), 4);
+        assert_eq!(lines.iter().filter(|line| line.contains("-.->")).count(), 1);
+        assert_eq!(lines.len(), 12);
+        assert!(lines.contains(&"  n0[\"foo\"]"));
+        assert!(lines.contains(&"  n5 -->|cc| n3"));
+    }
+
+    #[test]
+    fn format_dot_graph() {
+        assert_eq!(
+            format_dot(&find(
+                "rule cc\n  command = cc $out\nbuild foo: cc bar || baz\n",
+                &[]
+            )),
+            [
+                "digraph turtle {",
+                "  rankdir=\"LR\"",
+                "  node [shape=box]",
+                "  n0 [label=\"bar\"]",
+                "  n1 [label=\"baz\"]",
+                "  n2 [label=\"foo\"]",
+                "  n0 -> n2 [label=\"cc\"]",
+                "  n1 -> n2 [label=\"cc\", style=dotted]",
+                "}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn use_unique_ids_for_same_base_names() {
+        let string = format_mermaid(&find(
+            "rule cc\n  command = cc $out\nbuild a/foo: cc b/foo\n",
+            &[],
+        ));
+
+        assert_eq!(
+            string,
+            "graph LR\n  n0[\"foo\"]\n  n1[\"foo\"]\n  n1 -->|cc| n0\n"
+        );
+    }
+
+    #[test]
+    fn escape_labels() {
+        assert_eq!(escape_mermaid("a\"b|c"), "a#quot;b#124;c");
+        assert_eq!(escape_dot("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    context::Context,
+    error::ApplicationError,
+    ir::{Build, Configuration},
+};
+use std::{collections::HashSet, error::Error, sync::Arc};
+
+pub async fn inputs(
+    context: &Context,
+    configuration: &Configuration,
+    outputs: &[String],
+    dependency_order: bool,
+) -> Result<(), Box<dyn Error>> {
+    let inputs = find_inputs(configuration, outputs, dependency_order)?;
+
+    write_inputs(context, &inputs).await
+}
+
+pub async fn print_inputs(
+    context: &Context,
+    configuration: &Configuration,
+    output: &str,
+) -> Result<(), Box<dyn Error>> {
+    let inputs = find_source_inputs(configuration, output)?;
+
+    write_inputs(context, &inputs).await
+}
+
+async fn write_inputs(context: &Context, inputs: &[Arc<str>]) -> Result<(), Box<dyn Error>> {
+    let mut console = context.console().lock().await;
+
+    for input in inputs {
+        console.write_stdout(input.as_bytes()).await?;
+        console.write_stdout(b"\n").await?;
+    }
+
+    Ok(())
+}
+
+// Inputs are transitive inputs and order-only inputs of outputs or default
+// outputs if none is given. They are sorted or in an order where inputs come
+// before outputs depending on them.
+pub(crate) fn find_inputs(
+    configuration: &Configuration,
+    outputs: &[String],
+    dependency_order: bool,
+) -> Result<Vec<Arc<str>>, Box<dyn Error>> {
+    let outputs = if outputs.is_empty() {
+        configuration.default_outputs().iter().cloned().collect()
+    } else {
+        outputs
+            .iter()
+            .map(|output| Arc::from(output.as_str()))
+            .collect::<Vec<_>>()
+    };
+    let mut visited = HashSet::new();
+    let mut inputs = vec![];
+
+    for output in &outputs {
+        let build = configuration
+            .outputs()
+            .get(output)
+            .ok_or_else(|| ApplicationError::OutputNotFound(output.to_string()))?;
+
+        visit_build(configuration, build, &mut visited, &mut inputs);
+    }
+
+    if !dependency_order {
+        inputs.sort();
+    }
+
+    Ok(inputs)
+}
+
+fn visit_build(
+    configuration: &Configuration,
+    build: &Build,
+    visited: &mut HashSet<Arc<str>>,
+    inputs: &mut Vec<Arc<str>>,
+) {
+    for input in build.inputs().iter().chain(build.order_only_inputs()) {
+        if !visited.insert(input.clone()) {
+            continue;
+        } else if let Some(build) = configuration.outputs().get(input) {
+            visit_build(configuration, build, visited, inputs);
+        }
+
+        inputs.push(input.clone());
+    }
+}
+
+// Source inputs are transitive inputs not generated by any builds.
+pub(crate) fn find_source_inputs(
+    configuration: &Configuration,
+    output: &str,
+) -> Result<Vec<Arc<str>>, Box<dyn Error>> {
+    Ok(find_inputs(configuration, &[output.into()], false)?
+        .into_iter()
+        .filter(|input| !configuration.outputs().contains_key(input))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use std::path::PathBuf;
+
+    fn compile_source(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    fn find(source: &str, output: &str) -> Result<Vec<Arc<str>>, Box<dyn Error>> {
+        find_source_inputs(&compile_source(source), output)
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc
+build foo.o: cc foo.c | foo.h
+build bar.o: cc bar.c | foo.h
+build generated.h: cc generator.py
+build foo.a: cc foo.o bar.o || generated.h
+build all: phony foo.a
+default all
+";
+
+    #[test]
+    fn find_leaf_sources() {
+        assert_eq!(
+            find(SOURCE, "all").unwrap(),
+            vec![
+                Arc::from("bar.c"),
+                "foo.c".into(),
+                "foo.h".into(),
+                "generator.py".into()
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_to_find_sources_of_unknown_output() {
+        assert!(find("", "foo").is_err());
+    }
+
+    #[test]
+    fn find_all_inputs() {
+        let inputs = [
+            "bar.c",
+            "bar.o",
+            "foo.a",
+            "foo.c",
+            "foo.h",
+            "foo.o",
+            "generated.h",
+            "generator.py",
+        ]
+        .into_iter()
+        .map(Arc::from)
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            find_inputs(&compile_source(SOURCE), &["all".into()], false).unwrap(),
+            inputs
+        );
+        assert_eq!(
+            find_inputs(&compile_source(SOURCE), &[], false).unwrap(),
+            inputs
+        );
+    }
+
+    #[test]
+    fn find_inputs_in_dependency_order() {
+        assert_eq!(
+            find_inputs(&compile_source(SOURCE), &["foo.a".into()], true).unwrap(),
+            [
+                "foo.c",
+                "foo.h",
+                "foo.o",
+                "bar.c",
+                "bar.o",
+                "generator.py",
+                "generated.h"
+            ]
+            .into_iter()
+            .map(Arc::from)
+            .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    context::Context,
+    ir::{Configuration, Rule, TEMPORARY_DIRECTORY_PLACEHOLDER},
+};
+use std::{collections::HashSet, error::Error, fmt::Write, path::Path};
+
+const SHELL: &str = "nu";
+const DEFAULT_TARGET: &str = "turtle-default";
+const TEMPORARY_DIRECTORY_VARIABLE: &str = "tmpdir";
+const TEMPORARY_DIRECTORY: &str = ".turtle/tmp/make";
+
+// This prints builds as a GNU Makefile so that they can run without Turtle.
+// Depfiles and pools are not supported.
+pub async fn makefile(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<(), Box<dyn Error>> {
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(format_makefile(configuration)?.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+fn format_makefile(configuration: &Configuration) -> Result<String, Box<dyn Error>> {
+    let mut builds = configuration.outputs().values().collect::<Vec<_>>();
+    let mut visited_builds = HashSet::new();
+
+    builds.sort_by_key(|build| build.outputs()[0].clone());
+    builds.retain(|build| visited_builds.insert(build.id()));
+
+    let mut default_outputs = configuration
+        .default_outputs()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    default_outputs.sort();
+
+    let mut string = String::new();
+
+    writeln!(string, "SHELL := {SHELL}")?;
+
+    // Temporary directories of runs are shared by builds in Makefiles.
+    if builds
+        .iter()
+        .filter_map(|build| build.rule())
+        .any(uses_temporary_directory)
+    {
+        writeln!(
+            string,
+            "{TEMPORARY_DIRECTORY_VARIABLE} := {}",
+            escape_command(
+                &Path::new(
+                    configuration
+                        .build_directory()
+                        .map(|directory| directory.as_ref())
+                        .unwrap_or_default()
+                )
+                .join(TEMPORARY_DIRECTORY)
+                .display()
+                .to_string()
+            )
+        )?;
+    }
+
+    writeln!(string, ".DEFAULT_GOAL := {DEFAULT_TARGET}\n")?;
+    writeln!(
+        string,
+        ".PHONY: {}",
+        [DEFAULT_TARGET.into()]
+            .iter()
+            .chain(
+                builds
+                    .iter()
+                    .filter(|build| build.rule().is_none())
+                    .flat_map(|build| build.outputs())
+            )
+            .map(|path| escape_path(path))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )?;
+    writeln!(
+        string,
+        "{DEFAULT_TARGET}:{}\n",
+        join_paths(&default_outputs)
+    )?;
+
+    for (index, build) in builds.into_iter().enumerate() {
+        let output = &build.outputs()[0];
+
+        write!(
+            string,
+            "{}:{}",
+            escape_path(output),
+            join_paths(build.inputs())
+        )?;
+
+        if !build.order_only_inputs().is_empty() || !build.validations().is_empty() {
+            write!(
+                string,
+                " |{}",
+                join_paths(
+                    &build
+                        .order_only_inputs()
+                        .iter()
+                        .chain(build.validations())
+                        .collect::<Vec<_>>()
+                )
+            )?;
+        }
+
+        writeln!(string)?;
+
+        if let Some(rule) = build.rule() {
+            if rule.mkdir() {
+                let mut directories = build
+                    .outputs()
+                    .iter()
+                    .chain(build.implicit_outputs())
+                    .filter_map(|output| Path::new(output.as_ref()).parent())
+                    .filter(|directory| !directory.as_os_str().is_empty())
+                    .map(|directory| directory.display().to_string())
+                    .collect::<Vec<_>>();
+
+                directories.sort();
+                directories.dedup();
+
+                for directory in directories {
+                    writeln!(string, "\tmkdir {}", escape_command(&directory))?;
+                }
+            }
+
+            if uses_temporary_directory(rule) {
+                writeln!(string, "\tmkdir $({TEMPORARY_DIRECTORY_VARIABLE})")?;
+            }
+
+            if let Some(file) = rule.response_file() {
+                writeln!(
+                    string,
+                    "\t$(file >{},$(response_file{index}))",
+                    escape_command(file.path())
+                )?;
+            }
+
+            for command in rule.commands() {
+                for line in command.lines() {
+                    writeln!(string, "\t{}", escape_command(line))?;
+                }
+            }
+
+            if let Some(file) = rule.response_file() {
+                write!(
+                    string,
+                    "\ndefine response_file{index}\n{}\nendef\n",
+                    escape_command(file.content())
+                )?;
+            }
+        }
+
+        // Secondary outputs are updated by primary ones.
+        for secondary in build
+            .outputs()
+            .iter()
+            .skip(1)
+            .chain(build.implicit_outputs())
+        {
+            writeln!(
+                string,
+                "{}: {}",
+                escape_path(secondary),
+                escape_path(output)
+            )?;
+        }
+
+        writeln!(string)?;
+    }
+
+    Ok(string)
+}
+
+fn join_paths(paths: &[impl AsRef<str>]) -> String {
+    paths
+        .iter()
+        .map(|path| format!(" {}", escape_path(path.as_ref())))
+        .collect()
+}
+
+fn escape_path(path: &str) -> String {
+    let mut string = String::new();
+
+    for character in path.chars() {
+        match character {
+            '$' => string.push_str("$$"),
+            ' ' | '#' | ':' | '%' => {
+                string.push('\\');
+                string.push(character);
+            }
+            _ => string.push(character),
+        }
+    }
+
+    string
+}
+
+fn escape_command(command: &str) -> String {
+    command.replace('$', "$$").replace(
+        TEMPORARY_DIRECTORY_PLACEHOLDER,
+        &format!("$({TEMPORARY_DIRECTORY_VARIABLE})"),
+    )
+}
+
+fn uses_temporary_directory(rule: &Rule) -> bool {
+    rule.commands()
+        .iter()
+        .map(|command| command.as_str())
+        .chain(rule.response_file().map(|file| file.content()))
+        .any(|string| string.contains(TEMPORARY_DIRECTORY_PLACEHOLDER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use std::{fs, path::PathBuf, process::Command};
+    use tempfile::tempdir;
+
+    fn format(source: &str) -> String {
+        let path = PathBuf::from("build.ninja");
+
+        format_makefile(
+            &compile(
+                &[(path.clone(), parse(source).unwrap())]
+                    .into_iter()
+                    .collect(),
+                &[(path.clone(), Default::default())].into_iter().collect(),
+                &path,
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc -c $in -o $out
+rule ld
+  command = ld $in -o $out
+  rspfile = $out.rsp
+  rspfile_content = $in
+build foo.o: cc foo.c
+build out/bar.o | out/bar.d: cc bar.c || foo.o
+build foo: ld foo.o out/bar.o
+build all: phony foo
+default all
+";
+
+    #[test]
+    fn format_builds() {
+        let string = format(SOURCE);
+
+        assert!(string.contains(".PHONY: turtle-default all\n"));
+        assert!(string.contains("turtle-default: all\n"));
+        assert!(string.contains("foo.o: foo.c\n\tcc -c foo.c -o foo.o\n"));
+        assert!(string.contains(
+            "out/bar.o: bar.c | foo.o\n\tmkdir out\n\tcc -c bar.c -o out/bar.o\nout/bar.d: out/bar.o\n"
+        ));
+        assert!(
+            string.contains("\t$(file >foo.rsp,$(response_file1))\n\tld foo.o out/bar.o -o foo\n")
+        );
+        assert!(string.contains("all: foo\n"));
+    }
+
+    #[test]
+    fn format_temporary_directory() {
+        let string = format(
+            "builddir = build\nrule cc\n  command = cc -o $tmpdir/foo.o $in && mv $tmpdir/foo.o $out\nbuild foo.o: cc foo.c\n",
+        );
+
+        assert!(!string.contains('\0'));
+        assert!(string.contains("tmpdir := build/.turtle/tmp/make\n"));
+        assert!(string.contains(
+            "\tmkdir $(tmpdir)\n\tcc -o $(tmpdir)/foo.o foo.c && mv $(tmpdir)/foo.o foo.o\n"
+        ));
+        assert!(!format(SOURCE).contains("tmpdir"));
+    }
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(escape_path("foo bar$#:%.c"), "foo\\ bar$$\\#\\:\\%.c");
+        assert_eq!(escape_command("echo $HOME"), "echo $$HOME");
+    }
+
+    #[test]
+    fn parse_makefile_with_make() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("Makefile");
+
+        fs::write(&path, format(SOURCE)).unwrap();
+
+        for name in ["foo.c", "bar.c"] {
+            fs::write(directory.path().join(name), "").unwrap();
+        }
+
+        // Make is not available on every platform.
+        let Ok(output) = Command::new("make")
+            .arg("-n")
+            .arg("-f")
+            .arg(&path)
+            .current_dir(directory.path())
+            .output()
+        else {
+            return;
+        };
+
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        assert!(stdout.contains("cc -c foo.c -o foo.o\n"));
+        assert!(stdout.contains("ld foo.o out/bar.o -o foo\n"));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{context::Context, ir::Configuration};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+use tokio::fs::write;
+
+const FILE_NAME: &str = ".ninja_deps";
+const SIGNATURE: &[u8] = b"# ninjadeps\n";
+const VERSION: u32 = 4;
+const DEPENDENCY_RECORD_FLAG: u32 = 1 << 31;
+
+// This exports dependencies recorded from depfiles into a file of Ninja's
+// deps log format at a build directory.
+pub async fn ninja_deps(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<(), Box<dyn Error>> {
+    let mut builds = configuration
+        .outputs()
+        .values()
+        .filter(|build| build.rule().is_some())
+        .collect::<Vec<_>>();
+    let mut visited_builds = HashSet::new();
+
+    builds.sort_by_key(|build| build.outputs()[0].clone());
+    builds.retain(|build| visited_builds.insert(build.id()));
+
+    let mut records = vec![];
+
+    for build in builds {
+        let dependencies = context.database().get_dependencies(build.id())?;
+
+        if dependencies.is_empty() {
+            continue;
+        }
+
+        let output = build.outputs()[0].to_string();
+        // Ninja considers outputs older than recorded ones dirty.
+        let modified_time = context
+            .file_system()
+            .metadata(output.as_ref())
+            .await
+            .ok()
+            .and_then(|metadata| metadata.modified_time().duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+
+        records.push((output, modified_time, dependencies));
+    }
+
+    write(
+        configuration
+            .build_directory()
+            .map(|directory| Path::new(directory.as_ref()))
+            .unwrap_or_else(|| Path::new("."))
+            .join(FILE_NAME),
+        encode(&records),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn encode(records: &[(String, u64, Vec<String>)]) -> Vec<u8> {
+    let mut bytes = SIGNATURE.to_vec();
+    let mut ids = HashMap::<&str, u32>::new();
+
+    bytes.extend(VERSION.to_le_bytes());
+
+    for (output, modified_time, dependencies) in records {
+        for path in [output].into_iter().chain(dependencies) {
+            if ids.contains_key(path.as_str()) {
+                continue;
+            }
+
+            let id = ids.len() as u32;
+            let padding = (4 - path.len() % 4) % 4;
+
+            bytes.extend(((path.len() + padding + 4) as u32).to_le_bytes());
+            bytes.extend(path.as_bytes());
+            bytes.extend(vec![0; padding]);
+            bytes.extend((!id).to_le_bytes());
+            ids.insert(path, id);
+        }
+
+        bytes.extend((DEPENDENCY_RECORD_FLAG | (12 + 4 * dependencies.len() as u32)).to_le_bytes());
+        bytes.extend(ids[output.as_str()].to_le_bytes());
+        bytes.extend((*modified_time as u32).to_le_bytes());
+        bytes.extend(((modified_time >> 32) as u32).to_le_bytes());
+
+        for dependency in dependencies {
+            bytes.extend(ids[dependency.as_str()].to_le_bytes());
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn read_u32(bytes: &[u8], offset: &mut usize) -> u32 {
+        let value = u32::from_le_bytes(bytes[*offset..*offset + 4].try_into().unwrap());
+        *offset += 4;
+        value
+    }
+
+    // This follows Ninja's DepsLog::Load().
+    fn decode(bytes: &[u8]) -> Vec<(String, u64, Vec<String>)> {
+        assert_eq!(&bytes[..SIGNATURE.len()], SIGNATURE);
+
+        let mut offset = SIGNATURE.len();
+        let mut paths = vec![];
+        let mut records = vec![];
+
+        assert_eq!(read_u32(bytes, &mut offset), VERSION);
+
+        while offset < bytes.len() {
+            let size = read_u32(bytes, &mut offset);
+
+            if size & DEPENDENCY_RECORD_FLAG == 0 {
+                let end = offset + size as usize - 4;
+                let path = String::from_utf8(bytes[offset..end].to_vec()).unwrap();
+                offset = end;
+
+                assert_eq!(!read_u32(bytes, &mut offset), paths.len() as u32);
+                paths.push(path.trim_end_matches('\0').to_string());
+            } else {
+                let count = ((size & !DEPENDENCY_RECORD_FLAG) - 12) / 4;
+                let output = paths[read_u32(bytes, &mut offset) as usize].clone();
+                let modified_time = read_u32(bytes, &mut offset) as u64
+                    | (read_u32(bytes, &mut offset) as u64) << 32;
+
+                records.push((
+                    output,
+                    modified_time,
+                    (0..count)
+                        .map(|_| paths[read_u32(bytes, &mut offset) as usize].clone())
+                        .collect(),
+                ));
+            }
+        }
+
+        records
+    }
+
+    #[test]
+    fn encode_nothing() {
+        assert_eq!(decode(&encode(&[])), vec![]);
+    }
+
+    #[test]
+    fn round_trip_dependencies() {
+        let records = vec![
+            (
+                "foo.o".into(),
+                1 << 40 | 42,
+                vec!["foo.c".into(), "foo.h".into()],
+            ),
+            ("bar.o".into(), 42, vec!["bar.c".into(), "foo.h".into()]),
+        ];
+        let bytes = encode(&records);
+
+        assert_eq!(decode(&bytes), records);
+        // Paths are shared across records.
+        assert_eq!(bytes.len(), 16 + 5 * 16 + 2 * 24);
+    }
+}
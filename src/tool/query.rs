@@ -0,0 +1,254 @@
+use crate::{context::Context, error::ApplicationError, ir::Configuration};
+use std::{collections::HashSet, error::Error, sync::Arc};
+
+// This shows inputs and dependents of outputs like Ninja's query tool or trees
+// of transitive inputs of outputs. Default outputs are queried if none is
+// given.
+pub async fn query(
+    context: &Context,
+    configuration: &Configuration,
+    outputs: &[String],
+    transitive: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut string = String::new();
+
+    for output in resolve_outputs(configuration, outputs)? {
+        if transitive {
+            format_tree(configuration, &output, &mut string);
+        } else {
+            format_build(configuration, &output, &mut string);
+        }
+    }
+
+    context
+        .console()
+        .lock()
+        .await
+        .write_stdout(string.as_bytes())
+        .await?;
+
+    Ok(())
+}
+
+fn resolve_outputs(
+    configuration: &Configuration,
+    outputs: &[String],
+) -> Result<Vec<Arc<str>>, ApplicationError> {
+    if outputs.is_empty() {
+        let mut outputs = configuration
+            .default_outputs()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        outputs.sort();
+
+        return Ok(outputs);
+    }
+
+    outputs
+        .iter()
+        .map(|output| {
+            configuration
+                .outputs()
+                .get_key_value(output.as_str())
+                .map(|(output, _)| output.clone())
+                .ok_or_else(|| ApplicationError::OutputNotFound(output.clone()))
+        })
+        .collect()
+}
+
+// Order-only inputs are prefixed with `||`.
+fn format_build(configuration: &Configuration, output: &str, string: &mut String) {
+    string.push_str(&format!("{output}:\n"));
+
+    if let Some(build) = configuration.outputs().get(output) {
+        string.push_str(&format!(
+            "  input: {}\n",
+            build.rule().map(|rule| rule.name()).unwrap_or("phony")
+        ));
+
+        for input in build.inputs() {
+            string.push_str(&format!("    {input}\n"));
+        }
+
+        for input in build.order_only_inputs() {
+            string.push_str(&format!("    || {input}\n"));
+        }
+    }
+
+    let mut dependents = configuration
+        .outputs()
+        .values()
+        .filter(|build| {
+            build
+                .inputs()
+                .iter()
+                .chain(build.order_only_inputs())
+                .any(|input| input.as_ref() == output)
+        })
+        .flat_map(|build| build.outputs())
+        .collect::<Vec<_>>();
+
+    dependents.sort();
+    dependents.dedup();
+
+    string.push_str("  outputs:\n");
+
+    for output in dependents {
+        string.push_str(&format!("    {output}\n"));
+    }
+}
+
+fn format_tree(configuration: &Configuration, output: &str, string: &mut String) {
+    string.push_str(&format!("{output}\n"));
+
+    format_inputs(
+        configuration,
+        output,
+        1,
+        &mut vec![output.into()],
+        &mut HashSet::new(),
+        string,
+    );
+}
+
+// Inputs already expanded elsewhere are marked with `...` and ones depending
+// on themselves with `(cycle)` instead of being expanded again.
+fn format_inputs(
+    configuration: &Configuration,
+    output: &str,
+    depth: usize,
+    ancestors: &mut Vec<Arc<str>>,
+    visited: &mut HashSet<Arc<str>>,
+    string: &mut String,
+) {
+    let Some(build) = configuration.outputs().get(output) else {
+        return;
+    };
+
+    for (input, order_only) in build
+        .inputs()
+        .iter()
+        .map(|input| (input, false))
+        .chain(build.order_only_inputs().iter().map(|input| (input, true)))
+    {
+        string.push_str(&"  ".repeat(depth));
+
+        if order_only {
+            string.push_str("|| ");
+        }
+
+        string.push_str(input);
+
+        if ancestors.contains(input) {
+            string.push_str(" (cycle)\n");
+        } else if configuration.outputs().contains_key(input) && !visited.insert(input.clone()) {
+            string.push_str(" ...\n");
+        } else {
+            string.push('\n');
+
+            ancestors.push(input.clone());
+            format_inputs(configuration, input, depth + 1, ancestors, visited, string);
+            ancestors.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn compile_source(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc
+build common.o: cc common.c
+build foo.o: cc foo.c common.o
+build bar.o: cc bar.c common.o
+build foo.a: cc foo.o bar.o || generated.h
+build generated.h: cc generator.py
+";
+
+    #[test]
+    fn format_inputs_and_dependents() {
+        let mut string = String::new();
+
+        format_build(&compile_source(SOURCE), "foo.o", &mut string);
+
+        assert_eq!(
+            string,
+            "foo.o:\n  input: cc\n    foo.c\n    common.o\n  outputs:\n    foo.a\n"
+        );
+    }
+
+    #[test]
+    fn format_order_only_inputs() {
+        let mut string = String::new();
+
+        format_build(&compile_source(SOURCE), "foo.a", &mut string);
+
+        assert_eq!(
+            string,
+            "foo.a:\n  input: cc\n    foo.o\n    bar.o\n    || generated.h\n  outputs:\n"
+        );
+    }
+
+    #[test]
+    fn format_tree_with_shared_inputs() {
+        let mut string = String::new();
+
+        format_tree(&compile_source(SOURCE), "foo.a", &mut string);
+
+        assert_eq!(
+            string,
+            [
+                "foo.a",
+                "  foo.o",
+                "    foo.c",
+                "    common.o",
+                "      common.c",
+                "  bar.o",
+                "    bar.c",
+                "    common.o ...",
+                "  || generated.h",
+                "    generator.py",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn format_tree_with_cycle() {
+        let mut string = String::new();
+
+        format_tree(
+            &compile_source("rule cc\n  command = cc\nbuild foo: cc bar\nbuild bar: cc foo\n"),
+            "foo",
+            &mut string,
+        );
+
+        assert_eq!(string, "foo\n  bar\n    foo (cycle)\n");
+    }
+
+    #[test]
+    fn fail_to_query_unknown_output() {
+        assert!(resolve_outputs(&compile_source(""), &["foo".into()]).is_err());
+    }
+}
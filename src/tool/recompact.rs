@@ -0,0 +1,74 @@
+use crate::{
+    context::Context,
+    ir::{BuildId, Configuration},
+};
+use std::{collections::HashSet, error::Error};
+
+// Configurations compiled partially, e.g. lazily, are rejected because builds
+// missing in them are not stale.
+pub async fn recompact(
+    context: &Context,
+    configuration: &Configuration,
+    partial: bool,
+) -> Result<(), Box<dyn Error>> {
+    if partial {
+        return Err("cannot recompact a build database with build files compiled lazily".into());
+    }
+
+    remove_stale_builds(context, &find_build_ids(configuration), 1).await?;
+
+    Ok(())
+}
+
+// Stale builds are the ones recorded previously but not defined in build files
+// anymore. They are removed only if their number reaches a threshold and the
+// number is returned. Records are scanned only if a number of recorded builds
+// exceeds the one of builds in a configuration by the threshold.
+pub async fn compact(
+    context: &Context,
+    configuration: &Configuration,
+    threshold: usize,
+    partial: bool,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    if partial {
+        return Ok(None);
+    }
+
+    let ids = find_build_ids(configuration);
+
+    if context.database().get_build_count()? < ids.len() + threshold {
+        return Ok(None);
+    }
+
+    remove_stale_builds(context, &ids, threshold).await
+}
+
+fn find_build_ids(configuration: &Configuration) -> HashSet<BuildId> {
+    configuration
+        .outputs()
+        .values()
+        .map(|build| build.id())
+        .collect()
+}
+
+async fn remove_stale_builds(
+    context: &Context,
+    ids: &HashSet<BuildId>,
+    threshold: usize,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    let stale_ids = context
+        .database()
+        .get_build_ids()?
+        .into_iter()
+        .filter(|id| !ids.contains(id))
+        .collect::<Vec<_>>();
+
+    if stale_ids.is_empty() || stale_ids.len() < threshold {
+        return Ok(None);
+    }
+
+    context.database().remove_builds(&stale_ids)?;
+    context.database().flush().await?;
+
+    Ok(Some(stale_ids.len()))
+}
@@ -0,0 +1,150 @@
+use crate::{context::Context, ir::Configuration};
+use glob::Pattern;
+use std::{error::Error, sync::Arc};
+
+pub async fn targets(
+    context: &Context,
+    configuration: &Configuration,
+    patterns: &[String],
+) -> Result<(), Box<dyn Error>> {
+    write_outputs(context, &find_outputs(configuration, patterns)?).await
+}
+
+pub async fn print_default_targets(
+    context: &Context,
+    configuration: &Configuration,
+) -> Result<(), Box<dyn Error>> {
+    write_outputs(context, &find_default_outputs(configuration)).await
+}
+
+async fn write_outputs(context: &Context, outputs: &[Arc<str>]) -> Result<(), Box<dyn Error>> {
+    let mut console = context.console().lock().await;
+
+    for output in outputs {
+        console.write_stdout(output.as_bytes()).await?;
+        console.write_stdout(b"\n").await?;
+    }
+
+    Ok(())
+}
+
+// Default outputs are ones in `default` statements or all outputs if there is
+// none.
+fn find_default_outputs(configuration: &Configuration) -> Vec<Arc<str>> {
+    let mut outputs = configuration
+        .default_outputs()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    outputs.sort();
+
+    outputs
+}
+
+// Outputs of builds with rules are matched against glob patterns. All of them
+// are returned if no pattern is given.
+pub(crate) fn find_outputs(
+    configuration: &Configuration,
+    patterns: &[String],
+) -> Result<Vec<Arc<str>>, Box<dyn Error>> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| Pattern::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut outputs = configuration
+        .outputs()
+        .iter()
+        .filter(|(_, build)| build.rule().is_some())
+        .map(|(output, _)| output)
+        .filter(|output| {
+            patterns.is_empty() || patterns.iter().any(|pattern| pattern.matches(output))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    outputs.sort();
+
+    Ok(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use std::path::PathBuf;
+
+    fn compile_source(source: &str) -> Configuration {
+        let path = PathBuf::from("build.ninja");
+
+        compile(
+            &[(path.clone(), parse(source).unwrap())]
+                .into_iter()
+                .collect(),
+            &[(path.clone(), Default::default())].into_iter().collect(),
+            &path,
+        )
+        .unwrap()
+    }
+
+    fn find(source: &str, patterns: &[&str]) -> Vec<Arc<str>> {
+        find_outputs(
+            &compile_source(source),
+            &patterns
+                .iter()
+                .map(|&pattern| pattern.into())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    const SOURCE: &str = "
+rule cc
+  command = cc
+build foo.o: cc foo.c
+build src/bar.o: cc bar.c
+build foo.a: cc foo.o
+build all: phony foo.a
+";
+
+    #[test]
+    fn find_all_outputs() {
+        assert_eq!(
+            find(SOURCE, &[]),
+            vec!["foo.a".into(), "foo.o".into(), "src/bar.o".into()]
+        );
+    }
+
+    #[test]
+    fn find_outputs_matching_pattern() {
+        assert_eq!(
+            find(SOURCE, &["*.o"]),
+            vec![Arc::from("foo.o"), "src/bar.o".into()]
+        );
+        assert_eq!(find(SOURCE, &["foo.a"]), vec![Arc::from("foo.a")]);
+        assert_eq!(find(SOURCE, &["*.so"]), Vec::<Arc<str>>::new());
+    }
+
+    #[test]
+    fn find_default_outputs_in_default_statements() {
+        assert_eq!(
+            find_default_outputs(&compile_source(&format!(
+                "{SOURCE}default foo.o src/bar.o\n"
+            ))),
+            vec![Arc::from("foo.o"), "src/bar.o".into()]
+        );
+    }
+
+    #[test]
+    fn find_all_outputs_as_default_outputs() {
+        assert_eq!(
+            find_default_outputs(&compile_source(SOURCE)),
+            vec![
+                Arc::from("all"),
+                "foo.a".into(),
+                "foo.o".into(),
+                "src/bar.o".into()
+            ]
+        );
+    }
+}
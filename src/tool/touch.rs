@@ -0,0 +1,27 @@
+use crate::{
+    context::Context,
+    error::ApplicationError,
+    ir::Configuration,
+    run::{run, Options},
+};
+use std::sync::Arc;
+
+// This only updates the build database as if builds ran. Outputs on a file
+// system are neither created nor modified.
+pub async fn touch(
+    context: &Arc<Context>,
+    configuration: Arc<Configuration>,
+    outputs: &[String],
+    options: Options,
+) -> Result<(), ApplicationError> {
+    run(
+        context,
+        configuration,
+        outputs,
+        Options {
+            touch: true,
+            ..options
+        },
+    )
+    .await
+}
@@ -0,0 +1,175 @@
+use crate::{
+    ast::{Module, Statement},
+    context::Context,
+    ir::Configuration,
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    error::Error,
+    path::PathBuf,
+};
+
+pub async fn unused(
+    context: &Context,
+    configuration: &Configuration,
+    modules: &HashMap<PathBuf, Module>,
+) -> Result<(), Box<dyn Error>> {
+    let mut console = context.console().lock().await;
+
+    for line in find_unused(configuration, modules) {
+        console.write_stdout(line.as_bytes()).await?;
+        console.write_stdout(b"\n").await?;
+    }
+
+    Ok(())
+}
+
+// Rules and pools are unused if no builds refer to them by their names in any
+// scopes. Bases of used rules are used as well. Built-in ones are never
+// defined in build files.
+fn find_unused(configuration: &Configuration, modules: &HashMap<PathBuf, Module>) -> Vec<String> {
+    let mut rules = BTreeSet::new();
+    let mut pools = BTreeSet::new();
+    let mut bases = HashMap::new();
+
+    for statement in modules.values().flat_map(|module| module.statements()) {
+        match statement {
+            Statement::Rule(rule) => {
+                rules.insert(rule.name());
+
+                if let Some(base) = rule.base() {
+                    bases.insert(rule.name(), base);
+                }
+            }
+            Statement::Pool(pool) => {
+                pools.insert(pool.name());
+            }
+            _ => {}
+        }
+    }
+
+    let mut used_rules = HashSet::new();
+    let mut used_pools = HashSet::new();
+
+    for rule in configuration
+        .outputs()
+        .values()
+        .flat_map(|build| build.rule())
+    {
+        let mut name = rule.name();
+
+        while used_rules.insert(name) {
+            if let Some(base) = bases.get(name) {
+                name = base;
+            }
+        }
+
+        if let Some(pool) = rule.pool() {
+            used_pools.insert(pool.name().as_ref());
+        }
+    }
+
+    rules
+        .into_iter()
+        .filter(|rule| !used_rules.contains(rule))
+        .map(|rule| format!("rule {rule}"))
+        .chain(
+            pools
+                .into_iter()
+                .filter(|pool| !used_pools.contains(pool))
+                .map(|pool| format!("pool {pool}")),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::compile, parse::parse};
+    use pretty_assertions::assert_eq;
+
+    fn find(source: &str) -> Vec<String> {
+        let path = PathBuf::from("build.ninja");
+        let modules = [(path.clone(), parse(source).unwrap())]
+            .into_iter()
+            .collect();
+
+        find_unused(
+            &compile(
+                &modules,
+                &[(path.clone(), Default::default())].into_iter().collect(),
+                &path,
+            )
+            .unwrap(),
+            &modules,
+        )
+    }
+
+    #[test]
+    fn find_unused_rule() {
+        assert_eq!(
+            find(
+                "
+rule cc
+  command = cc $in -o $out
+rule link
+  command = link $in -o $out
+build foo.o: cc foo.c
+build all: phony foo.o
+"
+            ),
+            vec!["rule link"]
+        );
+    }
+
+    #[test]
+    fn find_no_base_of_used_rule() {
+        assert_eq!(
+            find(
+                "
+rule cc
+  command = cc $in -o $out
+rule cxx : cc
+  flags = -x c++
+rule cxx2 : cxx
+rule unused : cc
+build foo.o: cxx2 foo.cpp
+"
+            ),
+            vec!["rule unused"]
+        );
+    }
+
+    #[test]
+    fn find_unused_pool() {
+        assert_eq!(
+            find(
+                "
+pool link_pool
+  depth = 1
+pool test_pool
+  depth = 2
+rule link
+  command = link $in -o $out
+  pool = link_pool
+build foo: link foo.o
+"
+            ),
+            vec!["pool test_pool"]
+        );
+    }
+
+    #[test]
+    fn find_nothing_unused() {
+        assert_eq!(
+            find(
+                "
+rule cc
+  command = cc $in -o $out
+build foo.o: cc foo.c
+"
+            ),
+            Vec::<String>::new()
+        );
+    }
+}